@@ -0,0 +1,28 @@
+use embassy_time::Duration;
+
+/// Per-LED highlight envelopes.
+/// Rising highlights jump straight to their target so attacks stay immediate;
+/// falling ones decay linearly over the release time, giving notes a tail.
+pub struct Envelopes<const N: usize> {
+    levels: [f32; N],
+}
+
+impl<const N: usize> Envelopes<N> {
+    pub const fn new() -> Self {
+        Self { levels: [0.0; N] }
+    }
+
+    /// Advances envelope `idx` by `dt` towards `target` and returns its current level.
+    pub fn update(&mut self, idx: usize, target: f32, dt: Duration, release: Duration) -> f32 {
+        let Some(level) = self.levels.get_mut(idx) else {
+            return target;
+        };
+        if target >= *level || release.as_micros() == 0 {
+            *level = target;
+        } else {
+            let step = dt.as_micros() as f32 / release.as_micros() as f32;
+            *level = (*level - step).max(target);
+        }
+        *level
+    }
+}
@@ -0,0 +1,148 @@
+use crate::layouts::CurrentLayout;
+use crate::midi::{MidiSender, ToU7};
+use crate::tuning::{get_key_pitch, get_midi_event};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use lattice_board_core::arp::{ArpPattern, Arpeggio};
+use lattice_board_core::layout::Coordinate;
+use lattice_board_core::rng::Rng;
+use log::info;
+
+/// Note rates, as notes per beat of the internal clock.
+pub const RATES: [(&str, u32); 6] = [
+    ("1/4", 1),
+    ("1/8", 2),
+    ("1/8T", 3),
+    ("1/16", 4),
+    ("1/16T", 6),
+    ("1/32", 8),
+];
+
+const MAX_OCTAVES: u8 = 4;
+
+/// Arpeggiator settings.
+/// While enabled, held keys don't sound directly; instead they're played one at a
+/// time in the pattern's order, at the rate, over the octave range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArpConfig {
+    pub enabled: bool,
+    pub pattern: ArpPattern,
+    pub rate: usize,
+    /// How much of each note's time it sounds for, in percent.
+    pub gate: u8,
+    pub octaves: u8,
+}
+
+static ARP_CONFIG: Mutex<CriticalSectionRawMutex, Cell<ArpConfig>> =
+    Mutex::new(Cell::new(ArpConfig {
+        enabled: false,
+        pattern: ArpPattern::Up,
+        rate: 3,
+        gate: 50,
+        octaves: 1,
+    }));
+
+pub fn get_config() -> ArpConfig {
+    ARP_CONFIG.lock(|c| c.get())
+}
+
+pub fn is_enabled() -> bool {
+    get_config().enabled
+}
+
+fn update(f: impl FnOnce(&mut ArpConfig)) {
+    ARP_CONFIG.lock(|c| {
+        let mut cfg = c.get();
+        f(&mut cfg);
+        c.set(cfg);
+    });
+}
+
+pub fn toggle() {
+    update(|cfg| cfg.enabled = !cfg.enabled);
+    info!("Arp {}", if is_enabled() { "on" } else { "off" });
+}
+
+pub fn cycle_pattern(delta: i8) {
+    update(|cfg| {
+        let all = ArpPattern::ALL;
+        let i = all.iter().position(|&p| p == cfg.pattern).unwrap_or(0);
+        cfg.pattern = all[(i as i32 + delta as i32).rem_euclid(all.len() as i32) as usize];
+    });
+}
+
+pub fn cycle_rate(delta: i8) {
+    update(|cfg| {
+        cfg.rate = (cfg.rate as i32 + delta as i32).clamp(0, RATES.len() as i32 - 1) as usize
+    });
+}
+
+pub fn adjust_gate(delta: i8) {
+    update(|cfg| cfg.gate = (cfg.gate as i16 + 10 * delta as i16).clamp(10, 100) as u8);
+}
+
+pub fn adjust_octaves(delta: i8) {
+    update(|cfg| cfg.octaves = (cfg.octaves as i8 + delta).clamp(1, MAX_OCTAVES as i8) as u8);
+}
+
+pub fn rate_name(rate: usize) -> &'static str {
+    RATES[rate.min(RATES.len() - 1)].0
+}
+
+/// Time from one note to the next at `rate`.
+fn note_duration(rate: usize) -> Duration {
+    let per_beat = RATES[rate.min(RATES.len() - 1)].1;
+    let micros = 60_000_000.0 / (crate::clock::get_bpm() * per_beat as f32);
+    Duration::from_micros(micros as u64)
+}
+
+/// The key `octaves` octaves above `coord`: one step right and two down per octave.
+fn octave_up(coord: Coordinate, octaves: usize) -> Coordinate {
+    let octaves = octaves as i8;
+    Coordinate {
+        x: coord.x.saturating_add(octaves),
+        y: coord.y.saturating_sub(2 * octaves),
+    }
+}
+
+#[embassy_executor::task]
+pub async fn arp_task(sender: MidiSender) {
+    let mut arpeggio = Arpeggio::new();
+    let mut rng = Rng::new(Instant::now().as_ticks() as u32);
+    let mut next = Instant::now();
+
+    loop {
+        let step = note_duration(get_config().rate);
+        next += step;
+        Timer::at(next).await;
+
+        let cfg = get_config();
+        if !cfg.enabled {
+            arpeggio.restart();
+            next = Instant::now();
+            continue;
+        }
+
+        let mut chord = crate::keys::active_keys();
+        chord.sort_unstable_by(|a, b| {
+            get_key_pitch::<CurrentLayout>(*a).total_cmp(&get_key_pitch::<CurrentLayout>(*b))
+        });
+        let notes = chord.len() * cfg.octaves as usize;
+        let Some(note) = arpeggio.next(cfg.pattern, notes, &mut rng) else {
+            // A new chord starts the pattern from the beginning
+            arpeggio.restart();
+            continue;
+        };
+        let coord = octave_up(chord[note % chord.len()], note / chord.len());
+
+        if let Some(event) = get_midi_event::<CurrentLayout>(coord, 100.to_u7(), true) {
+            sender.send(event).await;
+            Timer::after(step * cfg.gate as u32 / 100).await;
+            if let Some(event) = get_midi_event::<CurrentLayout>(coord, 0.to_u7(), false) {
+                sender.send(event).await;
+            }
+        }
+    }
+}
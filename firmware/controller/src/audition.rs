@@ -0,0 +1,124 @@
+//! Tuning audition: after each step of a tuning change, the held chord is played
+//! again in the new tuning, or with nothing held the last chord played sounds for a
+//! moment, so the change can be heard without touching the keys.
+
+use crate::keys::play_key;
+use crate::layouts::CurrentLayout;
+use crate::midi::ToU7;
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::Vec;
+use lattice_board_core::layout::Coordinate;
+use log::info;
+
+/// Keys of a chord remembered for auditions.
+const CHORD_KEYS: usize = 8;
+/// How long the last chord sounds after a step, restarted by every step.
+const RING: Duration = Duration::from_millis(800);
+const VELOCITY: u8 = 90;
+
+type Chord = Vec<Coordinate, CHORD_KEYS>;
+
+static ENABLED: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+/// The keys of the latest chord, from its first press while nothing was held.
+static LAST_CHORD: Mutex<CriticalSectionRawMutex, RefCell<Chord>> =
+    Mutex::new(RefCell::new(Vec::new()));
+/// The last chord while an audition plays it, with nothing held.
+static RINGING: Mutex<CriticalSectionRawMutex, RefCell<Chord>> =
+    Mutex::new(RefCell::new(Vec::new()));
+/// When the ringing chord stops.
+static RING_UNTIL: Signal<CriticalSectionRawMutex, Instant> = Signal::new();
+
+pub fn is_enabled() -> bool {
+    ENABLED.lock(|e| e.get())
+}
+
+pub fn toggle() {
+    let enabled = ENABLED.lock(|e| {
+        e.set(!e.get());
+        e.get()
+    });
+    info!("Tuning audition {}", if enabled { "on" } else { "off" });
+}
+
+/// Notes a key press, given whether other keys were already held.
+pub fn key_pressed(coord: Coordinate, others_held: bool) {
+    if RINGING.lock(|r| r.borrow().contains(&coord)) {
+        // Played by the audition itself
+        return;
+    }
+    LAST_CHORD.lock(|c| {
+        let mut chord = c.borrow_mut();
+        if !others_held {
+            chord.clear();
+        }
+        let _ = chord.push(coord);
+    });
+}
+
+fn play(chord: &[Coordinate], is_pressed: bool) {
+    let Some(sender) = crate::keys::sender() else {
+        return;
+    };
+    for &coord in chord {
+        play_key::<CurrentLayout>(coord, is_pressed, VELOCITY.to_u7(), &sender);
+    }
+}
+
+/// Applies a step of a tuning change. While auditioning, the chord sounding is
+/// released in the old tuning first, as a changed mode sends its note offs
+/// differently, then played again in the new one.
+pub fn retune(change: impl FnOnce()) {
+    if !is_enabled() {
+        change();
+        return;
+    }
+    // Ringing keys count as held too
+    let ringing = RINGING.lock(|r| r.borrow().clone());
+    let held: Chord = crate::keys::active_keys()
+        .iter()
+        .filter(|c| !ringing.contains(c))
+        .take(CHORD_KEYS)
+        .copied()
+        .collect();
+    play(&ringing, false);
+    let chord = if held.is_empty() {
+        LAST_CHORD.lock(|c| c.borrow().clone())
+    } else {
+        play(&held, false);
+        held.clone()
+    };
+    // Set before playing, so the presses aren't taken for a new chord
+    RINGING.lock(|r| {
+        *r.borrow_mut() = if held.is_empty() {
+            chord.clone()
+        } else {
+            Vec::new()
+        }
+    });
+    change();
+    play(&chord, true);
+    if held.is_empty() && !chord.is_empty() {
+        RING_UNTIL.signal(Instant::now() + RING);
+    }
+}
+
+/// Lets the last chord ring until the latest step's time runs out.
+#[embassy_executor::task]
+pub async fn audition_task() {
+    loop {
+        let mut until = RING_UNTIL.wait().await;
+        loop {
+            Timer::at(until).await;
+            match RING_UNTIL.try_take() {
+                Some(later) => until = later,
+                None => break,
+            }
+        }
+        let ringing = RINGING.lock(|r| core::mem::take(&mut *r.borrow_mut()));
+        play(&ringing, false);
+    }
+}
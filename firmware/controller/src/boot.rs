@@ -0,0 +1,78 @@
+//! What happens at power-on: which profile's preset is loaded, the pause and key map
+//! dump on the 5x25, and the startup animation. Kept with the board settings, so they
+//! apply whichever profile comes up.
+
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use lattice_board_core::storage::PROFILES;
+
+/// Startup steps that can be turned off, as bits of the stored skip mask. Stored as
+/// what's skipped so settings saved before them keep every step.
+const SKIP_DELAY: u8 = 1 << 0;
+const SKIP_KEY_MAP: u8 = 1 << 1;
+const SKIP_ANIMATION: u8 = 1 << 2;
+
+/// Profile loaded at boot, from 1; 0 for the one used last.
+static PROFILE: Mutex<CriticalSectionRawMutex, Cell<u8>> = Mutex::new(Cell::new(0));
+static SKIP: Mutex<CriticalSectionRawMutex, Cell<u8>> = Mutex::new(Cell::new(0));
+
+/// Reads the boot settings; call before [`crate::profile::load`].
+pub fn load() {
+    let settings = crate::util::stored_settings();
+    PROFILE.lock(|p| p.set(settings.boot_profile.min(PROFILES as u8)));
+    SKIP.lock(|s| s.set(settings.boot_skip));
+}
+
+fn store() {
+    crate::util::store_boot(PROFILE.lock(|p| p.get()), SKIP.lock(|s| s.get()));
+}
+
+/// The profile always loaded at boot, or `None` for the one used last.
+pub fn profile() -> Option<u8> {
+    PROFILE.lock(|p| p.get()).checked_sub(1)
+}
+
+/// Steps through "last used" and each profile.
+pub fn cycle_profile(delta: i8) {
+    PROFILE.lock(|p| {
+        let choices = PROFILES as i16 + 1;
+        p.set((p.get() as i16 + delta as i16).rem_euclid(choices) as u8)
+    });
+    store();
+}
+
+fn runs(step: u8) -> bool {
+    SKIP.lock(|s| s.get()) & step == 0
+}
+
+fn toggle(step: u8) {
+    SKIP.lock(|s| s.set(s.get() ^ step));
+    store();
+}
+
+/// Whether the 5x25 waits 2 s for the console before going on.
+pub fn delay() -> bool {
+    runs(SKIP_DELAY)
+}
+
+pub fn toggle_delay() {
+    toggle(SKIP_DELAY)
+}
+
+/// Whether the 5x25 logs its key map at boot.
+pub fn key_map() -> bool {
+    runs(SKIP_KEY_MAP)
+}
+
+pub fn toggle_key_map() {
+    toggle(SKIP_KEY_MAP)
+}
+
+pub fn animation() -> bool {
+    runs(SKIP_ANIMATION)
+}
+
+pub fn toggle_animation() {
+    toggle(SKIP_ANIMATION)
+}
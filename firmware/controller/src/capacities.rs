@@ -0,0 +1,113 @@
+//! Sizes of the fixed-capacity queues and lists shared between tasks.
+//!
+//! Statics are sized when the firmware is built, before it knows which board it runs
+//! on, so sizes that depend on the board are the largest any board asks for in its
+//! [`BoardConfig`].
+
+use crate::layouts::{layout_5x25, layout_7x32, prototype, BoardConfig};
+use crate::midi::{MidiEvent, RemoteVoice};
+use core::mem::size_of;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::pipe::Pipe;
+use embassy_sync::watch::Watch;
+use heapless::Vec;
+use lattice_board_core::layout::Coordinate;
+use log::info;
+
+const BOARDS: [BoardConfig; 3] = [prototype::BOARD, layout_5x25::BOARD, layout_7x32::BOARD];
+
+/// Events waiting for the MIDI task.
+pub const MIDI_QUEUE: usize = {
+    let mut largest = 0;
+    let mut i = 0;
+    while i < BOARDS.len() {
+        if BOARDS[i].midi_queue > largest {
+            largest = BOARDS[i].midi_queue;
+        }
+        i += 1;
+    }
+    largest
+};
+/// Held keys tracked at once; presses past this still sound but aren't lit or chorded.
+pub const HELD_KEYS: usize = {
+    let mut largest = 0;
+    let mut i = 0;
+    while i < BOARDS.len() {
+        if BOARDS[i].held_keys > largest {
+            largest = BOARDS[i].held_keys;
+        }
+        i += 1;
+    }
+    largest
+};
+/// Keys of one matrix row, all of which can be held at once, as on the glide strip.
+pub const ROW_KEYS: usize = {
+    let mut largest = 0;
+    let mut i = 0;
+    while i < BOARDS.len() {
+        if BOARDS[i].cols > largest {
+            largest = BOARDS[i].cols;
+        }
+        i += 1;
+    }
+    largest
+};
+/// Notes from the host tracked for the LEDs.
+pub const REMOTE_VOICES: usize = 32;
+/// Log output waiting for the serial console, in bytes.
+pub const LOG_PIPE: usize = 1024;
+/// Encoded defmt frames waiting for their serial port, in bytes.
+pub const DEFMT_PIPE: usize = 1024;
+/// Encoded defmt frames waiting for a debug probe, in bytes.
+pub const RTT_BUFFER: usize = 1024;
+
+/// RAM taken by the statics sized here.
+pub const STATIC_BYTES: usize =
+    size_of::<Channel<CriticalSectionRawMutex, MidiEvent, MIDI_QUEUE>>()
+        + size_of::<Watch<CriticalSectionRawMutex, Vec<Coordinate, HELD_KEYS>, 2>>()
+        + size_of::<Watch<CriticalSectionRawMutex, Vec<RemoteVoice, REMOTE_VOICES>, 2>>()
+        + size_of::<Pipe<CriticalSectionRawMutex, LOG_PIPE>>()
+        + size_of::<Pipe<CriticalSectionRawMutex, DEFMT_PIPE>>()
+        + RTT_BUFFER;
+/// Share of the RAM these statics may grow to before a bigger board needs a closer look.
+const STATIC_BUDGET: usize = 16 * 1024;
+
+const _: () = assert!(
+    STATIC_BYTES <= STATIC_BUDGET,
+    "queue and list capacities outgrew their RAM budget"
+);
+
+// Start and end of the initialized and zeroed statics, from the cortex-m-rt linker script
+extern "C" {
+    static __sdata: u8;
+    static __edata: u8;
+    static __sbss: u8;
+    static __ebss: u8;
+}
+
+/// Bytes of RAM taken by all statics, as linked.
+pub fn linked_static_bytes() -> usize {
+    let span = |start: *const u8, end: *const u8| end as usize - start as usize;
+    // Only the addresses of the linker symbols are taken, never their contents
+    span(core::ptr::addr_of!(__sdata), core::ptr::addr_of!(__edata))
+        + span(core::ptr::addr_of!(__sbss), core::ptr::addr_of!(__ebss))
+}
+
+/// Logs the capacities and what the statics take (`config verify`).
+pub fn log_report() {
+    info!(
+        "Capacities: MIDI queue {}, held keys {}, remote voices {}, log pipe {} bytes",
+        MIDI_QUEUE, HELD_KEYS, REMOTE_VOICES, LOG_PIPE
+    );
+    info!(
+        "defmt buffers: serial {} bytes, RTT {} bytes",
+        DEFMT_PIPE, RTT_BUFFER
+    );
+    info!(
+        "Static RAM: {} bytes in all, {} of them in these ({} budget)",
+        linked_static_bytes(),
+        STATIC_BYTES,
+        STATIC_BUDGET
+    );
+}
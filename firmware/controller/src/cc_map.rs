@@ -0,0 +1,291 @@
+use crate::fields::Field;
+use crate::midi::{index_to_channel, MidiEvent, MidiSender};
+use crate::util::CC_MAP_LEN;
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Timer};
+use lattice_board_core::cc_map::{scale, scale14, stored_len, unscale, CcMap, CcSource, Control};
+use lattice_board_core::nrpn::Data;
+use log::info;
+
+/// Settings that incoming CCs can be MIDI-learned to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Param {
+    Brightness,
+    Hue,
+    Transpose,
+    /// The clock tempo, which steps the Euclid arpeggiator and the walk.
+    ArpRate,
+}
+
+impl Param {
+    pub const ALL: [Param; 4] = [
+        Param::Brightness,
+        Param::Hue,
+        Param::Transpose,
+        Param::ArpRate,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Param::Brightness => "Brightness",
+            Param::Hue => "Hue",
+            Param::Transpose => "Transpose",
+            Param::ArpRate => "Arp rate",
+        }
+    }
+
+    /// Value range, as CC values map onto it.
+    fn range(self) -> (f32, f32) {
+        match self {
+            Param::Brightness => (0.0, 1.0),
+            Param::Hue => (0.0, 359.0),
+            Param::Transpose => (0.0, 11.0),
+            Param::ArpRate => (20.0, 300.0),
+        }
+    }
+
+    /// Current value as a 14-bit controller value.
+    fn value14(self) -> u16 {
+        let led = crate::leds::led_config();
+        let value = match self {
+            Param::Brightness => led.brightness,
+            Param::Hue => led.hue_rotation,
+            Param::Transpose => led.transpose as f32,
+            Param::ArpRate => crate::clock::get_bpm(),
+        };
+        let (min, max) = self.range();
+        unscale(value, min, max)
+    }
+
+    /// The dashboard setting stepping the same value.
+    fn field(self) -> Field {
+        match self {
+            Param::Brightness => Field::Brightness,
+            Param::Hue => Field::Hue,
+            Param::Transpose => Field::Transpose,
+            Param::ArpRate => Field::Bpm,
+        }
+    }
+
+    /// Sets the parameter to `value`, within its range.
+    fn set(self, value: f32) {
+        match self {
+            Param::Brightness => crate::leds::update_config(|c| c.brightness = value),
+            Param::Hue => crate::leds::update_config(|c| c.hue_rotation = value),
+            Param::Transpose => crate::leds::update_config(|c| c.transpose = (value + 0.5) as u8),
+            Param::ArpRate => crate::clock::set_bpm(value),
+        }
+    }
+
+    /// Sets the parameter from a CC value across its whole range.
+    fn apply(self, value: u8) {
+        let (min, max) = self.range();
+        self.set(scale(value, min, max));
+    }
+}
+
+const PARAMS: usize = Param::ALL.len();
+const _: () = assert!(stored_len(PARAMS) <= CC_MAP_LEN);
+
+static MAP: Mutex<CriticalSectionRawMutex, RefCell<CcMap<PARAMS>>> =
+    Mutex::new(RefCell::new(CcMap::new()));
+/// Whether local changes are sent back to the host.
+static FEEDBACK: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+/// Last value of each parameter the host was sent or sent us; `u16::MAX` = unknown.
+static HOST_VALUES: Mutex<CriticalSectionRawMutex, Cell<[u16; PARAMS]>> =
+    Mutex::new(Cell::new([u16::MAX; PARAMS]));
+/// NRPN parameter number of the first parameter, for feedback to those without a CC
+/// and for hosts setting them by NRPN.
+pub const NRPN_BASE: u16 = 0;
+const FEEDBACK_TICK: Duration = Duration::from_millis(50);
+
+/// Parameter shown and learned from the dashboard.
+static SELECTED: Mutex<CriticalSectionRawMutex, Cell<Param>> =
+    Mutex::new(Cell::new(Param::Brightness));
+
+/// Restores the map saved in flash, or starts empty if there is none.
+pub fn load() {
+    let mut bytes = [0u8; CC_MAP_LEN];
+    let map = crate::util::stored_cc_map(&mut bytes)
+        .then(|| CcMap::decode(&bytes))
+        .flatten()
+        .unwrap_or(CcMap::new());
+    MAP.lock(|m| *m.borrow_mut() = map);
+}
+
+fn store() {
+    let mut bytes = [0u8; stored_len(PARAMS)];
+    MAP.lock(|m| m.borrow().encode(&mut bytes));
+    crate::util::store_cc_map(&bytes);
+}
+
+pub fn selected() -> Param {
+    SELECTED.lock(|s| s.get())
+}
+
+pub fn cycle_selected(delta: i8) {
+    SELECTED.lock(|s| {
+        let i = (s.get() as i32 + delta as i32).rem_euclid(PARAMS as i32);
+        s.set(Param::ALL[i as usize]);
+    });
+}
+
+/// Starts learning the selected parameter; while it's learning, unmaps it instead.
+pub fn toggle_learn() {
+    let param = selected();
+    let cleared = MAP.lock(|m| {
+        let mut map = m.borrow_mut();
+        if map.learning() == Some(param as usize) {
+            map.clear(param as usize);
+            true
+        } else {
+            map.learn(param as usize);
+            false
+        }
+    });
+    if cleared {
+        info!("{} unmapped", param.name());
+        store();
+    } else {
+        info!("Move a controller to map {}", param.name());
+    }
+}
+
+/// Writes the selected parameter's mapping.
+pub fn write_selected(out: &mut impl core::fmt::Write) -> core::fmt::Result {
+    let param = selected();
+    let (learning, source) = MAP.lock(|m| {
+        let map = m.borrow();
+        (
+            map.learning() == Some(param as usize),
+            map.source(param as usize),
+        )
+    });
+    match (learning, source) {
+        (true, _) => write!(out, "{}: learning", param.name()),
+        (false, Some(s)) => write!(out, "{}: CC{} Ch{}", param.name(), s.cc, s.channel + 1),
+        (false, None) => write!(out, "{}: -", param.name()),
+    }
+}
+
+/// Incoming CC from the host: learned if a parameter is waiting for one, otherwise
+/// applied to the parameter it's mapped to.
+pub fn handle(channel: u8, cc: u8, value: u8) {
+    if let Control::Set(param) = control(channel, cc, value) {
+        host_has(Param::ALL[param]);
+    }
+}
+
+/// CC from the controller on the host port, learned and applied like the host's. The
+/// host didn't send it, so feedback passes the change on. False if it isn't mapped,
+/// for it to be merged into the outgoing stream instead.
+#[cfg(feature = "usb-host")]
+pub fn handle_controller(channel: u8, cc: u8, value: u8) -> bool {
+    control(channel, cc, value) != Control::Unmapped
+}
+
+/// Learns or applies a CC, returning which it did.
+fn control(channel: u8, cc: u8, value: u8) -> Control {
+    let control = MAP.lock(|m| m.borrow_mut().control(CcSource { channel, cc }));
+    match control {
+        Control::Learned(param) => {
+            info!(
+                "CC{} Ch{} mapped to {}",
+                cc,
+                channel + 1,
+                Param::ALL[param].name()
+            );
+            store();
+        }
+        Control::Set(param) => Param::ALL[param].apply(value),
+        Control::Unmapped => {}
+    }
+    control
+}
+
+/// NRPN data for parameter number `param`: data entry sets it across its range, the
+/// MSB alone like a CC and the LSB to 14 bits; increment and decrement step it like
+/// the dashboard does. False if no parameter has that number.
+pub fn set_from_nrpn(param: u16, data: Data) -> bool {
+    let Some(&param) = param
+        .checked_sub(NRPN_BASE)
+        .and_then(|i| Param::ALL.get(i as usize))
+    else {
+        return false;
+    };
+    let (min, max) = param.range();
+    match data {
+        Data::Entry(value) => param.apply((value >> 7) as u8),
+        Data::Fine(value) => param.set(scale14(value, min, max)),
+        // The host doesn't know where a step lands; feedback tells it
+        Data::Step(direction) => {
+            param.field().adjust(direction);
+            return true;
+        }
+    }
+    host_has(param);
+    true
+}
+
+/// The host set `param` itself, so feedback doesn't echo it back.
+fn host_has(param: Param) {
+    let value = param.value14();
+    HOST_VALUES.lock(|v| {
+        let mut values = v.get();
+        values[param as usize] = value;
+        v.set(values);
+    });
+}
+
+pub fn feedback_enabled() -> bool {
+    FEEDBACK.lock(|f| f.get())
+}
+
+/// Turns feedback on or off. Turning it on sends every parameter once, so the host
+/// starts in sync.
+pub fn toggle_feedback() {
+    FEEDBACK.lock(|f| f.set(!f.get()));
+    HOST_VALUES.lock(|v| v.set([u16::MAX; PARAMS]));
+}
+
+/// While feedback is on, sends parameters changed on the board (encoder, dashboard,
+/// console) to the host: on their learned CC, or as NRPN `NRPN_BASE + index` on
+/// channel 1 when they have none. LFO offsets aren't included.
+#[embassy_executor::task]
+pub async fn feedback_task(sender: MidiSender) {
+    loop {
+        Timer::after(FEEDBACK_TICK).await;
+        if !feedback_enabled() {
+            continue;
+        }
+        for param in Param::ALL {
+            let value = param.value14();
+            let changed = HOST_VALUES.lock(|v| {
+                let mut values = v.get();
+                let changed = values[param as usize] != value;
+                values[param as usize] = value;
+                v.set(values);
+                changed
+            });
+            if !changed {
+                continue;
+            }
+            let source = MAP.lock(|m| m.borrow().source(param as usize));
+            let event = match source {
+                Some(s) => MidiEvent::ControlChange {
+                    channel: index_to_channel(s.channel).unwrap_or(wmidi::Channel::Ch1),
+                    control: s.cc,
+                    value: (value >> 7) as u8,
+                },
+                None => MidiEvent::Nrpn {
+                    channel: wmidi::Channel::Ch1,
+                    param: NRPN_BASE + param as u16,
+                    value,
+                },
+            };
+            let _ = sender.try_send(event);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Duration;
+
+/// Internal tempo in beats per minute. Generators step in sixteenth notes.
+static BPM: Mutex<CriticalSectionRawMutex, Cell<f32>> = Mutex::new(Cell::new(120.0));
+
+const STEPS_PER_BEAT: f32 = 4.0;
+
+pub fn get_bpm() -> f32 {
+    BPM.lock(|b| b.get())
+}
+
+pub fn adjust_bpm(delta: f32) {
+    BPM.lock(|b| {
+        let current = b.get();
+        b.set((current + delta).clamp(20.0, 300.0));
+    });
+}
+
+pub fn set_bpm(bpm: f32) {
+    BPM.lock(|b| b.set(bpm.clamp(20.0, 300.0)));
+}
+
+/// Duration of one generator step (a sixteenth note at the current tempo).
+pub fn step_duration() -> Duration {
+    let micros = 60_000_000.0 / (get_bpm() * STEPS_PER_BEAT);
+    Duration::from_micros(micros as u64)
+}
@@ -0,0 +1,404 @@
+use crate::leds::LedConfig;
+use core::fmt::Write;
+use heapless::String;
+
+/// What a settings key does.
+#[derive(Clone, Copy)]
+enum Action {
+    Led(fn(&mut LedConfig)),
+    Run(fn()),
+}
+
+/// Single-key console command that changes a setting. Recorded into macros and
+/// replayed from them.
+pub struct Command {
+    /// Keys for the command, usually a down/up pair.
+    pub keys: &'static [u8],
+    pub help: &'static str,
+    /// The action of each key in `keys`; keys past the end use the last one.
+    actions: &'static [Action],
+}
+
+fn nudge(v: u8, delta: i16) -> u8 {
+    (v as i16 + delta).clamp(0, 255) as u8
+}
+
+fn anchor(config: &mut LedConfig) -> &mut smart_leds::RGB8 {
+    &mut config.rgb_anchors[config.selected_anchor]
+}
+
+/// Settings keys, in the order the help page lists them.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        keys: b"[]",
+        help: "Palette anchor -/+",
+        actions: &[
+            Action::Led(|c| c.selected_anchor = (c.selected_anchor + 11) % 12),
+            Action::Led(|c| c.selected_anchor = (c.selected_anchor + 1) % 12),
+        ],
+    },
+    Command {
+        keys: b"rR",
+        help: "Anchor red -/+",
+        actions: &[
+            Action::Led(|c| anchor(c).r = nudge(anchor(c).r, -5)),
+            Action::Led(|c| anchor(c).r = nudge(anchor(c).r, 5)),
+        ],
+    },
+    Command {
+        keys: b"gG",
+        help: "Anchor green -/+",
+        actions: &[
+            Action::Led(|c| anchor(c).g = nudge(anchor(c).g, -5)),
+            Action::Led(|c| anchor(c).g = nudge(anchor(c).g, 5)),
+        ],
+    },
+    Command {
+        keys: b"bB",
+        help: "Anchor blue -/+",
+        actions: &[
+            Action::Led(|c| anchor(c).b = nudge(anchor(c).b, -5)),
+            Action::Led(|c| anchor(c).b = nudge(anchor(c).b, 5)),
+        ],
+    },
+    Command {
+        keys: b"lL",
+        help: "Brightness -/+ 0.05",
+        actions: &[
+            Action::Led(|c| c.brightness = (c.brightness - 0.05).max(0.0)),
+            Action::Led(|c| c.brightness = (c.brightness + 0.05).min(1.0)),
+        ],
+    },
+    Command {
+        keys: b"-+_=",
+        help: "Brightness -/+ 0.01",
+        actions: &[
+            Action::Led(|c| c.brightness = (c.brightness - 0.01).max(0.0)),
+            Action::Led(|c| c.brightness = (c.brightness + 0.01).min(1.0)),
+            Action::Led(|c| c.brightness = (c.brightness - 0.01).max(0.0)),
+            Action::Led(|c| c.brightness = (c.brightness + 0.01).min(1.0)),
+        ],
+    },
+    Command {
+        keys: b"hH",
+        help: "Hue rotation -/+",
+        actions: &[
+            Action::Led(|c| c.hue_rotation = (c.hue_rotation - 1.0 + 360.0) % 360.0),
+            Action::Led(|c| c.hue_rotation = (c.hue_rotation + 1.0) % 360.0),
+        ],
+    },
+    Command {
+        keys: b"oO",
+        help: "Octave gradient -/+",
+        actions: &[
+            Action::Led(|c| c.octave_gradient = (c.octave_gradient - 0.05).max(0.0)),
+            Action::Led(|c| c.octave_gradient = (c.octave_gradient + 0.05).min(0.5)),
+        ],
+    },
+    Command {
+        keys: b"aA",
+        help: "Landmarks",
+        actions: &[
+            Action::Led(|c| c.landmarks = crate::leds::cycle_landmarks(c.landmarks, -1)),
+            Action::Led(|c| c.landmarks = crate::leds::cycle_landmarks(c.landmarks, 1)),
+        ],
+    },
+    Command {
+        keys: b"uU",
+        help: "Guides",
+        actions: &[
+            Action::Led(|c| c.guides = c.guides.prev()),
+            Action::Led(|c| c.guides = c.guides.next()),
+        ],
+    },
+    Command {
+        keys: b"pP",
+        help: "Color transpose -/+",
+        actions: &[
+            Action::Led(|c| c.transpose = (c.transpose + 11) % 12),
+            Action::Led(|c| c.transpose = (c.transpose + 1) % 12),
+        ],
+    },
+    Command {
+        keys: b"zZ",
+        help: "LED release -/+ 50ms",
+        actions: &[
+            Action::Led(|c| c.release_ms = c.release_ms.saturating_sub(50)),
+            Action::Led(|c| c.release_ms = (c.release_ms + 50).min(5000)),
+        ],
+    },
+    Command {
+        keys: b"fF",
+        help: "LED frame -/+ 1ms",
+        actions: &[
+            Action::Led(|c| c.frame_ms = c.frame_ms.saturating_sub(1).max(1)),
+            Action::Led(|c| c.frame_ms = (c.frame_ms + 1).min(50)),
+        ],
+    },
+    Command {
+        keys: b"xX",
+        help: "Reset stats",
+        actions: &[Action::Run(crate::stats::reset)],
+    },
+    Command {
+        keys: b"tT",
+        help: "Tuning mode",
+        actions: &[Action::Run(|| {
+            crate::audition::retune(|| {
+                let _ = crate::tuning::toggle_mode();
+            })
+        })],
+    },
+    Command {
+        keys: b"()",
+        help: "Fifth -/+ 1c",
+        actions: &[
+            Action::Run(|| crate::audition::retune(|| crate::tuning::adjust_fifth_size(-1.0))),
+            Action::Run(|| crate::audition::retune(|| crate::tuning::adjust_fifth_size(1.0))),
+        ],
+    },
+    Command {
+        keys: b"{}",
+        help: "Fifth -/+ 0.1c",
+        actions: &[
+            Action::Run(|| crate::audition::retune(|| crate::tuning::adjust_fifth_size(-0.1))),
+            Action::Run(|| crate::audition::retune(|| crate::tuning::adjust_fifth_size(0.1))),
+        ],
+    },
+    Command {
+        keys: b",.",
+        help: "MPE bend range -/+ 1",
+        actions: &[
+            Action::Run(|| crate::tuning::adjust_mpe_pbr(-1.0)),
+            Action::Run(|| crate::tuning::adjust_mpe_pbr(1.0)),
+        ],
+    },
+    Command {
+        keys: b"<>",
+        help: "MPE bend range -/+ 0.1",
+        actions: &[
+            Action::Run(|| crate::tuning::adjust_mpe_pbr(-0.1)),
+            Action::Run(|| crate::tuning::adjust_mpe_pbr(0.1)),
+        ],
+    },
+    Command {
+        keys: b"|",
+        help: "A/B tuning switch",
+        actions: &[Action::Run(crate::compare::switch)],
+    },
+    Command {
+        keys: b"eE",
+        help: "Euclid on/off",
+        actions: &[Action::Run(crate::euclid::toggle)],
+    },
+    Command {
+        keys: b"kK",
+        help: "Euclid pulses -/+",
+        actions: &[
+            Action::Run(|| crate::euclid::adjust_pulses(-1)),
+            Action::Run(|| crate::euclid::adjust_pulses(1)),
+        ],
+    },
+    Command {
+        keys: b"nN",
+        help: "Euclid steps -/+",
+        actions: &[
+            Action::Run(|| crate::euclid::adjust_steps(-1)),
+            Action::Run(|| crate::euclid::adjust_steps(1)),
+        ],
+    },
+    Command {
+        keys: b"wW",
+        help: "Walk on/off",
+        actions: &[Action::Run(crate::walk::toggle)],
+    },
+    Command {
+        keys: b"jJ",
+        help: "Walk step -/+",
+        actions: &[
+            Action::Run(|| crate::walk::adjust_step_size(-1)),
+            Action::Run(|| crate::walk::adjust_step_size(1)),
+        ],
+    },
+    Command {
+        keys: b"sS",
+        help: "Walk scale",
+        actions: &[
+            Action::Run(|| crate::walk::cycle_scale(-1)),
+            Action::Run(|| crate::walk::cycle_scale(1)),
+        ],
+    },
+    Command {
+        keys: b"vV",
+        help: "Scroll voices",
+        actions: &[
+            Action::Run(|| crate::dashboard::scroll_voices(-1)),
+            Action::Run(|| crate::dashboard::scroll_voices(1)),
+        ],
+    },
+    Command {
+        keys: b"^",
+        help: "New practice session",
+        actions: &[Action::Run(crate::practice::new_session)],
+    },
+    Command {
+        keys: b"@",
+        help: "Practice heatmap on/off",
+        actions: &[Action::Run(crate::practice::toggle_heatmap)],
+    },
+    Command {
+        keys: b"mM",
+        help: "BPM -/+",
+        actions: &[
+            Action::Run(|| crate::clock::adjust_bpm(-1.0)),
+            Action::Run(|| crate::clock::adjust_bpm(1.0)),
+        ],
+    },
+];
+
+/// Console keys the serial task handles itself: modes, pages, macros and board actions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// Switches between the log and the dashboard.
+    Mode,
+    /// Next dashboard page.
+    Page,
+    Help,
+    LayoutDump,
+    /// `config verify`: partition map and settings bank health.
+    Verify,
+    Record,
+    /// Stores the recording in the slot picked by the next key.
+    StoreMacro,
+    PlayMacro,
+    FactoryReset,
+    Lock,
+    /// Times how long the rows take to settle.
+    SettleTest,
+    /// Prints the practice log.
+    PracticeLog,
+    /// Times releases of played chords from key to USB.
+    ReleaseBenchmark,
+}
+
+pub struct ControlKey {
+    pub keys: &'static [u8],
+    pub control: Control,
+    pub help: &'static str,
+}
+
+pub const CONTROLS: &[ControlKey] = &[
+    ControlKey {
+        keys: b"Dd",
+        control: Control::Mode,
+        help: "Log / dashboard",
+    },
+    ControlKey {
+        keys: b"Cc",
+        control: Control::Page,
+        help: "Next page (dashboard)",
+    },
+    ControlKey {
+        keys: b"?",
+        control: Control::Help,
+        help: "This help (dashboard)",
+    },
+    ControlKey {
+        keys: b"Yy",
+        control: Control::LayoutDump,
+        help: "Layout dump (log)",
+    },
+    ControlKey {
+        keys: b"Ii",
+        control: Control::Verify,
+        help: "Config verify (log)",
+    },
+    ControlKey {
+        keys: b"q",
+        control: Control::Record,
+        help: "Record macro on/off",
+    },
+    ControlKey {
+        keys: b"Q",
+        control: Control::StoreMacro,
+        help: "Store macro, then slot",
+    },
+    ControlKey {
+        keys: b"12345678",
+        control: Control::PlayMacro,
+        help: "Play macro",
+    },
+    ControlKey {
+        keys: b"!",
+        control: Control::FactoryReset,
+        help: "Factory reset (log)",
+    },
+    ControlKey {
+        keys: b"#",
+        control: Control::Lock,
+        help: "Performance lock",
+    },
+    ControlKey {
+        keys: b"%",
+        control: Control::SettleTest,
+        help: "Settle test (log)",
+    },
+    ControlKey {
+        keys: b"$",
+        control: Control::PracticeLog,
+        help: "Practice log (log)",
+    },
+    ControlKey {
+        keys: b"~",
+        control: Control::ReleaseBenchmark,
+        help: "Release benchmark (log)",
+    },
+];
+
+pub fn control(key: u8) -> Option<Control> {
+    CONTROLS
+        .iter()
+        .find(|c| c.keys.contains(&key))
+        .map(|c| c.control)
+}
+
+/// Writes `keys` for the help page: "a/A" for a few, "1-8" for a run.
+pub fn write_keys(out: &mut impl Write, keys: &[u8]) -> core::fmt::Result {
+    match keys {
+        [first, .., last] if keys.len() > 4 => write!(out, "{}-{}", *first as char, *last as char),
+        _ => {
+            for (i, &k) in keys.iter().enumerate() {
+                if i > 0 {
+                    out.write_char('/')?;
+                }
+                out.write_char(k as char)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// A help page entry: keys padded to a column, then what they do.
+pub fn help_entry(keys: &[u8], help: &str) -> String<40> {
+    let mut keys_text: String<16> = String::new();
+    let _ = write_keys(&mut keys_text, keys);
+    let mut entry = String::new();
+    let _ = write!(entry, "{:<8}{}", keys_text, help);
+    entry
+}
+
+/// Applies console command keys to the settings. Also used to replay recorded macros.
+pub fn apply_keys(data: &[u8]) {
+    let actions = data.iter().filter_map(|&key| {
+        COMMANDS.iter().find_map(|c| {
+            let i = c.keys.iter().position(|&k| k == key)?;
+            Some(c.actions[i.min(c.actions.len() - 1)])
+        })
+    });
+    for action in actions {
+        match action {
+            Action::Led(f) => crate::leds::update_config(f),
+            Action::Run(f) => f(),
+        }
+    }
+}
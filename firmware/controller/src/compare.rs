@@ -0,0 +1,68 @@
+//! A/B tuning comparison: two complete tuning specs, one in use and edited as usual,
+//! and a switch between them that retunes the held notes on the spot.
+
+use crate::keys::play_key;
+use crate::layouts::CurrentLayout;
+use crate::midi::{MidiEvent, ToU7};
+use crate::tuning::{retune_voices, set_spec, spec, TuningSpec};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use log::info;
+
+/// Velocity of held keys played again after a switch that can't bend them.
+const VELOCITY: u8 = 100;
+
+/// Whether B is in use, and the spec of the slot not in use. That starts out unset,
+/// so the first switch copies the tuning in use.
+static SLOTS: Mutex<CriticalSectionRawMutex, Cell<(bool, Option<TuningSpec>)>> =
+    Mutex::new(Cell::new((false, None)));
+
+/// Name of the slot in use.
+pub fn slot() -> char {
+    if SLOTS.lock(|s| s.get()).0 {
+        'B'
+    } else {
+        'A'
+    }
+}
+
+/// Stores the tuning in use into its slot and switches to the other one.
+pub fn switch() {
+    let current = spec();
+    let next = SLOTS.lock(|s| {
+        let (b, other) = s.get();
+        s.set((!b, Some(current)));
+        other.unwrap_or(current)
+    });
+    let sender = crate::keys::sender();
+    match sender {
+        _ if crate::audition::is_enabled() => crate::audition::retune(|| set_spec(next)),
+        Some(sender) if current.voices_follow(&next) => {
+            set_spec(next);
+            for (channel, value) in retune_voices::<CurrentLayout>() {
+                let _ = sender.try_send(MidiEvent::PitchBendChange { channel, value });
+            }
+        }
+        Some(sender) => {
+            // The held keys' note offs have to go out the way their note ons did
+            let held = crate::keys::active_keys();
+            for &coord in held.iter() {
+                play_key::<CurrentLayout>(coord, false, VELOCITY.to_u7(), &sender);
+            }
+            set_spec(next);
+            for &coord in held.iter() {
+                play_key::<CurrentLayout>(coord, true, VELOCITY.to_u7(), &sender);
+            }
+        }
+        // Nothing can be held before the scanner runs
+        None => set_spec(next),
+    }
+    info!(
+        "Tuning {}: {:?}, fifth {:.1}c, PBR {:.1}",
+        slot(),
+        next.mode,
+        next.fifth_size,
+        next.mpe_pbr
+    );
+}
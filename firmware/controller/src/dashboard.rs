@@ -0,0 +1,223 @@
+use core::cell::Cell;
+use core::fmt::Write;
+use embassy_rp::peripherals;
+use embassy_rp::usb::Driver;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+use heapless::{String, Vec};
+use lattice_board_core::screen::LineCache;
+
+/// USB full-speed CDC packet size.
+const PACKET_SIZE: usize = 64;
+/// Longest dashboard row; longer rows are cut off.
+pub const LINE_LEN: usize = 160;
+/// Rows tracked for diffing; rows below this are redrawn every frame.
+pub const MAX_ROWS: usize = 40;
+
+/// Dashboard rows drawn last frame, kept across frames by the serial task.
+pub type DashboardCache = LineCache<MAX_ROWS>;
+
+/// Dashboard pages; 'c' switches between them, '?' to and from help.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Page {
+    Main,
+    /// MPE channel allocation, for chasing voice leaks.
+    Channels,
+    /// Graphs of scan, LED and MIDI timing over the last few seconds.
+    Stats,
+    /// The practice log of the session.
+    Practice,
+    /// Console keys and key combos.
+    Help,
+}
+
+impl Page {
+    pub fn next(self) -> Self {
+        match self {
+            Page::Main => Page::Channels,
+            Page::Channels => Page::Stats,
+            Page::Stats => Page::Practice,
+            Page::Practice | Page::Help => Page::Main,
+        }
+    }
+}
+
+/// Longest single entry of a dashboard list.
+pub const ITEM_LEN: usize = 24;
+/// One entry of a dashboard list.
+pub type Item = String<ITEM_LEN>;
+
+const LIST_SEPARATOR: &str = " | ";
+/// Room kept on a list's last row for the "+N more" marker.
+const MORE_LEN: usize = 12;
+/// Entries scrolled per key press.
+const SCROLL_STEP: usize = 8;
+
+/// First remote voice shown in the dashboard's voice list.
+static VOICE_SCROLL: Mutex<CriticalSectionRawMutex, Cell<usize>> = Mutex::new(Cell::new(0));
+
+/// Scrolls the voice list by `pages` steps (negative scrolls back up).
+pub fn scroll_voices(pages: i8) {
+    VOICE_SCROLL.lock(|s| {
+        let delta = pages.unsigned_abs() as usize * SCROLL_STEP;
+        s.set(if pages < 0 {
+            s.get().saturating_sub(delta)
+        } else {
+            s.get() + delta
+        });
+    });
+}
+
+/// Current voice list scroll position, clamped so at least one voice stays visible.
+pub fn voice_scroll(total: usize) -> usize {
+    VOICE_SCROLL.lock(|s| {
+        let scroll = s.get().min(total.saturating_sub(1));
+        s.set(scroll);
+        scroll
+    })
+}
+
+/// Formats a list entry, cutting it off at `ITEM_LEN`.
+pub fn item(args: core::fmt::Arguments<'_>) -> Item {
+    let mut item = Item::new();
+    let _ = item.write_fmt(args);
+    item
+}
+
+/// Terminal dimensions as reported by the host; assumed 80x24 until it answers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TerminalSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl TerminalSize {
+    pub const DEFAULT: Self = Self { rows: 24, cols: 80 };
+
+    /// Usable row width, leaving the last column free so rows never auto-wrap.
+    fn width(&self) -> usize {
+        (self.cols as usize).saturating_sub(1).clamp(1, LINE_LEN)
+    }
+}
+
+/// Renders dashboard rows straight into 64-byte CDC packets.
+/// Only rows that changed since the last frame are sent, each positioned with a
+/// cursor escape, so a frame costs a few packets instead of a full-screen buffer.
+pub struct DashboardWriter<'a> {
+    class: &'a mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>,
+    cache: &'a mut DashboardCache,
+    packet: Vec<u8, PACKET_SIZE>,
+    row: usize,
+    size: TerminalSize,
+}
+
+impl<'a> DashboardWriter<'a> {
+    pub fn new(
+        class: &'a mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>,
+        cache: &'a mut DashboardCache,
+        size: TerminalSize,
+    ) -> Self {
+        Self {
+            class,
+            cache,
+            packet: Vec::new(),
+            row: 0,
+            size,
+        }
+    }
+
+    /// Writes the next row, skipping it if unchanged.
+    pub async fn line(&mut self, args: core::fmt::Arguments<'_>) {
+        let mut line: String<LINE_LEN> = String::new();
+        let _ = line.write_fmt(args);
+        line.truncate(self.size.width());
+        let row = self.row;
+        self.row += 1;
+        if !self.cache.update(row, line.as_bytes()) {
+            return;
+        }
+
+        let mut cursor: String<12> = String::new();
+        let _ = write!(cursor, "\x1B[{};1H", row + 1);
+        self.write(cursor.as_bytes()).await;
+        self.write(line.as_bytes()).await;
+        self.write(b"\x1B[K").await;
+    }
+
+    /// Writes `items` wrapped over at most `max_rows` rows, starting at entry `skip`.
+    /// Entries that don't fit are counted in a "+N more" marker instead of being cut off.
+    pub async fn list(
+        &mut self,
+        items: impl ExactSizeIterator<Item = Item>,
+        skip: usize,
+        max_rows: usize,
+    ) {
+        let total = items.len();
+        if total == 0 {
+            self.line(format_args!(" (None)")).await;
+            return;
+        }
+
+        let skip = skip.min(total - 1);
+        let mut row: String<LINE_LEN> = String::new();
+        let width = self.size.width().saturating_sub(1);
+        let mut rows = 1;
+        let mut shown = skip;
+        if skip > 0 {
+            let _ = write!(row, "+{} earlier{}", skip, LIST_SEPARATOR);
+        }
+        for (i, entry) in items.enumerate().skip(skip) {
+            let last = i + 1 == total;
+            let reserve = if rows == max_rows && !last {
+                MORE_LEN
+            } else {
+                0
+            };
+            if row.len() + entry.len() + LIST_SEPARATOR.len() + reserve > width {
+                if rows == max_rows {
+                    break;
+                }
+                self.line(format_args!("{}", row)).await;
+                row.clear();
+                rows += 1;
+            }
+            let _ = write!(row, "{}{}", entry, LIST_SEPARATOR);
+            shown = i + 1;
+        }
+        if shown < total {
+            let _ = write!(row, "+{} more", total - shown);
+        }
+        self.line(format_args!("{}", row)).await;
+    }
+
+    /// Ends the frame: clears rows left over from a taller previous frame and sends
+    /// the final partial packet.
+    pub async fn finish(mut self) {
+        if self.cache.finish(self.row) {
+            let mut cursor: String<12> = String::new();
+            let _ = write!(cursor, "\x1B[{};1H", self.row + 1);
+            self.write(cursor.as_bytes()).await;
+            self.write(b"\x1B[J").await;
+        }
+        self.flush().await;
+    }
+
+    async fn write(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let n = (PACKET_SIZE - self.packet.len()).min(bytes.len());
+            let _ = self.packet.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if self.packet.is_full() {
+                self.flush().await;
+            }
+        }
+    }
+
+    async fn flush(&mut self) {
+        if !self.packet.is_empty() {
+            let _ = self.class.write_packet(&self.packet).await;
+            self.packet.clear();
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! The defmt logger. Frames go to RTT while a debug probe reads them, and to a second
+//! CDC port otherwise (see [`crate::rtt`]), so the same build logs with or without a
+//! probe.
+//!
+//! The frames are only readable with the ELF they were built into: decode the serial
+//! port's on the host with `tools/defmt-cdc`.
+
+use crate::capacities::DEFMT_PIPE;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_rp::peripherals;
+use embassy_rp::usb::Driver;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::pipe::Pipe;
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+use heapless::Vec;
+
+/// Longest encoded frame; longer ones are dropped whole.
+const FRAME_LEN: usize = 256;
+
+// A frame must always fit into an empty pipe
+const _: () = assert!(DEFMT_PIPE >= FRAME_LEN);
+
+/// Encoded frames waiting for the host.
+static FRAMES: Pipe<CriticalSectionRawMutex, DEFMT_PIPE> = Pipe::new();
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+static mut RESTORE: critical_section::RestoreState = critical_section::RestoreState::invalid();
+static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+/// The frame being logged, held back until it's complete so a full pipe never gets
+/// half of one.
+static mut FRAME: Vec<u8, FRAME_LEN> = Vec::new();
+static mut FRAME_CUT: bool = false;
+
+fn buffer(bytes: &[u8]) {
+    // Only called between acquire and release, inside the critical section
+    unsafe {
+        let frame = &mut *core::ptr::addr_of_mut!(FRAME);
+        if frame.extend_from_slice(bytes).is_err() {
+            FRAME_CUT = true;
+        }
+    }
+}
+
+/// Queues a whole frame, or drops it if the host isn't keeping up.
+fn queue(frame: &[u8]) {
+    if FRAMES.free_capacity() < frame.len() {
+        crate::stats::record_log_drop();
+        return;
+    }
+    // The pipe is a ring, so a write can stop at its end; the rest goes in next
+    let mut written = 0;
+    while written < frame.len() {
+        match FRAMES.try_write(&frame[written..]) {
+            Ok(n) => written += n,
+            Err(_) => return,
+        }
+    }
+}
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        let restore = unsafe { critical_section::acquire() };
+        if TAKEN.load(Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly");
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+        unsafe {
+            RESTORE = restore;
+            (*core::ptr::addr_of_mut!(FRAME)).clear();
+            FRAME_CUT = false;
+            (*core::ptr::addr_of_mut!(ENCODER)).start_frame(buffer);
+        }
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        (*core::ptr::addr_of_mut!(ENCODER)).end_frame(buffer);
+        let frame = &*core::ptr::addr_of!(FRAME);
+        // Frames always go to RTT too, as that's how a newly attached probe is noticed
+        let sent = !FRAME_CUT && crate::rtt::write_frame(frame);
+        if FRAME_CUT || (crate::rtt::attached() && !sent) {
+            crate::stats::record_log_drop();
+        } else if !crate::rtt::attached() {
+            queue(frame);
+        }
+        TAKEN.store(false, Ordering::Relaxed);
+        critical_section::release(RESTORE);
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        (*core::ptr::addr_of_mut!(ENCODER)).write(bytes, buffer);
+    }
+}
+
+/// Sends queued frames while a host has the port open. Frames logged while nobody
+/// is listening wait in the pipe, so the start of a boot is kept until it fills.
+#[embassy_executor::task]
+pub async fn defmt_task(mut class: CdcAcmClass<'static, Driver<'static, peripherals::USB>>) {
+    let mut buf = [0u8; 64];
+    loop {
+        class.wait_connection().await;
+        loop {
+            let n = FRAMES.read(&mut buf).await;
+            if class.write_packet(&buf[..n]).await.is_err() {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Drift guard for long MPE sessions: a bend the host missed, say after a USB error,
+//! leaves a voice off pitch for as long as it's held. Every held voice's last sent
+//! bend is checked against its target now and then, and re-sent where they differ.
+
+use crate::layouts::CurrentLayout;
+use crate::midi::{MidiEvent, MidiSender};
+use embassy_time::{Duration, Timer};
+use heapless::Vec;
+use log::warn;
+use wmidi::Channel;
+
+const CHECK_PERIOD: Duration = Duration::from_millis(500);
+
+/// Corrects a voice only once it was off the same way at two checks in a row, so a
+/// bend still queued or held back by the latency offset isn't sent twice.
+#[embassy_executor::task]
+pub async fn drift_task(sender: MidiSender) {
+    let mut suspects: Vec<(Channel, u16), 16> = Vec::new();
+    loop {
+        Timer::after(CHECK_PERIOD).await;
+        let mut drifted = Vec::new();
+        for (channel, target) in crate::tuning::voice_targets::<CurrentLayout>() {
+            if crate::midi::sent_bend(channel) == Some(target) {
+                continue;
+            }
+            if suspects.contains(&(channel, target)) {
+                let event = MidiEvent::PitchBendChange {
+                    channel,
+                    value: target,
+                };
+                if sender.try_send(event).is_ok() {
+                    warn!("Drift guard: re-sent bend {} on {:?}", target, channel);
+                    crate::stats::record_drift_correction();
+                }
+            } else {
+                let _ = drifted.push((channel, target));
+            }
+        }
+        suspects = drifted;
+    }
+}
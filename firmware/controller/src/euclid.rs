@@ -0,0 +1,120 @@
+use crate::clock;
+use crate::layouts::CurrentLayout;
+use crate::midi::{MidiSender, ToU7};
+use crate::tuning::{get_key_pitch, get_midi_event};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Instant, Timer};
+use lattice_board_core::rhythm::{euclidean, is_pulse, MAX_STEPS};
+use log::info;
+
+/// Euclidean rhythm generator settings.
+/// While enabled, held keys don't sound directly; instead each pulse of the
+/// pattern plays the next held chord tone (lowest to highest).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EuclidConfig {
+    pub enabled: bool,
+    pub pulses: u8,
+    pub steps: u8,
+}
+
+static EUCLID_CONFIG: Mutex<CriticalSectionRawMutex, Cell<EuclidConfig>> =
+    Mutex::new(Cell::new(EuclidConfig {
+        enabled: false,
+        pulses: 3,
+        steps: 8,
+    }));
+
+static CURRENT_STEP: Mutex<CriticalSectionRawMutex, Cell<u8>> = Mutex::new(Cell::new(0));
+
+pub fn get_config() -> EuclidConfig {
+    EUCLID_CONFIG.lock(|c| c.get())
+}
+
+pub fn is_enabled() -> bool {
+    get_config().enabled
+}
+
+pub fn toggle() {
+    let enabled = EUCLID_CONFIG.lock(|c| {
+        let mut cfg = c.get();
+        cfg.enabled = !cfg.enabled;
+        c.set(cfg);
+        cfg.enabled
+    });
+    info!("Euclid {}", if enabled { "on" } else { "off" });
+}
+
+pub fn adjust_pulses(delta: i8) {
+    EUCLID_CONFIG.lock(|c| {
+        let mut cfg = c.get();
+        cfg.pulses = (cfg.pulses as i8 + delta).clamp(0, cfg.steps as i8) as u8;
+        c.set(cfg);
+    });
+}
+
+pub fn adjust_steps(delta: i8) {
+    EUCLID_CONFIG.lock(|c| {
+        let mut cfg = c.get();
+        cfg.steps = (cfg.steps as i8 + delta).clamp(1, MAX_STEPS as i8) as u8;
+        cfg.pulses = cfg.pulses.min(cfg.steps);
+        c.set(cfg);
+    });
+}
+
+/// Returns (pattern, steps, current step) while the generator is running, for the LED row display.
+pub fn display_state() -> Option<(u32, u8, u8)> {
+    let cfg = get_config();
+    if !cfg.enabled {
+        return None;
+    }
+    let step = CURRENT_STEP.lock(|s| s.get());
+    Some((euclidean(cfg.pulses, cfg.steps), cfg.steps, step))
+}
+
+#[embassy_executor::task]
+pub async fn euclid_task(sender: MidiSender) {
+    let mut step: u8 = 0;
+    let mut tone: usize = 0;
+    let mut next = Instant::now();
+
+    loop {
+        next += clock::step_duration();
+        Timer::at(next).await;
+
+        let cfg = get_config();
+        if !cfg.enabled {
+            step = 0;
+            tone = 0;
+            next = Instant::now();
+            continue;
+        }
+
+        step %= cfg.steps;
+        CURRENT_STEP.lock(|s| s.set(step));
+
+        if is_pulse(euclidean(cfg.pulses, cfg.steps), step) {
+            let mut chord = crate::keys::active_keys();
+            if !chord.is_empty() {
+                chord.sort_unstable_by(|a, b| {
+                    get_key_pitch::<CurrentLayout>(*a)
+                        .total_cmp(&get_key_pitch::<CurrentLayout>(*b))
+                });
+                let coord = chord[tone % chord.len()];
+                tone = tone.wrapping_add(1);
+
+                if let Some(event) = get_midi_event::<CurrentLayout>(coord, 100.to_u7(), true) {
+                    sender.send(event).await;
+                    // Gate is half a step
+                    Timer::after(clock::step_duration() / 2).await;
+                    if let Some(event) = get_midi_event::<CurrentLayout>(coord, 0.to_u7(), false) {
+                        sender.send(event).await;
+                    }
+                }
+            }
+        }
+
+        step = (step + 1) % cfg.steps;
+    }
+}
@@ -0,0 +1,39 @@
+use super::{write, write_read};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Timer};
+
+/// LIS3DH accelerometer.
+pub const ADDRESS: u8 = 0x18;
+
+pub const WHO_AM_I: u8 = 0x0F;
+/// 100 Hz, all axes enabled.
+const CTRL_REG1: [u8; 2] = [0x20, 0x57];
+/// Block data update, +-2g, high resolution (1 mg per digit).
+const CTRL_REG4: [u8; 2] = [0x23, 0x88];
+/// OUT_X_L with the auto-increment bit set, so one read returns all three axes.
+const OUT_XYZ: u8 = 0xA8;
+const POLL_PERIOD: Duration = Duration::from_millis(20);
+
+static TILT_MG: Mutex<CriticalSectionRawMutex, Cell<[i16; 3]>> = Mutex::new(Cell::new([0; 3]));
+
+/// Latest acceleration on x, y, z in milli-g.
+pub fn tilt_mg() -> [i16; 3] {
+    TILT_MG.lock(|t| t.get())
+}
+
+#[embassy_executor::task]
+pub async fn accel_task() {
+    let _ = write(ADDRESS, &CTRL_REG1).await;
+    let _ = write(ADDRESS, &CTRL_REG4).await;
+    loop {
+        let mut buf = [0u8; 6];
+        if write_read(ADDRESS, &[OUT_XYZ], &mut buf).await.is_ok() {
+            // Samples are 12-bit, left-justified
+            let axis = |i: usize| i16::from_le_bytes([buf[2 * i], buf[2 * i + 1]]) >> 4;
+            TILT_MG.lock(|t| t.set([axis(0), axis(1), axis(2)]));
+        }
+        Timer::after(POLL_PERIOD).await;
+    }
+}
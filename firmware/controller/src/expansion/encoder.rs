@@ -0,0 +1,98 @@
+use super::{write, write_read};
+use crate::fields::Field;
+use embassy_time::{Duration, Instant, Timer};
+
+/// Adafruit seesaw rotary encoder.
+pub const ADDRESS: u8 = 0x36;
+
+/// Seesaw encoder module, position register.
+const POSITION: [u8; 2] = [0x11, 0x30];
+const POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// Seesaw GPIO registers, taking a 32-bit pin mask, and the pin of the push switch.
+const GPIO_DIRCLR: [u8; 2] = [0x01, 0x03];
+const GPIO_BULK: [u8; 2] = [0x01, 0x04];
+const GPIO_SET: [u8; 2] = [0x01, 0x05];
+const GPIO_PULLENSET: [u8; 2] = [0x01, 0x0B];
+const SWITCH_MASK: u32 = 1 << 24;
+
+/// Holding the switch this long toggles palette editing.
+const LONG_PRESS: Duration = Duration::from_millis(800);
+
+/// Makes the switch pin an input pulled up; pressing pulls it low.
+async fn setup_switch() {
+    for register in [GPIO_DIRCLR, GPIO_PULLENSET, GPIO_SET] {
+        let mut bytes = [register[0], register[1], 0, 0, 0, 0];
+        bytes[2..].copy_from_slice(&SWITCH_MASK.to_be_bytes());
+        let _ = write(ADDRESS, &bytes).await;
+    }
+}
+
+async fn switch_pressed() -> Option<bool> {
+    let mut buf = [0u8; 4];
+    write_read(ADDRESS, &GPIO_BULK, &mut buf).await.ok()?;
+    Some(u32::from_be_bytes(buf) & SWITCH_MASK == 0)
+}
+
+/// Turning the encoder steps LED brightness, one detent per step, or edits the palette
+/// while palette editing is on (see [`crate::palette_edit`]). A long press toggles
+/// palette editing and a short one picks what turning edits. Ignored while the
+/// performance lock is on.
+#[embassy_executor::task]
+pub async fn encoder_task() {
+    setup_switch().await;
+    let mut last: Option<i32> = None;
+    // When the switch went down, until the press is handled
+    let mut pressed_at: Option<Instant> = None;
+    let mut long_handled = false;
+    loop {
+        if let Some(down) = switch_pressed().await {
+            match (down, pressed_at) {
+                (true, None) => {
+                    pressed_at = Some(Instant::now());
+                    long_handled = false;
+                }
+                (true, Some(at)) if !long_handled && at.elapsed() >= LONG_PRESS => {
+                    long_handled = true;
+                    if !crate::lock::is_locked() {
+                        crate::palette_edit::toggle();
+                    }
+                }
+                (false, Some(_)) => {
+                    pressed_at = None;
+                    if !long_handled && !crate::lock::is_locked() {
+                        crate::palette_edit::next_channel();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut buf = [0u8; 4];
+        if write_read(ADDRESS, &POSITION, &mut buf).await.is_ok() {
+            // Seesaw counts clockwise turns down
+            let position = -i32::from_be_bytes(buf);
+            if let Some(last) = last {
+                let delta = position.wrapping_sub(last);
+                if delta != 0 {
+                    crate::power::note_activity();
+                }
+                // Turns while locked are dropped, not applied on unlocking
+                let steps = if crate::lock::is_locked() {
+                    0
+                } else {
+                    delta.unsigned_abs().min(16)
+                };
+                for _ in 0..steps {
+                    if crate::palette_edit::is_editing() {
+                        crate::palette_edit::turn(delta.signum() as i8);
+                    } else {
+                        Field::Brightness.adjust(delta.signum() as i8);
+                    }
+                }
+            }
+            last = Some(position);
+        }
+        Timer::after(POLL_PERIOD).await;
+    }
+}
@@ -0,0 +1,180 @@
+use core::cell::Cell;
+use core::fmt::Write;
+use embassy_executor::{SpawnError, Spawner};
+use embassy_rp::i2c::{Async, I2c};
+use embassy_rp::peripherals::I2C0;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{with_timeout, Duration};
+use log::{error, info};
+
+pub mod accel;
+pub mod encoder;
+pub mod oled;
+pub mod satellite;
+pub mod touch;
+
+// Expansion modules hang off I2C0 on GPIO4 (SDA) / GPIO5 (SCL), which are free on
+// every layout. Whatever answers at boot gets its task spawned, so add-ons can be
+// mixed freely without a cargo feature per combination.
+
+pub type Bus = I2c<'static, I2C0, Async>;
+
+/// The shared bus; module tasks hold it for one transaction at a time.
+static BUS: embassy_sync::mutex::Mutex<CriticalSectionRawMutex, Option<Bus>> =
+    embassy_sync::mutex::Mutex::new(None);
+
+/// Longest a single transaction may take, so a bus without pull-ups can't hang a task.
+const TRANSACTION_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// A known add-on and how to start it.
+pub struct Module {
+    pub name: &'static str,
+    pub address: u8,
+    /// Harmless byte written to check the module answers (usually a register pointer).
+    probe: u8,
+    spawn: fn(Spawner) -> Result<(), SpawnError>,
+}
+
+/// Every module the firmware knows about, probed in this order.
+pub const MODULES: [Module; 4] = [
+    Module {
+        name: "OLED",
+        address: oled::ADDRESS,
+        probe: 0x00,
+        spawn: |s| s.spawn(oled::oled_task()),
+    },
+    Module {
+        name: "Encoder",
+        address: encoder::ADDRESS,
+        probe: 0x00,
+        spawn: |s| s.spawn(encoder::encoder_task()),
+    },
+    Module {
+        name: "Tilt",
+        address: accel::ADDRESS,
+        probe: accel::WHO_AM_I,
+        spawn: |s| s.spawn(accel::accel_task()),
+    },
+    Module {
+        name: "Touch",
+        address: touch::ADDRESS,
+        probe: 0x00,
+        spawn: |s| s.spawn(touch::touch_task()),
+    },
+];
+
+/// Bit `i` set if `MODULES[i]` was found at boot.
+static DETECTED: Mutex<CriticalSectionRawMutex, Cell<u8>> = Mutex::new(Cell::new(0));
+
+/// Probes the bus for every known module and spawns the tasks of those present.
+pub async fn init(spawner: Spawner, bus: Bus) {
+    *BUS.lock().await = Some(bus);
+
+    let mut detected = 0;
+    for (i, module) in MODULES.iter().enumerate() {
+        if write(module.address, &[module.probe]).await.is_err() {
+            continue;
+        }
+        match (module.spawn)(spawner) {
+            Ok(()) => {
+                info!("Expansion: {} at {:#04x}", module.name, module.address);
+                detected |= 1 << i;
+            }
+            Err(_) => error!("Expansion: could not start {}", module.name),
+        }
+    }
+    DETECTED.lock(|d| d.set(detected));
+
+    // Satellite keys sit wherever the layout declares them
+    if let Some(keys) = crate::layouts::satellite() {
+        if write(keys.address, &[0x00]).await.is_ok() {
+            match spawner.spawn(satellite::satellite_task(keys)) {
+                Ok(()) => info!(
+                    "Expansion: {} satellite keys on {:?} at {:#04x}",
+                    keys.keys, keys.expander, keys.address
+                ),
+                Err(_) => error!("Expansion: could not start satellite keys"),
+            }
+        }
+    }
+}
+
+/// Modules found at boot.
+pub fn detected() -> impl Iterator<Item = &'static Module> {
+    let mask = DETECTED.lock(|d| d.get());
+    MODULES
+        .iter()
+        .enumerate()
+        .filter(move |(i, _)| mask & (1 << i) != 0)
+        .map(|(_, m)| m)
+}
+
+/// Writes a dashboard summary of the attached modules and their latest readings.
+pub fn write_summary(out: &mut impl Write) -> core::fmt::Result {
+    let mut any = false;
+    for module in detected() {
+        if any {
+            write!(out, " | ")?;
+        }
+        any = true;
+        write!(out, "{}", module.name)?;
+        if module.address == accel::ADDRESS {
+            let [x, y, z] = accel::tilt_mg();
+            write!(out, " {},{},{}mg", x, y, z)?;
+        } else if module.address == touch::ADDRESS {
+            match touch::position() {
+                Some(p) => write!(out, " {}", p)?,
+                None => write!(out, " -")?,
+            }
+        }
+    }
+    if satellite::found() {
+        if any {
+            write!(out, " | ")?;
+        }
+        any = true;
+        write!(out, "Satellite")?;
+    }
+    if !any {
+        write!(out, "None")?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum BusError {
+    /// Nothing answered, or the transfer failed.
+    Transfer,
+    Timeout,
+}
+
+/// Writes `bytes` to the module at `address`.
+pub async fn write(address: u8, bytes: &[u8]) -> Result<(), BusError> {
+    let mut bus = BUS.lock().await;
+    let Some(bus) = bus.as_mut() else {
+        return Err(BusError::Transfer);
+    };
+    with_timeout(
+        TRANSACTION_TIMEOUT,
+        bus.write_async(address, bytes.iter().copied()),
+    )
+    .await
+    .map_err(|_| BusError::Timeout)?
+    .map_err(|_| BusError::Transfer)
+}
+
+/// Writes `bytes` (usually a register address) then reads into `buf`.
+pub async fn write_read(address: u8, bytes: &[u8], buf: &mut [u8]) -> Result<(), BusError> {
+    let mut bus = BUS.lock().await;
+    let Some(bus) = bus.as_mut() else {
+        return Err(BusError::Transfer);
+    };
+    with_timeout(
+        TRANSACTION_TIMEOUT,
+        bus.write_read_async(address, bytes.iter().copied(), buf),
+    )
+    .await
+    .map_err(|_| BusError::Timeout)?
+    .map_err(|_| BusError::Transfer)
+}
@@ -0,0 +1,50 @@
+use super::write;
+use log::error;
+
+/// SSD1306 128x64 OLED.
+pub const ADDRESS: u8 = 0x3C;
+
+/// Control byte prefixes: a run of commands, or display data.
+const COMMANDS: u8 = 0x00;
+const DATA: u8 = 0x40;
+
+const INIT: &[u8] = &[
+    COMMANDS, 0xAE, // Display off
+    0xD5, 0x80, // Clock divide
+    0xA8, 0x3F, // Multiplex 64
+    0xD3, 0x00, // No display offset
+    0x40, // Start line 0
+    0x8D, 0x14, // Charge pump on
+    0x20, 0x00, // Horizontal addressing
+    0xA1, 0xC8, // Flip to match the usual module orientation
+    0xDA, 0x12, // COM pins
+    0x81, 0xCF, // Contrast
+    0xD9, 0xF1, // Precharge
+    0xDB, 0x40, // VCOM detect
+    0xA4, 0xA6, // Show RAM, not inverted
+    0x21, 0x00, 0x7F, // Column range
+    0x22, 0x00, 0x07, // Page range
+];
+const DISPLAY_ON: &[u8] = &[COMMANDS, 0xAF];
+
+/// Bytes of display RAM, one bit per pixel.
+const RAM_SIZE: usize = 128 * 64 / 8;
+const CHUNK: usize = 16;
+
+/// Brings the panel up blank, so it doesn't show power-on noise. Nothing draws on it yet.
+#[embassy_executor::task]
+pub async fn oled_task() {
+    if write(ADDRESS, INIT).await.is_err() {
+        error!("OLED init failed");
+        return;
+    }
+    let mut blank = [0u8; CHUNK + 1];
+    blank[0] = DATA;
+    for _ in 0..RAM_SIZE / CHUNK {
+        if write(ADDRESS, &blank).await.is_err() {
+            error!("OLED clear failed");
+            return;
+        }
+    }
+    let _ = write(ADDRESS, DISPLAY_ON).await;
+}
@@ -0,0 +1,80 @@
+//! Satellite keys: a cluster wired to an I2C GPIO expander, such as a detached bass
+//! row (see [`crate::layouts::Satellite`]). Its keys are debounced like matrix keys
+//! and played on the scanners' channel, so they join the same event stream.
+
+use super::{write, write_read, BusError};
+use crate::layouts::{CurrentLayout, Expander, Satellite};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use lattice_board_core::debounce::Debouncer;
+use lattice_board_core::layout::Layout;
+use log::error;
+
+const POLL_PERIOD: Duration = Duration::from_millis(2);
+
+// MCP23017 registers in the default bank layout, where each port B register follows
+// its port A one.
+const MCP_IODIRA: u8 = 0x00;
+const MCP_GPPUA: u8 = 0x0C;
+const MCP_GPIOA: u8 = 0x12;
+// TCA9555 registers, port 1 following port 0.
+const TCA_INPUT0: u8 = 0x00;
+const TCA_CONFIG0: u8 = 0x06;
+
+static FOUND: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// Whether the satellite keys are up and polled.
+pub fn found() -> bool {
+    FOUND.lock(|f| f.get())
+}
+
+/// Makes every pin an input, pulled up where the chip can.
+async fn configure(satellite: &Satellite) -> Result<(), BusError> {
+    let address = satellite.address;
+    match satellite.expander {
+        Expander::Mcp23017 => {
+            write(address, &[MCP_IODIRA, 0xFF, 0xFF]).await?;
+            write(address, &[MCP_GPPUA, 0xFF, 0xFF]).await
+        }
+        Expander::Tca9555 => write(address, &[TCA_CONFIG0, 0xFF, 0xFF]).await,
+    }
+}
+
+#[embassy_executor::task]
+pub async fn satellite_task(satellite: Satellite) {
+    if let Err(e) = configure(&satellite).await {
+        error!("Satellite keys: setup failed ({:?})", e);
+        return;
+    }
+    FOUND.lock(|f| f.set(true));
+
+    let input = match satellite.expander {
+        Expander::Mcp23017 => MCP_GPIOA,
+        Expander::Tca9555 => TCA_INPUT0,
+    };
+    let center = CurrentLayout::center_coord();
+    let mut keys = [Debouncer::new(); 16];
+    loop {
+        let mut buf = [0u8; 2];
+        if write_read(satellite.address, &[input], &mut buf)
+            .await
+            .is_ok()
+        {
+            // Pressed keys pull their pin to ground
+            let pressed = !u16::from_le_bytes(buf);
+            let now = Instant::now().as_micros();
+            let hold = crate::keys::debounce_us() as u64;
+            for (pin, key) in keys.iter_mut().enumerate().take(satellite.keys as usize) {
+                let Some(settled) = key.update(pressed & (1 << pin) != 0, now, hold) else {
+                    continue;
+                };
+                if let Some(coord) = satellite.coord(center, pin as u8) {
+                    crate::keys::play(coord, 100, settled.pressed);
+                }
+            }
+        }
+        Timer::after(crate::power::scan_period(POLL_PERIOD)).await;
+    }
+}
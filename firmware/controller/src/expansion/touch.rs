@@ -0,0 +1,52 @@
+use super::{write, write_read};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Timer};
+
+/// MPR121 capacitive sensor, its electrodes laid out in a row as a touch strip.
+pub const ADDRESS: u8 = 0x5A;
+
+pub const ELECTRODES: u8 = 12;
+const SOFT_RESET: [u8; 2] = [0x80, 0x63];
+/// First touch/release threshold register; electrodes follow in pairs.
+const THRESHOLDS: u8 = 0x41;
+const TOUCH_THRESHOLD: u8 = 12;
+const RELEASE_THRESHOLD: u8 = 6;
+/// Run mode with baseline tracking on all electrodes.
+const START: [u8; 2] = [0x5E, 0x80 | ELECTRODES];
+const TOUCH_STATUS: u8 = 0x00;
+const POLL_PERIOD: Duration = Duration::from_millis(10);
+
+static POSITION: Mutex<CriticalSectionRawMutex, Cell<Option<u8>>> = Mutex::new(Cell::new(None));
+
+/// Touched electrode (0 at the strip's start), averaged when several are touched.
+pub fn position() -> Option<u8> {
+    POSITION.lock(|p| p.get())
+}
+
+#[embassy_executor::task]
+pub async fn touch_task() {
+    let _ = write(ADDRESS, &SOFT_RESET).await;
+    for e in 0..ELECTRODES {
+        let reg = THRESHOLDS + 2 * e;
+        let _ = write(ADDRESS, &[reg, TOUCH_THRESHOLD]).await;
+        let _ = write(ADDRESS, &[reg + 1, RELEASE_THRESHOLD]).await;
+    }
+    let _ = write(ADDRESS, &START).await;
+    loop {
+        let mut buf = [0u8; 2];
+        if write_read(ADDRESS, &[TOUCH_STATUS], &mut buf).await.is_ok() {
+            let touched = u16::from_le_bytes(buf) & ((1 << ELECTRODES) - 1);
+            let count = touched.count_ones() as u16;
+            let position = (count > 0).then(|| {
+                let sum: u16 = (0..ELECTRODES as u16)
+                    .filter(|e| touched & (1 << e) != 0)
+                    .sum();
+                (sum / count) as u8
+            });
+            POSITION.lock(|p| p.set(position));
+        }
+        Timer::after(POLL_PERIOD).await;
+    }
+}
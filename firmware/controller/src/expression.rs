@@ -0,0 +1,173 @@
+//! Expression output: continuous sources on the expansion modules sent to the host as
+//! CCs, in 14-bit (an MSB/LSB pair) where the receiving end takes it.
+
+use crate::midi::{MidiEvent, MidiSender};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Timer};
+use lattice_board_core::cc_map::unscale;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    TiltX,
+    TiltY,
+    Touch,
+}
+
+impl Source {
+    pub const ALL: [Source; 3] = [Source::TiltX, Source::TiltY, Source::Touch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::TiltX => "Tilt X",
+            Source::TiltY => "Tilt Y",
+            Source::Touch => "Touch strip",
+        }
+    }
+
+    /// Current reading as a 14-bit value, `None` while there's nothing to send.
+    fn value14(self) -> Option<u16> {
+        let detected = |address| crate::expansion::detected().any(|m| m.address == address);
+        match self {
+            Source::TiltX | Source::TiltY if detected(crate::expansion::accel::ADDRESS) => {
+                let [x, y, _] = crate::expansion::accel::tilt_mg();
+                let mg = if self == Source::TiltX { x } else { y };
+                Some(unscale(mg as f32, -TILT_RANGE_MG, TILT_RANGE_MG))
+            }
+            // A released strip holds its last value
+            Source::Touch => crate::expansion::touch::position().map(|p| {
+                unscale(
+                    p as f32,
+                    0.0,
+                    (crate::expansion::touch::ELECTRODES - 1) as f32,
+                )
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Tilt either way that reaches the ends of the controller range: 1 g, the board on
+/// its edge.
+const TILT_RANGE_MG: f32 = 1000.0;
+const TICK: Duration = Duration::from_millis(20);
+/// CCs with an LSB partner, 32 above.
+const HI_RES_CCS: u8 = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Route {
+    /// `None` when the source isn't sent.
+    cc: Option<u8>,
+    /// Whether the destination takes 14-bit values on CCs 0-31. Off for synths that
+    /// treat the LSB CC as a controller of its own.
+    hi_res: bool,
+}
+
+const IDLE_ROUTE: Route = Route {
+    cc: None,
+    hi_res: true,
+};
+
+static ROUTES: Mutex<CriticalSectionRawMutex, Cell<[Route; 3]>> =
+    Mutex::new(Cell::new([IDLE_ROUTE; 3]));
+/// Source shown and edited on the dashboard.
+static SELECTED: Mutex<CriticalSectionRawMutex, Cell<Source>> =
+    Mutex::new(Cell::new(Source::TiltX));
+
+pub fn selected() -> Source {
+    SELECTED.lock(|s| s.get())
+}
+
+pub fn cycle_selected(delta: i8) {
+    SELECTED.lock(|s| {
+        let i = (s.get() as i32 + delta as i32).rem_euclid(Source::ALL.len() as i32);
+        s.set(Source::ALL[i as usize]);
+    });
+}
+
+fn update_route(f: impl FnOnce(&mut Route)) {
+    let source = selected();
+    ROUTES.lock(|r| {
+        let mut routes = r.get();
+        f(&mut routes[source as usize]);
+        r.set(routes);
+    });
+}
+
+fn route(source: Source) -> Route {
+    ROUTES.lock(|r| r.get()[source as usize])
+}
+
+/// Steps the selected source's CC through Off and CCs 1-119 (bank select and the
+/// channel mode messages left out).
+pub fn cycle_cc(delta: i8) {
+    update_route(|route| {
+        let next = (route.cc.unwrap_or(0) as i32 + delta as i32).rem_euclid(120);
+        route.cc = (next > 0).then_some(next as u8);
+    });
+}
+
+pub fn toggle_hi_res() {
+    update_route(|route| route.hi_res = !route.hi_res);
+}
+
+/// Writes the selected source's CC and resolution.
+pub fn write_selected(out: &mut impl core::fmt::Write) -> core::fmt::Result {
+    let route = route(selected());
+    match route.cc {
+        None => write!(out, "Off"),
+        Some(cc) if route.hi_res && cc < HI_RES_CCS => write!(out, "CC{}/{} 14-bit", cc, cc + 32),
+        Some(cc) => write!(out, "CC{} 7-bit", cc),
+    }
+}
+
+pub fn hi_res() -> bool {
+    route(selected()).hi_res
+}
+
+/// Sends each routed source when its value changes, on channel 1 (the MPE master
+/// channel, so it reaches every voice).
+#[embassy_executor::task]
+pub async fn expression_task(sender: MidiSender) {
+    // What each source last sent, with the route it went out on
+    let mut sent: [Option<(Route, u16)>; 3] = [None; 3];
+    loop {
+        Timer::after(TICK).await;
+        for source in Source::ALL {
+            let route = route(source);
+            let (Some(cc), Some(value)) = (route.cc, source.value14()) else {
+                continue;
+            };
+            let hi_res = route.hi_res && cc < HI_RES_CCS;
+            let last = &mut sent[source as usize];
+            let unchanged = last.is_some_and(|(r, v)| {
+                r == route
+                    && if hi_res {
+                        v == value
+                    } else {
+                        v >> 7 == value >> 7
+                    }
+            });
+            if unchanged {
+                continue;
+            }
+            let event = if hi_res {
+                MidiEvent::ControlChange14 {
+                    channel: wmidi::Channel::Ch1,
+                    control: cc,
+                    value,
+                }
+            } else {
+                MidiEvent::ControlChange {
+                    channel: wmidi::Channel::Ch1,
+                    control: cc,
+                    value: (value >> 7) as u8,
+                }
+            };
+            if sender.try_send(event).is_ok() {
+                *last = Some((route, value));
+            }
+        }
+    }
+}
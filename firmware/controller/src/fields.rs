@@ -0,0 +1,467 @@
+use crate::modulation::Target;
+use crate::tuning::TuningMode;
+use core::fmt::Write;
+use lattice_board_core::pitch::{write_pitch_classes, PITCH_CLASS_NAMES};
+
+/// Settings that can be selected on the dashboard with Up/Down and edited with
+/// Left/Right (Enter flips on/off settings).
+///
+/// The order gives each its NRPN number (see [`crate::nrpn`]), so new settings go at
+/// the end; [`FIELDS`] sets where they show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Brightness,
+    Transpose,
+    Hue,
+    Gradient,
+    Guides,
+    Landmarks,
+    Coloring,
+    Intervals,
+    VoiceLeading,
+    Zone,
+    DrumVelocity,
+    BendStep,
+    GlideRow,
+    TremoloRate,
+    Humanize,
+    LatencyOffset,
+    Debounce,
+    Settle,
+    ThermalLimit,
+    Sleep,
+    Release,
+    Frame,
+    Anchor,
+    Red,
+    Green,
+    Blue,
+    Mode,
+    Fifth,
+    Audition,
+    Pbr,
+    Bpm,
+    Euclid,
+    Pulses,
+    Steps,
+    Walk,
+    WalkStep,
+    Scale,
+    Lfo,
+    LfoShape,
+    LfoPeriod,
+    LfoDepth,
+    Expr,
+    ExprCc,
+    ExprHiRes,
+    CcLearn,
+    CcFeedback,
+    Thru,
+    ThruChannel,
+    EchoWindow,
+    Naming,
+    Spelling,
+    Tonic,
+    VelocityCurve,
+    BootProfile,
+    BootDelay,
+    BootKeyMap,
+    BootAnimation,
+    Arp,
+    ArpPattern,
+    ArpRate,
+    ArpGate,
+    ArpOctaves,
+}
+
+/// Dashboard selection order.
+pub const FIELDS: [Field; 62] = [
+    Field::Brightness,
+    Field::Transpose,
+    Field::Hue,
+    Field::Gradient,
+    Field::Guides,
+    Field::Landmarks,
+    Field::Coloring,
+    Field::Intervals,
+    Field::VoiceLeading,
+    Field::Zone,
+    Field::DrumVelocity,
+    Field::BendStep,
+    Field::GlideRow,
+    Field::TremoloRate,
+    Field::Humanize,
+    Field::LatencyOffset,
+    Field::Debounce,
+    Field::Settle,
+    Field::VelocityCurve,
+    Field::BootProfile,
+    Field::BootDelay,
+    Field::BootKeyMap,
+    Field::BootAnimation,
+    Field::ThermalLimit,
+    Field::Sleep,
+    Field::Release,
+    Field::Frame,
+    Field::Anchor,
+    Field::Red,
+    Field::Green,
+    Field::Blue,
+    Field::Mode,
+    Field::Fifth,
+    Field::Audition,
+    Field::Pbr,
+    Field::Bpm,
+    Field::Euclid,
+    Field::Pulses,
+    Field::Steps,
+    Field::Walk,
+    Field::WalkStep,
+    Field::Scale,
+    Field::Arp,
+    Field::ArpPattern,
+    Field::ArpRate,
+    Field::ArpGate,
+    Field::ArpOctaves,
+    Field::Lfo,
+    Field::LfoShape,
+    Field::LfoPeriod,
+    Field::LfoDepth,
+    Field::Expr,
+    Field::ExprCc,
+    Field::ExprHiRes,
+    Field::CcLearn,
+    Field::CcFeedback,
+    Field::Thru,
+    Field::ThruChannel,
+    Field::EchoWindow,
+    Field::Naming,
+    Field::Spelling,
+    Field::Tonic,
+];
+
+fn step_u8(v: u8, delta: i16) -> u8 {
+    (v as i16 + delta).clamp(0, 255) as u8
+}
+
+impl Field {
+    pub fn label(self) -> &'static str {
+        match self {
+            Field::Brightness => "Brightness",
+            Field::Transpose => "Red pitch class",
+            Field::Hue => "Hue rotation",
+            Field::Gradient => "Octave gradient",
+            Field::Guides => "Guides",
+            Field::Landmarks => "Landmarks",
+            Field::Coloring => "Coloring",
+            Field::Intervals => "Interval hints",
+            Field::VoiceLeading => "Voice leading",
+            Field::Zone => "Zone",
+            Field::DrumVelocity => "Drum velocity",
+            Field::BendStep => "Bend key step",
+            Field::GlideRow => "Glide row",
+            Field::TremoloRate => "Tremolo rate",
+            Field::Humanize => "Humanize",
+            Field::LatencyOffset => "Latency offset",
+            Field::Debounce => "Key debounce",
+            Field::Settle => "Column settle",
+            Field::VelocityCurve => "Velocity curve",
+            Field::BootProfile => "Boot profile",
+            Field::BootDelay => "Boot delay",
+            Field::BootKeyMap => "Boot key map",
+            Field::BootAnimation => "Boot animation",
+            Field::ThermalLimit => "Thermal limit",
+            Field::Sleep => "Sleep after",
+            Field::Release => "Release",
+            Field::Frame => "Frame",
+            Field::Anchor => "Palette index",
+            Field::Red => "Red",
+            Field::Green => "Green",
+            Field::Blue => "Blue",
+            Field::Mode => "Mode",
+            Field::Fifth => "Fifth",
+            Field::Audition => "Tuning audition",
+            Field::Pbr => "PBR",
+            Field::Bpm => "BPM",
+            Field::Euclid => "Euclid",
+            Field::Pulses => "Euclid pulses",
+            Field::Steps => "Euclid steps",
+            Field::Walk => "Walk",
+            Field::WalkStep => "Walk step",
+            Field::Scale => "Walk scale",
+            Field::Arp => "Arp",
+            Field::ArpPattern => "Arp pattern",
+            Field::ArpRate => "Arp rate",
+            Field::ArpGate => "Arp gate",
+            Field::ArpOctaves => "Arp octaves",
+            Field::Lfo => "LFO on",
+            Field::LfoShape => "LFO shape",
+            Field::LfoPeriod => "LFO period",
+            Field::LfoDepth => "LFO depth",
+            Field::Expr => "Expression",
+            Field::ExprCc => "Expression CC",
+            Field::ExprHiRes => "Expression 14-bit",
+            Field::CcLearn => "CC learn",
+            Field::CcFeedback => "CC feedback",
+            Field::Thru => "Thru",
+            Field::ThruChannel => "Thru channel",
+            Field::EchoWindow => "Echo window",
+            Field::Naming => "Note names",
+            Field::Spelling => "Accidentals",
+            Field::Tonic => "Key tonic",
+        }
+    }
+
+    /// Steps the setting up (`direction` > 0) or down; on/off settings flip either way.
+    pub fn adjust(self, direction: i8) {
+        let d = direction.signum();
+        match self {
+            Field::Brightness => crate::leds::update_config(|c| {
+                c.brightness = (c.brightness + 0.01 * d as f32).clamp(0.0, 1.0)
+            }),
+            Field::Transpose => crate::leds::update_config(|c| {
+                c.transpose = (c.transpose as i8 + d).rem_euclid(12) as u8
+            }),
+            Field::Hue => crate::leds::update_config(|c| {
+                c.hue_rotation = (c.hue_rotation + d as f32 + 360.0) % 360.0
+            }),
+            Field::Gradient => crate::leds::update_config(|c| {
+                c.octave_gradient = (c.octave_gradient + 0.05 * d as f32).clamp(0.0, 0.5)
+            }),
+            Field::Guides => crate::leds::update_config(|c| {
+                c.guides = if d > 0 {
+                    c.guides.next()
+                } else {
+                    c.guides.prev()
+                }
+            }),
+            Field::Landmarks => crate::leds::update_config(|c| {
+                c.landmarks = crate::leds::cycle_landmarks(c.landmarks, d)
+            }),
+            Field::Coloring => crate::leds::update_config(|c| {
+                c.coloring = if d > 0 {
+                    c.coloring.next()
+                } else {
+                    c.coloring.prev()
+                }
+            }),
+            Field::Intervals => crate::leds::update_config(|c| c.intervals = !c.intervals),
+            Field::VoiceLeading => crate::voice_leading::toggle(),
+            Field::Zone => crate::zones::cycle_kind(d),
+            Field::DrumVelocity => crate::zones::cycle_drum_velocity(d),
+            Field::BendStep => crate::zones::cycle_bend_divisions(d),
+            Field::GlideRow => crate::glide::cycle_row(d),
+            Field::TremoloRate => crate::tremolo::cycle_rate(d),
+            Field::Humanize => crate::midi::toggle_humanize(),
+            Field::LatencyOffset => crate::midi::adjust_latency_offset(d),
+            Field::Debounce => crate::keys::adjust_debounce(d),
+            Field::Settle => crate::keys::adjust_settle(d),
+            Field::VelocityCurve => crate::keys::cycle_velocity_curve(d),
+            Field::BootProfile => crate::boot::cycle_profile(d),
+            Field::BootDelay => crate::boot::toggle_delay(),
+            Field::BootKeyMap => crate::boot::toggle_key_map(),
+            Field::BootAnimation => crate::boot::toggle_animation(),
+            Field::ThermalLimit => crate::leds::update_config(|c| {
+                c.thermal_limit_c = (c.thermal_limit_c as i16 + 5 * d as i16).clamp(0, 90) as u8
+            }),
+            Field::Sleep => crate::power::adjust_sleep_after(30 * d as i32),
+            Field::Release => crate::leds::update_config(|c| {
+                c.release_ms = (c.release_ms as i32 + 50 * d as i32).clamp(0, 5000) as u32
+            }),
+            Field::Frame => crate::leds::update_config(|c| {
+                c.frame_ms = (c.frame_ms as i32 + d as i32).clamp(1, 50) as u32
+            }),
+            Field::Anchor => crate::leds::update_config(|c| {
+                c.selected_anchor = (c.selected_anchor as i32 + d as i32).rem_euclid(12) as usize
+            }),
+            Field::Red => crate::leds::update_config(|c| {
+                let rgb = &mut c.rgb_anchors[c.selected_anchor];
+                rgb.r = step_u8(rgb.r, 5 * d as i16);
+            }),
+            Field::Green => crate::leds::update_config(|c| {
+                let rgb = &mut c.rgb_anchors[c.selected_anchor];
+                rgb.g = step_u8(rgb.g, 5 * d as i16);
+            }),
+            Field::Blue => crate::leds::update_config(|c| {
+                let rgb = &mut c.rgb_anchors[c.selected_anchor];
+                rgb.b = step_u8(rgb.b, 5 * d as i16);
+            }),
+            Field::Mode => crate::audition::retune(|| {
+                let _ = crate::tuning::toggle_mode();
+            }),
+            Field::Fifth => {
+                crate::audition::retune(|| crate::tuning::adjust_fifth_size(0.1 * d as f32))
+            }
+            Field::Audition => crate::audition::toggle(),
+            Field::Pbr => crate::tuning::adjust_mpe_pbr(d as f32),
+            Field::Bpm => crate::clock::adjust_bpm(d as f32),
+            Field::Euclid => crate::euclid::toggle(),
+            Field::Pulses => crate::euclid::adjust_pulses(d),
+            Field::Steps => crate::euclid::adjust_steps(d),
+            Field::Walk => crate::walk::toggle(),
+            Field::WalkStep => crate::walk::adjust_step_size(d),
+            Field::Scale => crate::walk::cycle_scale(d),
+            Field::Arp => crate::arp::toggle(),
+            Field::ArpPattern => crate::arp::cycle_pattern(d),
+            Field::ArpRate => crate::arp::cycle_rate(d),
+            Field::ArpGate => crate::arp::adjust_gate(d),
+            Field::ArpOctaves => crate::arp::adjust_octaves(d),
+            Field::Lfo => crate::modulation::cycle_selected(d),
+            Field::LfoShape => crate::modulation::cycle_shape(d),
+            Field::LfoPeriod => crate::modulation::adjust_period(d),
+            Field::LfoDepth => crate::modulation::adjust_depth(d),
+            Field::Expr => crate::expression::cycle_selected(d),
+            Field::ExprCc => crate::expression::cycle_cc(d),
+            Field::ExprHiRes => crate::expression::toggle_hi_res(),
+            Field::CcLearn => crate::cc_map::cycle_selected(d),
+            Field::CcFeedback => crate::cc_map::toggle_feedback(),
+            Field::Thru => crate::thru::cycle_class(d),
+            Field::ThruChannel => crate::thru::cycle_channel(d),
+            Field::EchoWindow => crate::midi::adjust_echo_window(10 * d as i32),
+            Field::Naming => crate::spelling::cycle_convention(d),
+            Field::Spelling => crate::spelling::cycle_policy(d),
+            Field::Tonic => crate::spelling::cycle_tonic(d),
+        }
+    }
+
+    /// Enter key: flips on/off settings (for thru, the shown class or channel), starts
+    /// (or cancels) CC learn and moves the zone to the held keys, ignored for numeric
+    /// ones.
+    pub fn activate(self) {
+        match self {
+            Field::Mode
+            | Field::Intervals
+            | Field::VoiceLeading
+            | Field::Audition
+            | Field::ExprHiRes
+            | Field::Humanize
+            | Field::Euclid
+            | Field::Walk
+            | Field::Arp
+            | Field::CcFeedback
+            | Field::BootDelay
+            | Field::BootKeyMap
+            | Field::BootAnimation => self.adjust(1),
+            Field::CcLearn => crate::cc_map::toggle_learn(),
+            Field::Thru => crate::thru::toggle_class(),
+            Field::ThruChannel => crate::thru::toggle_channel(),
+            Field::Zone => crate::zones::select_rect(),
+            _ => {}
+        }
+    }
+
+    /// Writes the setting's current value.
+    pub fn write_value(self, out: &mut impl Write) -> core::fmt::Result {
+        let led = crate::leds::led_config();
+        let rgb = led.rgb_anchors[led.selected_anchor];
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match self {
+            Field::Brightness => write!(out, "{:.2}", led.brightness),
+            Field::Transpose => write!(out, "{}", PITCH_CLASS_NAMES[led.transpose as usize % 12]),
+            Field::Hue => write!(out, "{:.0} deg", led.hue_rotation),
+            Field::Gradient => write!(out, "{:.2}", led.octave_gradient),
+            Field::Guides => write!(out, "{:?}", led.guides),
+            Field::Landmarks => write_pitch_classes(out, led.landmarks),
+            Field::Coloring => write!(out, "{:?}", led.coloring),
+            Field::Intervals => write!(out, "{}", on_off(led.intervals)),
+            Field::VoiceLeading => write!(out, "{}", on_off(crate::voice_leading::is_enabled())),
+            Field::Zone => write!(out, "{}", crate::zones::zone().kind.name()),
+            Field::DrumVelocity => write!(out, "{}", crate::zones::drum_velocity()),
+            Field::BendStep => write!(out, "1/{} oct", crate::zones::bend_divisions()),
+            Field::TremoloRate => write!(out, "{}", crate::tremolo::rate_name()),
+            Field::Humanize => write!(out, "{}", on_off(crate::midi::humanize())),
+            Field::LatencyOffset => write!(out, "{:+} ms", crate::midi::latency_offset_ms()),
+            Field::Debounce => write!(out, "{} us", crate::keys::debounce_us()),
+            Field::Settle => write!(out, "{} us", crate::keys::settle_us()),
+            Field::VelocityCurve => {
+                write!(out, "{}", crate::keys::velocity_curve().name())?;
+                match crate::layouts::board().config().dual_contact {
+                    Some(_) => Ok(()),
+                    None => write!(out, " (fixed velocity)"),
+                }
+            }
+            Field::BootProfile => match crate::boot::profile() {
+                Some(profile) => write!(out, "{}", crate::profile::name(profile)),
+                None => write!(out, "Last used"),
+            },
+            Field::BootDelay => write!(out, "{}", if crate::boot::delay() { "2s" } else { "Off" }),
+            Field::BootKeyMap => write!(out, "{}", on_off(crate::boot::key_map())),
+            Field::BootAnimation => write!(out, "{}", on_off(crate::boot::animation())),
+            Field::GlideRow => match crate::glide::row() {
+                Some(row) => write!(out, "Row {}", row + 1),
+                None => write!(out, "Off"),
+            },
+            Field::ThermalLimit => match led.thermal_limit_c {
+                0 => write!(out, "Off"),
+                limit => write!(out, "{}C", limit),
+            },
+            Field::Sleep => match crate::power::sleep_after_s() {
+                0 => write!(out, "Never"),
+                s => write!(out, "{}s", s),
+            },
+            Field::Release => write!(out, "{}ms", led.release_ms),
+            Field::Frame => write!(out, "{}ms", led.frame_ms),
+            Field::Anchor => write!(out, "{}", led.selected_anchor),
+            Field::Red => write!(out, "{}", rgb.r),
+            Field::Green => write!(out, "{}", rgb.g),
+            Field::Blue => write!(out, "{}", rgb.b),
+            Field::Mode => match (crate::tuning::get_mode(), crate::tuning::scale_notes()) {
+                (TuningMode::Scale, Some(notes)) => write!(out, "Scale ({} notes)", notes),
+                (mode, _) => write!(out, "{:?}", mode),
+            },
+            Field::Fifth => write!(out, "{:.1}c", crate::tuning::get_fifth_size()),
+            Field::Audition => write!(out, "{}", on_off(crate::audition::is_enabled())),
+            Field::Pbr => write!(out, "{:.1}", crate::tuning::get_mpe_pbr()),
+            Field::Bpm => write!(out, "{:.0}", crate::clock::get_bpm()),
+            Field::Euclid => write!(out, "{}", on_off(crate::euclid::get_config().enabled)),
+            Field::Pulses => write!(out, "{}", crate::euclid::get_config().pulses),
+            Field::Steps => write!(out, "{}", crate::euclid::get_config().steps),
+            Field::Walk => write!(out, "{}", on_off(crate::walk::get_config().enabled)),
+            Field::WalkStep => write!(out, "{}", crate::walk::get_config().step_size),
+            Field::Scale => write!(
+                out,
+                "{}",
+                crate::walk::scale_name(crate::walk::get_config().scale)
+            ),
+            Field::Arp => write!(out, "{}", on_off(crate::arp::is_enabled())),
+            Field::ArpPattern => write!(out, "{}", crate::arp::get_config().pattern.name()),
+            Field::ArpRate => write!(
+                out,
+                "{}",
+                crate::arp::rate_name(crate::arp::get_config().rate)
+            ),
+            Field::ArpGate => write!(out, "{}%", crate::arp::get_config().gate),
+            Field::ArpOctaves => write!(out, "{}", crate::arp::get_config().octaves),
+            Field::Lfo => write!(out, "{}", crate::modulation::selected().name()),
+            Field::Expr => write!(out, "{}", crate::expression::selected().name()),
+            Field::ExprCc => crate::expression::write_selected(out),
+            Field::ExprHiRes => write!(out, "{}", on_off(crate::expression::hi_res())),
+            Field::LfoShape => write!(out, "{}", crate::modulation::shape_name()),
+            Field::LfoPeriod => write!(out, "{} beats", crate::modulation::period_beats()),
+            Field::LfoDepth => match crate::modulation::selected() {
+                Target::Hue => write!(out, "{:.0} deg", crate::modulation::depth()),
+                Target::Brightness => write!(out, "{:.2}", crate::modulation::depth()),
+                Target::Fifth => write!(out, "{:.1}c", crate::modulation::depth()),
+            },
+            Field::CcLearn => crate::cc_map::write_selected(out),
+            Field::CcFeedback => write!(out, "{}", on_off(crate::cc_map::feedback_enabled())),
+            Field::Thru => {
+                let class = crate::thru::selected_class();
+                let on = crate::thru::filter().enabled(class);
+                write!(out, "{} {}", class.name(), on_off(on))
+            }
+            Field::ThruChannel => {
+                let channel = crate::thru::selected_channel();
+                let on = crate::thru::filter().channel_enabled(channel);
+                write!(out, "Ch{} {}", channel + 1, on_off(on))
+            }
+            Field::EchoWindow => match crate::midi::echo_window_ms() {
+                0 => write!(out, "Off"),
+                ms => write!(out, "{}ms", ms),
+            },
+            Field::Naming => write!(out, "{}", crate::spelling::convention().name()),
+            Field::Spelling => write!(out, "{}", crate::spelling::policy().name()),
+            Field::Tonic => write!(out, "{}", crate::spelling::tonic_name()),
+        }
+    }
+}
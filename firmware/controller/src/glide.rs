@@ -0,0 +1,127 @@
+use crate::capacities::ROW_KEYS;
+use crate::layouts::{cols, rows};
+use crate::midi::{MidiEvent, MidiSender};
+use crate::tuning::{get_key_pitch, get_mode, get_mpe_pbr, TuningMode};
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use heapless::Vec;
+use lattice_board_core::layout::{Coordinate, Layout};
+use lattice_board_core::tuning::{bend_for, mpe_note};
+use log::error;
+use wmidi::{Channel, Note, U7};
+
+/// Matrix row played as a glide strip, if any. Sliding along it bends one MPE note
+/// instead of playing a note per key; the bend range has to cover the strip.
+static ROW: Mutex<CriticalSectionRawMutex, Cell<Option<u8>>> = Mutex::new(Cell::new(None));
+
+/// The strip's note while any of its keys are held.
+struct Glide {
+    channel: Channel,
+    note: u8,
+    /// Every key of the strip can be held at once.
+    held: Vec<Coordinate, ROW_KEYS>,
+}
+
+static GLIDE: Mutex<CriticalSectionRawMutex, RefCell<Option<Glide>>> =
+    Mutex::new(RefCell::new(None));
+
+pub fn row() -> Option<u8> {
+    ROW.lock(|r| r.get())
+}
+
+/// Steps through off and the board's rows. Only while the strip isn't sounding, so
+/// its note can't be left hanging.
+pub fn cycle_row(delta: i8) {
+    if GLIDE.lock(|g| g.borrow().is_some()) {
+        return;
+    }
+    ROW.lock(|r| {
+        // 0 is off
+        let current = r.get().map_or(0, |row| row as i32 + 1);
+        let next = (current + delta as i32).rem_euclid(rows() as i32 + 1);
+        r.set((next > 0).then(|| next as u8 - 1));
+    });
+}
+
+/// Whether `coord` is on the glide strip, whose keys don't play notes of their own.
+/// The strip needs a channel of its own to bend, so doesn't play in Fifths mode.
+pub fn on_strip<L: Layout>(coord: Coordinate) -> bool {
+    get_mode() != TuningMode::Fifths
+        && row()
+            .is_some_and(|row| (0..cols()).any(|c| L::key_to_coord(row as usize, c) == Some(coord)))
+}
+
+/// Handles a key of the glide strip. Returns `None` for other keys, which play as
+/// usual, otherwise whether its events could be queued.
+pub fn key_changed<L: Layout>(
+    coord: Coordinate,
+    is_pressed: bool,
+    velocity: U7,
+    sender: &MidiSender,
+) -> Option<bool> {
+    if !on_strip::<L>(coord) {
+        return None;
+    }
+    crate::keys::set_key_active(coord, is_pressed);
+    let event = GLIDE.lock(|g| {
+        let mut glide = g.borrow_mut();
+        match (glide.as_mut(), is_pressed) {
+            (None, true) => {
+                let channel = crate::tuning::claim_mpe_channel()?;
+                let (note, pitch_bend) = mpe_note(get_key_pitch::<L>(coord), get_mpe_pbr());
+                let mut held = Vec::new();
+                // Can't fail: the strip is one row, and a row has at most ROW_KEYS keys
+                let _ = held.push(coord);
+                *glide = Some(Glide {
+                    channel,
+                    note,
+                    held,
+                });
+                Some(MidiEvent::MpeNoteOn {
+                    channel,
+                    note: Note::try_from(note).ok()?,
+                    velocity,
+                    pitch_bend,
+                })
+            }
+            (None, false) => None,
+            (Some(strip), _) => {
+                if is_pressed {
+                    if !strip.held.contains(&coord) {
+                        let _ = strip.held.push(coord);
+                    }
+                } else {
+                    strip.held.retain(|&c| c != coord);
+                }
+                if strip.held.is_empty() {
+                    let Glide { channel, note, .. } = glide.take()?;
+                    crate::tuning::release_mpe_channel(channel);
+                    return Some(MidiEvent::NoteOff {
+                        channel,
+                        note: Note::try_from(note).ok()?,
+                        velocity,
+                    });
+                }
+                // Keys held together, as when the finger is between two, bend halfway
+                let pitches = strip.held.iter().map(|&c| get_key_pitch::<L>(c));
+                let pitch = pitches.sum::<f32>() / strip.held.len() as f32;
+                Some(MidiEvent::PitchBendChange {
+                    channel: strip.channel,
+                    value: bend_for(strip.note, pitch, get_mpe_pbr()),
+                })
+            }
+        }
+    });
+    let queued = match event {
+        Some(event @ MidiEvent::NoteOff { .. }) => crate::midi::try_send_release(event)
+            .or_else(|event| sender.try_send(event))
+            .is_ok(),
+        Some(event) => sender.try_send(event).is_ok(),
+        None => true,
+    };
+    if !queued {
+        error!("MIDI Channel Full! Dropping Event");
+    }
+    Some(queued)
+}
@@ -1,87 +1,82 @@
 use embassy_executor::task;
-use embassy_rp::gpio::{AnyPin, Input, Level, Output, Pull};
-use embassy_time::{Duration, Timer};
+use embassy_rp::gpio::{AnyPin, Input, Output};
+use embassy_time::{Duration, Instant};
+use lattice_board_core::settle::Steady;
 use log::info;
 
-use crate::layout::Layout;
-use crate::layouts::{CurrentLayout, COLS, ROWS};
-use core::cell::RefCell;
-use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::blocking_mutex::Mutex;
-use heapless::Vec;
-use lattice_board_core::layout::Coordinate;
+use super::scanner::{self, MatrixScanner};
+use super::settle_test;
+use crate::layouts::prototype::{PrototypeLayout, BOARD, COLS, ROWS};
+use crate::layouts::Polarity;
+use crate::midi::MidiSender;
 
-// Shared state for Active Keys (Coordinates)
-pub static ACTIVE_KEYS: Mutex<CriticalSectionRawMutex, RefCell<Vec<Coordinate, 16>>> =
-    Mutex::new(RefCell::new(Vec::new()));
+const _: () = assert!(
+    BOARD.shift_registers == 0,
+    "direct scanning drives columns from GPIO"
+);
 
-#[task]
-pub async fn keys_task_direct(
-    row_pins: [AnyPin; ROWS],
-    col_pins: [AnyPin; COLS],
-    sender: embassy_sync::channel::Sender<
-        'static,
-        embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-        crate::midi::MidiEvent,
-        32,
-    >,
-) {
-    use crate::midi::ToU7;
-
-    // Direct GPIO Scanning
-    // Columns are Outputs, Rows are Inputs.
-    // Active High: Col set High, Row read High (Pull-Down).
-    let rows: [Input<'static>; ROWS] = row_pins.map(|p| Input::new(p, Pull::Down));
-    let mut cols: [Output<'static>; COLS] = col_pins.map(|p| Output::new(p, Level::Low));
+/// Equal readings in a row that count as settled. Each takes a fraction of a us.
+const STEADY_READS: u8 = 4;
 
-    info!("Keys task started. Direct GPIO Scanning.");
-
-    let mut key_state = [[false; COLS]; ROWS];
-
-    loop {
-        for (c_idx, col) in cols.iter_mut().enumerate() {
-            // Activate Column
-            col.set_high();
-            // Allow signal to settle
-            Timer::after(Duration::from_micros(10)).await;
+/// Columns on GPIO outputs, rows on inputs; levels follow the board's polarity.
+struct DirectScanner {
+    rows: [Input<'static>; ROWS],
+    cols: [Output<'static>; COLS],
+    polarity: Polarity,
+}
 
-            // Scan Rows
-            for (r_idx, row) in rows.iter().enumerate() {
-                let is_pressed = row.is_high();
-                let was_pressed = key_state[r_idx][c_idx];
+impl MatrixScanner for DirectScanner {
+    /// Waits for the column to settle: until the rows read the same [`STEADY_READS`]
+    /// times in a row, or for the settle time at most. Clean lines settle long before
+    /// that, which shortens the dwell on each column.
+    async fn select(&mut self, col: usize) {
+        self.cols[col].set_level(self.polarity.active());
+        if settle_test::running() {
+            settle_test::settle(&self.rows, self.polarity).await;
+            return;
+        }
+        let deadline = Instant::now() + super::settle();
+        let mut steady = Steady::new(STEADY_READS);
+        while !steady.feed(self.read()) && Instant::now() < deadline {}
+    }
 
-                if is_pressed != was_pressed {
-                    key_state[r_idx][c_idx] = is_pressed;
+    fn read(&mut self) -> u32 {
+        scanner::read_rows(&self.rows, self.polarity)
+    }
 
-                    if let Some(coord) = CurrentLayout::key_to_coord(r_idx, c_idx) {
-                        // Use tuning module to generate event (Standard or Fifths)
-                        if let Some(event) = crate::tuning::get_midi_event::<CurrentLayout>(
-                            coord,
-                            100.to_u7(),
-                            is_pressed,
-                        ) {
-                            sender.send(event).await;
+    fn deselect(&mut self, col: usize) {
+        self.cols[col].set_level(self.polarity.idle());
+    }
+}
 
-                            // Track Active keys
-                            ACTIVE_KEYS.lock(|c| {
-                                let mut keys = c.borrow_mut();
-                                if is_pressed {
-                                    if !keys.contains(&coord) {
-                                        let _ = keys.push(coord);
-                                    }
-                                } else {
-                                    keys.retain(|&x| x != coord);
-                                }
-                            });
-                        }
-                    }
-                }
-            }
+#[task]
+pub async fn keys_task_direct(
+    row_pins: [AnyPin; ROWS],
+    col_pins: [AnyPin; COLS],
+    sender: MidiSender,
+) {
+    let polarity = BOARD.polarity;
+    let rows = row_pins.map(|p| Input::new(p, polarity.pull()));
+    let mut cols = col_pins.map(|p| Output::new(p, polarity.idle()));
+    for col in cols.iter_mut() {
+        BOARD.drive.apply(col);
+    }
 
-            // Deactivate Column
-            col.set_low();
-        }
+    info!(
+        "Keys task started. Direct GPIO Scanning ({:?}, {:?}).",
+        polarity, BOARD.drive
+    );
 
-        Timer::after(Duration::from_millis(1)).await;
-    }
+    let scanner = DirectScanner {
+        rows,
+        cols,
+        polarity,
+    };
+    scanner::run::<PrototypeLayout, ROWS, COLS>(
+        scanner,
+        sender,
+        Duration::from_millis(1),
+        BOARD.dual_contact,
+    )
+    .await
 }
@@ -1,9 +1,318 @@
-#[cfg(feature = "layout-5x25")]
-pub mod shift_reg;
-#[cfg(feature = "layout-5x25")]
-pub use shift_reg::*;
+use crate::capacities::HELD_KEYS;
+use crate::layouts::{cols, rows, CurrentLayout};
+use crate::midi::{MidiEvent, MidiSender, ToU7};
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+use lattice_board_core::debounce::Debouncer;
+use lattice_board_core::layout::{Coordinate, Layout};
+use lattice_board_core::matrix::KeyMatrix;
+use lattice_board_core::velocity::VelocityCurve;
+use lattice_board_core::wear::WEAR_KEYS;
+use log::error;
+use wmidi::U7;
 
-#[cfg(feature = "layout-prototype")]
+// One scanner per matrix wiring; main spawns the one matching the detected board.
 pub mod direct;
-#[cfg(feature = "layout-prototype")]
-pub use direct::*;
+pub mod scanner;
+pub mod settle_test;
+pub mod shift_reg;
+
+/// Held keys in press order; the voice-leading task redoes its suggestions on each
+/// change, and the LED task wakes on one while idle.
+pub static ACTIVE_KEYS: Watch<CriticalSectionRawMutex, Vec<Coordinate, HELD_KEYS>, 2> =
+    Watch::new_with(Vec::new());
+
+/// Snapshot of the currently held keys, in press order.
+pub fn active_keys() -> Vec<Coordinate, HELD_KEYS> {
+    ACTIVE_KEYS.try_get().unwrap_or_default()
+}
+
+/// How long a key's level must hold after its last edge before the change counts,
+/// in us. 0 takes every edge as it comes.
+static DEBOUNCE_US: Mutex<CriticalSectionRawMutex, Cell<u16>> = Mutex::new(Cell::new(0));
+const MAX_DEBOUNCE_US: u16 = 20_000;
+const DEBOUNCE_STEP_US: u16 = 250;
+/// How long a column is driven before its rows are read, in us. Direct-wired boards
+/// read sooner once the rows hold steady, so for them it's the longest wait.
+static SETTLE_US: Mutex<CriticalSectionRawMutex, Cell<u8>> =
+    Mutex::new(Cell::new(DEFAULT_SETTLE_US));
+const DEFAULT_SETTLE_US: u8 = 10;
+const MAX_SETTLE_US: u8 = 100;
+
+pub fn debounce_us() -> u16 {
+    DEBOUNCE_US.lock(|d| d.get())
+}
+
+pub fn settle() -> Duration {
+    Duration::from_micros(SETTLE_US.lock(|s| s.get()) as u64)
+}
+
+pub fn settle_us() -> u8 {
+    SETTLE_US.lock(|s| s.get())
+}
+
+/// Curve turning strike speed into velocity, on boards with dual-contact switches.
+static VELOCITY_CURVE: Mutex<CriticalSectionRawMutex, Cell<VelocityCurve>> =
+    Mutex::new(Cell::new(VelocityCurve::Linear));
+
+pub fn velocity_curve() -> VelocityCurve {
+    VELOCITY_CURVE.lock(|c| c.get())
+}
+
+pub fn cycle_velocity_curve(delta: i8) {
+    VELOCITY_CURVE.lock(|c| {
+        let i = (c.get() as i32 + delta as i32).rem_euclid(VelocityCurve::ALL.len() as i32);
+        c.set(VelocityCurve::ALL[i as usize]);
+    });
+}
+
+/// The scan timings, (debounce, settle) in us.
+pub fn timing() -> (u16, u8) {
+    (debounce_us(), settle_us())
+}
+
+/// The scan timings as stored, or the defaults.
+fn stored_timing() -> (u16, u8) {
+    let settings = crate::util::stored_settings();
+    let settle = match settings.settle_us {
+        0 => DEFAULT_SETTLE_US,
+        us => us.min(MAX_SETTLE_US),
+    };
+    (settings.debounce_us.min(MAX_DEBOUNCE_US), settle)
+}
+
+/// Applies the stored scan timings, or the defaults.
+pub fn load_timing() {
+    let (debounce, settle) = stored_timing();
+    DEBOUNCE_US.lock(|d| d.set(debounce));
+    SETTLE_US.lock(|s| s.set(settle));
+}
+
+/// Saves the scan timings if they differ from the stored ones, returning whether they
+/// were written. Adjusting them only changes them in RAM; the preset autosave calls
+/// this once they've stayed put.
+pub fn store_timing() -> bool {
+    let timing = timing();
+    timing != stored_timing() && crate::util::store_key_timing(timing.0, timing.1)
+}
+
+pub fn adjust_debounce(delta: i8) {
+    let us = debounce_us() as i32 + delta as i32 * DEBOUNCE_STEP_US as i32;
+    DEBOUNCE_US.lock(|d| d.set(us.clamp(0, MAX_DEBOUNCE_US as i32) as u16));
+}
+
+pub fn adjust_settle(delta: i8) {
+    let us = (settle_us() as i16 + delta as i16).clamp(1, MAX_SETTLE_US as i16);
+    SETTLE_US.lock(|s| s.set(us as u8));
+}
+
+/// Bounce of each matrix position, (last, longest) in us, by [`key_index`].
+static BOUNCES: Mutex<CriticalSectionRawMutex, RefCell<[(u16, u16); WEAR_KEYS]>> =
+    Mutex::new(RefCell::new([(0, 0); WEAR_KEYS]));
+
+/// Debounced state of every matrix position, by [`key_index`]. Unlike
+/// [`ACTIVE_KEYS`] it has every pressed key, including ones handed to zones or the
+/// glide strip.
+static MATRIX: Mutex<CriticalSectionRawMutex, RefCell<KeyMatrix>> =
+    Mutex::new(RefCell::new(KeyMatrix::EMPTY));
+
+/// A copy of the whole key matrix as scanned, for diagnostics and other readers of the
+/// raw key state.
+pub fn matrix() -> KeyMatrix {
+    MATRIX.lock(|m| *m.borrow())
+}
+
+/// Every pressed key of the matrix, including ones handed to zones or the glide
+/// strip, in scan order.
+pub fn pressed_keys() -> Vec<Coordinate, HELD_KEYS> {
+    let matrix = matrix();
+    matrix
+        .pressed()
+        .filter_map(|i| CurrentLayout::key_to_coord(i / cols(), i % cols()))
+        .take(HELD_KEYS)
+        .collect()
+}
+
+/// Index of a matrix position in per-key tables: row by row.
+pub fn key_index(row: usize, col: usize) -> usize {
+    row * cols() + col
+}
+
+/// Feeds a reading of the key at `row`, `col` to its debouncer, counting presses for
+/// wear tracking. Returns the new state if it changed.
+///
+/// Bounce is measured over each burst of edges, whatever the debounce hold: a burst
+/// ends once the key has had no edge for [`crate::wear::CHATTER_US`], so a release
+/// and a press closer together than that are chatter in the same burst.
+pub fn debounce(debouncer: &mut Debouncer, row: usize, col: usize, raw: bool) -> Option<bool> {
+    let now = Instant::now().as_micros();
+    let settled = debouncer.update(raw, now, debounce_us() as u64);
+    let index = key_index(row, col);
+    if let Some(burst) = debouncer.bounce(now, crate::wear::CHATTER_US) {
+        let bounce = burst.bounce.min(u16::MAX as u64) as u16;
+        BOUNCES.lock(|b| {
+            if let Some(entry) = b.borrow_mut().get_mut(index) {
+                *entry = (bounce, entry.1.max(bounce));
+            }
+        });
+        if burst.pressed && bounce > 0 {
+            crate::wear::record_bounce(index);
+        }
+    }
+    let settled = settled?;
+    MATRIX.lock(|m| m.borrow_mut().set(index, settled.pressed));
+    if settled.pressed {
+        crate::wear::record_press(index);
+    }
+    Some(settled.pressed)
+}
+
+/// The (last, longest) bounce seen on the key at `row`, `col`, in us.
+pub fn bounce_us(row: usize, col: usize) -> (u16, u16) {
+    BOUNCES.lock(|b| {
+        b.borrow()
+            .get(key_index(row, col))
+            .copied()
+            .unwrap_or_default()
+    })
+}
+
+/// The scanners' MIDI channel, for injected key presses.
+static SENDER: Mutex<CriticalSectionRawMutex, Cell<Option<MidiSender>>> =
+    Mutex::new(Cell::new(None));
+
+pub fn set_sender(sender: MidiSender) {
+    SENDER.lock(|s| s.set(Some(sender)));
+}
+
+/// The scanners' MIDI channel, once they run.
+pub fn sender() -> Option<MidiSender> {
+    SENDER.lock(|s| s.get())
+}
+
+/// Handles a key changing state: hands keys in a zone or on the glide strip to them,
+/// and plays the others.
+/// Returns false if an event had to be dropped.
+pub fn key_changed<L: Layout>(
+    coord: Coordinate,
+    is_pressed: bool,
+    velocity: U7,
+    sender: &MidiSender,
+) -> bool {
+    if let Some(queued) = crate::zones::key_changed::<L>(coord, is_pressed, velocity, sender) {
+        return queued;
+    }
+    if let Some(queued) = crate::glide::key_changed::<L>(coord, is_pressed, velocity, sender) {
+        return queued;
+    }
+    play_key::<L>(coord, is_pressed, velocity, sender)
+}
+
+/// Plays a key: queues its MIDI event (releases on the priority path) and tracks it
+/// as held. Returns false if the event had to be dropped.
+pub fn play_key<L: Layout>(
+    coord: Coordinate,
+    is_pressed: bool,
+    velocity: U7,
+    sender: &MidiSender,
+) -> bool {
+    // Held notes are voiced by the Euclidean generator or the arpeggiator while they run
+    let captured = is_pressed && (crate::euclid::is_enabled() || crate::arp::is_enabled());
+
+    let event = if captured {
+        None
+    } else {
+        crate::tuning::get_midi_event::<L>(coord, velocity, is_pressed)
+    };
+    // A pad sounding the same note keeps it on after the key lets go
+    let event = event.filter(|event| match *event {
+        MidiEvent::NoteOff { channel, note, .. } => !crate::zones::holds_note(channel, note),
+        _ => true,
+    });
+    let mut queued = true;
+    if let Some(event) = event {
+        queued = if is_pressed {
+            sender.try_send(event).is_ok()
+        } else {
+            crate::midi::try_send_release(event)
+                .or_else(|event| sender.try_send(event))
+                .is_ok()
+        };
+        if !queued {
+            error!("MIDI Channel Full! Dropping Event");
+        } else if is_pressed {
+            crate::tuning::note_played::<L>(coord);
+        }
+    }
+
+    if captured || event.is_some() || !is_pressed {
+        // Track Active keys
+        set_key_active(coord, is_pressed);
+    }
+    queued
+}
+
+/// Presses or releases the key at matrix (`row`, `col`) as if it had been scanned.
+/// Returns false if there is no such key.
+pub fn inject(row: usize, col: usize, velocity: u8, is_pressed: bool) -> bool {
+    CurrentLayout::key_to_coord(row, col).is_some_and(|coord| play(coord, velocity, is_pressed))
+}
+
+/// Presses or releases the key at `coord` on the scanners' channel, for keys outside
+/// the matrix. Returns false before the scanner runs.
+pub fn play(coord: Coordinate, velocity: u8, is_pressed: bool) -> bool {
+    match sender() {
+        Some(sender) => {
+            key_changed::<CurrentLayout>(coord, is_pressed, velocity.to_u7(), &sender);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Every key of the board in scan order, row by row.
+pub fn matrix_keys() -> impl DoubleEndedIterator<Item = Coordinate> {
+    (0..rows())
+        .flat_map(|r| (0..cols()).map(move |c| (r, c)))
+        .filter_map(|(r, c)| CurrentLayout::key_to_coord(r, c))
+}
+
+/// The first and last keys of the scan matrix, in the board's opposite corners.
+fn corner_keys() -> Option<(Coordinate, Coordinate)> {
+    Some((matrix_keys().next()?, matrix_keys().next_back()?))
+}
+
+/// Whether both corner keys are held, the gesture for board-level actions that
+/// mustn't happen by accident.
+pub fn corners_held() -> bool {
+    let Some((first, last)) = corner_keys() else {
+        return false;
+    };
+    let held = active_keys();
+    held.contains(&first) && held.contains(&last)
+}
+
+/// Marks a key as held or released, notifying watchers only if the set changed.
+pub fn set_key_active(coord: Coordinate, active: bool) {
+    crate::power::note_activity();
+    ACTIVE_KEYS.sender().send_if_modified(|keys| {
+        let Some(keys) = keys.as_mut() else {
+            return false;
+        };
+        if active {
+            if keys.contains(&coord) {
+                return false;
+            }
+            crate::audition::key_pressed(coord, !keys.is_empty());
+            keys.push(coord).is_ok()
+        } else {
+            let len = keys.len();
+            keys.retain(|&x| x != coord);
+            keys.len() != len
+        }
+    });
+}
@@ -0,0 +1,126 @@
+//! What every key scanner shares. A backend only drives its columns and reads its rows
+//! ([`MatrixScanner`]); debouncing, the key matrix and key events are handled by
+//! [`KeyStateTracker`] the same way for every board.
+
+use crate::layouts::{DualContact, Polarity};
+use crate::midi::{MidiSender, ToU7};
+use embassy_rp::gpio::Input;
+use embassy_time::{Duration, Instant, Timer};
+use lattice_board_core::debounce::Debouncer;
+use lattice_board_core::layout::Layout;
+use lattice_board_core::velocity::{ContactEvent, DualContactKey};
+
+/// Velocity of keys without a second contact, and of every release.
+const FIXED_VELOCITY: u8 = 100;
+
+/// The hardware side of a key matrix, scanned one column at a time.
+pub trait MatrixScanner {
+    /// Gets ready for a pass over the columns.
+    async fn begin_pass(&mut self) {}
+
+    /// Drives column `col` and waits for its rows to settle.
+    async fn select(&mut self, col: usize);
+
+    /// The rows of the selected column that read pressed, one bit each.
+    fn read(&mut self) -> u32;
+
+    /// Stops driving column `col` once its rows are read.
+    fn deselect(&mut self, _col: usize) {}
+
+    /// Finishes a pass, after the last column.
+    async fn end_pass(&mut self) {}
+}
+
+/// The rows that read pressed, one bit each.
+pub fn read_rows(rows: &[Input<'static>], polarity: Polarity) -> u32 {
+    rows.iter().take(32).enumerate().fold(0, |bits, (i, row)| {
+        bits | ((row.get_level() == polarity.active()) as u32) << i
+    })
+}
+
+/// Debounced state of a `ROWS` x `COLS` matrix, turning readings into key events.
+pub struct KeyStateTracker<const ROWS: usize, const COLS: usize> {
+    keys: [[Debouncer; COLS]; ROWS],
+    /// Contacts of each key on boards with dual-contact switches, by first contact.
+    contacts: [[DualContactKey; COLS]; ROWS],
+    dual_contact: Option<DualContact>,
+}
+
+impl<const ROWS: usize, const COLS: usize> KeyStateTracker<ROWS, COLS> {
+    // Rows are read as the bits of a u32
+    const FITS: () = assert!(ROWS <= 32, "more rows than a reading holds");
+
+    pub const fn new(dual_contact: Option<DualContact>) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::FITS;
+        Self {
+            keys: [[Debouncer::new(); COLS]; ROWS],
+            contacts: [[DualContactKey::new(); COLS]; ROWS],
+            dual_contact,
+        }
+    }
+
+    /// Feeds the `rows` read from column `col`, sending events for the keys that
+    /// changed.
+    pub fn update<L: Layout>(&mut self, col: usize, rows: u32, sender: &MidiSender) {
+        for (row, keys) in self.keys.iter_mut().enumerate() {
+            let raw = rows & (1 << row) != 0;
+            let Some(is_pressed) = super::debounce(&mut keys[col], row, col, raw) else {
+                continue;
+            };
+            let Some(dual) = self.dual_contact else {
+                if let Some(coord) = L::key_to_coord(row, col) {
+                    super::key_changed::<L>(coord, is_pressed, FIXED_VELOCITY.to_u7(), sender);
+                }
+                continue;
+            };
+
+            let now = Instant::now().as_micros() as u32;
+            let (key_row, event) = match row.checked_sub(dual.second_row) {
+                Some(key_row) => (key_row, self.contacts[key_row][col].second(is_pressed, now)),
+                None => (row, self.contacts[row][col].first(is_pressed, now)),
+            };
+            let Some(coord) = L::key_to_coord(key_row, col) else {
+                continue;
+            };
+            match event {
+                Some(ContactEvent::Press { gap_us }) => {
+                    let velocity = super::velocity_curve().velocity(gap_us);
+                    super::key_changed::<L>(coord, true, velocity.to_u7(), sender);
+                }
+                Some(ContactEvent::Release) => {
+                    super::key_changed::<L>(coord, false, FIXED_VELOCITY.to_u7(), sender);
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// Scans the matrix for good, resting `pass_period` between passes (stretched while
+/// the board sleeps).
+pub async fn run<L: Layout, const ROWS: usize, const COLS: usize>(
+    mut scanner: impl MatrixScanner,
+    sender: MidiSender,
+    pass_period: Duration,
+    dual_contact: Option<DualContact>,
+) -> ! {
+    let mut tracker = KeyStateTracker::<ROWS, COLS>::new(dual_contact);
+    let mut last_pass = Instant::now();
+    loop {
+        let now = Instant::now();
+        crate::stats::record_duration(crate::stats::Graph::ScanPeriod, now - last_pass);
+        last_pass = now;
+
+        scanner.begin_pass().await;
+        for col in 0..COLS {
+            scanner.select(col).await;
+            let rows = scanner.read();
+            tracker.update::<L>(col, rows, &sender);
+            scanner.deselect(col);
+        }
+        scanner.end_pass().await;
+
+        Timer::after(crate::power::scan_period(pass_period)).await;
+    }
+}
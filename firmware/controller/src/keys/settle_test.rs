@@ -0,0 +1,88 @@
+//! Settle test: measures how long the rows take to settle after a column is driven,
+//! to pick a settle time by measurement rather than by guess.
+//!
+//! While it runs, the scanners busy-poll the rows for the longest allowed settle time
+//! after every column instead of waiting out the set one, then a summary is logged.
+
+use super::scanner::read_rows;
+use super::MAX_SETTLE_US;
+use crate::layouts::Polarity;
+use core::cell::Cell;
+use embassy_rp::gpio::Input;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use lattice_board_core::settle::SettleTest;
+use log::info;
+
+/// How long a test runs.
+const TEST_LEN: Duration = Duration::from_secs(10);
+
+/// The test in progress and when it started.
+static TEST: Mutex<CriticalSectionRawMutex, Cell<Option<(Instant, SettleTest)>>> =
+    Mutex::new(Cell::new(None));
+
+/// Starts a test, or restarts one already running.
+pub fn start() {
+    TEST.lock(|t| t.set(Some((Instant::now(), SettleTest::new()))));
+    info!(
+        "Settle test: play or hold keys for the next {}s",
+        TEST_LEN.as_secs()
+    );
+}
+
+/// Whether a test is running.
+pub fn running() -> bool {
+    TEST.lock(|t| t.get()).is_some()
+}
+
+fn finish(test: SettleTest) {
+    info!(
+        "Settle test: {} columns driven, rows changed after {} of them",
+        test.columns, test.changed
+    );
+    match test.recommended_us() {
+        Some(us) => info!(
+            "Settle test: rows settled within {}us; {}us leaves a 2x margin (set: {}us)",
+            test.longest_us,
+            us.min(MAX_SETTLE_US as u32),
+            super::settle_us()
+        ),
+        None => info!("Settle test: no row changed; hold some keys while it runs"),
+    }
+}
+
+/// Waits for a just-driven column to settle before its rows are read. During a test
+/// the rows are watched for the longest allowed settle time instead, timing changes
+/// from here on: for shift register boards that's after the latch pulse.
+pub async fn settle(rows: &[Input<'static>], polarity: Polarity) {
+    let Some((started, mut test)) = TEST.lock(|t| t.get()) else {
+        Timer::after(super::settle()).await;
+        return;
+    };
+
+    let drive = Instant::now();
+    let window = Duration::from_micros(MAX_SETTLE_US as u64);
+    let mut levels = read_rows(rows, polarity);
+    let mut last_change = None;
+    while drive.elapsed() < window {
+        let now = read_rows(rows, polarity);
+        if now != levels {
+            levels = now;
+            last_change = Some(drive.elapsed().as_micros() as u32);
+        }
+    }
+    test.record(last_change);
+
+    if started.elapsed() >= TEST_LEN {
+        TEST.lock(|t| t.set(None));
+        finish(test);
+    } else {
+        TEST.lock(|t| {
+            // Unless a restart replaced it meanwhile
+            if t.get().is_some_and(|(s, _)| s == started) {
+                t.set(Some((started, test)));
+            }
+        });
+    }
+}
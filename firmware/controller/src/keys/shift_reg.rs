@@ -1,153 +1,171 @@
 use embassy_executor::task;
-use embassy_rp::gpio::{AnyPin, Input, Pull};
+use embassy_rp::gpio::{AnyPin, Input, Level, Output};
 use embassy_time::{Duration, Timer};
 use log::info;
 
+use super::scanner::{self, MatrixScanner};
 use crate::layout::Layout;
-use crate::layouts::{CurrentLayout, COLS, ROWS};
-use core::cell::RefCell;
-use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::blocking_mutex::Mutex;
-use heapless::Vec;
-use lattice_board_core::layout::Coordinate;
-
-// Shared state for Active Keys (Coordinates)
-pub static ACTIVE_KEYS: Mutex<CriticalSectionRawMutex, RefCell<Vec<Coordinate, 16>>> =
-    Mutex::new(RefCell::new(Vec::new()));
+use crate::layouts::{
+    layout_5x25, layout_7x32, Blanking, BoardConfig, Layout5x25, Layout7x32, Polarity,
+};
+
+use crate::midi::MidiSender;
+
+const _: () = assert!(
+    layout_5x25::COLS <= layout_5x25::BOARD.shift_registers * 8,
+    "not enough shift register outputs for the 5x25 columns"
+);
+const _: () = assert!(
+    layout_7x32::COLS <= layout_7x32::BOARD.shift_registers * 8,
+    "not enough shift register outputs for the 7x32 columns"
+);
+
+/// Clock, data and latch edges are held this long so the registers see them.
+const PULSE: Duration = Duration::from_micros(1);
+
+async fn pulse(pin: &mut Output<'static>) {
+    pin.set_high();
+    Timer::after(PULSE).await;
+    pin.set_low();
+    Timer::after(PULSE).await;
+}
 
 #[task]
 pub async fn keys_task_shift_reg(
-    row_pins: [AnyPin; ROWS],
+    row_pins: [AnyPin; layout_5x25::ROWS],
     // Shift Register Pins
     data_pin: AnyPin,  // GPIO 0
     latch_pin: AnyPin, // GPIO 1
     clock_pin: AnyPin, // GPIO 2
-    sender: embassy_sync::channel::Sender<
-        'static,
-        embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-        crate::midi::MidiEvent,
-        32,
-    >,
+    sender: MidiSender,
 ) {
-    use embassy_rp::gpio::{Level, Output};
-
-    // Active High Configuration (Standard 74HC595 + Rows with Pull-Down)
-    // Shift in '1', Rows read High when pressed.
-    let rows: [Input<'static>; ROWS] = row_pins.map(|p| Input::new(p, Pull::Down));
-
-    let mut data = Output::new(data_pin, Level::Low);
-    let mut latch = Output::new(latch_pin, Level::Low);
-    let mut clock = Output::new(clock_pin, Level::Low);
-
-    info!("Keys task started. Shift Register Scanning (Active High).");
-
-    let mut key_state = [[false; COLS]; ROWS];
-
-    loop {
-        // Ensure we start clean
-        data.set_low();
-        latch.set_low();
-        clock.set_low();
-
-        // ---------------------------------------------------------
-        // Column 0: Shift in a High bit
-        // ---------------------------------------------------------
-
-        // 1. Set Data High
-        data.set_high();
-
-        // 2. Pulse Clock to shift '1' into Q0
-        clock.set_high();
-        Timer::after(Duration::from_micros(1)).await;
-        clock.set_low();
-        Timer::after(Duration::from_micros(1)).await;
+    scan::<Layout5x25, { layout_5x25::ROWS }, { layout_5x25::COLS }>(
+        &layout_5x25::BOARD,
+        row_pins,
+        [data_pin, latch_pin, clock_pin],
+        None,
+        sender,
+    )
+    .await
+}
 
-        // 3. Pulse Latch to output
-        latch.set_high();
-        Timer::after(Duration::from_micros(1)).await;
-        latch.set_low();
-        Timer::after(Duration::from_micros(1)).await;
+#[task]
+pub async fn keys_task_7x32(
+    row_pins: [AnyPin; layout_7x32::ROWS],
+    data_pin: AnyPin,
+    latch_pin: AnyPin,
+    clock_pin: AnyPin,
+    output_enable_pin: AnyPin,
+    sender: MidiSender,
+) {
+    scan::<Layout7x32, { layout_7x32::ROWS }, { layout_7x32::COLS }>(
+        &layout_7x32::BOARD,
+        row_pins,
+        [data_pin, latch_pin, clock_pin],
+        Some(output_enable_pin),
+        sender,
+    )
+    .await
+}
 
-        scan_rows(0, &rows, &mut key_state, &sender).await;
+/// Columns on the outputs of a 74HC595 chain (data, latch, clock pins), rows on
+/// inputs. One active bit is shifted along the chain per pass; each clock moves it to
+/// the next output and the first clock of the next pass pushes it out of the last
+/// register, so only one column is ever driven.
+struct ShiftRegScanner<const ROWS: usize> {
+    rows: [Input<'static>; ROWS],
+    data: Output<'static>,
+    latch: Output<'static>,
+    clock: Output<'static>,
+    /// The board's blanking and its /OE pin, if it has them.
+    blanking: Option<(Blanking, Output<'static>)>,
+    polarity: Polarity,
+    /// Columns, then outputs on the whole chain.
+    cols: usize,
+    outputs: usize,
+}
 
-        // ---------------------------------------------------------
-        // Columns 1..COLS: Shift in Low bits (pushing the High bit along)
-        // ---------------------------------------------------------
-        data.set_low(); // We want 0s following the single 1
+impl<const ROWS: usize> MatrixScanner for ShiftRegScanner<ROWS> {
+    async fn begin_pass(&mut self) {
+        self.data.set_level(self.polarity.active());
+    }
 
-        for c_idx in 1..COLS {
-            // Pulse Clock to shift
-            clock.set_high();
-            Timer::after(Duration::from_micros(1)).await;
-            clock.set_low();
-            Timer::after(Duration::from_micros(1)).await;
+    /// Blanks the outputs around the latch if the board has /OE on a GPIO.
+    async fn select(&mut self, _col: usize) {
+        pulse(&mut self.clock).await;
+        self.data.set_level(self.polarity.idle());
+        if let Some((_, output_enable)) = self.blanking.as_mut() {
+            output_enable.set_high();
+        }
+        pulse(&mut self.latch).await;
+        if let Some((blanking, output_enable)) = self.blanking.as_mut() {
+            Timer::after(blanking.gap).await;
+            output_enable.set_low();
+        }
+        super::settle_test::settle(&self.rows, self.polarity).await;
+    }
 
-            // Pulse Latch to output
-            latch.set_high();
-            Timer::after(Duration::from_micros(1)).await;
-            latch.set_low();
-            Timer::after(Duration::from_micros(1)).await;
+    fn read(&mut self) -> u32 {
+        scanner::read_rows(&self.rows, self.polarity)
+    }
 
-            scan_rows(c_idx, &rows, &mut key_state, &sender).await;
+    /// Outputs past the last column are shifted through without latching.
+    async fn end_pass(&mut self) {
+        for _ in self.cols..self.outputs {
+            pulse(&mut self.clock).await;
         }
-
-        // Scan rate control: Fast as possible while yielding
-        Timer::after(Duration::from_micros(100)).await;
     }
 }
 
-// Helper to scan rows and update state
-async fn scan_rows(
-    c_idx: usize,
-    rows: &[Input<'static>; ROWS],
-    key_state: &mut [[bool; COLS]; ROWS],
-    sender: &embassy_sync::channel::Sender<
-        'static,
-        embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-        crate::midi::MidiEvent,
-        32,
-    >,
+async fn scan<L: Layout, const ROWS: usize, const COLS: usize>(
+    board: &BoardConfig,
+    row_pins: [AnyPin; ROWS],
+    [data_pin, latch_pin, clock_pin]: [AnyPin; 3],
+    output_enable_pin: Option<AnyPin>,
+    sender: MidiSender,
 ) {
-    use crate::midi::ToU7;
-    use log::error;
-
-    for (r_idx, row) in rows.iter().enumerate() {
-        let is_pressed = row.is_high();
-        let was_pressed = key_state[r_idx][c_idx];
-
-        if is_pressed != was_pressed {
-            key_state[r_idx][c_idx] = is_pressed;
-
-            // Debug: Raw Matrix Event (Optional, good for verification)
-            if is_pressed {
-                //info!("Raw Press: r{} c{}", r_idx, c_idx);
-            }
-
-            // State Changed
-            // State Changed
-            if let Some(coord) = CurrentLayout::key_to_coord(r_idx, c_idx) {
-                // info!("Coord: {:?}", coord);
-
-                if let Some(event) =
-                    crate::tuning::get_midi_event::<CurrentLayout>(coord, 100.to_u7(), is_pressed)
-                {
-                    if let Err(_) = sender.try_send(event) {
-                        error!("MIDI Channel Full! Dropping Event");
-                    }
-
-                    // Track Active keys
-                    ACTIVE_KEYS.lock(|c| {
-                        let mut keys = c.borrow_mut();
-                        if is_pressed {
-                            if !keys.contains(&coord) {
-                                let _ = keys.push(coord);
-                            }
-                        } else {
-                            keys.retain(|&x| x != coord);
-                        }
-                    });
-                }
-            }
-        }
+    // The scanned column carries the active level and rows read it when pressed
+    let polarity = board.polarity;
+    let rows = row_pins.map(|p| Input::new(p, polarity.pull()));
+
+    let mut data = Output::new(data_pin, polarity.idle());
+    let mut latch = Output::new(latch_pin, Level::Low);
+    let mut clock = Output::new(clock_pin, Level::Low);
+    for pin in [&mut data, &mut latch, &mut clock] {
+        board.drive.apply(pin);
+    }
+    // Outputs stay off until the first column is latched
+    let mut blanking = board
+        .blanking
+        .zip(output_enable_pin)
+        .map(|(blanking, pin)| (blanking, Output::new(pin, Level::High)));
+    if let Some((_, output_enable)) = blanking.as_mut() {
+        board.drive.apply(output_enable);
     }
+
+    info!(
+        "Keys task started. Shift Register Scanning ({:?}, {:?}, blanking {:?}).",
+        polarity,
+        board.drive,
+        blanking.as_ref().map(|(b, _)| b.gap)
+    );
+
+    let scanner = ShiftRegScanner {
+        rows,
+        data,
+        latch,
+        clock,
+        blanking,
+        polarity,
+        cols: COLS,
+        outputs: board.shift_registers * 8,
+    };
+    // Fast as possible while yielding
+    scanner::run::<L, ROWS, COLS>(
+        scanner,
+        sender,
+        Duration::from_micros(100),
+        board.dual_contact,
+    )
+    .await
 }
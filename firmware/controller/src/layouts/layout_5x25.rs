@@ -1,14 +1,29 @@
-use crate::layout::{Coordinate, Layout, LedIndex};
+use crate::layout::{Coordinate, Geometry, KeyShape, Layout, LedIndex, LedMatrixEntry, NO_LED};
 
 pub struct Layout5x25;
-pub type CurrentLayout = Layout5x25;
 
 // Configuration Constants
 pub const ROWS: usize = 10;
 pub const COLS: usize = 13;
 pub const NUM_LEDS: usize = 123; // Two are missing to make room for MCU
 
-const NO_LED: u8 = 255;
+pub const BOARD: super::BoardConfig = super::BoardConfig {
+    rows: ROWS,
+    cols: COLS,
+    shift_registers: 2,
+    polarity: super::Polarity::ActiveHigh,
+    held_keys: 16,
+    midi_queue: 32,
+    // The register lines run the length of the board
+    drive: super::PadDrive {
+        strength: super::DriveStrength::Ma8,
+        fast_slew: true,
+    },
+    // /OE is tied low
+    blanking: None,
+    satellite: Some(super::BASS_ROW),
+    dual_contact: None,
+};
 
 // Need to convert PCB rows/cols to logical rows/cols.
 // Each PCB row forms a zigzag pattern in blocks of 6. See example.
@@ -44,7 +59,7 @@ const fn calculate_coordinate(row: usize, col: usize) -> Option<Coordinate> {
         return None;
     }
 
-    if row % 2 == 0 {
+    if row.is_multiple_of(2) {
         if col == 0 || col >= 13 {
             return None;
         }
@@ -76,7 +91,7 @@ const fn calculate_coordinate(row: usize, col: usize) -> Option<Coordinate> {
         let block_offset_y = (BLOCK_OFFSET_ROWS * block_count) as i8;
 
         Some(Coordinate {
-            x: -2 + x_pat + block_offset_x - ((row + 1) / 2) as i8,
+            x: -2 + x_pat + block_offset_x - row.div_ceil(2) as i8,
             y: (row as i8) - 1 + y_pat + block_offset_y,
         })
     }
@@ -106,8 +121,8 @@ static KEY_MAP: [[Option<Coordinate>; COLS]; ROWS] = build_key_map();
 // ----------------------------------------------------------------------------
 
 /// Calculates the LED index (0-122) for a given physical (row, col).
-/// Returns NO_LED (255) if no LED is present at that position.
-const fn calculate_led_index(row: usize, col: usize) -> u8 {
+/// Returns NO_LED if no LED is present at that position.
+const fn calculate_led_index(row: usize, col: usize) -> LedMatrixEntry {
     // Bounds check
     if row >= ROWS || col >= COLS {
         return NO_LED;
@@ -120,7 +135,7 @@ const fn calculate_led_index(row: usize, col: usize) -> u8 {
 
     // Calculate "Raw Index" based on physical snake pattern
     // This maps the 2D grid to a 1D sequence of "Potential LED Positions"
-    let raw_idx = if row % 2 == 0 {
+    let raw_idx = if row.is_multiple_of(2) {
         // Even rows: left to right
         if col == 0 {
             // Even rows start at col 1
@@ -136,10 +151,10 @@ const fn calculate_led_index(row: usize, col: usize) -> u8 {
     // Shift index to account for missing LEDs
     if raw_idx >= 25 {
         // Shift by -2 (skipping 2 gaps)
-        (raw_idx - 2) as u8
+        (raw_idx - 2) as LedMatrixEntry
     } else if raw_idx >= 1 {
         // Shift by -1 (skipping 1 gap)
-        (raw_idx - 1) as u8
+        (raw_idx - 1) as LedMatrixEntry
     } else {
         // raw_idx 0 (Gap 1)
         NO_LED
@@ -147,7 +162,7 @@ const fn calculate_led_index(row: usize, col: usize) -> u8 {
 }
 
 // Generate the LED matrix (Physical (r,c) -> LED Index) at compile time
-const fn build_led_matrix() -> [[u8; COLS]; ROWS] {
+const fn build_led_matrix() -> [[LedMatrixEntry; COLS]; ROWS] {
     let mut map = [[NO_LED; COLS]; ROWS];
     let mut r = 0;
     while r < ROWS {
@@ -162,8 +177,17 @@ const fn build_led_matrix() -> [[u8; COLS]; ROWS] {
     map
 }
 
+/// Switch placement from the PCB: 17mm spacing, rotated by ~21 degrees, 16.5mm caps.
+static GEOMETRY: Geometry = Geometry {
+    step_x: (15.881, -6.066),
+    step_y: (7.940, 15.165),
+    rotation_deg: 20.9,
+    key_shape: KeyShape::Square,
+    key_size_mm: 16.5,
+};
+
 // LED Index Mapping
-static LED_MATRIX: [[u8; COLS]; ROWS] = build_led_matrix();
+static LED_MATRIX: [[LedMatrixEntry; COLS]; ROWS] = build_led_matrix();
 
 impl Layout for Layout5x25 {
     fn key_to_coord(row: usize, col: usize) -> Option<Coordinate> {
@@ -177,6 +201,10 @@ impl Layout for Layout5x25 {
         Coordinate { x: 1, y: 6 }
     }
 
+    fn geometry() -> Option<&'static Geometry> {
+        Some(&GEOMETRY)
+    }
+
     fn led_to_coord(idx: LedIndex) -> Option<Coordinate> {
         if idx < NUM_LEDS {
             Some(LED_LOOKUP[idx])
@@ -229,9 +257,8 @@ const fn build_led_lookup() -> [Coordinate; NUM_LEDS] {
 static LED_LOOKUP: [Coordinate; NUM_LEDS] = build_led_lookup();
 
 /// Helper macro to define the row pins.
-/// Usage: `let rows = get_rows!(p);`
+/// Usage: `let rows = layout_5x25::get_rows!(p);`
 /// Returns the available pins in 10-29 range on RP2040-Zero: 10,11,12,13,14,15, 26,27,28,29
-#[macro_export]
 macro_rules! get_rows {
     ($p:ident) => {
         [
@@ -248,6 +275,31 @@ macro_rules! get_rows {
         ]
     };
 }
+pub(crate) use get_rows;
+
+/// Data pin of the LED chain, taken with [`get_led_pin`].
+pub type LedPin = embassy_rp::peripherals::PIN_3;
+
+/// Helper macro to take the LED data pin.
+/// Usage: `let led_pin = layout_5x25::get_led_pin!(p);`
+macro_rules! get_led_pin {
+    ($p:ident) => {
+        $p.PIN_3
+    };
+}
+pub(crate) use get_led_pin;
+
+/// Helper macro to take the host port's D+ and D- pins, on the edge of the
+/// RP2040-Zero next to the expansion I2C pins.
+/// Usage: `let host_pins = layout_5x25::get_host_pins!(p);`
+#[cfg(feature = "usb-host")]
+macro_rules! get_host_pins {
+    ($p:ident) => {
+        ($p.PIN_6, $p.PIN_7)
+    };
+}
+#[cfg(feature = "usb-host")]
+pub(crate) use get_host_pins;
 
 /// Debug function to print the current key map
 #[allow(dead_code)]
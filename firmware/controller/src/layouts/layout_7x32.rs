@@ -0,0 +1,261 @@
+use crate::layout::{Coordinate, Geometry, KeyShape, Layout, LedIndex, LedMatrixEntry, NO_LED};
+
+/// Planned full-size board: 7 octaves with a fifthspan of 32 (224 keys).
+pub struct Layout7x32;
+
+// Configuration Constants
+pub const ROWS: usize = 14;
+pub const COLS: usize = 17;
+pub const NUM_LEDS: usize = 224;
+
+/// LED index ranges of the separate chains: the top half on GPIO3, the bottom on GPIO22.
+pub const LED_CHAINS: [core::ops::Range<usize>; 2] = [0..NUM_LEDS / 2, NUM_LEDS / 2..NUM_LEDS];
+
+/// Keys per PCB row pair, i.e. per octave.
+const KEYS_PER_OCTAVE: usize = 32;
+
+pub const BOARD: super::BoardConfig = super::BoardConfig {
+    rows: ROWS,
+    cols: COLS,
+    shift_registers: 3,
+    polarity: super::Polarity::ActiveHigh,
+    // Room for two players
+    held_keys: 24,
+    midi_queue: 48,
+    drive: super::PadDrive {
+        strength: super::DriveStrength::Ma8,
+        fast_slew: true,
+    },
+    // /OE on GPIO26
+    blanking: Some(super::Blanking {
+        gap: embassy_time::Duration::from_micros(2),
+    }),
+    satellite: Some(super::BASS_ROW),
+    dual_contact: None,
+};
+
+// Same zigzag wiring as the 5x25 board, extended to 16 keys on every PCB row:
+// even rows use cols 1-16, odd rows cols 0-15. Each row pair covers one octave.
+static X_PATTERN: [usize; 6] = [0, 1, 2, 2, 3, 4];
+static Y_PATTERN: [usize; 6] = [0, 0, 0, 1, 1, 1];
+
+// Offset between each successive block of 6
+const BLOCK_OFFSET_COLS: usize = 5;
+const BLOCK_OFFSET_ROWS: usize = 2;
+
+/// Calculates the logical coordinate for a given physical (row, col).
+const fn calculate_coordinate(row: usize, col: usize) -> Option<Coordinate> {
+    if row >= ROWS || col >= COLS {
+        return None;
+    }
+
+    let (block_pos, x_base, y_base) = if row.is_multiple_of(2) {
+        if col == 0 {
+            return None;
+        }
+        (col - 1, -((row / 2) as i8), row as i8)
+    } else {
+        if col >= KEYS_PER_OCTAVE / 2 {
+            return None;
+        }
+        (col + 2, -2 - row.div_ceil(2) as i8, row as i8 - 1)
+    };
+    let block_count = block_pos / 6;
+    let block_idx = block_pos % 6;
+
+    Some(Coordinate {
+        x: x_base + (X_PATTERN[block_idx] + BLOCK_OFFSET_COLS * block_count) as i8,
+        y: y_base + (Y_PATTERN[block_idx] + BLOCK_OFFSET_ROWS * block_count) as i8,
+    })
+}
+
+/// Calculates the LED index for a given physical (row, col).
+/// The chain snakes left to right along even rows and back along odd rows.
+const fn calculate_led_index(row: usize, col: usize) -> LedMatrixEntry {
+    if calculate_coordinate(row, col).is_none() {
+        return NO_LED;
+    }
+    let octave_start = KEYS_PER_OCTAVE * (row / 2);
+    let idx = if row.is_multiple_of(2) {
+        octave_start + col - 1
+    } else {
+        octave_start + KEYS_PER_OCTAVE - 1 - col
+    };
+    idx as LedMatrixEntry
+}
+
+const fn build_key_map() -> [[Option<Coordinate>; COLS]; ROWS] {
+    let mut map = [[None; COLS]; ROWS];
+    let mut r = 0;
+    while r < ROWS {
+        let mut c = 0;
+        while c < COLS {
+            map[r][c] = calculate_coordinate(r, c);
+            c += 1;
+        }
+        r += 1;
+    }
+    map
+}
+
+const fn build_led_lookup() -> [Coordinate; NUM_LEDS] {
+    let mut lookup = [Coordinate { x: 0, y: 0 }; NUM_LEDS];
+    let mut r = 0;
+    while r < ROWS {
+        let mut c = 0;
+        while c < COLS {
+            let led_idx = calculate_led_index(r, c);
+            if let Some(coord) = calculate_coordinate(r, c) {
+                lookup[led_idx as usize] = coord;
+            }
+            c += 1;
+        }
+        r += 1;
+    }
+    lookup
+}
+
+static KEY_MAP: [[Option<Coordinate>; COLS]; ROWS] = build_key_map();
+static LED_LOOKUP: [Coordinate; NUM_LEDS] = build_led_lookup();
+
+/// Checks run at compile time: every key has its own LED, the LED chain has no gaps,
+/// no two keys share a coordinate, and the center key exists.
+const fn check_consistency() {
+    let mut seen = [false; NUM_LEDS];
+    let mut keys = 0;
+    let mut r = 0;
+    while r < ROWS {
+        let mut c = 0;
+        while c < COLS {
+            if calculate_coordinate(r, c).is_some() {
+                let idx = calculate_led_index(r, c) as usize;
+                assert!(idx < NUM_LEDS, "LED index out of range");
+                assert!(!seen[idx], "two keys share an LED");
+                seen[idx] = true;
+                keys += 1;
+            }
+            c += 1;
+        }
+        r += 1;
+    }
+    assert!(keys == NUM_LEDS, "key count doesn't match NUM_LEDS");
+
+    let mut next = 0;
+    let mut chain = 0;
+    while chain < LED_CHAINS.len() {
+        assert!(
+            LED_CHAINS[chain].start == next,
+            "LED chains must be contiguous"
+        );
+        next = LED_CHAINS[chain].end;
+        chain += 1;
+    }
+    assert!(next == NUM_LEDS, "LED chains don't cover every LED");
+
+    let lookup = build_led_lookup();
+    let center = CENTER;
+    let mut found_center = false;
+    let mut i = 0;
+    while i < NUM_LEDS {
+        let mut j = i + 1;
+        while j < NUM_LEDS {
+            assert!(
+                lookup[i].x != lookup[j].x || lookup[i].y != lookup[j].y,
+                "two keys share a coordinate"
+            );
+            j += 1;
+        }
+        found_center |= lookup[i].x == center.x && lookup[i].y == center.y;
+        i += 1;
+    }
+    assert!(found_center, "center key missing");
+}
+const _: () = check_consistency();
+
+/// Middle of the fourth octave, near the middle of the board.
+const CENTER: Coordinate = Coordinate { x: 2, y: 8 };
+
+/// Planned with the same spacing and caps as the 5x25 board.
+static GEOMETRY: Geometry = Geometry {
+    step_x: (15.881, -6.066),
+    step_y: (7.940, 15.165),
+    rotation_deg: 20.9,
+    key_shape: KeyShape::Square,
+    key_size_mm: 16.5,
+};
+
+impl Layout for Layout7x32 {
+    fn key_to_coord(row: usize, col: usize) -> Option<Coordinate> {
+        if row < ROWS && col < COLS {
+            return KEY_MAP[row][col];
+        }
+        None
+    }
+
+    fn center_coord() -> Coordinate {
+        CENTER
+    }
+
+    fn geometry() -> Option<&'static Geometry> {
+        Some(&GEOMETRY)
+    }
+
+    fn led_to_coord(idx: LedIndex) -> Option<Coordinate> {
+        LED_LOOKUP.get(idx).copied()
+    }
+
+    fn coord_to_led(coord: Coordinate) -> Option<LedIndex> {
+        LED_LOOKUP.iter().position(|&c| c == coord)
+    }
+}
+
+/// Helper macro to define the row pins.
+/// Usage: `let rows = layout_7x32::get_rows!(p);`
+macro_rules! get_rows {
+    ($p:ident) => {
+        [
+            $p.PIN_6.into(),
+            $p.PIN_7.into(),
+            $p.PIN_8.into(),
+            $p.PIN_9.into(),
+            $p.PIN_10.into(),
+            $p.PIN_11.into(),
+            $p.PIN_12.into(),
+            $p.PIN_13.into(),
+            $p.PIN_14.into(),
+            $p.PIN_15.into(),
+            $p.PIN_16.into(),
+            $p.PIN_17.into(),
+            $p.PIN_18.into(),
+            $p.PIN_19.into(),
+        ]
+    };
+}
+pub(crate) use get_rows;
+
+/// Data pins of the top and bottom LED chains, taken with [`get_led_pins`].
+pub type LedPins = (
+    embassy_rp::peripherals::PIN_3,
+    embassy_rp::peripherals::PIN_22,
+);
+
+/// Helper macro to take the LED data pins, in [`LED_CHAINS`] order.
+/// Usage: `let led_pins = layout_7x32::get_led_pins!(p);`
+macro_rules! get_led_pins {
+    ($p:ident) => {
+        ($p.PIN_3, $p.PIN_22)
+    };
+}
+pub(crate) use get_led_pins;
+
+/// Helper macro to take the host port's D+ and D- pins, left free by the
+/// matrix and the VSYS divider.
+/// Usage: `let host_pins = layout_7x32::get_host_pins!(p);`
+#[cfg(feature = "usb-host")]
+macro_rules! get_host_pins {
+    ($p:ident) => {
+        ($p.PIN_27, $p.PIN_28)
+    };
+}
+#[cfg(feature = "usb-host")]
+pub(crate) use get_host_pins;
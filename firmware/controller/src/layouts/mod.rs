@@ -1,9 +1,303 @@
-#[cfg(feature = "layout-prototype")]
-pub mod prototype;
-#[cfg(feature = "layout-prototype")]
-pub use prototype::*;
+use embassy_rp::gpio::{Drive, Input, Level, Output, Pull, SlewRate};
+use lattice_board_core::layout::{Coordinate, Geometry, Layout, LedIndex};
+use portable_atomic::{AtomicU8, Ordering};
 
-#[cfg(feature = "layout-5x25")]
 pub mod layout_5x25;
-#[cfg(feature = "layout-5x25")]
-pub use layout_5x25::*;
+pub mod layout_7x32;
+pub mod prototype;
+
+pub use layout_5x25::Layout5x25;
+pub use layout_7x32::Layout7x32;
+pub use prototype::PrototypeLayout;
+
+/// Wiring details the key scanners need beyond the key map.
+pub struct BoardConfig {
+    pub rows: usize,
+    pub cols: usize,
+    /// 74HC595s cascaded on the column chain, 8 outputs each; 0 for direct-wired columns.
+    /// Columns take the first `COLS` outputs, any left over are unused.
+    pub shift_registers: usize,
+    pub polarity: Polarity,
+    /// Held keys to track at once (see [`crate::capacities`]).
+    pub held_keys: usize,
+    /// Events the MIDI queue holds.
+    pub midi_queue: usize,
+    /// Pads of the lines driving the matrix: the columns on direct-wired boards, the
+    /// shift register lines otherwise.
+    pub drive: PadDrive,
+    /// Blanking of the shift register outputs through their /OE pin, for boards
+    /// that wire it to a GPIO rather than tying it low.
+    pub blanking: Option<Blanking>,
+    /// Key cluster on an I2C expander, played alongside the matrix.
+    pub satellite: Option<Satellite>,
+    /// Second contacts of dual-contact switches, for boards that have them; keys on
+    /// the others play at a fixed velocity.
+    pub dual_contact: Option<DualContact>,
+}
+
+/// Where the second contacts of dual-contact switches are wired. Each key closes one
+/// contact, then another further down, each on a matrix position of its own; the time
+/// between the two gives the velocity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)] // No current board has dual-contact switches
+pub struct DualContact {
+    /// First row of second contacts. The key whose first contact is at (`row`, `col`)
+    /// has its second at (`row + second_row`, `col`); only the first contacts are in
+    /// the key map.
+    pub second_row: usize,
+}
+
+/// I2C GPIO expanders a satellite cluster can hang off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)] // Not every expander is used by a board
+pub enum Expander {
+    /// Has its own pull-ups.
+    Mcp23017,
+    /// Needs pull-ups on the board.
+    Tca9555,
+}
+
+/// A detached cluster of keys, such as a bass row, on the 16 pins of an I2C expander.
+/// Each key connects one pin to ground and sits at a coordinate of its own, so it
+/// plays, sounds and glides like a matrix key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Satellite {
+    pub expander: Expander,
+    pub address: u8,
+    /// Keys wired, from pin 0 (port A bit 0) up.
+    pub keys: u8,
+    /// Coordinate of the key on pin 0, relative to the board's center key.
+    pub origin: Coordinate,
+    /// Step from each pin's key to the next.
+    pub step: Coordinate,
+}
+
+impl Satellite {
+    /// Coordinate of the key on `pin`, given the board's center key.
+    pub fn coord(&self, center: Coordinate, pin: u8) -> Option<Coordinate> {
+        let axis = |center: i8, origin: i8, step: i8| {
+            i8::try_from(center as i16 + origin as i16 + step as i16 * pin as i16).ok()
+        };
+        Some(Coordinate {
+            x: axis(center.x, self.origin.x, self.step.x)?,
+            y: axis(center.y, self.origin.y, self.step.y)?,
+        })
+    }
+}
+
+/// A row of 16 bass keys on an MCP23017 at its base address, rising by semitones
+/// from C two octaves below the center key.
+pub const BASS_ROW: Satellite = Satellite {
+    expander: Expander::Mcp23017,
+    address: 0x20,
+    keys: 16,
+    origin: Coordinate { x: -2, y: 4 },
+    step: Coordinate { x: -2, y: -1 },
+};
+
+/// Releases every column for a moment around each latch, so the row lines can drop
+/// back to idle before the next column drives them. Keeps a pressed key on one column
+/// from ghosting onto the next across dense rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Blanking {
+    /// How long the outputs stay off after the latch.
+    pub gap: embassy_time::Duration,
+}
+
+/// Output current of a GPIO pad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)] // Not every strength is used by a board
+pub enum DriveStrength {
+    Ma2,
+    Ma4,
+    Ma8,
+    Ma12,
+}
+
+/// Drive strength and slew rate of output pads. Long matrix traces take stronger,
+/// faster edges to settle within the scan.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PadDrive {
+    pub strength: DriveStrength,
+    pub fast_slew: bool,
+}
+
+impl PadDrive {
+    /// The RP2040's reset state.
+    pub const DEFAULT: Self = Self {
+        strength: DriveStrength::Ma4,
+        fast_slew: false,
+    };
+
+    pub fn apply(self, pin: &mut Output<'_>) {
+        pin.set_drive_strength(match self.strength {
+            DriveStrength::Ma2 => Drive::_2mA,
+            DriveStrength::Ma4 => Drive::_4mA,
+            DriveStrength::Ma8 => Drive::_8mA,
+            DriveStrength::Ma12 => Drive::_12mA,
+        });
+        pin.set_slew_rate(if self.fast_slew {
+            SlewRate::Fast
+        } else {
+            SlewRate::Slow
+        });
+    }
+}
+
+/// How the key matrix is wired. Columns are always driven and rows read; the diode
+/// direction decides which way the scanned column has to pull.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    /// Diodes point from column to row: the scanned column is driven high and rows
+    /// have pull-downs, reading high when pressed.
+    ActiveHigh,
+    /// Diodes point from row to column: the scanned column is driven low, the others
+    /// high, and rows have pull-ups, reading low when pressed.
+    #[allow(dead_code)] // No current board is wired this way
+    ActiveLow,
+}
+
+impl Polarity {
+    /// Level of the column being scanned, and of a row whose key is pressed.
+    pub const fn active(self) -> Level {
+        match self {
+            Polarity::ActiveHigh => Level::High,
+            Polarity::ActiveLow => Level::Low,
+        }
+    }
+
+    /// Level of the columns not being scanned.
+    pub const fn idle(self) -> Level {
+        match self {
+            Polarity::ActiveHigh => Level::Low,
+            Polarity::ActiveLow => Level::High,
+        }
+    }
+
+    /// Row pull that holds unpressed rows at the idle level.
+    pub const fn pull(self) -> Pull {
+        match self {
+            Polarity::ActiveHigh => Pull::Down,
+            Polarity::ActiveLow => Pull::Up,
+        }
+    }
+}
+
+/// Boards this firmware can drive. Every layout is compiled in and one is picked at
+/// boot, so a single UF2 serves all hardware revisions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Board {
+    Prototype,
+    Layout5x25,
+    Layout7x32,
+}
+
+/// Used when neither strap pins nor flash name a board. The 5x25 only reads the pins
+/// the prototype drives, so guessing it wrong can't fight the prototype's outputs;
+/// guessing the prototype on a 5x25 would drive GPIO10, 11 and 29 against its row
+/// inputs. An unstrapped prototype boots with the wrong key map instead, until its ID
+/// is stored with SET_BOARD (`F0 7D 30 01 F7`).
+const FALLBACK_BOARD: Board = Board::Layout5x25;
+
+impl Board {
+    /// Board ID as set by the strap pins or stored in flash; 0 means none.
+    pub const fn id(self) -> u8 {
+        match self {
+            Board::Prototype => 1,
+            Board::Layout5x25 => 2,
+            Board::Layout7x32 => 3,
+        }
+    }
+
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Board::Prototype),
+            2 => Some(Board::Layout5x25),
+            3 => Some(Board::Layout7x32),
+            _ => None,
+        }
+    }
+
+    pub const fn config(self) -> &'static BoardConfig {
+        match self {
+            Board::Prototype => &prototype::BOARD,
+            Board::Layout5x25 => &layout_5x25::BOARD,
+            Board::Layout7x32 => &layout_7x32::BOARD,
+        }
+    }
+}
+
+static BOARD: AtomicU8 = AtomicU8::new(FALLBACK_BOARD.id());
+
+/// The board detected at boot.
+pub fn board() -> Board {
+    Board::from_id(BOARD.load(Ordering::Relaxed)).unwrap_or(FALLBACK_BOARD)
+}
+
+pub fn rows() -> usize {
+    board().config().rows
+}
+
+pub fn cols() -> usize {
+    board().config().cols
+}
+
+pub fn satellite() -> Option<Satellite> {
+    board().config().satellite
+}
+
+/// Picks the board: strap pins win, then the ID stored in flash, then the fallback.
+/// Straps are pulled up and tied to ground to set a bit of the board ID, so an
+/// unstrapped board reads 0.
+pub fn detect(straps: &[Input<'_>], stored_id: Option<u8>) -> Board {
+    let strapped = straps
+        .iter()
+        .enumerate()
+        .fold(0, |id, (bit, pin)| id | ((pin.is_low() as u8) << bit));
+    let board = Board::from_id(strapped)
+        .or(stored_id.and_then(Board::from_id))
+        .unwrap_or_else(|| {
+            log::warn!(
+                "No board strapped or stored, assuming {:?}; a prototype needs SET_BOARD 1",
+                FALLBACK_BOARD
+            );
+            FALLBACK_BOARD
+        });
+    BOARD.store(board.id(), Ordering::Relaxed);
+    board
+}
+
+/// Layout of the board detected at boot, dispatching to the matching layout.
+pub struct CurrentLayout;
+
+macro_rules! dispatch {
+    ($method:ident($($arg:expr),*)) => {
+        match board() {
+            Board::Prototype => PrototypeLayout::$method($($arg),*),
+            Board::Layout5x25 => Layout5x25::$method($($arg),*),
+            Board::Layout7x32 => Layout7x32::$method($($arg),*),
+        }
+    };
+}
+
+impl Layout for CurrentLayout {
+    fn key_to_coord(row: usize, col: usize) -> Option<Coordinate> {
+        dispatch!(key_to_coord(row, col))
+    }
+
+    fn led_to_coord(idx: LedIndex) -> Option<Coordinate> {
+        dispatch!(led_to_coord(idx))
+    }
+
+    fn coord_to_led(coord: Coordinate) -> Option<LedIndex> {
+        dispatch!(coord_to_led(coord))
+    }
+
+    fn center_coord() -> Coordinate {
+        dispatch!(center_coord())
+    }
+
+    fn geometry() -> Option<&'static Geometry> {
+        dispatch!(geometry())
+    }
+}
@@ -1,7 +1,6 @@
-use crate::layout::{Coordinate, Layout, LedIndex};
+use crate::layout::{Coordinate, Geometry, KeyShape, Layout, LedIndex, LedMatrixEntry, NO_LED};
 
 pub struct PrototypeLayout;
-pub type CurrentLayout = PrototypeLayout;
 
 // 1 = Key Present, 0 = No Key
 #[rustfmt::skip]
@@ -13,13 +12,12 @@ static KEY_PRESENCE: [[u8; COLS]; ROWS] = [
     [0,     0,     0,     1,     1,     1,     1], // Row 3
     [0,     0,     0,     0,     0,     1,     0], // Row 4
 ];
-const NO_LED: u8 = 255;
 pub const NUM_LEDS: usize = 19;
 
 // LED Index Mapping
 // 0, 1, 2... = LED Index, NO_LED = No LED
 #[rustfmt::skip]
-static LED_MATRIX: [[u8; COLS]; ROWS] = [
+static LED_MATRIX: [[LedMatrixEntry; COLS]; ROWS] = [
     // Col 0     Col 1     Col 2     Col 3     Col 4     Col 5     Col 6
     [NO_LED,   0,        1,        NO_LED,   NO_LED,   NO_LED,   NO_LED], // Row 0
     [2,        3,        4,        5,        6,        NO_LED,   NO_LED], // Row 1
@@ -28,15 +26,23 @@ static LED_MATRIX: [[u8; COLS]; ROWS] = [
     [NO_LED,   NO_LED,   NO_LED,   NO_LED,   NO_LED,   18,       NO_LED], // Row 4
 ];
 
+/// Switch placement from the PCB: MX switches on 16.5mm spacing, rotated by ~21 degrees.
+/// The cap size is approximate.
+static GEOMETRY: Geometry = Geometry {
+    step_x: (15.414, -5.888),
+    step_y: (7.707, 14.719),
+    rotation_deg: 20.9,
+    key_shape: KeyShape::Square,
+    key_size_mm: 15.5,
+};
+
 impl Layout for PrototypeLayout {
     fn key_to_coord(row: usize, col: usize) -> Option<Coordinate> {
-        if row < ROWS && col < COLS {
-            if KEY_PRESENCE[row][col] == 1 {
-                return Some(Coordinate {
-                    x: col as i8,
-                    y: row as i8,
-                });
-            }
+        if row < ROWS && col < COLS && KEY_PRESENCE[row][col] == 1 {
+            return Some(Coordinate {
+                x: col as i8,
+                y: row as i8,
+            });
         }
         None
     }
@@ -45,6 +51,10 @@ impl Layout for PrototypeLayout {
         Coordinate { x: 3, y: 2 }
     }
 
+    fn geometry() -> Option<&'static Geometry> {
+        Some(&GEOMETRY)
+    }
+
     fn led_to_coord(idx: LedIndex) -> Option<Coordinate> {
         if idx < NUM_LEDS {
             Some(LED_LOOKUP[idx])
@@ -74,15 +84,27 @@ impl Layout for PrototypeLayout {
 // ---------------------------
 
 static LED_LOOKUP: [Coordinate; NUM_LEDS] =
-    crate::layout::build_reversed_lookup::<ROWS, COLS, NUM_LEDS>(LED_MATRIX, NO_LED);
+    crate::layout::build_reversed_lookup::<ROWS, COLS, NUM_LEDS>(LED_MATRIX);
 
 // Configuration Constants
 pub const ROWS: usize = 5;
 pub const COLS: usize = 7;
 
+pub const BOARD: super::BoardConfig = super::BoardConfig {
+    rows: ROWS,
+    cols: COLS,
+    shift_registers: 0,
+    polarity: super::Polarity::ActiveHigh,
+    held_keys: 16,
+    midi_queue: 32,
+    drive: super::PadDrive::DEFAULT,
+    blanking: None,
+    satellite: None,
+    dual_contact: None,
+};
+
 /// Helper macro to define the row pins.
-/// Usage: `let rows = get_rows!(p);`
-#[macro_export]
+/// Usage: `let rows = prototype::get_rows!(p);`
 macro_rules! get_rows {
     ($p:ident) => {
         [
@@ -96,8 +118,7 @@ macro_rules! get_rows {
 }
 
 /// Helper macro to define the column pins.
-/// Usage: `let cols = get_cols!(p);`
-#[macro_export]
+/// Usage: `let cols = prototype::get_cols!(p);`
 macro_rules! get_cols {
     ($p:ident) => {
         [
@@ -111,3 +132,27 @@ macro_rules! get_cols {
         ]
     };
 }
+
+/// Data pin of the LED chain, taken with [`get_led_pin`].
+pub type LedPin = embassy_rp::peripherals::PIN_29;
+
+/// Helper macro to take the LED data pin.
+/// Usage: `let led_pin = prototype::get_led_pin!(p);`
+macro_rules! get_led_pin {
+    ($p:ident) => {
+        $p.PIN_29
+    };
+}
+pub(crate) use {get_cols, get_led_pin, get_rows};
+
+/// Helper macro to take the host port's D+ and D- pins, free between the
+/// column pins and the strap pins.
+/// Usage: `let host_pins = prototype::get_host_pins!(p);`
+#[cfg(feature = "usb-host")]
+macro_rules! get_host_pins {
+    ($p:ident) => {
+        ($p.PIN_16, $p.PIN_17)
+    };
+}
+#[cfg(feature = "usb-host")]
+pub(crate) use get_host_pins;
@@ -1,119 +1,791 @@
-use core::cell::RefCell;
-use embassy_rp::pio::Pio;
+use core::cell::Cell;
+use embassy_futures::join::join;
+use embassy_rp::peripherals::{DMA_CH0, DMA_CH1, PIO0};
+use embassy_rp::pio::{Common, Pio, PioPin, StateMachine};
+use embassy_rp::pio_programs::ws2812::{PioWs2812, PioWs2812Program};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::blocking_mutex::Mutex;
-use embassy_time::Duration;
+use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Instant};
 use heapless::Vec;
+use lattice_board_core::battery::BatteryLevel;
+use lattice_board_core::harmony::interval_level;
 use lattice_board_core::layout::{Coordinate, Layout};
+use lattice_board_core::practice::Session;
+use lattice_board_core::zones::{Zone, ZoneKind};
 use smart_leds::RGB8;
 
+use crate::animation::Envelopes;
 use crate::keys::ACTIVE_KEYS;
-use crate::layouts::{COLS, ROWS};
+use crate::layouts::{cols, rows, CurrentLayout};
 use crate::midi::REMOTE_VOICES;
-use crate::tuning::{get_mpe_pbr, PITCH_ANCHOR_CENTS};
+use crate::modulation::Target;
+use crate::tuning::{get_fifth_size, get_mode, get_mpe_pbr, PITCH_ANCHOR_CENTS};
+
+/// Structural guide lines drawn faintly over the palette.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuideMode {
+    Off,
+    /// The octave axis through the center key (x + 1, y - 2 per octave).
+    Octaves,
+    /// Every key sharing the first palette color's pitch class.
+    PitchClass,
+}
+
+impl GuideMode {
+    pub fn next(self) -> Self {
+        match self {
+            GuideMode::Off => GuideMode::Octaves,
+            GuideMode::Octaves => GuideMode::PitchClass,
+            GuideMode::PitchClass => GuideMode::Off,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        self.next().next()
+    }
+}
+
+/// Order in which pitch classes take the palette colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coloring {
+    /// Semitone order: neighbouring colors a semitone apart.
+    Chromatic,
+    /// Circle of fifths: neighbouring colors a fifth apart, so keys of a scale get
+    /// one run of the palette.
+    Fifths,
+    /// Pitch height instead of pitch class: red at the bottom of [`HEIGHT_RANGE`]
+    /// through to violet at the top, ignoring the palette.
+    Height,
+}
+
+impl Coloring {
+    pub fn next(self) -> Self {
+        match self {
+            Coloring::Chromatic => Coloring::Fifths,
+            Coloring::Fifths => Coloring::Height,
+            Coloring::Height => Coloring::Chromatic,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        self.next().next()
+    }
+}
+
+/// Pitches (cents, C2 to C7) spread over the hues of [`Coloring::Height`]. Fixed, so
+/// the colors show where the board sits in absolute pitch.
+const HEIGHT_RANGE: core::ops::Range<f32> = 3600.0..9600.0;
+/// Hue of the top of the range; red is 0.
+const HEIGHT_TOP_HUE: f32 = 270.0;
+
+/// Fully saturated color of `hue` degrees.
+fn hue_rgb(hue: f32) -> [f32; 3] {
+    let h = hue / 60.0;
+    let x = 255.0 * (1.0 - (h % 2.0 - 1.0).abs());
+    match h as u32 {
+        0 => [255.0, x, 0.0],
+        1 => [x, 255.0, 0.0],
+        2 => [0.0, 255.0, x],
+        3 => [0.0, x, 255.0],
+        4 => [x, 0.0, 255.0],
+        _ => [255.0, 0.0, x],
+    }
+}
+
+/// Color of `pad` of `zone`, in place of the palette.
+fn pad_rgb(zone: &Zone, pad: usize) -> [f32; 3] {
+    match zone.kind {
+        ZoneKind::ChordPads if zone.chords[pad].keys().is_empty() => EMPTY_PAD_RGB,
+        ZoneKind::Drums if pad < 2 => KICK_SNARE_RGB,
+        ZoneKind::Drums => DRUM_RGB,
+        ZoneKind::BendKeys => BEND_RGB,
+        ZoneKind::TremoloKey => TREMOLO_RGB,
+        _ => PAD_RGB,
+    }
+}
+
+/// Heatmap hue of the least played pitch classes, shading to red for the most played.
+const HEAT_COLD_HUE: f32 = 240.0;
+/// Heatmap color of pitch classes not played at all.
+const UNPLAYED_RGB: [f32; 3] = [0.0, 0.0, 40.0];
+
+/// Heatmap color for a pitch class played `level` (0 to 1) as often as the most played.
+fn heat_rgb(level: f32) -> [f32; 3] {
+    if level <= 0.0 {
+        return UNPLAYED_RGB;
+    }
+    hue_rgb(HEAT_COLD_HUE * (1.0 - level))
+}
+
+fn height_rgb(cents: f32) -> [f32; 3] {
+    let t = (cents - HEIGHT_RANGE.start) / (HEIGHT_RANGE.end - HEIGHT_RANGE.start);
+    hue_rgb(HEIGHT_TOP_HUE * t.clamp(0.0, 1.0))
+}
+
+/// Palette color `position` steps from the first anchor, blending between anchors.
+fn palette_rgb(config: &LedConfig, position: f32) -> [f32; 3] {
+    let idx = position as usize; // 0..11
+    let t = position - idx as f32; // 0.0..1.0
+    let c1 = config.rgb_anchors[idx];
+    let c2 = config.rgb_anchors[(idx + 1) % 12];
+    // Linear RGB Interpolation
+    [
+        c1.r as f32 + (c2.r as f32 - c1.r as f32) * t,
+        c1.g as f32 + (c2.g as f32 - c1.g as f32) * t,
+        c1.b as f32 + (c2.b as f32 - c1.b as f32) * t,
+    ]
+}
 
+/// Landmark pitch-class presets (bit 0 = C), like the marked keys of physical instruments.
+pub const LANDMARK_PRESETS: [u16; 4] = [0x000, 0x001, 0x081, 0x0A1];
+
+/// Steps through `LANDMARK_PRESETS`; a custom mask restarts from the first preset.
+pub fn cycle_landmarks(mask: u16, delta: i8) -> u16 {
+    let len = LANDMARK_PRESETS.len() as i32;
+    let current = LANDMARK_PRESETS
+        .iter()
+        .position(|&m| m == mask)
+        .unwrap_or(0) as i32;
+    LANDMARK_PRESETS[(current + delta as i32).rem_euclid(len) as usize]
+}
+
+/// Whitening of landmark keys' core color, and their slow brightness pulse.
+const LANDMARK_WHITEN: f32 = 0.5;
+const LANDMARK_PULSE: Duration = Duration::from_millis(2000);
+const LANDMARK_PULSE_DEPTH: f32 = 0.6;
+
+/// Colors of chord pads holding a chord and of empty ones.
+const PAD_RGB: [f32; 3] = [200.0, 150.0, 90.0];
+const EMPTY_PAD_RGB: [f32; 3] = [40.0, 40.0, 40.0];
+/// Color of drum pads, the kick and snare brighter.
+const DRUM_RGB: [f32; 3] = [0.0, 110.0, 150.0];
+const KICK_SNARE_RGB: [f32; 3] = [60.0, 200.0, 255.0];
+/// Color of the bend keys.
+const BEND_RGB: [f32; 3] = [160.0, 0.0, 200.0];
+/// Color of the tremolo key.
+const TREMOLO_RGB: [f32; 3] = [220.0, 220.0, 0.0];
+
+/// Red level of the low battery warning LED, and its blink period once voices are
+/// shut down.
+const BATTERY_WARNING_LEVEL: f32 = 60.0;
+const BATTERY_BLINK: Duration = Duration::from_millis(1000);
+
+/// Blink period of the keys showing the anchor being edited, and their level while off.
+const EDIT_BLINK: Duration = Duration::from_millis(500);
+const EDIT_BLINK_LEVEL: f32 = 0.1;
+
+/// Highlight level of keys suggested by the voice-leading assistant.
+const SUGGESTION_LEVEL: f32 = 0.3;
+
+/// Brightness boost of the strongest interval hints (see [`LedConfig::intervals`]).
+const INTERVAL_BOOST: f32 = 1.5;
+
+/// Brightness boost and whitening of keys on a guide line.
+const GUIDE_BOOST: f32 = 1.6;
+const GUIDE_WHITEN: f32 = 0.15;
+
+#[derive(Clone, PartialEq)]
 pub struct LedConfig {
     pub brightness: f32, // Global brightness (0-1)
-    pub hue_offset: f32, // Input rotation
+    /// Display transposition: the pitch class (0 = C .. 11 = B) shown in the first
+    /// palette color. Moves colors by whole semitones so every pitch keeps a palette color.
+    pub transpose: u8,
+    /// Continuous hue rotation in degrees (0-360). Slides the colors along the palette,
+    /// 30 degrees per palette step, blending between neighbouring anchors.
+    pub hue_rotation: f32,
+    pub coloring: Coloring,
+    /// Brightness change per physical octave band, relative to the center row band
+    /// (0 = off). Upper bands get brighter and lower ones darker, to help orientation
+    /// where the same pitch classes repeat across the board.
+    pub octave_gradient: f32,
+    pub guides: GuideMode,
+    /// Pitch classes drawn as landmarks (bit 0 = C .. bit 11 = B): white core and a
+    /// slow pulse, independent of the palette.
+    pub landmarks: u16,
+    /// While keys are held, brighten the others by their interval from the lowest
+    /// held note, so chord tones stand out.
+    pub intervals: bool,
+    /// Chip temperature (C) above which brightness is derated; 0 disables derating.
+    pub thermal_limit_c: u8,
     pub rgb_anchors: [RGB8; 12],
     pub selected_anchor: usize,
+    pub release_ms: u32, // Highlight fade-out time after a note ends
+    pub frame_ms: u32,   // LED refresh period
+}
+
+const DEFAULT_LED_CONFIG: LedConfig = LedConfig {
+    brightness: 0.05,
+    transpose: 0,
+    hue_rotation: 0.0,
+    coloring: Coloring::Chromatic,
+    octave_gradient: 0.0,
+    guides: GuideMode::Off,
+    landmarks: 0,
+    intervals: false,
+    thermal_limit_c: 60,
+    // Standard 12-tone Rainbow as default
+    rgb_anchors: [
+        RGB8::new(255, 5, 5),   // 0: Red
+        RGB8::new(225, 35, 0),  // 1: Orange
+        RGB8::new(210, 75, 0),  // 2: Yellow
+        RGB8::new(175, 130, 0), // 3: Yellow green
+        RGB8::new(90, 220, 0),  // 4: Green
+        RGB8::new(0, 245, 35),  // 5: Spring Green
+        RGB8::new(0, 165, 130), // 6: Cyan
+        RGB8::new(0, 80, 200),  // 7: Azure
+        RGB8::new(20, 20, 245), // 8: Blue
+        RGB8::new(100, 0, 200), // 9: Purple
+        RGB8::new(200, 0, 100), // 10: Magenta
+        RGB8::new(215, 0, 25),  // 11: Rose
+    ],
+    selected_anchor: 0,
+    release_ms: 300,
+    frame_ms: 2,
+};
+
+/// Bytes of an encoded [`LedConfig`].
+pub const LED_CONFIG_LEN: usize = 63;
+
+/// The next `N` bytes of `rest`, which must hold them.
+fn take<const N: usize>(rest: &mut &[u8]) -> [u8; N] {
+    let (head, tail) = rest.split_at(N);
+    *rest = tail;
+    head.try_into().unwrap()
+}
+
+/// A float stored with [`LedConfig::encode`]; erased flash reads as NaN.
+fn take_f32(rest: &mut &[u8]) -> Option<f32> {
+    Some(f32::from_le_bytes(take(rest))).filter(|v| v.is_finite())
+}
+
+impl LedConfig {
+    /// Encodes the config for storing, all but the anchor being edited.
+    pub fn encode(&self, out: &mut [u8; LED_CONFIG_LEN]) {
+        let mut at = 0;
+        let mut put = |bytes: &[u8]| {
+            out[at..at + bytes.len()].copy_from_slice(bytes);
+            at += bytes.len();
+        };
+        put(&self.brightness.to_le_bytes());
+        put(&[self.transpose]);
+        put(&self.hue_rotation.to_le_bytes());
+        put(&[self.coloring as u8]);
+        put(&self.octave_gradient.to_le_bytes());
+        put(&[self.guides as u8]);
+        put(&self.landmarks.to_le_bytes());
+        put(&[self.intervals as u8, self.thermal_limit_c]);
+        for anchor in self.rgb_anchors {
+            put(&[anchor.r, anchor.g, anchor.b]);
+        }
+        put(&self.release_ms.to_le_bytes());
+        put(&self.frame_ms.to_le_bytes());
+    }
+
+    /// Reads a config stored with [`Self::encode`], if `bytes` holds a whole valid one.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut rest = bytes.get(..LED_CONFIG_LEN)?;
+        let brightness = take_f32(&mut rest)?.clamp(0.0, 1.0);
+        let [transpose] = take(&mut rest);
+        let hue_rotation = (take_f32(&mut rest)? % 360.0 + 360.0) % 360.0;
+        let coloring = match take(&mut rest) {
+            [0] => Coloring::Chromatic,
+            [1] => Coloring::Fifths,
+            [2] => Coloring::Height,
+            _ => return None,
+        };
+        let octave_gradient = take_f32(&mut rest)?;
+        let guides = match take(&mut rest) {
+            [0] => GuideMode::Off,
+            [1] => GuideMode::Octaves,
+            [2] => GuideMode::PitchClass,
+            _ => return None,
+        };
+        let landmarks = u16::from_le_bytes(take(&mut rest));
+        let [intervals, thermal_limit_c] = take(&mut rest);
+        let mut rgb_anchors = [RGB8::default(); 12];
+        for anchor in rgb_anchors.iter_mut() {
+            let [r, g, b] = take(&mut rest);
+            *anchor = RGB8::new(r, g, b);
+        }
+        Some(Self {
+            brightness,
+            transpose: transpose % 12,
+            hue_rotation,
+            coloring,
+            octave_gradient,
+            guides,
+            landmarks,
+            intervals: intervals == 1,
+            thermal_limit_c,
+            rgb_anchors,
+            selected_anchor: 0,
+            release_ms: u32::from_le_bytes(take(&mut rest)),
+            frame_ms: u32::from_le_bytes(take(&mut rest)).max(1),
+        })
+    }
+}
+
+/// When a factory reset last finished, for the wipe pattern.
+static WIPED_AT: Mutex<CriticalSectionRawMutex, Cell<Option<Instant>>> =
+    Mutex::new(Cell::new(None));
+/// How long the wipe pattern shows, and its stripe width and speed.
+const WIPE_PATTERN: Duration = Duration::from_millis(4000);
+const WIPE_STRIPE: usize = 3;
+const WIPE_STEP: Duration = Duration::from_millis(80);
+
+/// Shows red and white stripes running along the strip for a few seconds, over
+/// everything else, to confirm a factory reset.
+pub fn show_wipe() {
+    WIPED_AT.lock(|w| w.set(Some(Instant::now())));
+}
+
+/// When the startup animation started.
+static STARTUP_AT: Mutex<CriticalSectionRawMutex, Cell<Option<Instant>>> =
+    Mutex::new(Cell::new(None));
+/// How long the startup animation takes to light the whole strip.
+const STARTUP_SWEEP: Duration = Duration::from_millis(1200);
+
+/// Lights the board along the strip from dark, with a white leading edge, as it
+/// powers up.
+pub fn show_startup() {
+    STARTUP_AT.lock(|s| s.set(Some(Instant::now())));
+}
+
+// A Watch so the LED task only picks up the config when it actually changes.
+pub static LED_CONFIG: Watch<CriticalSectionRawMutex, LedConfig, 2> =
+    Watch::new_with(DEFAULT_LED_CONFIG);
+
+pub fn led_config() -> LedConfig {
+    LED_CONFIG.try_get().unwrap_or(DEFAULT_LED_CONFIG)
+}
+
+/// Applies `f` to the LED config, notifying the LED task only if something changed.
+pub fn update_config(f: impl Fn(&mut LedConfig)) {
+    LED_CONFIG.sender().send_if_modified(|c| {
+        let Some(config) = c.as_mut() else {
+            return false;
+        };
+        let before = config.clone();
+        f(config);
+        *config != before
+    });
+}
+
+/// Replaces the whole LED config; `None` goes back to the defaults.
+pub fn set_config(config: Option<LedConfig>) {
+    let config = config.unwrap_or(DEFAULT_LED_CONFIG);
+    update_config(|c| *c = config.clone());
+}
+
+/// LED chain lengths, including positions without a key.
+const CHAIN_5X25: usize = 125;
+const CHAIN_PROTOTYPE: usize = 20;
+
+/// The 7x32's LEDs, split over two chains.
+const LEDS_7X32: usize = crate::layouts::layout_7x32::NUM_LEDS;
+const CHAINS_7X32: [core::ops::Range<usize>; 2] = crate::layouts::layout_7x32::LED_CHAINS;
+
+/// One LED chain, showing LEDs `start..start + N` of the logical frame.
+struct Chain<const S: usize, const N: usize> {
+    ws2812: PioWs2812<'static, PIO0, S, N>,
+    start: usize,
+    buf: [RGB8; N],
+}
+
+impl<const S: usize, const N: usize> Chain<S, N> {
+    fn new(
+        common: &mut Common<'static, PIO0>,
+        sm: StateMachine<'static, PIO0, S>,
+        dma: impl embassy_rp::Peripheral<P = impl embassy_rp::dma::Channel> + 'static,
+        pin: impl PioPin,
+        program: &PioWs2812Program<'static, PIO0>,
+        start: usize,
+    ) -> Self {
+        Self {
+            ws2812: PioWs2812::new(common, sm, dma, pin, program),
+            start,
+            buf: [RGB8::default(); N],
+        }
+    }
+}
+
+/// Where a finished frame goes: one chain, or several driven at once.
+trait FrameOutput {
+    async fn write(&mut self, frame: &[RGB8]);
+}
+
+impl<const S: usize, const N: usize> FrameOutput for Chain<S, N> {
+    async fn write(&mut self, frame: &[RGB8]) {
+        self.buf.copy_from_slice(&frame[self.start..self.start + N]);
+        self.ws2812.write(&self.buf).await;
+    }
+}
+
+impl<A: FrameOutput, B: FrameOutput> FrameOutput for (A, B) {
+    async fn write(&mut self, frame: &[RGB8]) {
+        join(self.0.write(frame), self.1.write(frame)).await;
+    }
+}
+
+use embassy_futures::select::{select3, Either3};
+use embassy_time::Timer;
+
+/// Refresh period used while nothing is animating or while USB MIDI is backed up.
+const THROTTLED_FRAME: Duration = Duration::from_millis(40);
+
+/// Highlight level of a fresh attack and of a long-held note.
+const ATTACK_LEVEL: f32 = 1.0;
+const SUSTAIN_LEVEL: f32 = 0.6;
+/// Time for a held note's highlight to settle from attack to sustain level.
+const AGE_FADE: Duration = Duration::from_millis(1500);
+
+/// Highlight level for a note held for `age`: fresh attacks flash brighter,
+/// long-held notes settle to a steadier, dimmer state.
+fn age_level(age: Duration) -> f32 {
+    let t = (age.as_millis() as f32 / AGE_FADE.as_millis() as f32).min(1.0);
+    ATTACK_LEVEL + (SUSTAIN_LEVEL - ATTACK_LEVEL) * t
 }
 
-pub static LED_CONFIG: Mutex<CriticalSectionRawMutex, RefCell<LedConfig>> =
-    Mutex::new(RefCell::new(LedConfig {
-        brightness: 0.05,
-        hue_offset: 0.0,
-        // Standard 12-tone Rainbow as default
-        rgb_anchors: [
-            RGB8::new(255, 5, 5),   // 0: Red
-            RGB8::new(225, 35, 0),  // 1: Orange
-            RGB8::new(210, 75, 0),  // 2: Yellow
-            RGB8::new(175, 130, 0), // 3: Yellow green
-            RGB8::new(90, 220, 0),  // 4: Green
-            RGB8::new(0, 245, 35),  // 5: Spring Green
-            RGB8::new(0, 165, 130), // 6: Cyan
-            RGB8::new(0, 80, 200),  // 7: Azure
-            RGB8::new(20, 20, 245), // 8: Blue
-            RGB8::new(100, 0, 200), // 9: Purple
-            RGB8::new(200, 0, 100), // 10: Magenta
-            RGB8::new(215, 0, 25),  // 11: Rose
-        ],
-        selected_anchor: 0,
-    }));
-
-#[cfg(feature = "layout-5x25")]
-type LedPin = embassy_rp::peripherals::PIN_3;
-#[cfg(feature = "layout-prototype")]
-type LedPin = embassy_rp::peripherals::PIN_29;
-
-#[cfg(feature = "layout-5x25")]
-use crate::layouts::layout_5x25::Layout5x25 as CurrentLayout;
-#[cfg(feature = "layout-5x25")]
-const NUM_LEDS: usize = 125;
-
-#[cfg(feature = "layout-prototype")]
-use crate::layouts::prototype::PrototypeLayout as CurrentLayout;
-#[cfg(feature = "layout-prototype")]
-const NUM_LEDS: usize = 20;
-
-use embassy_time::Ticker;
+#[derive(Clone, Copy)]
+struct BaseColor {
+    coord: Coordinate,
+    rgb: [f32; 3],
+    landmark: bool,
+    /// The anchor nearest the key's palette color, if it shows the palette.
+    anchor: Option<usize>,
+}
+
+/// `config` with its hue rotation moved by `offset` degrees.
+fn hue_rotated(config: &LedConfig, offset: f32) -> LedConfig {
+    LedConfig {
+        hue_rotation: ((config.hue_rotation + offset) % 360.0 + 360.0) % 360.0,
+        ..config.clone()
+    }
+}
+
+/// Unhighlighted color of every LED before brightness, derived from the palette.
+/// Off-board LEDs get `None`.
+fn base_colors<const N: usize>(config: &LedConfig) -> [Option<BaseColor>; N] {
+    let mut base = [None; N];
+    // Get center coordinate for relative calculation
+    let center = CurrentLayout::center_coord();
+    for (i, entry) in base.iter_mut().enumerate() {
+        // Get logical coordinate for this LED
+        let Some(coord) = CurrentLayout::led_to_coord(i) else {
+            continue;
+        };
+        let dx = coord.x as i32 - center.x as i32;
+        let dy = coord.y as i32 - center.y as i32;
+
+        // Calculate semitone position (0-11) relative to center
+        // x (Major 2nd, +2 st) = 2 Fifths
+        // y (Desc 4th, -5 st) = 1 Fifth
+        // Center matches Red (Color 0)
+        let fifths = (dx * 2) + dy;
+        let notes = (fifths * 7).rem_euclid(12); // 0..11 integer semitone
+
+        // Transposition picks the pitch class in color 0; rotation (degrees) then
+        // slides along the palette, 30 degrees per anchor
+        let pitch_class = (notes - config.transpose as i32).rem_euclid(12);
+        let rotation = config.hue_rotation / 30.0;
+        let position = match config.coloring {
+            Coloring::Chromatic => Some((pitch_class as f32 + rotation) % 12.0),
+            // Seven semitones up is a fifth
+            Coloring::Fifths => Some((((pitch_class * 7) % 12) as f32 + rotation) % 12.0),
+            Coloring::Height => None,
+        };
+        let color = match position {
+            Some(position) => palette_rgb(config, position),
+            None => height_rgb(crate::tuning::get_key_pitch::<CurrentLayout>(coord)),
+        };
+
+        // Octave bands are two rows tall
+        let (octave, _) = crate::tuning::calculate_fifths_offsets::<CurrentLayout>(coord);
+        let band = (1.0 + config.octave_gradient * octave as f32).clamp(0.2, 2.0);
+        let mut rgb = color.map(|v| v * band);
+
+        let on_guide = match config.guides {
+            GuideMode::Off => false,
+            // An octave is one step right and two down, keeping the pitch class
+            GuideMode::Octaves => dy == -2 * dx,
+            GuideMode::PitchClass => pitch_class == 0,
+        };
+        if on_guide {
+            for v in rgb.iter_mut() {
+                *v = (*v + (255.0 - *v) * GUIDE_WHITEN) * GUIDE_BOOST;
+            }
+        }
+        let landmark = config.landmarks & (1 << notes) != 0;
+        if landmark {
+            for v in rgb.iter_mut() {
+                *v += (255.0 - *v) * LANDMARK_WHITEN;
+            }
+        }
+
+        *entry = Some(BaseColor {
+            coord,
+            rgb,
+            landmark,
+            anchor: position.map(|p| (p + 0.5) as usize % 12),
+        });
+    }
+    base
+}
+
+/// Heatmap level of every LED: how often its key's pitch class was played in
+/// `session`, next to the most played one.
+fn heat_levels<const N: usize>(base: &[Option<BaseColor>; N], session: &Session) -> [f32; N] {
+    let mut levels = [0.0; N];
+    for (level, color) in levels.iter_mut().zip(base.iter()) {
+        if let Some(color) = color {
+            let pitch = crate::tuning::get_key_pitch::<CurrentLayout>(color.coord);
+            *level = session.heat(crate::practice::pitch_class(pitch));
+        }
+    }
+    levels
+}
+
+/// Adds a coordinate to the lit set with the time its note started, keeping the most
+/// recent (and therefore brightest) start if already present.
+fn light(active_lit: &mut Vec<(Coordinate, Instant), 32>, coord: Coordinate, started: Instant) {
+    if let Some(entry) = active_lit.iter_mut().find(|(c, _)| *c == coord) {
+        entry.1 = entry.1.max(started);
+    } else {
+        let _ = active_lit.push((coord, started));
+    }
+}
 
 #[embassy_executor::task]
-pub async fn led_task(
-    mut pio: Pio<'static, embassy_rp::peripherals::PIO0>,
-    pin: LedPin,
-    dma: embassy_rp::peripherals::DMA_CH0,
+pub async fn led_task_5x25(
+    mut pio: Pio<'static, PIO0>,
+    pin: crate::layouts::layout_5x25::LedPin,
+    dma: DMA_CH0,
 ) {
-    use embassy_rp::pio_programs::ws2812::{PioWs2812, PioWs2812Program};
+    let program = PioWs2812Program::new(&mut pio.common);
+    let chain = Chain::<0, CHAIN_5X25>::new(&mut pio.common, pio.sm0, dma, pin, &program, 0);
+    run::<CHAIN_5X25>(chain).await
+}
 
-    // Load the program
+#[embassy_executor::task]
+pub async fn led_task_prototype(
+    mut pio: Pio<'static, PIO0>,
+    pin: crate::layouts::prototype::LedPin,
+    dma: DMA_CH0,
+) {
     let program = PioWs2812Program::new(&mut pio.common);
+    let chain = Chain::<0, CHAIN_PROTOTYPE>::new(&mut pio.common, pio.sm0, dma, pin, &program, 0);
+    run::<CHAIN_PROTOTYPE>(chain).await
+}
 
-    // Configure State Machine
-    let mut ws2812 = PioWs2812::new(&mut pio.common, pio.sm0, dma, pin, &program);
+/// Top and bottom halves of the 7x32 on their own pins and state machines.
+#[embassy_executor::task]
+pub async fn led_task_7x32(
+    mut pio: Pio<'static, PIO0>,
+    pins: crate::layouts::layout_7x32::LedPins,
+    dma: (DMA_CH0, DMA_CH1),
+) {
+    const TOP: usize = CHAINS_7X32[0].end - CHAINS_7X32[0].start;
+    const BOTTOM: usize = CHAINS_7X32[1].end - CHAINS_7X32[1].start;
+    let program = PioWs2812Program::new(&mut pio.common);
+    let top = Chain::<0, TOP>::new(
+        &mut pio.common,
+        pio.sm0,
+        dma.0,
+        pins.0,
+        &program,
+        CHAINS_7X32[0].start,
+    );
+    let bottom = Chain::<1, BOTTOM>::new(
+        &mut pio.common,
+        pio.sm1,
+        dma.1,
+        pins.1,
+        &program,
+        CHAINS_7X32[1].start,
+    );
+    run::<LEDS_7X32>((top, bottom)).await
+}
 
-    // Buffer: NUM_LEDS (RGB8)
-    let mut data = [RGB8::default(); NUM_LEDS];
-    let mut ticker = Ticker::every(Duration::from_millis(2));
+/// Renders frames of `N` LEDs into `output`; one task per board, as each uses
+/// different pins.
+async fn run<const N: usize>(mut output: impl FrameOutput) {
+    // Buffers: N (RGB8). The back buffer is rebuilt each frame; the front buffer
+    // holds what the strip currently shows, so unchanged frames are never re-sent
+    let mut back = [RGB8::default(); N];
+    let mut front: Option<[RGB8; N]> = None;
+    // When each held key was first seen, for the note-age fade
+    let mut key_ages: Vec<(Coordinate, Instant), { crate::capacities::HELD_KEYS }> = Vec::new();
+    let mut envelopes = Envelopes::<N>::new();
+    let mut last_frame = Instant::now();
+
+    let mut config_rx = LED_CONFIG.anon_receiver();
+    // Full receivers so an idle frame can wake as soon as something is played
+    let mut keys_rx = ACTIVE_KEYS.receiver().unwrap();
+    let mut voices_rx = REMOTE_VOICES.receiver().unwrap();
+    let mut config = led_config();
+    // Palette colors per LED, only rebuilt when the palette or its layout on the board changes
+    let mut hue_offset = crate::modulation::offset(Target::Hue);
+    let mut base = base_colors::<N>(&hue_rotated(&config, hue_offset));
+    let mut keys = crate::keys::active_keys();
+    let mut pad_keys = crate::zones::sounding_keys();
+    let mut voices = crate::midi::remote_voices();
+    // Lit coordinates with the start of the note lighting them; the enharmonic key
+    // search is only redone when keys, voices or tuning change
+    let mut active_lit: Vec<(Coordinate, Instant), 32> = Vec::new();
+    // Interval hint level per LED, redone with the lit keys
+    let mut hints = [0.0f32; N];
+    let mut last_tuning = None;
+    // Heatmap levels per LED while it shows, with the note count they were made at
+    let mut heat: Option<(u32, [f32; N])> = None;
+    let mut idle = false;
 
     loop {
-        ticker.next().await;
+        let mut dirty = false;
+        let frame = Duration::from_millis(config.frame_ms as u64);
+        if idle {
+            // Nothing is animating: refresh slowly, but react to new notes straight away
+            match select3(
+                Timer::at(last_frame + frame.max(THROTTLED_FRAME)),
+                keys_rx.changed(),
+                voices_rx.changed(),
+            )
+            .await
+            {
+                Either3::First(_) => {}
+                Either3::Second(k) => {
+                    keys = k;
+                    dirty = true;
+                }
+                Either3::Third(v) => {
+                    voices = v;
+                    dirty = true;
+                }
+            }
+        } else if crate::midi::is_busy() {
+            // Leave the bus to MIDI timing on busy passages
+            Timer::at(last_frame + frame.max(THROTTLED_FRAME)).await;
+        } else {
+            Timer::at(last_frame + frame).await;
+        }
 
-        // Read config
-        let (brightness, h_offset, anchors) = LED_CONFIG.lock(|c| {
-            let config = c.borrow();
-            (config.brightness, config.hue_offset, config.rgb_anchors)
+        if let Some(c) = config_rx.try_changed() {
+            if c.rgb_anchors != config.rgb_anchors
+                || c.transpose != config.transpose
+                || c.hue_rotation != config.hue_rotation
+                || c.coloring != config.coloring
+                || c.octave_gradient != config.octave_gradient
+                || c.guides != config.guides
+                || c.landmarks != config.landmarks
+            {
+                base = base_colors(&hue_rotated(&c, hue_offset));
+            }
+            config = c;
+            dirty = true;
+        }
+        let offset = crate::modulation::offset(Target::Hue);
+        if offset != hue_offset {
+            hue_offset = offset;
+            base = base_colors(&hue_rotated(&config, hue_offset));
+        }
+        if let Some(k) = keys_rx.try_changed() {
+            keys = k;
+            dirty = true;
+        }
+        if let Some(v) = voices_rx.try_changed() {
+            voices = v;
+            dirty = true;
+        }
+        // Chord pads sound their keys without holding them, so don't wake an idle frame
+        let sounding = crate::zones::sounding_keys();
+        if sounding != pad_keys {
+            pad_keys = sounding;
+            dirty = true;
+        }
+        let tuning = Some((get_mode(), get_fifth_size(), get_mpe_pbr()));
+        let retuned = tuning != last_tuning;
+        if retuned {
+            last_tuning = tuning;
+            dirty = true;
+            if config.coloring == Coloring::Height {
+                // Key heights move with the fifth size
+                base = base_colors(&hue_rotated(&config, hue_offset));
+            }
+        }
+
+        // Only redone as notes are played, or when keys change pitch class
+        heat = crate::practice::heatmap().map(|session| match heat {
+            Some((notes, levels)) if notes == session.notes && !retuned => (notes, levels),
+            _ => (session.notes, heat_levels(&base, &session)),
         });
 
+        let brightness =
+            if crate::power::is_asleep() || crate::power::voices_shut_down() {
+                0.0
+            } else {
+                (config.brightness + crate::modulation::offset(Target::Brightness)).clamp(0.0, 1.0)
+            } * crate::telemetry::derate(crate::stats::temperature_c(), config.thermal_limit_c)
+                * crate::telemetry::supply_cap();
+        let release = Duration::from_millis(config.release_ms as u64);
+
+        let now = Instant::now();
+        let dt = now - last_frame;
+        last_frame = now;
+
         // Resolve All Active Coordinates (Local + Remote)
-        let mut active_lit: Vec<Coordinate, 32> = Vec::new();
-        // 1. Local (Physical) Keys: Find all enharmonic equivalents
-        ACTIVE_KEYS.lock(|k| {
-            for &coord in k.borrow().iter() {
+        if dirty {
+            active_lit.clear();
+
+            // 1. Local (Physical) Keys and the chord pads': Find all enharmonic equivalents
+            key_ages.retain(|(c, _)| keys.contains(c) || pad_keys.contains(c));
+            for &coord in keys.iter().chain(pad_keys.iter()) {
+                let pressed_at = match key_ages.iter().find(|(c, _)| *c == coord) {
+                    Some(&(_, t)) => t,
+                    None => {
+                        let _ = key_ages.push((coord, now));
+                        now
+                    }
+                };
+
                 let pitch_cents = crate::tuning::get_key_pitch::<CurrentLayout>(coord);
 
                 let candidates = crate::tuning::find_closest_keys::<CurrentLayout>(
                     pitch_cents,
                     200.0,
-                    ROWS,
-                    COLS,
+                    rows(),
+                    cols(),
                     None, // No MIDI note bias for local keys
                 );
 
                 for c in candidates {
-                    if !active_lit.contains(&c) {
-                        let _ = active_lit.push(c);
+                    light(&mut active_lit, c, pressed_at);
+                }
+            }
+
+            // Interval hints from the lowest held key
+            hints = [0.0; N];
+            let bass = keys
+                .iter()
+                .filter(|_| config.intervals)
+                .map(|&c| (crate::tuning::get_key_pitch::<CurrentLayout>(c), c))
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            if let Some((_, bass)) = bass {
+                let (_, bass_fifths) =
+                    crate::tuning::calculate_fifths_offsets::<CurrentLayout>(bass);
+                for (hint, color) in hints.iter_mut().zip(base.iter()) {
+                    if let Some(color) = color {
+                        let (_, fifths) =
+                            crate::tuning::calculate_fifths_offsets::<CurrentLayout>(color.coord);
+                        *hint = interval_level(fifths - bass_fifths);
                     }
                 }
             }
-        });
 
-        // 2. Remote (MIDI) Voices
-        REMOTE_VOICES.lock(|v| {
-            for voice in v.borrow().iter() {
+            // 2. Remote (MIDI) Voices
+            let mpe_pbr = get_mpe_pbr();
+            for voice in voices.iter() {
                 // Calculate target cents relative to PITCH_ANCHOR_CENTS
                 let bend_val = voice.pitch_bend as f32;
-                let mpe_pbr = get_mpe_pbr();
                 let bend_semitones = (bend_val - 8192.0) / (8192.0 / mpe_pbr);
 
                 let target_cents = ((u8::from(voice.note) as f32 - 60.0) * 100.0)
@@ -123,79 +795,173 @@ pub async fn led_task(
                 let candidates = crate::tuning::find_closest_keys::<CurrentLayout>(
                     target_cents,
                     200.0,
-                    ROWS,
-                    COLS,
+                    rows(),
+                    cols(),
                     Some(u8::from(voice.note)),
                 );
 
                 for coord in candidates {
-                    if !active_lit.contains(&coord) {
-                        let _ = active_lit.push(coord);
-                    }
+                    light(&mut active_lit, coord, voice.started);
                 }
             }
-        });
-
-        for i in 0..NUM_LEDS {
-            // Get logical coordinate for this LED
-            if let Some(coord) = CurrentLayout::led_to_coord(i) {
-                // Get center coordinate for relative calculation
-                let center = CurrentLayout::center_coord();
-                let dx = coord.x as i32 - center.x as i32;
-                let dy = coord.y as i32 - center.y as i32;
-
-                // Calculate semitone position (0-11) relative to center
-                // x (Major 2nd, +2 st) = 2 Fifths
-                // y (Desc 4th, -5 st) = 1 Fifth
-                // Center matches Red (Color 0)
-                let fifths = (dx * 2) + (dy * 1);
-                let notes = (fifths * 7).rem_euclid(12); // 0..11 integer semitone
-                let _notes2 = fifths.rem_euclid(12);
-
-                // Add offset. Assuming h_offset is in degrees (0..360), map to 0..12
-                let offset_semitones = h_offset / 30.0;
-                let position = (notes as f32 + offset_semitones) % 12.0;
-
-                // Interpolate
-                let idx = position as usize; // 0..11
-                let t = position - idx as f32; // 0.0..1.0
-
-                let next_idx = (idx + 1) % 12;
-
-                let c1 = anchors[idx];
-                let c2 = anchors[next_idx];
-
-                // Linear RGB Interpolation
-                // We cast to f32 to do the math, then scale and cast back to u8
-                let mut r_f = c1.r as f32 + (c2.r as f32 - c1.r as f32) * t;
-                let mut g_f = c1.g as f32 + (c2.g as f32 - c1.g as f32) * t;
-                let mut b_f = c1.b as f32 + (c2.b as f32 - c1.b as f32) * t;
+        }
 
+        // 3. Drunk walk trail, newest brightest
+        let trail = crate::walk::trail();
+        let suggested = crate::voice_leading::suggested();
+        let zone = crate::zones::zone();
+        let euclid = crate::euclid::display_state();
+        let mut animating = active_lit
+            .iter()
+            .any(|&(_, started)| now - started < AGE_FADE);
+        // Triangle wave 0..1 for the landmark pulse
+        let phase = (now.as_millis() % LANDMARK_PULSE.as_millis()) as f32
+            / LANDMARK_PULSE.as_millis() as f32;
+        let pulse = 1.0 - (2.0 * phase - 1.0).abs();
+        // Keys showing the anchor being edited blink
+        let editing = crate::palette_edit::is_editing();
+        animating |= editing;
+        let blink_off =
+            editing && now.as_millis() % EDIT_BLINK.as_millis() >= EDIT_BLINK.as_millis() / 2;
+
+        for (i, led) in back.iter_mut().enumerate() {
+            if let Some(BaseColor {
+                coord,
+                rgb: [mut r_f, mut g_f, mut b_f],
+                landmark,
+                anchor,
+            }) = base[i]
+            {
+                if let Some(pad) = zone.pad(coord) {
+                    [r_f, g_f, b_f] = pad_rgb(&zone, pad);
+                }
+                if let Some((_, levels)) = &heat {
+                    [r_f, g_f, b_f] = heat_rgb(levels[i]);
+                }
                 // Scale by global brightness
                 let mut scale = brightness;
+                if landmark {
+                    scale *= 1.0 + LANDMARK_PULSE_DEPTH * pulse;
+                    animating = true;
+                }
+                scale *= 1.0 + INTERVAL_BOOST * hints[i];
+                if blink_off && anchor == Some(config.selected_anchor) {
+                    scale *= EDIT_BLINK_LEVEL;
+                }
 
                 // Check if this LED should be lit by any active interaction (held keys)
-                if active_lit.contains(&coord) {
-                    // Move 1/3 of the way towards white (255)
-                    r_f = r_f + (255.0 - r_f) * 0.6;
-                    g_f = g_f + (255.0 - g_f) * 0.6;
-                    b_f = b_f + (255.0 - b_f) * 0.6;
-
-                    // Double the brightness
-                    scale *= 3.0;
+                let target =
+                    if let Some(&(_, started)) = active_lit.iter().find(|(c, _)| *c == coord) {
+                        age_level(now - started)
+                    } else if let Some(pos) = trail.iter().position(|&c| c == coord) {
+                        (pos + 1) as f32 / trail.len() as f32
+                    } else if suggested.contains(&coord) {
+                        SUGGESTION_LEVEL
+                    } else {
+                        0.0
+                    };
+                let highlight = envelopes.update(i, target, dt, release);
+                animating |= highlight != target;
+                if highlight > 0.0 {
+                    // Move towards white (255)
+                    r_f += (255.0 - r_f) * 0.6 * highlight;
+                    g_f += (255.0 - g_f) * 0.6 * highlight;
+                    b_f += (255.0 - b_f) * 0.6 * highlight;
+
+                    // Up to triple the brightness
+                    scale *= 1.0 + 2.0 * highlight;
                 }
 
                 let r = (r_f * scale).min(255.0) as u8;
                 let g = (g_f * scale).min(255.0) as u8;
                 let b = (b_f * scale).min(255.0) as u8;
 
-                data[i] = RGB8::new(r, g, b);
+                *led = RGB8::new(r, g, b);
             } else {
                 let v = (50.0 * brightness) as u8;
-                data[i] = RGB8::new(v, v, v);
+                *led = RGB8::new(v, v, v);
+            }
+        }
+
+        // Euclidean generator pattern along the start of the strip
+        if let Some((pattern, steps, current)) = euclid {
+            for (i, led) in back.iter_mut().take(steps as usize).enumerate() {
+                let level = if i == current as usize {
+                    255.0
+                } else if lattice_board_core::rhythm::is_pulse(pattern, i as u8) {
+                    120.0
+                } else {
+                    0.0
+                };
+                let v = (level * brightness * 3.0).min(255.0) as u8;
+                *led = RGB8::new(v, v, v);
+            }
+        }
+
+        // Low battery: the first LED pulses red, or blinks once voices are shut down
+        let battery = crate::power::battery_level();
+        if battery != BatteryLevel::Ok {
+            let level = if battery == BatteryLevel::Critical {
+                let on =
+                    now.as_millis() % BATTERY_BLINK.as_millis() < BATTERY_BLINK.as_millis() / 2;
+                if on {
+                    1.0
+                } else {
+                    0.0
+                }
+            } else {
+                pulse
+            };
+            if let Some(led) = back.first_mut() {
+                // Fixed level, so it shows even with brightness turned down or asleep
+                *led = RGB8::new((BATTERY_WARNING_LEVEL * level) as u8, 0, 0);
             }
         }
 
-        ws2812.write(&data).await;
+        let wipe = WIPED_AT
+            .lock(|w| w.get())
+            .map(|at| now - at)
+            .filter(|&shown| shown < WIPE_PATTERN);
+        if let Some(shown) = wipe {
+            let shift = (shown.as_millis() / WIPE_STEP.as_millis()) as usize;
+            for (i, led) in back.iter_mut().enumerate() {
+                // Fixed level, so it shows even with brightness turned down
+                *led = if ((i + shift) / WIPE_STRIPE).is_multiple_of(2) {
+                    RGB8::new(60, 0, 0)
+                } else {
+                    RGB8::new(30, 30, 30)
+                };
+            }
+        }
+
+        let startup = STARTUP_AT
+            .lock(|s| s.get())
+            .map(|at| now - at)
+            .filter(|&shown| shown < STARTUP_SWEEP);
+        if let Some(shown) = startup {
+            let edge = N * shown.as_millis() as usize / STARTUP_SWEEP.as_millis() as usize;
+            for led in back.iter_mut().skip(edge) {
+                *led = RGB8::default();
+            }
+            if let Some(led) = back.get_mut(edge) {
+                let v = (255.0 * brightness) as u8;
+                *led = RGB8::new(v, v, v);
+            }
+        }
+
+        // WS2812 needs full-frame writes, so the best we can do is skip identical frames
+        if front != Some(back) {
+            output.write(&back).await;
+            front = Some(back);
+        }
+        crate::stats::record_duration(crate::stats::Graph::LedFrame, now.elapsed());
+        idle = !dirty
+            && !animating
+            && trail.is_empty()
+            && euclid.is_none()
+            && wipe.is_none()
+            && startup.is_none()
+            && battery == BatteryLevel::Ok
+            && !crate::modulation::animates_leds();
     }
 }
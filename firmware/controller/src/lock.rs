@@ -0,0 +1,47 @@
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Timer};
+use log::info;
+
+/// Performance lock: while set, console keys, dashboard edits, the encoder and SysEx
+/// settings changes are ignored, so nothing gets changed by accident mid-gig. Playing, MIDI
+/// in and out, and read-only console views keep working.
+static LOCKED: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// How long the corner keys are held to toggle the lock.
+pub const COMBO_HOLD: Duration = Duration::from_secs(2);
+const COMBO_POLL: Duration = Duration::from_millis(100);
+
+pub fn is_locked() -> bool {
+    LOCKED.lock(|l| l.get())
+}
+
+pub fn toggle() {
+    let locked = LOCKED.lock(|l| {
+        l.set(!l.get());
+        l.get()
+    });
+    info!("Performance lock {}", if locked { "on" } else { "off" });
+}
+
+/// Toggles the lock when both corner keys are held for [`COMBO_HOLD`]; they have to
+/// be let go before they toggle it again.
+#[embassy_executor::task]
+pub async fn combo_task() {
+    // Counted as already toggled, so keys still held from the boot gesture don't lock
+    let mut held_for = COMBO_HOLD;
+    loop {
+        Timer::after(COMBO_POLL).await;
+        if !crate::keys::corners_held() {
+            held_for = Duration::from_ticks(0);
+            continue;
+        }
+        if held_for < COMBO_HOLD {
+            held_for += COMBO_POLL;
+            if held_for >= COMBO_HOLD {
+                toggle();
+            }
+        }
+    }
+}
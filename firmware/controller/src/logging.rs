@@ -1,15 +1,81 @@
 use crate::usb;
+use core::cell::Cell;
+use core::fmt::Write;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use heapless::String;
 use log::{LevelFilter, Metadata, Record};
 
-// A dummy struct to help us write to the pipe using the 'write!' macro
-struct LogPipeWriter;
-impl core::fmt::Write for LogPipeWriter {
+/// Longest log line; longer ones are cut off.
+const LINE_LEN: usize = 160;
+const CUT_MARK: &str = "...\r\n";
+/// Longest "[n lines dropped]" marker.
+const MARKER_LEN: usize = 32;
+
+// Sized in capacities; a line and its marker must always fit into an empty pipe
+const _: () = assert!(crate::capacities::LOG_PIPE >= LINE_LEN + MARKER_LEN);
+
+/// Lines dropped since the last one that made it into the pipe.
+static DROPPED: Mutex<CriticalSectionRawMutex, Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// Formats a line, cutting it off instead of failing when it's too long.
+struct LineWriter {
+    line: String<LINE_LEN>,
+    cut: bool,
+}
+
+impl Write for LineWriter {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        let _ = usb::LOG_PIPE.try_write(s.as_bytes());
+        for c in s.chars() {
+            if self.cut || self.line.len() + c.len_utf8() + CUT_MARK.len() > LINE_LEN {
+                self.cut = true;
+                return Ok(());
+            }
+            let _ = self.line.push(c);
+        }
         Ok(())
     }
 }
 
+/// Writes `bytes` into the pipe whole, or not at all if they don't fit.
+fn try_write_all(bytes: &[u8]) -> bool {
+    if usb::LOG_PIPE.free_capacity() < bytes.len() {
+        return false;
+    }
+    // The pipe is a ring, so a write can stop at its end; the rest goes in next
+    let mut written = 0;
+    while written < bytes.len() {
+        match usb::LOG_PIPE.try_write(&bytes[written..]) {
+            Ok(n) => written += n,
+            Err(_) => return false,
+        }
+    }
+    true
+}
+
+/// Queues a whole line for the console. When the pipe is full the line is dropped
+/// and counted, and the next line that fits is preceded by how many were lost.
+fn write_line(line: &str) {
+    DROPPED.lock(|dropped| {
+        if dropped.get() > 0 {
+            let mut marker: String<MARKER_LEN> = String::new();
+            let _ = write!(marker, "[{} lines dropped]\r\n", dropped.get());
+            if usb::LOG_PIPE.free_capacity() < marker.len() + line.len()
+                || !try_write_all(marker.as_bytes())
+            {
+                dropped.set(dropped.get() + 1);
+                crate::stats::record_log_drop();
+                return;
+            }
+            dropped.set(0);
+        }
+        if !try_write_all(line.as_bytes()) {
+            dropped.set(dropped.get() + 1);
+            crate::stats::record_log_drop();
+        }
+    });
+}
+
 // Logger implementation
 struct Logger;
 static LOGGER: Logger = Logger;
@@ -21,8 +87,13 @@ impl log::Log for Logger {
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            use core::fmt::Write;
-            let _ = write!(LogPipeWriter, "{}: {}\r\n", record.level(), record.args());
+            let mut out = LineWriter {
+                line: String::new(),
+                cut: false,
+            };
+            let _ = write!(out, "{}: {}", record.level(), record.args());
+            let _ = out.line.push_str(if out.cut { CUT_MARK } else { "\r\n" });
+            write_line(&out.line);
         }
     }
 
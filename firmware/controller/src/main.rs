@@ -1,9 +1,9 @@
 #![no_std]
 #![no_main]
 
-use defmt_rtt as _;
 use embassy_executor::Spawner;
 use embassy_rp::bind_interrupts;
+use embassy_rp::gpio::{Input, Pull};
 use embassy_rp::peripherals::{PIO0, USB};
 use embassy_rp::pio::Pio;
 use embassy_rp::usb::{Driver, InterruptHandler};
@@ -12,29 +12,83 @@ use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
 use embassy_usb::class::midi::MidiClass;
 use embassy_usb::{Builder, Config};
 use log::info;
-use panic_probe as _;
 use static_cell::StaticCell;
 
+mod animation;
+mod arp;
+mod audition;
+mod boot;
+mod capacities;
+mod cc_map;
+mod clock;
+mod commands;
+mod compare;
+mod dashboard;
+mod defmt_log;
+mod drift;
+mod euclid;
+mod expansion;
+mod expression;
+mod fields;
+mod glide;
 mod keys;
 mod layouts;
 mod leds;
+mod lock;
 mod logging;
 mod midi;
+mod modulation;
 mod mpe;
+mod nrpn;
+mod palette_edit;
+mod panic;
+mod player;
+mod power;
+mod practice;
+mod preset;
+mod profile;
+mod recorder;
+mod reset;
+mod rtt;
+mod selftest;
+mod spelling;
+mod stats;
+mod storage;
+mod sysex;
+mod telemetry;
+mod thru;
+mod toast;
+mod transfer;
+mod tremolo;
 mod tuning;
 mod usb;
+#[cfg(feature = "usb-host")]
+mod usb_host;
 mod util;
+mod voice_leading;
+mod walk;
+mod wear;
+mod zones;
 
 pub use lattice_board_core::layout;
 pub use lattice_board_core::pitch;
 
+use layouts::Board;
+
 bind_interrupts!(struct Irqs {
     USBCTRL_IRQ => InterruptHandler<USB>;
     PIO0_IRQ_0 => embassy_rp::pio::InterruptHandler<PIO0>;
+    ADC_IRQ_FIFO => embassy_rp::adc::InterruptHandler;
+    I2C0_IRQ => embassy_rp::i2c::InterruptHandler<embassy_rp::peripherals::I2C0>;
+    #[cfg(feature = "usb-host")]
+    PIO1_IRQ_0 => embassy_rp::pio::InterruptHandler<embassy_rp::peripherals::PIO1>;
 });
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
+    #[cfg(feature = "usb-host")]
+    let p = embassy_rp::init(usb_host::config());
+    #[cfg(not(feature = "usb-host"))]
     let p = embassy_rp::init(Default::default());
 
     let driver = Driver::new(p.USB, Irqs);
@@ -43,7 +97,11 @@ async fn main(spawner: Spawner) {
     config.manufacturer = Some("YH");
     config.product = Some("LatticeBoard");
 
-    let uid = util::read_unique_id(p.FLASH);
+    storage::init(p.FLASH);
+    let uid = util::read_unique_id();
+    boot::load();
+    profile::load();
+    cc_map::load();
     static SERIAL_STRING: StaticCell<heapless::String<32>> = StaticCell::new();
     let uid_static = SERIAL_STRING.init(uid);
     config.serial_number = Some(uid_static.as_str());
@@ -62,77 +120,203 @@ async fn main(spawner: Spawner) {
         CONTROL_BUF.init([0; 64]),
     );
 
+    // Endpoints are numbered in this order (see panic::MIDI_IN_ENDPOINT)
     let class_cdc = CdcAcmClass::new(&mut builder, STATE.init(State::new()), 64);
     let class_midi = MidiClass::new(&mut builder, 1, 1, 64);
+    let class_defmt = {
+        static DEFMT_STATE: StaticCell<State> = StaticCell::new();
+        CdcAcmClass::new(&mut builder, DEFMT_STATE.init(State::new()), 64)
+    };
 
     let usb = builder.build();
 
     logging::init();
-    let pio = Pio::new(p.PIO0, Irqs);
 
-    #[cfg(feature = "layout-5x25")]
-    {
-        spawner
-            .spawn(leds::led_task(pio, p.PIN_3, p.DMA_CH0))
-            .unwrap();
-    }
-    #[cfg(feature = "layout-prototype")]
-    {
-        spawner
-            .spawn(leds::led_task(pio, p.PIN_29, p.DMA_CH0))
-            .unwrap();
+    // Board ID strap pins, free on every board; tie to ground to set a bit
+    let straps = [
+        Input::new(p.PIN_20, Pull::Up),
+        Input::new(p.PIN_21, Pull::Up),
+    ];
+    Timer::after(Duration::from_micros(10)).await;
+    let board = layouts::detect(&straps, util::stored_board_id());
+    info!("Board: {:?}", board);
+    keys::load_timing();
+    wear::load();
+    preset::load();
+    let pio = Pio::new(p.PIO0, Irqs);
+    if boot::animation() {
+        leds::show_startup();
     }
 
     spawner.spawn(usb::usb_task(usb)).unwrap();
     spawner.spawn(usb::serial_task(class_cdc)).unwrap();
+    spawner.spawn(defmt_log::defmt_task(class_defmt)).unwrap();
+    spawner.spawn(rtt::probe_task()).unwrap();
 
     static MIDI_CHANNEL: StaticCell<
         embassy_sync::channel::Channel<
             embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
             midi::MidiEvent,
-            32,
+            { capacities::MIDI_QUEUE },
         >,
     > = StaticCell::new();
     let channel = MIDI_CHANNEL.init(embassy_sync::channel::Channel::new());
+    keys::set_sender(channel.sender());
 
     spawner
-        .spawn(midi::midi_task(class_midi, channel.receiver()))
+        .spawn(midi::midi_task(
+            class_midi,
+            channel.receiver(),
+            channel.sender(),
+        ))
+        .unwrap();
+    spawner
+        .spawn(euclid::euclid_task(channel.sender()))
+        .unwrap();
+    spawner.spawn(walk::walk_task(channel.sender())).unwrap();
+    spawner.spawn(arp::arp_task(channel.sender())).unwrap();
+    spawner.spawn(voice_leading::voice_leading_task()).unwrap();
+    spawner.spawn(audition::audition_task()).unwrap();
+    spawner.spawn(drift::drift_task(channel.sender())).unwrap();
+    spawner.spawn(wear::wear_task()).unwrap();
+    spawner.spawn(preset::autosave_task()).unwrap();
+    spawner.spawn(selftest::release_benchmark_task()).unwrap();
+    spawner
+        .spawn(tremolo::tremolo_task(channel.sender()))
+        .unwrap();
+    spawner
+        .spawn(modulation::modulation_task(channel.sender()))
+        .unwrap();
+    spawner
+        .spawn(expression::expression_task(channel.sender()))
+        .unwrap();
+    spawner
+        .spawn(cc_map::feedback_task(channel.sender()))
+        .unwrap();
+    spawner
+        .spawn(player::player_task(channel.sender()))
         .unwrap();
 
-    use crate::get_rows;
+    let i2c = embassy_rp::i2c::I2c::new_async(
+        p.I2C0,
+        p.PIN_5,
+        p.PIN_4,
+        Irqs,
+        embassy_rp::i2c::Config::default(),
+    );
+    expansion::init(spawner, i2c).await;
 
-    #[cfg(feature = "layout-5x25")]
-    {
-        Timer::after(Duration::from_millis(2000)).await;
-        crate::layouts::log_key_map();
+    // Pin roles differ between boards, so each arm takes only its own board's pins,
+    // handing back GPIO29 for measuring VSYS where the board leaves it free
+    let vsys = match board {
+        Board::Layout5x25 => {
+            spawner
+                .spawn(leds::led_task_5x25(
+                    pio,
+                    layouts::layout_5x25::get_led_pin!(p),
+                    p.DMA_CH0,
+                ))
+                .unwrap();
 
-        let row_pins = get_rows!(p);
-        let data_pin = p.PIN_0.into();
-        let latch_pin = p.PIN_1.into();
-        let clock_pin = p.PIN_2.into();
+            if boot::delay() {
+                Timer::after(Duration::from_millis(2000)).await;
+            }
+            if boot::key_map() {
+                layouts::layout_5x25::log_key_map();
+            }
 
-        spawner
-            .spawn(keys::keys_task_shift_reg(
-                row_pins,
-                data_pin,
-                latch_pin,
-                clock_pin,
+            let row_pins = layouts::layout_5x25::get_rows!(p);
+            let data_pin = p.PIN_0.into();
+            let latch_pin = p.PIN_1.into();
+            let clock_pin = p.PIN_2.into();
+
+            spawner
+                .spawn(keys::shift_reg::keys_task_shift_reg(
+                    row_pins,
+                    data_pin,
+                    latch_pin,
+                    clock_pin,
+                    channel.sender(),
+                ))
+                .unwrap();
+            #[cfg(feature = "usb-host")]
+            usb_host::start(
+                spawner,
+                Pio::new(p.PIO1, Irqs),
+                layouts::layout_5x25::get_host_pins!(p),
                 channel.sender(),
-            ))
-            .unwrap();
-    }
+            );
+            None
+        }
+        Board::Layout7x32 => {
+            spawner
+                .spawn(leds::led_task_7x32(
+                    pio,
+                    layouts::layout_7x32::get_led_pins!(p),
+                    (p.DMA_CH0, p.DMA_CH1),
+                ))
+                .unwrap();
 
-    #[cfg(feature = "layout-prototype")]
-    {
-        use crate::get_cols;
-        let row_pins = get_rows!(p);
-        let col_pins = get_cols!(p);
-        spawner
-            .spawn(keys::keys_task_direct(row_pins, col_pins, channel.sender()))
-            .unwrap();
-    }
+            let row_pins = layouts::layout_7x32::get_rows!(p);
+            spawner
+                .spawn(keys::shift_reg::keys_task_7x32(
+                    row_pins,
+                    p.PIN_0.into(),
+                    p.PIN_1.into(),
+                    p.PIN_2.into(),
+                    p.PIN_26.into(),
+                    channel.sender(),
+                ))
+                .unwrap();
+            #[cfg(feature = "usb-host")]
+            usb_host::start(
+                spawner,
+                Pio::new(p.PIO1, Irqs),
+                layouts::layout_7x32::get_host_pins!(p),
+                channel.sender(),
+            );
+            Some(embassy_rp::adc::Channel::new_pin(p.PIN_29, Pull::None))
+        }
+        Board::Prototype => {
+            spawner
+                .spawn(leds::led_task_prototype(
+                    pio,
+                    layouts::prototype::get_led_pin!(p),
+                    p.DMA_CH0,
+                ))
+                .unwrap();
+
+            let row_pins = layouts::prototype::get_rows!(p);
+            let col_pins = layouts::prototype::get_cols!(p);
+            spawner
+                .spawn(keys::direct::keys_task_direct(
+                    row_pins,
+                    col_pins,
+                    channel.sender(),
+                ))
+                .unwrap();
+            #[cfg(feature = "usb-host")]
+            usb_host::start(
+                spawner,
+                Pio::new(p.PIO1, Irqs),
+                layouts::prototype::get_host_pins!(p),
+                channel.sender(),
+            );
+            None
+        }
+    };
+
+    let adc = embassy_rp::adc::Adc::new(p.ADC, Irqs, embassy_rp::adc::Config::default());
+    let temp_sensor = embassy_rp::adc::Channel::new_temp_sensor(p.ADC_TEMP_SENSOR);
+    spawner
+        .spawn(telemetry::telemetry_task(adc, temp_sensor, vsys))
+        .unwrap();
 
     info!("Controller start. Serial number: {}", uid_static.as_str());
+    reset::check_safe_mode().await;
+    profile::select_at_boot().await;
+    reset::check_boot_gesture().await;
+    spawner.spawn(lock::combo_task()).unwrap();
 
     loop {
         Timer::after(Duration::from_secs(1)).await;
@@ -1,13 +1,27 @@
+use crate::sysex::{handle_sysex, SYSEX_BUFFER_SIZE};
 use core::cell::{Cell, RefCell};
-use embassy_futures::join::join;
+use embassy_futures::join::join3;
+use embassy_futures::select::{select3, Either3};
 use embassy_rp::peripherals::USB;
 use embassy_rp::usb::Driver as UsbDriver;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::blocking_mutex::Mutex;
-use embassy_time::{with_timeout, Duration, Timer};
+use embassy_sync::watch::Watch;
+use embassy_time::{with_timeout, Duration, Instant, Timer};
 use embassy_usb::class::midi::MidiClass;
 use heapless::Vec;
+use lattice_board_core::config::ConfigValue;
+use lattice_board_core::echo::EchoFilter;
+use lattice_board_core::midi_stream::{Message as StreamMessage, StreamParser};
+use lattice_board_core::release::{NoteKey, ReleaseGuard};
+use lattice_board_core::repeat::{RepeatFilter, ValueKey, BEND};
+use lattice_board_core::rng::Rng;
+use lattice_board_core::schedule::{Offered, Schedule, Timed};
+use lattice_board_core::sysex::{is_sysex_packet, packets as sysex_packets, SysexAssembler};
+use lattice_board_core::transfer::Reply;
+use lattice_board_core::usb_midi::payload;
 use log::{error, info};
+use portable_atomic::{AtomicUsize, Ordering};
 use wmidi::*;
 
 // ----------------------------------------------------------------------------
@@ -19,17 +33,214 @@ pub struct RemoteVoice {
     pub channel: Channel,
     pub note: Note,
     pub velocity: U7,
-    pub pitch_bend: u16, // Raw 14-bit value (0-16383, center 8192)
+    pub pitch_bend: u16,  // Raw 14-bit value (0-16383, center 8192)
+    pub started: Instant, // Time of the (latest) attack, for the note-age fade
 }
 
-pub static REMOTE_VOICES: Mutex<
+/// Notes the host is playing; the LED task wakes on a change while idle.
+pub static REMOTE_VOICES: Watch<
     CriticalSectionRawMutex,
-    RefCell<Vec<RemoteVoice, 32>>, // Support polyphony
-> = Mutex::new(RefCell::new(Vec::new()));
+    Vec<RemoteVoice, { crate::capacities::REMOTE_VOICES }>, // Support polyphony
+    2,
+> = Watch::new_with(Vec::new());
 
 pub static CHANNEL_BENDS: Mutex<CriticalSectionRawMutex, Cell<[u16; 16]>> =
     Mutex::new(Cell::new([8192u16; 16]));
 
+/// The last bend written to USB on each channel; `None` until one was, or after a
+/// write failed and the host may have missed it.
+static SENT_BENDS: Mutex<CriticalSectionRawMutex, Cell<[Option<u16>; 16]>> =
+    Mutex::new(Cell::new([None; 16]));
+
+pub fn sent_bend(channel: Channel) -> Option<u16> {
+    SENT_BENDS.lock(|b| b.get()[channel_to_index(channel)])
+}
+
+/// Note ons we sent, so the host echoing them back doesn't light them a second time.
+static SENT_NOTES: Mutex<CriticalSectionRawMutex, RefCell<EchoFilter<32>>> =
+    Mutex::new(RefCell::new(EchoFilter::new()));
+/// How long after sending a note on the same note coming back counts as its echo,
+/// in ms (0 = off).
+static ECHO_WINDOW_MS: Mutex<CriticalSectionRawMutex, Cell<u32>> = Mutex::new(Cell::new(50));
+
+pub fn echo_window_ms() -> u32 {
+    ECHO_WINDOW_MS.lock(|w| w.get())
+}
+
+pub fn adjust_echo_window(delta: i32) {
+    ECHO_WINDOW_MS.lock(|w| w.set((w.get() as i32 + delta).clamp(0, 500) as u32));
+}
+
+/// Whether note ons leave with a little random delay and velocity change.
+static HUMANIZE: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+/// Longest delay humanize adds to a note on, in ms.
+const HUMANIZE_MS: u32 = 6;
+/// Most humanize moves a velocity either way.
+const HUMANIZE_VELOCITY: i8 = 8;
+
+pub fn humanize() -> bool {
+    HUMANIZE.lock(|h| h.get())
+}
+
+pub fn set_humanize(on: bool) {
+    HUMANIZE.lock(|h| h.set(on));
+}
+
+pub fn toggle_humanize() {
+    set_humanize(!humanize());
+    crate::preset::store();
+}
+
+/// `event` with its velocity jittered, and how long to hold it back in ms. Only note
+/// ons are humanized; everything else leaves as it is.
+fn humanized(event: MidiEvent, rng: &mut Rng) -> (MidiEvent, u32) {
+    let jitter = |velocity: U7, rng: &mut Rng| {
+        let v = u8::from(velocity) as i16 + rng.span(HUMANIZE_VELOCITY) as i16;
+        U7::from_u8_lossy(v.clamp(1, 127) as u8)
+    };
+    let event = match event {
+        MidiEvent::NoteOn {
+            channel,
+            note,
+            velocity,
+        } => MidiEvent::NoteOn {
+            channel,
+            note,
+            velocity: jitter(velocity, rng),
+        },
+        MidiEvent::MpeNoteOn {
+            channel,
+            note,
+            velocity,
+            pitch_bend,
+        } => MidiEvent::MpeNoteOn {
+            channel,
+            note,
+            velocity: jitter(velocity, rng),
+            pitch_bend,
+        },
+        _ => return (event, 0),
+    };
+    (event, rng.below(HUMANIZE_MS + 1))
+}
+
+/// Delay lining the board up with other instruments, in ms. Notes can't leave before
+/// they're played, so a negative offset holds back what soft-thru forwards from them
+/// instead of the board's own events.
+static LATENCY_OFFSET_MS: Mutex<CriticalSectionRawMutex, Cell<i8>> = Mutex::new(Cell::new(0));
+const MAX_LATENCY_OFFSET_MS: i8 = 50;
+
+pub fn latency_offset_ms() -> i8 {
+    LATENCY_OFFSET_MS.lock(|o| o.get())
+}
+
+pub fn set_latency_offset(ms: i8) {
+    let ms = ms.clamp(-MAX_LATENCY_OFFSET_MS, MAX_LATENCY_OFFSET_MS);
+    LATENCY_OFFSET_MS.lock(|o| o.set(ms));
+}
+
+pub fn adjust_latency_offset(delta: i8) {
+    set_latency_offset(latency_offset_ms().saturating_add(delta));
+    crate::preset::store();
+}
+
+/// How long the latency offset holds `event` back, in ms.
+fn offset_delay_ms(event: MidiEvent) -> u32 {
+    let offset = latency_offset_ms() as i32;
+    match event {
+        MidiEvent::TransferReply(_) | MidiEvent::ConfigReply(_) => 0,
+        MidiEvent::Thru(_) => (-offset).max(0) as u32,
+        _ => offset.max(0) as u32,
+    }
+}
+
+/// NoteOffs the release guard can hold back for their NoteOn at once.
+const RELEASES_HELD: usize = 32;
+
+/// Events waiting in the send schedule beyond this many are merged or dropped.
+const SCHEDULE_LEN: usize = 64;
+
+/// An event held back in the send schedule.
+#[derive(Clone, Copy)]
+struct Pending {
+    event: MidiEvent,
+    released_at: Option<Instant>,
+}
+
+impl Pending {
+    /// Events on one channel keep their order through the schedule; events without
+    /// a channel share a key of their own.
+    fn key(&self) -> u8 {
+        match self.event {
+            MidiEvent::NoteOn { channel, .. }
+            | MidiEvent::NoteOff { channel, .. }
+            | MidiEvent::PitchBendChange { channel, .. }
+            | MidiEvent::MpeNoteOn { channel, .. }
+            | MidiEvent::ControlChange { channel, .. }
+            | MidiEvent::ControlChange14 { channel, .. }
+            | MidiEvent::Nrpn { channel, .. }
+            | MidiEvent::Rpn { channel, .. } => channel_to_index(channel) as u8,
+            MidiEvent::Thru(_) | MidiEvent::TransferReply(_) | MidiEvent::ConfigReply(_) => 16,
+        }
+    }
+
+    /// The note the event starts or ends, and whether it starts it.
+    fn note(&self) -> Option<(NoteKey, bool)> {
+        match self.event {
+            MidiEvent::NoteOn { channel, note, .. }
+            | MidiEvent::MpeNoteOn { channel, note, .. } => Some((note_key(channel, note), true)),
+            MidiEvent::NoteOff { channel, note, .. } => Some((note_key(channel, note), false)),
+            MidiEvent::Thru(message) => match *message.as_bytes() {
+                [status, note, velocity] if status & 0xF0 == 0x90 => {
+                    Some(((status & 0x0F, note), velocity > 0))
+                }
+                [status, note, _] if status & 0xF0 == 0x80 => Some(((status & 0x0F, note), false)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Timed for Pending {
+    fn merge_key(&self) -> Option<u16> {
+        match self.event {
+            MidiEvent::ControlChange { control, .. } => Some(control as u16),
+            MidiEvent::ControlChange14 { control, .. } => Some(0x2000 | control as u16),
+            MidiEvent::PitchBendChange { .. } => Some(0x4000),
+            MidiEvent::Nrpn { param, .. } => Some(0x8000 | param),
+            MidiEvent::Rpn { param, .. } => Some(0xC000 | param),
+            _ => None,
+        }
+    }
+
+    /// Anything but a release or a transfer reply. Dropping a note on is a lost note;
+    /// dropping its note off would be a hanging one.
+    fn droppable(&self) -> bool {
+        match self.event {
+            MidiEvent::NoteOff { .. } | MidiEvent::TransferReply(_) | MidiEvent::ConfigReply(_) => {
+                false
+            }
+            MidiEvent::Thru(message) => !matches!(
+                *message.as_bytes(),
+                [status, _, _] if status & 0xF0 == 0x80 || status & 0xF0 == 0x90
+            ),
+            _ => true,
+        }
+    }
+}
+
+fn record_sent(key: NoteKey) {
+    let now = Instant::now().as_millis();
+    SENT_NOTES.lock(|s| s.borrow_mut().record(key, now));
+}
+
+fn is_echo(key: NoteKey) -> bool {
+    let window = echo_window_ms();
+    let now = Instant::now().as_millis();
+    window > 0 && SENT_NOTES.lock(|s| s.borrow_mut().is_echo(key, now, window))
+}
+
 // ----------------------------------------------------------------------------
 // MIDI Task Types
 // ----------------------------------------------------------------------------
@@ -46,6 +257,14 @@ impl ToU7 for u8 {
     }
 }
 
+/// Sending half of the MIDI event channel, shared by the scanners and generators.
+pub type MidiSender = embassy_sync::channel::Sender<
+    'static,
+    CriticalSectionRawMutex,
+    MidiEvent,
+    { crate::capacities::MIDI_QUEUE },
+>;
+
 // Define the event type for inter-task communication
 #[derive(Debug, Clone, Copy)]
 pub enum MidiEvent {
@@ -70,8 +289,87 @@ pub enum MidiEvent {
         velocity: U7,
         pitch_bend: u16,
     },
+    ControlChange {
+        channel: wmidi::Channel,
+        control: u8,
+        value: u8,
+    },
+    /// A 14-bit controller: the MSB on CC `control` (0-31), then the LSB on CC
+    /// `control + 32`, back to back so nothing comes between the halves.
+    ControlChange14 {
+        channel: wmidi::Channel,
+        control: u8,
+        value: u16, // 14-bit value
+    },
+    /// Sent as the CC 99/98/6/38 sequence.
+    Nrpn {
+        channel: wmidi::Channel,
+        param: u16, // 14-bit parameter number
+        value: u16, // 14-bit value
+    },
+    /// Sent as the CC 101/100/6/38 sequence.
+    Rpn {
+        channel: wmidi::Channel,
+        param: u16,
+        value: u16,
+    },
+    /// A received message sent back out by soft-thru.
+    Thru(StreamMessage),
+    /// Answer to a SysEx transfer command.
+    TransferReply(Reply),
+    /// A setting's value, answering a SysEx config command.
+    ConfigReply(ConfigValue),
+}
+
+/// NoteOffs from the scanners, stamped with the time the release was detected.
+/// Served ahead of the main queue so releases never wait behind NoteOns or generator traffic.
+static RELEASE_CHANNEL: embassy_sync::channel::Channel<
+    CriticalSectionRawMutex,
+    (MidiEvent, Instant),
+    16,
+> = embassy_sync::channel::Channel::new();
+
+/// Outgoing events still waiting for the USB endpoint after the last send.
+static QUEUE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+/// Queue depth above which USB output is considered saturated.
+const BUSY_QUEUE_DEPTH: usize = 8;
+
+/// Bends and CCs remembered for dropping repeats, and how long an unchanged value
+/// is held back before it goes out again.
+const REPEAT_SLOTS: usize = 32;
+const REPEAT_WINDOW_MS: u32 = 100;
+
+/// The controller a bend or CC message sets, and its value.
+fn repeat_value(message: &MidiMessage<'_>) -> Option<(ValueKey, u16)> {
+    match *message {
+        MidiMessage::PitchBendChange(channel, bend) => {
+            Some(((channel.index(), BEND), u16::from(bend)))
+        }
+        MidiMessage::ControlChange(channel, control, value) => {
+            Some(((channel.index(), u8::from(control)), u8::from(value) as u16))
+        }
+        _ => None,
+    }
+}
+
+/// True while outgoing MIDI is backing up, so other tasks can back off.
+pub fn is_busy() -> bool {
+    QUEUE_DEPTH.load(Ordering::Relaxed) >= BUSY_QUEUE_DEPTH
+}
+
+/// Queues a NoteOff on the priority path. Returns the event back if that queue is full.
+pub fn try_send_release(event: MidiEvent) -> Result<(), MidiEvent> {
+    RELEASE_CHANNEL
+        .try_send((event, Instant::now()))
+        .map_err(|embassy_sync::channel::TrySendError::Full((event, _))| event)
 }
 
+fn note_key(channel: Channel, note: Note) -> NoteKey {
+    (channel_to_index(channel) as u8, u8::from(note))
+}
+
+/// Sends queued events over USB and handles what the host sends. `queue` feeds the
+/// send side from the receive side, for soft-thru and SysEx replies.
 #[embassy_executor::task]
 pub async fn midi_task(
     midi: MidiClass<'static, UsbDriver<'static, USB>>,
@@ -79,8 +377,9 @@ pub async fn midi_task(
         'static,
         embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
         MidiEvent,
-        32,
+        { crate::capacities::MIDI_QUEUE },
     >,
+    queue: MidiSender,
 ) {
     // Wait a moment for USB to settle
     Timer::after(Duration::from_millis(1000)).await;
@@ -89,56 +388,194 @@ pub async fn midi_task(
     let (mut sender, mut rx) = midi.split();
 
     let send_future = async {
+        let mut guard = ReleaseGuard::<(MidiEvent, Option<Instant>), RELEASES_HELD>::new();
+        // Releases the guard let go of, sent before anything else
+        let mut follow_ups: Vec<(MidiEvent, Option<Instant>), RELEASES_HELD> = Vec::new();
+        let mut repeats = RepeatFilter::<REPEAT_SLOTS>::new();
+        let mut schedule = Schedule::<Pending, SCHEDULE_LEN>::new();
+        let mut rng = Rng::new(Instant::now().as_ticks() as u32);
         loop {
-            let event = receiver.receive().await;
+            // When the event should leave, for the jitter stats
+            let (event, released_at, intended, due, let_go) = match follow_ups.pop() {
+                // Held back after its way through the schedule, so it's due now
+                Some((event, released_at)) => (event, released_at, Instant::now(), true, true),
+                None => {
+                    let deadline = schedule
+                        .next_deadline()
+                        .map_or(Instant::MAX, Instant::from_ticks);
+                    // Releases are always served first
+                    let woke = select3(
+                        RELEASE_CHANNEL.receive(),
+                        receiver.receive(),
+                        Timer::at(deadline),
+                    )
+                    .await;
+                    let now = Instant::now();
+                    match woke {
+                        Either3::First((event, at)) => (event, Some(at), now, false, false),
+                        Either3::Second(event) => (event, None, now, false, false),
+                        Either3::Third(()) => match schedule.pop_due(now.as_ticks()) {
+                            Some(pending) => {
+                                (pending.event, pending.released_at, deadline, true, false)
+                            }
+                            None => continue,
+                        },
+                    }
+                }
+            };
+            let event = if due {
+                event
+            } else {
+                let (event, delay_ms) = if humanize() {
+                    humanized(event, &mut rng)
+                } else {
+                    (event, 0)
+                };
+                let delay_ms = delay_ms + offset_delay_ms(event);
+                // Anything behind a held-back event on its channel waits for it
+                let pending = Pending { event, released_at };
+                let key = pending.key();
+                if delay_ms > 0 || schedule.latest(key).is_some() {
+                    let at = Instant::now() + Duration::from_millis(delay_ms as u64);
+                    match schedule.offer(at.as_ticks(), key, pending) {
+                        Offered::Queued => continue,
+                        Offered::Merged => {
+                            crate::stats::record_schedule_merge();
+                            continue;
+                        }
+                        Offered::Evicted(_) => {
+                            crate::stats::record_schedule_drop();
+                            continue;
+                        }
+                        Offered::Rejected(pending) if pending.droppable() => {
+                            crate::stats::record_schedule_drop();
+                            continue;
+                        }
+                        // Nothing to make room with. A release whose note on still
+                        // waits would overtake it, so both go; anything else goes now
+                        Offered::Rejected(pending) => {
+                            let released = match pending.note() {
+                                Some((note, false)) => Some(note),
+                                _ => None,
+                            };
+                            let note_on = released.and_then(|released| {
+                                schedule.remove_last(key, |p| p.note() == Some((released, true)))
+                            });
+                            if note_on.is_some() {
+                                crate::stats::record_schedule_drop();
+                                crate::stats::record_schedule_drop();
+                                continue;
+                            }
+                        }
+                    }
+                }
+                event
+            };
+
+            let send = match event {
+                // Nothing new sounds while the battery is about to brown out
+                MidiEvent::NoteOn { .. } | MidiEvent::MpeNoteOn { .. }
+                    if crate::power::voices_shut_down() =>
+                {
+                    false
+                }
+                MidiEvent::NoteOn { channel, note, .. }
+                | MidiEvent::MpeNoteOn { channel, note, .. } => {
+                    // A release that overtook this note goes right behind it
+                    if let Some(release) = guard.on_note_on(note_key(channel, note)) {
+                        let _ = follow_ups.push(release);
+                    }
+                    true
+                }
+                // The guard already let it go
+                MidiEvent::NoteOff { .. } if let_go => true,
+                MidiEvent::NoteOff { channel, note, .. } => guard
+                    .on_note_off(
+                        note_key(channel, note),
+                        (event, released_at),
+                        released_at.is_some() && !(receiver.is_empty() && schedule.is_empty()),
+                    )
+                    .is_some(),
+                MidiEvent::PitchBendChange { .. }
+                | MidiEvent::ControlChange { .. }
+                | MidiEvent::ControlChange14 { .. }
+                | MidiEvent::Nrpn { .. }
+                | MidiEvent::Rpn { .. }
+                | MidiEvent::Thru(_)
+                | MidiEvent::TransferReply(_)
+                | MidiEvent::ConfigReply(_) => true,
+            };
+            if !send {
+                continue;
+            }
+            // Bends and CCs repeating the value last sent are dropped
+            let now_ms = Instant::now().as_millis();
+            let repeat = match event {
+                MidiEvent::PitchBendChange { .. } | MidiEvent::ControlChange { .. } => {
+                    event_messages(event).first().and_then(repeat_value)
+                }
+                _ => None,
+            };
+            if repeat
+                .is_some_and(|(key, value)| repeats.is_repeat(key, value, now_ms, REPEAT_WINDOW_MS))
+            {
+                crate::stats::record_repeat_suppressed();
+                continue;
+            }
 
+            for msg in event_messages(event) {
+                let written = try_send_midi_message(&mut sender, &msg).await;
+                // (N)RPN data entry CCs mean something else per parameter
+                if let Some((key, value)) = repeat_value(&msg)
+                    .filter(|_| !matches!(event, MidiEvent::Nrpn { .. } | MidiEvent::Rpn { .. }))
+                {
+                    if written {
+                        repeats.record(key, value, now_ms);
+                    } else {
+                        repeats.forget(key);
+                    }
+                }
+            }
             match event {
-                MidiEvent::NoteOn {
-                    channel,
-                    note,
-                    velocity,
-                } => {
-                    // Send Pitch Bend Reset (8192) first to ensure no lingering MPE bend affects this note
-                    let pb_reset = MidiMessage::PitchBendChange(
-                        channel,
-                        wmidi::U14::try_from(8192u16).unwrap(),
-                    );
-                    try_send_midi_message(&mut sender, &pb_reset).await;
-
-                    let msg = MidiMessage::NoteOn(channel, note, velocity);
-                    try_send_midi_message(&mut sender, &msg).await;
+                MidiEvent::TransferReply(reply) => {
+                    let mut buf = [0u8; 7];
+                    if !try_send_sysex(&mut sender, reply.encode(&mut buf)).await {
+                        error!("Packet write failure while sending {:?}", reply);
+                    }
                 }
-                MidiEvent::NoteOff {
-                    channel,
-                    note,
-                    velocity,
-                } => {
-                    let msg = MidiMessage::NoteOff(channel, note, velocity);
-                    try_send_midi_message(&mut sender, &msg).await;
+                MidiEvent::ConfigReply(value) => {
+                    let mut buf = [0u8; 12];
+                    if !try_send_sysex(&mut sender, value.encode(&mut buf)).await {
+                        error!("Packet write failure while sending {:?}", value);
+                    }
                 }
-                MidiEvent::PitchBendChange { channel, value } => {
-                    let msg = MidiMessage::PitchBendChange(
-                        channel,
-                        wmidi::U14::try_from(value.clamp(0, 16383)).unwrap(),
-                    );
-                    try_send_midi_message(&mut sender, &msg).await;
+                _ => {}
+            }
+            match event {
+                MidiEvent::NoteOn { channel, note, .. }
+                | MidiEvent::MpeNoteOn { channel, note, .. } => {
+                    record_sent(note_key(channel, note))
                 }
-                MidiEvent::MpeNoteOn {
-                    channel,
-                    note,
-                    velocity,
-                    pitch_bend,
-                } => {
-                    // Send Pitch Bend first
-                    let pb_msg = MidiMessage::PitchBendChange(
-                        channel,
-                        wmidi::U14::try_from(pitch_bend.clamp(0, 16383)).unwrap(),
-                    );
-                    try_send_midi_message(&mut sender, &pb_msg).await;
-
-                    // Then Note On
-                    let note_msg = MidiMessage::NoteOn(channel, note, velocity);
-                    try_send_midi_message(&mut sender, &note_msg).await;
+                MidiEvent::Thru(message) => {
+                    if let [status, note, velocity] = *message.as_bytes() {
+                        if status & 0xF0 == 0x90 && velocity > 0 {
+                            record_sent((status & 0x0F, note))
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            crate::stats::record_send_jitter(intended.elapsed());
+            if let Some(at) = released_at {
+                crate::stats::record_release_latency(at.elapsed());
+            }
+            let depth = receiver.len() + RELEASE_CHANNEL.len() + schedule.size();
+            QUEUE_DEPTH.store(depth, Ordering::Relaxed);
+            crate::stats::record_graph(crate::stats::Graph::QueueDepth, depth as u32);
+            if receiver.is_empty() && schedule.is_empty() {
+                while let Some(release) = guard.on_queue_drained() {
+                    let _ = follow_ups.push(release);
                 }
             }
         }
@@ -146,16 +583,31 @@ pub async fn midi_task(
 
     let receive_future = async {
         let mut buf = [0u8; 64];
+        let mut sysex = SysexAssembler::<SYSEX_BUFFER_SIZE>::new();
+        // Packets carry whole messages, but the shared parser also covers hosts
+        // that split them or use running status across packets
+        let mut parser = StreamParser::new();
         loop {
             match rx.read_packet(&mut buf).await {
                 Ok(n) => {
                     for chunk in buf[..n].chunks(4) {
-                        if chunk.len() == 4 && chunk[0] != 0 {
-                            match wmidi::MidiMessage::try_from(&chunk[1..]) {
-                                Ok(message) => {
-                                    process_remote_midi(&message);
+                        if chunk.len() == 4 && is_sysex_packet(chunk) {
+                            if let Some(msg) = sysex.push_packet(chunk) {
+                                handle_sysex(msg, &queue);
+                            }
+                        } else if chunk.len() == 4 && chunk[0] != 0 {
+                            let Some(bytes) = payload(chunk) else {
+                                info!("Received Raw: {:?}", chunk);
+                                continue;
+                            };
+                            for message in bytes.iter().filter_map(|&b| parser.feed(b)) {
+                                match MidiMessage::try_from(message.as_bytes()) {
+                                    Ok(parsed) => {
+                                        process_remote_midi(&parsed);
+                                        crate::thru::forward(message, &queue);
+                                    }
+                                    Err(_) => info!("Received Raw: {:?}", chunk),
                                 }
-                                Err(_) => info!("Received Raw: {:?}", chunk),
                             }
                         }
                     }
@@ -167,7 +619,119 @@ pub async fn midi_task(
         }
     };
 
-    join(send_future, receive_future).await;
+    join3(
+        send_future,
+        receive_future,
+        crate::mpe::announce_zone(&queue),
+    )
+    .await;
+}
+
+/// The messages sent for an event, in order.
+pub fn event_messages(event: MidiEvent) -> Vec<MidiMessage<'static>, 4> {
+    let bend = |channel, value: u16| {
+        MidiMessage::PitchBendChange(
+            channel,
+            wmidi::U14::try_from(value.clamp(0, 16383)).unwrap(),
+        )
+    };
+    let cc = |channel, control: u8, value: u16| {
+        MidiMessage::ControlChange(
+            channel,
+            wmidi::ControlFunction(control.to_u7()),
+            ((value & 0x7F) as u8).to_u7(),
+        )
+    };
+    let mut messages = Vec::new();
+    match event {
+        MidiEvent::NoteOn {
+            channel,
+            note,
+            velocity,
+        } => {
+            // Send Pitch Bend Reset (8192) first to ensure no lingering MPE bend affects this note
+            let _ = messages.push(bend(channel, 8192));
+            let _ = messages.push(MidiMessage::NoteOn(channel, note, velocity));
+        }
+        MidiEvent::NoteOff {
+            channel,
+            note,
+            velocity,
+        } => {
+            let _ = messages.push(MidiMessage::NoteOff(channel, note, velocity));
+        }
+        MidiEvent::PitchBendChange { channel, value } => {
+            let _ = messages.push(bend(channel, value));
+        }
+        MidiEvent::MpeNoteOn {
+            channel,
+            note,
+            velocity,
+            pitch_bend,
+        } => {
+            // Pitch Bend first, then Note On
+            let _ = messages.push(bend(channel, pitch_bend));
+            let _ = messages.push(MidiMessage::NoteOn(channel, note, velocity));
+        }
+        MidiEvent::ControlChange {
+            channel,
+            control,
+            value,
+        } => {
+            let _ = messages.push(cc(channel, control, value as u16));
+        }
+        MidiEvent::ControlChange14 {
+            channel,
+            control,
+            value,
+        } => {
+            let _ = messages.push(cc(channel, control, value >> 7));
+            let _ = messages.push(cc(channel, control + 32, value));
+        }
+        MidiEvent::Nrpn {
+            channel,
+            param,
+            value,
+        } => {
+            let _ = messages.push(cc(channel, 99, param >> 7));
+            let _ = messages.push(cc(channel, 98, param));
+            let _ = messages.push(cc(channel, 6, value >> 7));
+            let _ = messages.push(cc(channel, 38, value));
+        }
+        MidiEvent::Rpn {
+            channel,
+            param,
+            value,
+        } => {
+            let _ = messages.push(cc(channel, 101, param >> 7));
+            let _ = messages.push(cc(channel, 100, param));
+            let _ = messages.push(cc(channel, 6, value >> 7));
+            let _ = messages.push(cc(channel, 38, value));
+        }
+        MidiEvent::Thru(message) => {
+            if let Some(message) = MidiMessage::try_from(message.as_bytes())
+                .ok()
+                .and_then(|m| m.drop_unowned_sysex())
+            {
+                let _ = messages.push(message);
+            }
+        }
+        // SysEx, written separately
+        MidiEvent::TransferReply(_) | MidiEvent::ConfigReply(_) => {}
+    }
+    messages
+}
+
+/// USB-MIDI event packet for a channel voice message.
+pub fn encode_packet(message: &MidiMessage<'_>) -> Option<[u8; 4]> {
+    let mut buf = [0u8; 3];
+    message.copy_to_slice(&mut buf).ok()?;
+    Some(lattice_board_core::usb_midi::encode(buf))
+}
+
+/// Parses a received (non-SysEx) event packet.
+pub fn decode_packet(packet: &[u8]) -> Option<MidiMessage<'_>> {
+    MidiMessage::try_from(packet.get(1..)?).ok()
 }
 
 pub fn channel_to_index(ch: Channel) -> usize {
@@ -217,92 +781,138 @@ pub fn index_to_channel(idx: u8) -> Option<Channel> {
 // Remote Voice Tracking (for LED Visualization)
 // ----------------------------------------------------------------------------
 
-fn process_remote_midi(message: &MidiMessage) {
-    match message {
+/// Snapshot of the voices currently sounding on the host.
+pub fn remote_voices() -> Vec<RemoteVoice, { crate::capacities::REMOTE_VOICES }> {
+    REMOTE_VOICES.try_get().unwrap_or_default()
+}
+
+/// Applies `f` to the remote voice list; `f` returns whether it changed anything,
+/// so watchers are only notified on real changes.
+fn modify_voices(f: impl Fn(&mut Vec<RemoteVoice, { crate::capacities::REMOTE_VOICES }>) -> bool) {
+    crate::power::note_activity();
+    REMOTE_VOICES
+        .sender()
+        .send_if_modified(|voices| voices.as_mut().is_some_and(&f));
+}
+
+fn remove_voice(
+    voices: &mut Vec<RemoteVoice, { crate::capacities::REMOTE_VOICES }>,
+    ch: Channel,
+    note: Note,
+) -> bool {
+    let len = voices.len();
+    voices.retain(|v| !(v.channel == ch && v.note == note));
+    voices.len() != len
+}
+
+pub fn process_remote_midi(message: &MidiMessage) {
+    match *message {
         MidiMessage::NoteOn(ch, note, vel) => {
-            let velocity: u8 = (*vel).into();
+            let velocity: u8 = vel.into();
             if velocity > 0 {
-                let initial_bend = CHANNEL_BENDS.lock(|b| b.get()[channel_to_index(*ch)]);
-                REMOTE_VOICES.lock(|v| {
-                    let mut voices = v.borrow_mut();
+                if is_echo(note_key(ch, note)) {
+                    return;
+                }
+                let initial_bend = CHANNEL_BENDS.lock(|b| b.get()[channel_to_index(ch)]);
+                modify_voices(|voices| {
                     if let Some(existing) = voices
                         .iter_mut()
-                        .find(|v| v.channel == *ch && v.note == *note)
+                        .find(|v| v.channel == ch && v.note == note)
                     {
-                        existing.velocity = *vel;
+                        existing.velocity = vel;
                         existing.pitch_bend = initial_bend;
+                        existing.started = Instant::now();
+                        true
                     } else {
-                        let _ = voices.push(RemoteVoice {
-                            channel: *ch,
-                            note: *note,
-                            velocity: *vel,
-                            pitch_bend: initial_bend,
-                        });
+                        voices
+                            .push(RemoteVoice {
+                                channel: ch,
+                                note,
+                                velocity: vel,
+                                pitch_bend: initial_bend,
+                                started: Instant::now(),
+                            })
+                            .is_ok()
                     }
                 });
             } else {
-                REMOTE_VOICES.lock(|v| {
-                    v.borrow_mut()
-                        .retain(|v| !(v.channel == *ch && v.note == *note));
-                });
+                modify_voices(|voices| remove_voice(voices, ch, note));
             }
         }
         MidiMessage::NoteOff(ch, note, _vel) => {
-            REMOTE_VOICES.lock(|v| {
-                v.borrow_mut()
-                    .retain(|v| !(v.channel == *ch && v.note == *note));
-            });
+            modify_voices(|voices| remove_voice(voices, ch, note));
         }
         MidiMessage::PitchBendChange(ch, bend) => {
-            let bend_val: u16 = (*bend).into();
+            let bend_val: u16 = bend.into();
             CHANNEL_BENDS.lock(|b| {
                 let mut bends = b.get();
-                bends[channel_to_index(*ch)] = bend_val;
+                bends[channel_to_index(ch)] = bend_val;
                 b.set(bends);
             });
-            REMOTE_VOICES.lock(|v| {
-                for voice in v.borrow_mut().iter_mut() {
-                    if voice.channel == *ch {
+            modify_voices(|voices| {
+                let mut changed = false;
+                for voice in voices.iter_mut() {
+                    if voice.channel == ch && voice.pitch_bend != bend_val {
                         voice.pitch_bend = bend_val;
+                        changed = true;
                     }
                 }
+                changed
             });
         }
-        MidiMessage::ControlChange(_ch, cc, _val) => {
-            let cc_num: u8 = (*cc).into();
+        MidiMessage::ControlChange(ch, cc, val) => {
+            let cc_num: u8 = cc.into();
+            crate::cc_map::handle(channel_to_index(ch) as u8, cc_num, val.into());
+            crate::nrpn::handle_cc(channel_to_index(ch) as u8, cc_num, val.into());
             if cc_num == 120 || cc_num == 123 {
-                REMOTE_VOICES.lock(|v| v.borrow_mut().clear());
+                modify_voices(|voices| {
+                    let changed = !voices.is_empty();
+                    voices.clear();
+                    changed
+                });
             }
         }
         _ => {}
     }
 }
 
+/// Writes a SysEx message, returning whether all of it went out.
+async fn try_send_sysex(
+    sender: &mut embassy_usb::class::midi::Sender<'static, UsbDriver<'static, USB>>,
+    msg: &[u8],
+) -> bool {
+    for packet in sysex_packets(msg) {
+        if !matches!(
+            with_timeout(Duration::from_millis(10), sender.write_packet(&packet)).await,
+            Ok(Ok(_))
+        ) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Writes a message, returning whether it went out.
 async fn try_send_midi_message(
     sender: &mut embassy_usb::class::midi::Sender<'static, UsbDriver<'static, USB>>,
     message: &wmidi::MidiMessage<'_>,
-) {
-    let mut buf = [0u8; 3];
-    if message.copy_to_slice(&mut buf).is_err() {
+) -> bool {
+    let Some(packet) = encode_packet(message) else {
         error!("Buffer copy error while sending {:?}", message);
-        return;
-    }
-
-    let cin = match message {
-        wmidi::MidiMessage::NoteOff(..) => 0x08,
-        wmidi::MidiMessage::NoteOn(..) => 0x09,
-        wmidi::MidiMessage::PolyphonicKeyPressure(..) => 0x0A,
-        wmidi::MidiMessage::ControlChange(..) => 0x0B,
-        wmidi::MidiMessage::ProgramChange(..) => 0x0C,
-        wmidi::MidiMessage::ChannelPressure(..) => 0x0D,
-        wmidi::MidiMessage::PitchBendChange(..) => 0x0E,
-        _ => 0x0F,
+        return false;
     };
 
-    let packet = [cin, buf[0], buf[1], buf[2]];
-
-    match with_timeout(Duration::from_millis(10), sender.write_packet(&packet)).await {
-        Ok(Ok(_)) => {}
+    let written = with_timeout(Duration::from_millis(10), sender.write_packet(&packet)).await;
+    if let wmidi::MidiMessage::PitchBendChange(channel, bend) = *message {
+        let sent = matches!(written, Ok(Ok(_))).then_some(u16::from(bend));
+        SENT_BENDS.lock(|b| {
+            let mut bends = b.get();
+            bends[channel_to_index(channel)] = sent;
+            b.set(bends);
+        });
+    }
+    match written {
+        Ok(Ok(_)) => return true,
         Ok(Err(_)) => error!(
             "Packet write failure (USB Error) while sending {:?}",
             message
@@ -314,4 +924,5 @@ async fn try_send_midi_message(
             );
         }
     }
+    false
 }
@@ -0,0 +1,176 @@
+use crate::clock;
+use crate::layouts::CurrentLayout;
+use crate::midi::{MidiEvent, MidiSender};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use lattice_board_core::modulation::{Lfo, Shape};
+
+/// Settings an LFO can run on. Each keeps its own LFO; the modulation is an offset
+/// on top of the setting, which stays where it was set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Hue,
+    Brightness,
+    Fifth,
+}
+
+impl Target {
+    pub const ALL: [Target; 3] = [Target::Hue, Target::Brightness, Target::Fifth];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Hue => "Hue",
+            Target::Brightness => "Brightness",
+            Target::Fifth => "Fifth",
+        }
+    }
+
+    /// Depth change per adjustment step, and the largest depth.
+    fn depth_step(self) -> (f32, f32) {
+        match self {
+            Target::Hue => (5.0, 180.0),
+            Target::Brightness => (0.05, 1.0),
+            Target::Fifth => (0.5, 20.0),
+        }
+    }
+}
+
+/// LFO periods to pick from, in beats.
+const PERIODS: [f32; 8] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0];
+/// Beat count at which the clock wraps; a whole number of every period.
+const WRAP_BEATS: f32 = 32.0;
+const TICK: Duration = Duration::from_millis(20);
+
+#[derive(Clone, Copy)]
+struct Route {
+    /// `None` when the target isn't modulated.
+    shape: Option<Shape>,
+    period: usize,
+    depth: f32,
+}
+
+const IDLE_ROUTE: Route = Route {
+    shape: None,
+    period: 4,
+    depth: 0.0,
+};
+
+static ROUTES: Mutex<CriticalSectionRawMutex, Cell<[Route; 3]>> =
+    Mutex::new(Cell::new([IDLE_ROUTE; 3]));
+/// Target shown and edited on the dashboard.
+static SELECTED: Mutex<CriticalSectionRawMutex, Cell<Target>> = Mutex::new(Cell::new(Target::Hue));
+/// Current offset per target, in the setting's own units.
+static OFFSETS: Mutex<CriticalSectionRawMutex, Cell<[f32; 3]>> = Mutex::new(Cell::new([0.0; 3]));
+
+pub fn selected() -> Target {
+    SELECTED.lock(|s| s.get())
+}
+
+pub fn cycle_selected(delta: i8) {
+    SELECTED.lock(|s| {
+        let i = (s.get() as i32 + delta as i32).rem_euclid(Target::ALL.len() as i32);
+        s.set(Target::ALL[i as usize]);
+    });
+}
+
+fn update_route(f: impl FnOnce(&mut Route)) {
+    let target = selected();
+    ROUTES.lock(|r| {
+        let mut routes = r.get();
+        f(&mut routes[target as usize]);
+        r.set(routes);
+    });
+}
+
+fn route(target: Target) -> Route {
+    ROUTES.lock(|r| r.get()[target as usize])
+}
+
+/// Steps the selected target's shape through Off and each waveform.
+pub fn cycle_shape(delta: i8) {
+    update_route(|route| {
+        let index = route.shape.map_or(0, |s| {
+            Shape::ALL.iter().position(|&a| a == s).unwrap_or(0) + 1
+        });
+        let next = (index as i32 + delta as i32).rem_euclid(Shape::ALL.len() as i32 + 1);
+        route.shape = next.checked_sub(1).map(|i| Shape::ALL[i as usize]);
+    });
+}
+
+pub fn adjust_period(delta: i8) {
+    update_route(|route| {
+        route.period =
+            (route.period as i32 + delta as i32).clamp(0, PERIODS.len() as i32 - 1) as usize;
+    });
+}
+
+pub fn adjust_depth(delta: i8) {
+    let (step, max) = selected().depth_step();
+    update_route(|route| route.depth = (route.depth + step * delta as f32).clamp(0.0, max));
+}
+
+pub fn shape_name() -> &'static str {
+    route(selected()).shape.map_or("Off", Shape::name)
+}
+
+/// The selected target's period in beats.
+pub fn period_beats() -> f32 {
+    PERIODS[route(selected()).period]
+}
+
+/// The selected target's depth, in its own units.
+pub fn depth() -> f32 {
+    route(selected()).depth
+}
+
+/// Current modulation of `target`, added to the setting where it's used.
+pub fn offset(target: Target) -> f32 {
+    OFFSETS.lock(|o| o.get()[target as usize])
+}
+
+/// Whether an LFO is moving the LED colors, so frames can't be skipped.
+pub fn animates_leds() -> bool {
+    [Target::Hue, Target::Brightness]
+        .iter()
+        .any(|&t| route(t).shape.is_some())
+}
+
+/// Runs the LFOs on a clock that follows the tempo, and retunes held MPE notes while
+/// the fifth is modulated.
+#[embassy_executor::task]
+pub async fn modulation_task(sender: MidiSender) {
+    let mut beats = 0.0f32;
+    let mut last = Instant::now();
+
+    loop {
+        Timer::after(TICK).await;
+        let now = Instant::now();
+        let seconds = (now - last).as_micros() as f32 / 1_000_000.0;
+        last = now;
+        beats = (beats + seconds * clock::get_bpm() / 60.0) % WRAP_BEATS;
+
+        let routes = ROUTES.lock(|r| r.get());
+        let previous = OFFSETS.lock(|o| o.get());
+        let mut offsets = [0.0; 3];
+        for (offset, route) in offsets.iter_mut().zip(routes) {
+            if let Some(shape) = route.shape {
+                let lfo = Lfo {
+                    shape,
+                    period_beats: PERIODS[route.period],
+                    depth: route.depth,
+                };
+                *offset = lfo.value(beats);
+            }
+        }
+        OFFSETS.lock(|o| o.set(offsets));
+
+        let fifth = Target::Fifth as usize;
+        if offsets[fifth] != previous[fifth] {
+            for (channel, value) in crate::tuning::retune_voices::<CurrentLayout>() {
+                let _ = sender.try_send(MidiEvent::PitchBendChange { channel, value });
+            }
+        }
+    }
+}
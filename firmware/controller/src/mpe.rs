@@ -1,23 +1,71 @@
+use crate::midi::{MidiEvent, MidiSender};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
 use wmidi::Channel;
 
+/// Registered parameters the MPE zone is set up with.
+const RPN_BEND_RANGE: u16 = 0;
+const RPN_MCM: u16 = 6;
+
+/// Set when the zone needs announcing again.
+static REANNOUNCE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Has the zone announced again, for a new bend range or member channel count.
+pub fn reannounce() {
+    REANNOUNCE.signal(());
+}
+
+/// Announces the lower MPE zone so synths set themselves up for it: an MPE
+/// Configuration Message (RPN 6) on the master channel giving it the channels notes are
+/// allocated on, then the bend range (RPN 0) on the first member channel, which MPE
+/// applies to the whole zone. Once at start, then whenever either changes.
+pub async fn announce_zone(queue: &MidiSender) {
+    // Changes made while booting are in the first announcement
+    REANNOUNCE.reset();
+    loop {
+        // Semitones in the MSB, cents in the LSB
+        let cents = (crate::tuning::get_mpe_pbr() * 100.0 + 0.5) as u16;
+        queue
+            .send(MidiEvent::Rpn {
+                channel: Channel::Ch1,
+                param: RPN_MCM,
+                value: crate::tuning::mpe_member_channels() << 7,
+            })
+            .await;
+        queue
+            .send(MidiEvent::Rpn {
+                channel: Channel::Ch2,
+                param: RPN_BEND_RANGE,
+                value: ((cents / 100) << 7) | (cents % 100),
+            })
+            .await;
+        REANNOUNCE.wait().await;
+    }
+}
+
 pub struct MpeVoiceAllocator {
     // 0 = Free, 1 = Taken
     // We treat index 0 as Ch1 (Master), usually we don't alloc it for notes.
     // Indices 1..15 as Ch2..Ch16.
     usage_mask: u16,
+    /// Channels kept out of allocation for another use, same layout.
+    reserved: u16,
 }
 
 impl MpeVoiceAllocator {
     pub const fn new() -> Self {
-        Self { usage_mask: 0 }
+        Self {
+            usage_mask: 0,
+            reserved: 0,
+        }
     }
 
-    /// Try to allocate a channel from Ch2 to Ch16.
+    /// Try to allocate a channel from Ch2 to Ch16, skipping reserved ones.
     pub fn alloc(&mut self) -> Option<Channel> {
         // Iterate over indices 1 to 15 (Channels 2 to 16)
         for i in 1..16 {
             let mask = 1 << i;
-            if (self.usage_mask & mask) == 0 {
+            if ((self.usage_mask | self.reserved) & mask) == 0 {
                 self.usage_mask |= mask;
                 return Self::index_to_channel(i);
             }
@@ -25,6 +73,32 @@ impl MpeVoiceAllocator {
         None
     }
 
+    /// Bit `i` is set while channel `i + 1` is allocated.
+    pub fn usage_mask(&self) -> u16 {
+        self.usage_mask
+    }
+
+    /// Keeps `channel` out of allocation, or gives it back. A note already on it plays
+    /// on until it's freed. Returns whether that changed the channels notes get.
+    pub fn reserve(&mut self, channel: Channel, reserved: bool) -> bool {
+        let i = Self::channel_to_index(channel);
+        if i == 0 {
+            return false;
+        }
+        let before = self.reserved;
+        if reserved {
+            self.reserved |= 1 << i;
+        } else {
+            self.reserved &= !(1 << i);
+        }
+        self.reserved != before
+    }
+
+    /// How many channels notes can be allocated on: Ch2 to Ch16 less reserved ones.
+    pub fn members(&self) -> u16 {
+        15 - self.reserved.count_ones() as u16
+    }
+
     pub fn free(&mut self, channel: Channel) {
         let i = Self::channel_to_index(channel);
         if i > 0 {
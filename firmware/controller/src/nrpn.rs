@@ -0,0 +1,69 @@
+//! NRPN parameter numbers, so hosts that can only send CCs (hardware controllers,
+//! simple DAW automation) can still change any setting. SysEx `SET_PARAM` takes the
+//! same numbers.
+//!
+//! - `0..4`: the MIDI-learnable parameters, on the numbers their feedback goes out on
+//!   (see [`crate::cc_map::set_from_nrpn`]).
+//! - `128 + n` (MSB 1, LSB n): dashboard setting `n`, numbered by [`Field`]. Data
+//!   increment and decrement step it like Left/Right. Modes and on/off settings have no
+//!   absolute value, so data entry steps it too, by the MSB's distance from 64: 65 is
+//!   one step up, 62 two down. The LSB is ignored.
+
+use crate::fields::{Field, FIELDS};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use lattice_board_core::nrpn::{Data, NrpnParser};
+use log::info;
+
+/// Number of the first dashboard setting.
+pub const FIELD_BASE: u16 = 128;
+
+/// NRPN state of each channel.
+static PARSERS: Mutex<CriticalSectionRawMutex, Cell<[NrpnParser; 16]>> =
+    Mutex::new(Cell::new([NrpnParser::new(); 16]));
+
+/// Feeds a CC from the host on `channel` (0-15), applying the NRPN data it carries.
+pub fn handle_cc(channel: u8, control: u8, value: u8) {
+    let input = PARSERS.lock(|p| {
+        let mut parsers = p.get();
+        let input = parsers[channel as usize & 0x0F].feed(control, value);
+        p.set(parsers);
+        input
+    });
+    if let Some(input) = input {
+        apply(input.param, input.data);
+    }
+}
+
+/// Applies `data` to parameter number `param`; ignored while the board is locked.
+pub fn apply(param: u16, data: Data) {
+    if crate::lock::is_locked() {
+        info!("NRPN {} ignored while locked", param);
+        return;
+    }
+    let known = match param.checked_sub(FIELD_BASE) {
+        None => crate::cc_map::set_from_nrpn(param, data),
+        Some(n) => match FIELDS.iter().find(|&&f| f as u16 == n) {
+            Some(&field) => {
+                step(field, data);
+                true
+            }
+            None => false,
+        },
+    };
+    if !known {
+        info!("No parameter has NRPN {}", param);
+    }
+}
+
+fn step(field: Field, data: Data) {
+    let steps = match data {
+        Data::Step(direction) => direction as i16,
+        Data::Entry(value) => (value >> 7) as i16 - 64,
+        Data::Fine(_) => 0,
+    };
+    for _ in 0..steps.unsigned_abs() {
+        field.adjust(steps.signum() as i8);
+    }
+}
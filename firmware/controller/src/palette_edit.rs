@@ -0,0 +1,119 @@
+//! Palette editing on the encoder module: turning picks an anchor or changes its hue,
+//! saturation or value, a press moves on to the next of those, and the keys showing
+//! the anchor blink while it's edited.
+
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use lattice_board_core::color::Hsv;
+use log::info;
+use smart_leds::RGB8;
+
+/// What turning the encoder changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Anchor,
+    Hue,
+    Saturation,
+    Value,
+}
+
+impl Channel {
+    fn next(self) -> Self {
+        match self {
+            Channel::Anchor => Channel::Hue,
+            Channel::Hue => Channel::Saturation,
+            Channel::Saturation => Channel::Value,
+            Channel::Value => Channel::Anchor,
+        }
+    }
+}
+
+/// Degrees of hue, and steps of saturation or value, per detent.
+const HUE_STEP: i16 = 5;
+const LEVEL_STEP: i16 = 5;
+
+#[derive(Clone, Copy)]
+struct Edit {
+    channel: Channel,
+    anchor: usize,
+    /// The anchor's color as edited. Kept as HSV so hue survives a trip through grey
+    /// or black, which RGB can't hold.
+    hsv: Hsv,
+}
+
+static EDIT: Mutex<CriticalSectionRawMutex, Cell<Option<Edit>>> = Mutex::new(Cell::new(None));
+
+/// Starts editing the selected anchor's color as it is now.
+fn edit_selected(channel: Channel) -> Edit {
+    let led = crate::leds::led_config();
+    let rgb = led.rgb_anchors[led.selected_anchor];
+    Edit {
+        channel,
+        anchor: led.selected_anchor,
+        hsv: Hsv::from_rgb([rgb.r, rgb.g, rgb.b]),
+    }
+}
+
+/// Whether palette editing is on; it edits the selected anchor.
+pub fn is_editing() -> bool {
+    EDIT.lock(|e| e.get()).is_some()
+}
+
+/// Turns palette editing on or off.
+pub fn toggle() {
+    let on = EDIT.lock(|e| {
+        e.set(match e.get() {
+            Some(_) => None,
+            None => Some(edit_selected(Channel::Anchor)),
+        });
+        e.get().is_some()
+    });
+    info!("Palette edit {}", if on { "on" } else { "off" });
+}
+
+/// Moves on to the next channel.
+pub fn next_channel() {
+    let Some(edit) = EDIT.lock(|e| e.get()) else {
+        return;
+    };
+    let channel = edit.channel.next();
+    EDIT.lock(|e| e.set(Some(Edit { channel, ..edit })));
+    info!("Palette edit: {:?}", channel);
+}
+
+fn step(level: u8, delta: i8) -> u8 {
+    (level as i16 + LEVEL_STEP * delta as i16).clamp(0, 255) as u8
+}
+
+/// Applies `delta` detents to the channel being edited.
+pub fn turn(delta: i8) {
+    let Some(mut edit) = EDIT.lock(|e| e.get()) else {
+        return;
+    };
+    if edit.channel == Channel::Anchor {
+        crate::fields::Field::Anchor.adjust(delta);
+    }
+    if edit.anchor != crate::leds::led_config().selected_anchor {
+        // Picked here or from the console
+        edit = edit_selected(edit.channel);
+    }
+    let hsv = edit.hsv;
+    edit.hsv = match edit.channel {
+        Channel::Anchor => hsv,
+        Channel::Hue => hsv.rotated(HUE_STEP * delta as i16),
+        Channel::Saturation => Hsv {
+            s: step(hsv.s, delta),
+            ..hsv
+        },
+        Channel::Value => Hsv {
+            v: step(hsv.v, delta),
+            ..hsv
+        },
+    };
+    if edit.hsv != hsv {
+        let [r, g, b] = edit.hsv.to_rgb();
+        crate::leds::update_config(|c| c.rgb_anchors[edit.anchor] = RGB8::new(r, g, b));
+    }
+    EDIT.lock(|e| e.set(Some(edit)));
+}
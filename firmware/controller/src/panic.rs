@@ -0,0 +1,93 @@
+//! Panic handler: silences the synth before the board stops, so a crash mid-performance
+//! doesn't leave notes droning.
+//!
+//! The USB stack's tasks never run again once the handler has started, so the All Notes
+//! Off packet is put straight into the MIDI endpoint's buffer in the USB controller.
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_rp::pac;
+use embassy_time::{Duration, Instant};
+
+/// The MIDI class's IN endpoint. Endpoints are numbered in the order classes are added
+/// in main, and the console's CDC class before it takes IN 1 and 2.
+const MIDI_IN_ENDPOINT: usize = 3;
+/// How long the host gets to take each packet before the handler gives up on it.
+const SEND_TIMEOUT: Duration = Duration::from_millis(20);
+/// All Notes Off is a control change.
+pub const ALL_NOTES_OFF: u8 = 123;
+
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+/// Busy-waits for `done`, for at most [`SEND_TIMEOUT`].
+fn wait(done: impl Fn() -> bool) -> bool {
+    let start = Instant::now();
+    while !done() {
+        if start.elapsed() > SEND_TIMEOUT {
+            return false;
+        }
+    }
+    true
+}
+
+/// Sends All Notes Off on all 16 channels in one packet, if the host has the MIDI
+/// interface configured and is still taking packets.
+fn all_notes_off() {
+    let dpram = pac::USB_DPRAM;
+    let control = dpram.ep_in_control(MIDI_IN_ENDPOINT - 1).read();
+    if !control.enable() {
+        return;
+    }
+    let buffer_control = dpram.ep_in_buffer_control(MIDI_IN_ENDPOINT);
+    // A packet handed over before the panic goes first
+    if !wait(|| !buffer_control.read().available(0)) {
+        return;
+    }
+
+    let mut packet = [0u8; 64];
+    for (channel, event) in packet.chunks_exact_mut(4).enumerate() {
+        event.copy_from_slice(&lattice_board_core::usb_midi::encode([
+            0xB0 | channel as u8,
+            ALL_NOTES_OFF,
+            0,
+        ]));
+    }
+    // Nothing else touches the endpoint's buffer while the endpoint isn't available
+    unsafe {
+        let buffer = (dpram.as_ptr() as *mut u8).add(control.buffer_address() as usize);
+        core::ptr::copy_nonoverlapping(packet.as_ptr(), buffer, packet.len());
+    }
+    // Handed over the same way as the driver does: the rest of the buffer control
+    // settles before the available bit is set
+    let pid = !buffer_control.read().pid(0);
+    buffer_control.write(|w| {
+        w.set_pid(0, pid);
+        w.set_length(0, packet.len() as _);
+        w.set_full(0, true);
+    });
+    cortex_m::asm::delay(12);
+    buffer_control.write(|w| {
+        w.set_pid(0, pid);
+        w.set_length(0, packet.len() as _);
+        w.set_full(0, true);
+        w.set_available(0, true);
+    });
+    wait(|| !buffer_control.read().available(0));
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    // A panic while handling one goes straight to stopping
+    if !PANICKED.load(Ordering::Relaxed) {
+        PANICKED.store(true, Ordering::Relaxed);
+        defmt::error!("{}", defmt::Display2Format(info));
+        all_notes_off();
+    }
+    if crate::rtt::attached() {
+        // Halts with the probe's tools reporting the panic, as panic-probe did
+        cortex_m::asm::udf()
+    } else {
+        cortex_m::peripheral::SCB::sys_reset()
+    }
+}
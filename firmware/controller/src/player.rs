@@ -0,0 +1,147 @@
+use crate::midi::{channel_to_index, process_remote_midi, MidiEvent, MidiSender};
+use core::cell::{Cell, RefCell};
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Timer};
+use heapless::Vec;
+use lattice_board_core::sequence::{decode_events, SequenceEvent};
+use log::{error, info};
+use wmidi::{Channel, MidiMessage, Note, U7};
+
+/// Maximum number of events held in RAM (6 bytes each).
+pub const PLAYER_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayerCommand {
+    Play,
+    Stop,
+}
+
+static EVENTS: Mutex<CriticalSectionRawMutex, RefCell<Vec<SequenceEvent, PLAYER_CAPACITY>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+static COMMAND: Signal<CriticalSectionRawMutex, PlayerCommand> = Signal::new();
+static PLAYING: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// Stops playback and empties the event buffer.
+pub fn clear() {
+    stop();
+    EVENTS.lock(|e| e.borrow_mut().clear());
+}
+
+/// Appends events from an encoded SysEx payload. Events beyond capacity are dropped.
+pub fn append(payload: &[u8]) {
+    let dropped = EVENTS.lock(|e| {
+        let mut events = e.borrow_mut();
+        decode_events(payload)
+            .filter(|ev| events.push(*ev).is_err())
+            .count()
+    });
+    if dropped > 0 {
+        error!("Player buffer full, dropped {} events", dropped);
+    }
+}
+
+pub fn play() {
+    COMMAND.signal(PlayerCommand::Play);
+}
+
+pub fn stop() {
+    COMMAND.signal(PlayerCommand::Stop);
+}
+
+/// Returns (playing, number of loaded events).
+pub fn status() -> (bool, usize) {
+    (PLAYING.lock(|p| p.get()), EVENTS.lock(|e| e.borrow().len()))
+}
+
+#[embassy_executor::task]
+pub async fn player_task(sender: MidiSender) {
+    let mut pending: Option<PlayerCommand> = None;
+
+    loop {
+        let command = match pending.take() {
+            Some(command) => command,
+            None => COMMAND.wait().await,
+        };
+        if command != PlayerCommand::Play {
+            continue;
+        }
+
+        info!("Player started");
+        PLAYING.lock(|p| p.set(true));
+
+        let mut sounding: Vec<(Channel, Note), 32> = Vec::new();
+        let mut bends = [8192u16; 16];
+        let mut idx = 0;
+
+        while let Some(ev) = EVENTS.lock(|e| e.borrow().get(idx).copied()) {
+            idx += 1;
+
+            // Any new command interrupts playback; Play restarts from the top
+            let delay = Timer::after(Duration::from_millis(ev.delta_ms as u64));
+            if let Either::Second(command) = select(delay, COMMAND.wait()).await {
+                pending = Some(command);
+                break;
+            }
+
+            let Ok(message) = MidiMessage::try_from(&ev.message[..]) else {
+                continue;
+            };
+            // Feed the remote voice tracker so played notes light up
+            process_remote_midi(&message);
+
+            match message {
+                MidiMessage::NoteOn(channel, note, velocity) if u8::from(velocity) > 0 => {
+                    if !sounding.contains(&(channel, note)) {
+                        let _ = sounding.push((channel, note));
+                    }
+                    sender
+                        .send(MidiEvent::MpeNoteOn {
+                            channel,
+                            note,
+                            velocity,
+                            pitch_bend: bends[channel_to_index(channel)],
+                        })
+                        .await;
+                }
+                MidiMessage::NoteOn(channel, note, velocity)
+                | MidiMessage::NoteOff(channel, note, velocity) => {
+                    sounding.retain(|&s| s != (channel, note));
+                    sender
+                        .send(MidiEvent::NoteOff {
+                            channel,
+                            note,
+                            velocity,
+                        })
+                        .await;
+                }
+                MidiMessage::PitchBendChange(channel, value) => {
+                    let value = u16::from(value);
+                    bends[channel_to_index(channel)] = value;
+                    sender
+                        .send(MidiEvent::PitchBendChange { channel, value })
+                        .await;
+                }
+                _ => {}
+            }
+        }
+
+        // Release anything still sounding
+        for (channel, note) in sounding {
+            let velocity = U7::MIN;
+            process_remote_midi(&MidiMessage::NoteOff(channel, note, velocity));
+            sender
+                .send(MidiEvent::NoteOff {
+                    channel,
+                    note,
+                    velocity,
+                })
+                .await;
+        }
+
+        PLAYING.lock(|p| p.set(false));
+        info!("Player stopped");
+    }
+}
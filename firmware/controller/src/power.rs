@@ -0,0 +1,128 @@
+use crate::midi::{index_to_channel, MidiEvent, ToU7};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant};
+use lattice_board_core::battery::BatteryLevel;
+use log::{error, info, warn};
+use wmidi::Note;
+
+// Battery builds save power while idle, and watch the battery on boards that measure
+// VSYS (the 7x32, see telemetry): a low battery lights a warning, and before a
+// brown-out every voice is ended and no new ones start.
+
+/// Key scan period while asleep; a press still wakes the board within one period.
+const SLEEP_SCAN_PERIOD: Duration = Duration::from_millis(10);
+
+/// Battery builds go to sleep after 5 minutes by default; USB-powered ones never do.
+#[cfg(feature = "battery")]
+const DEFAULT_SLEEP_AFTER_S: u16 = 300;
+#[cfg(not(feature = "battery"))]
+const DEFAULT_SLEEP_AFTER_S: u16 = 0;
+const MAX_SLEEP_AFTER_S: u16 = 3600;
+
+static LAST_ACTIVITY: Mutex<CriticalSectionRawMutex, Cell<Instant>> =
+    Mutex::new(Cell::new(Instant::from_ticks(0)));
+static SLEEP_AFTER_S: Mutex<CriticalSectionRawMutex, Cell<u16>> =
+    Mutex::new(Cell::new(DEFAULT_SLEEP_AFTER_S));
+
+/// Keeps the board awake: call on key presses, incoming MIDI and serial input.
+pub fn note_activity() {
+    LAST_ACTIVITY.lock(|t| t.set(Instant::now()));
+}
+
+/// Idle time before sleeping in seconds; 0 never sleeps.
+pub fn sleep_after_s() -> u16 {
+    SLEEP_AFTER_S.lock(|s| s.get())
+}
+
+pub fn adjust_sleep_after(delta_s: i32) {
+    SLEEP_AFTER_S
+        .lock(|s| s.set((s.get() as i32 + delta_s).clamp(0, MAX_SLEEP_AFTER_S as i32) as u16));
+}
+
+/// True once nothing has been played or typed for the sleep timeout and no generator
+/// is running. While asleep
+/// the LEDs are blanked and keys are scanned less often; any activity wakes it.
+pub fn is_asleep() -> bool {
+    let after = sleep_after_s();
+    after > 0
+        && LAST_ACTIVITY.lock(|t| t.get()).elapsed() >= Duration::from_secs(after as u64)
+        && crate::keys::active_keys().is_empty()
+        && crate::midi::remote_voices().is_empty()
+        && !crate::euclid::is_enabled()
+        && !crate::walk::get_config().enabled
+        && !crate::arp::is_enabled()
+        && !crate::player::status().0
+}
+
+/// Battery level, as last measured. Stays OK on boards that can't measure it.
+static BATTERY: Mutex<CriticalSectionRawMutex, Cell<BatteryLevel>> =
+    Mutex::new(Cell::new(BatteryLevel::Ok));
+
+pub fn battery_level() -> BatteryLevel {
+    BATTERY.lock(|b| b.get())
+}
+
+/// True while the battery is about to brown out: voices have been ended, no notes
+/// start and the LEDs are dark, until it's charged again.
+pub fn voices_shut_down() -> bool {
+    battery_level() == BatteryLevel::Critical
+}
+
+/// Takes the battery level from the telemetry, ending every voice as it turns
+/// critical.
+pub async fn set_battery_level(level: BatteryLevel, volts: f32) {
+    if BATTERY.lock(|b| b.replace(level)) == level {
+        return;
+    }
+    match level {
+        BatteryLevel::Ok => info!("Battery at {:.2}V", volts),
+        BatteryLevel::Low => warn!("Battery low at {:.2}V", volts),
+        BatteryLevel::Critical => {
+            error!(
+                "Battery at {:.2}V, ending all voices before brown-out",
+                volts
+            );
+            shut_down_voices().await;
+        }
+    }
+}
+
+/// Ends every MPE voice on its channel, then sends All Notes Off on all 16 channels
+/// for the notes without one.
+async fn shut_down_voices() {
+    let Some(sender) = crate::keys::sender() else {
+        return;
+    };
+    for voice in crate::tuning::active_voices() {
+        if let Ok(note) = Note::try_from(voice.note) {
+            sender
+                .send(MidiEvent::NoteOff {
+                    channel: voice.channel,
+                    note,
+                    velocity: 0.to_u7(),
+                })
+                .await;
+        }
+    }
+    for channel in (0..16).filter_map(index_to_channel) {
+        sender
+            .send(MidiEvent::ControlChange {
+                channel,
+                control: crate::panic::ALL_NOTES_OFF,
+                value: 0,
+            })
+            .await;
+    }
+}
+
+/// Delay between key matrix scans: the scanner's own `awake` period, or a slower one
+/// while asleep.
+pub fn scan_period(awake: Duration) -> Duration {
+    if is_asleep() {
+        SLEEP_SCAN_PERIOD.max(awake)
+    } else {
+        awake
+    }
+}
@@ -0,0 +1,80 @@
+//! Practice log for the current session: notes and pitch classes played and time
+//! spent playing, on a dashboard page and printed to the log on request. A session
+//! runs from power-up until a new one is started.
+//!
+//! The heatmap shows on the LEDs how often each pitch class was played, over the
+//! usual colors until turned off again, to show which parts of the layout
+//! improvisation keeps to and which it avoids.
+
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+use heapless::String;
+use lattice_board_core::pitch::write_pitch_classes;
+use lattice_board_core::practice::Session;
+use log::info;
+
+static SESSION: Mutex<CriticalSectionRawMutex, Cell<Session>> =
+    Mutex::new(Cell::new(Session::new()));
+/// When the session started.
+static STARTED: Mutex<CriticalSectionRawMutex, Cell<Instant>> =
+    Mutex::new(Cell::new(Instant::from_ticks(0)));
+static HEATMAP: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// Nearest 12-EDO pitch class of `pitch_cents`, 0 for C.
+pub fn pitch_class(pitch_cents: f32) -> u8 {
+    ((pitch_cents / 100.0 + 0.5) as i32).rem_euclid(12) as u8
+}
+
+/// Records a key played at `pitch_cents`.
+pub fn note_played(pitch_cents: f32) {
+    let now = Instant::now().as_millis();
+    SESSION.lock(|s| {
+        let mut session = s.get();
+        session.note(now, pitch_class(pitch_cents));
+        s.set(session);
+    });
+}
+
+/// The session so far, and how long ago it started in minutes.
+pub fn session() -> (Session, u64) {
+    (
+        SESSION.lock(|s| s.get()),
+        STARTED.lock(|s| s.get()).elapsed().as_secs() / 60,
+    )
+}
+
+pub fn new_session() {
+    SESSION.lock(|s| s.set(Session::new()));
+    STARTED.lock(|s| s.set(Instant::now()));
+    info!("New practice session");
+}
+
+pub fn toggle_heatmap() {
+    let shown = HEATMAP.lock(|h| {
+        h.set(!h.get());
+        h.get()
+    });
+    info!("Practice heatmap {}", if shown { "on" } else { "off" });
+}
+
+/// The session to show as a heatmap, while it's on.
+pub fn heatmap() -> Option<Session> {
+    HEATMAP.lock(|h| h.get()).then(|| SESSION.lock(|s| s.get()))
+}
+
+/// Prints the session to the log.
+pub fn log_report() {
+    let (session, minutes) = session();
+    let mut classes: String<48> = String::new();
+    let _ = write_pitch_classes(&mut classes, session.pitch_classes);
+    info!(
+        "Practice: {} min session, {} min playing, {} notes, {} pitch classes ({})",
+        minutes,
+        session.playing_minutes(),
+        session.notes,
+        session.pitch_classes.count_ones(),
+        classes
+    );
+}
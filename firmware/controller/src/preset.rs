@@ -0,0 +1,135 @@
+use crate::leds::{LedConfig, LED_CONFIG_LEN};
+use crate::tuning::{TuningMode, TuningSpec, DEFAULT_SPEC};
+use crate::util::PRESET_LEN;
+use crate::zones::{DEFAULT_BEND_DIVISIONS, DEFAULT_DRUM_VELOCITY};
+use embassy_time::{Duration, Instant, Timer};
+use lattice_board_core::zones::{Zone, ZONE_LEN};
+use log::info;
+
+// What the active profile's preset holds. Fields added later go after the ones
+// before, and are left at their defaults when loading presets saved before them.
+const ZONE_AT: usize = 0;
+const DRUM_VELOCITY_AT: usize = ZONE_AT + ZONE_LEN;
+const BEND_DIVISIONS_AT: usize = DRUM_VELOCITY_AT + 1;
+const HUMANIZE_AT: usize = BEND_DIVISIONS_AT + 1;
+const LATENCY_OFFSET_AT: usize = HUMANIZE_AT + 1;
+const TUNING_MODE_AT: usize = LATENCY_OFFSET_AT + 1;
+const FIFTH_SIZE_AT: usize = TUNING_MODE_AT + 1;
+const MPE_PBR_AT: usize = FIFTH_SIZE_AT + 4;
+const LED_CONFIG_AT: usize = MPE_PBR_AT + 4;
+const END: usize = LED_CONFIG_AT + LED_CONFIG_LEN;
+
+const _: () = assert!(END <= PRESET_LEN);
+
+/// How long settings must stay put after a change before they're saved, so turning
+/// the encoder through a range erases a sector once rather than at every step.
+const SETTLE: Duration = Duration::from_secs(5);
+/// How often the autosave looks for changes, and for the board going quiet.
+const POLL: Duration = Duration::from_secs(1);
+
+fn f32_at(stored: &[u8], at: usize) -> Option<f32> {
+    let bytes = stored.get(at..at + 4)?.try_into().ok()?;
+    Some(f32::from_le_bytes(bytes)).filter(|v| v.is_finite())
+}
+
+/// The tuning in a stored preset, if it has one. Scales are only kept until power
+/// off, so a preset saved playing one comes back in Fifths mode.
+fn decode_tuning(stored: &[u8]) -> Option<TuningSpec> {
+    let mode = match stored.get(TUNING_MODE_AT)? {
+        0 => TuningMode::Standard,
+        2 if crate::tuning::scale_notes().is_some() => TuningMode::Scale,
+        1 | 2 => TuningMode::Fifths,
+        _ => return None,
+    };
+    Some(TuningSpec {
+        mode,
+        fifth_size: f32_at(stored, FIFTH_SIZE_AT)?.clamp(600.0, 800.0),
+        mpe_pbr: f32_at(stored, MPE_PBR_AT)?.clamp(0.1, 96.0),
+    })
+}
+
+/// Applies the active profile's stored preset, or the defaults if it has none.
+pub fn load() {
+    let mut bytes = [0xFF; PRESET_LEN];
+    let len = crate::util::stored_preset(&mut bytes).unwrap_or(0);
+    let stored = &bytes[..len];
+    crate::zones::set(stored.get(ZONE_AT..).and_then(Zone::decode));
+    let drum_velocity = stored.get(DRUM_VELOCITY_AT).copied();
+    crate::zones::set_drum_velocity_index(drum_velocity.unwrap_or(DEFAULT_DRUM_VELOCITY));
+    let bend_divisions = stored.get(BEND_DIVISIONS_AT).copied();
+    crate::zones::set_bend_divisions_index(bend_divisions.unwrap_or(DEFAULT_BEND_DIVISIONS));
+    crate::midi::set_humanize(stored.get(HUMANIZE_AT) == Some(&1));
+    let latency_offset = stored.get(LATENCY_OFFSET_AT).map_or(0, |&b| b as i8);
+    crate::midi::set_latency_offset(latency_offset);
+    crate::tuning::set_spec(decode_tuning(stored).unwrap_or(DEFAULT_SPEC));
+    crate::leds::set_config(stored.get(LED_CONFIG_AT..).and_then(LedConfig::decode));
+}
+
+/// The current settings as the preset stores them.
+fn encode() -> [u8; END] {
+    let mut bytes = [0xFF; END];
+    let zone: &mut [u8; ZONE_LEN] = (&mut bytes[ZONE_AT..ZONE_AT + ZONE_LEN])
+        .try_into()
+        .unwrap();
+    crate::zones::zone().encode(zone);
+    bytes[DRUM_VELOCITY_AT] = crate::zones::drum_velocity_index();
+    bytes[BEND_DIVISIONS_AT] = crate::zones::bend_divisions_index();
+    bytes[HUMANIZE_AT] = crate::midi::humanize() as u8;
+    bytes[LATENCY_OFFSET_AT] = crate::midi::latency_offset_ms() as u8;
+    let tuning = crate::tuning::spec();
+    bytes[TUNING_MODE_AT] = match tuning.mode {
+        TuningMode::Standard => 0,
+        TuningMode::Fifths => 1,
+        TuningMode::Scale => 2,
+    };
+    bytes[FIFTH_SIZE_AT..MPE_PBR_AT].copy_from_slice(&tuning.fifth_size.to_le_bytes());
+    bytes[MPE_PBR_AT..LED_CONFIG_AT].copy_from_slice(&tuning.mpe_pbr.to_le_bytes());
+    let led: &mut [u8; LED_CONFIG_LEN] = (&mut bytes[LED_CONFIG_AT..END]).try_into().unwrap();
+    crate::leds::led_config().encode(led);
+    bytes
+}
+
+/// Saves the current settings into the active profile's preset. False if they weren't
+/// written: in safe mode, or on a flash error.
+pub fn store() -> bool {
+    crate::util::store_preset(&encode())
+}
+
+/// Whether the active profile's preset already holds `bytes`.
+fn is_stored(bytes: &[u8]) -> bool {
+    let mut stored = [0xFF; PRESET_LEN];
+    crate::util::stored_preset(&mut stored).is_some_and(|len| &stored[..len] == bytes)
+}
+
+/// Saves the preset once its settings have changed and then stayed put for
+/// [`SETTLE`], for the LED and tuning settings, which change too often to save at
+/// each step. The key scan timings are saved the same way. Waits for no keys to be
+/// down, as an erase stalls the board for a moment.
+#[embassy_executor::task]
+pub async fn autosave_task() {
+    let mut last = (encode(), crate::keys::timing());
+    let mut changed_at = None;
+    loop {
+        Timer::after(POLL).await;
+        let current = (encode(), crate::keys::timing());
+        if current != last {
+            last = current;
+            changed_at = Some(Instant::now());
+            continue;
+        }
+        match changed_at {
+            Some(at) if at.elapsed() >= SETTLE => changed_at = None,
+            _ => continue,
+        }
+        while !crate::keys::matrix().is_empty() || crate::midi::is_busy() {
+            Timer::after(POLL).await;
+        }
+        // Already saved, or a profile switch loaded what's stored
+        if !is_stored(&current.0) && store() {
+            info!("Saved preset");
+        }
+        if crate::keys::store_timing() {
+            info!("Saved key timing");
+        }
+    }
+}
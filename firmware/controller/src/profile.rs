@@ -0,0 +1,54 @@
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Timer};
+use lattice_board_core::storage::PROFILES;
+use log::info;
+
+/// Player profile in use. Each keeps its own macros, CC map and presets in flash (see
+/// `storage::presets_sectors`), so players sharing a board don't overwrite each other.
+static ACTIVE: Mutex<CriticalSectionRawMutex, Cell<u8>> = Mutex::new(Cell::new(0));
+
+const NAMES: [&str; PROFILES] = ["Player 1", "Player 2", "Player 3", "Player 4"];
+
+pub fn active() -> u8 {
+    ACTIVE.lock(|a| a.get())
+}
+
+pub fn name(profile: u8) -> &'static str {
+    NAMES.get(profile as usize).copied().unwrap_or("?")
+}
+
+/// Picks up the profile set for boot, or else the one used last; call before loading
+/// per-profile settings.
+pub fn load() {
+    let profile = crate::boot::profile().unwrap_or(crate::util::stored_settings().profile);
+    if (profile as usize) < PROFILES {
+        ACTIVE.lock(|a| a.set(profile));
+    }
+    info!("Profile: {}", name(active()));
+}
+
+/// Switches profile if one of the keys after the first corner key is held as the board
+/// starts: the second key in scan order picks the first profile, and so on. The choice
+/// is kept for later boots. Call once the key scanner is running.
+pub async fn select_at_boot() {
+    // A few scan passes to see what's held
+    Timer::after(Duration::from_millis(100)).await;
+    let held = crate::keys::active_keys();
+    let Some(profile) = crate::keys::matrix_keys()
+        .skip(1)
+        .take(PROFILES)
+        .position(|key| held.contains(&key))
+    else {
+        return;
+    };
+    let profile = profile as u8;
+    if profile != active() {
+        ACTIVE.lock(|a| a.set(profile));
+        crate::util::store_profile(profile);
+        crate::cc_map::load();
+        crate::preset::load();
+    }
+    info!("Profile picked at boot: {}", name(profile));
+}
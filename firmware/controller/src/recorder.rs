@@ -0,0 +1,63 @@
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use heapless::Vec;
+use lattice_board_core::recording::{Recorder, MACRO_LEN, MACRO_SLOTS};
+use log::{info, warn};
+
+/// Console command macros: 'q' starts and stops recording the command keys typed in
+/// between, 'Q' followed by 1-8 stores the recording in that slot in flash, and 1-8
+/// replays a slot. Arrow-key field edits aren't recorded.
+static RECORDER: Mutex<CriticalSectionRawMutex, RefCell<Recorder>> =
+    Mutex::new(RefCell::new(Recorder::new()));
+
+/// Console keys that switch views rather than change settings; replaying them would
+/// do nothing.
+const NOT_RECORDED: &[u8] = b"DdcCyY\r";
+
+pub fn toggle() {
+    RECORDER.lock(|r| {
+        let mut r = r.borrow_mut();
+        if r.is_recording() {
+            r.stop();
+            info!("Macro recorded: {} keys", r.recorded().len());
+        } else {
+            r.start();
+            info!("Recording macro");
+        }
+    });
+}
+
+/// Adds a command key to the recording, if one is running.
+pub fn record(b: u8) {
+    if NOT_RECORDED.contains(&b) {
+        return;
+    }
+    RECORDER.lock(|r| {
+        if !r.borrow_mut().push(b) {
+            warn!("Macro full ({} keys), recording stopped", MACRO_LEN);
+        }
+    });
+}
+
+/// Stores the last recording in `slot`.
+pub fn store(slot: usize) {
+    let recorded: Vec<u8, MACRO_LEN> = RECORDER.lock(|r| {
+        let mut r = r.borrow_mut();
+        r.stop();
+        Vec::from_slice(r.recorded()).unwrap_or_default()
+    });
+    if slot < MACRO_SLOTS {
+        crate::util::store_macro(slot, &recorded);
+    }
+}
+
+/// The macro stored in `slot`, if any.
+pub fn load(slot: usize) -> Option<Vec<u8, MACRO_LEN>> {
+    let recorded = crate::util::stored_macro(slot);
+    match &recorded {
+        Some(keys) => info!("Replaying macro {} ({} keys)", slot + 1, keys.len()),
+        None => info!("Macro slot {} is empty", slot + 1),
+    }
+    recorded
+}
@@ -0,0 +1,118 @@
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Timer};
+use lattice_board_core::storage::Partition;
+use log::{error, info, warn};
+
+/// What a factory reset erases. Lesson data and the crash log are kept.
+const WIPED: [Partition; 5] = [
+    Partition::Settings0,
+    Partition::Settings1,
+    Partition::Macros,
+    Partition::ProfileMacros,
+    Partition::Presets,
+];
+
+/// Typed on the console to confirm a factory reset, followed by Enter.
+const CONFIRMATION: &[u8] = b"RESET";
+
+/// How long the corner keys must be held at boot to reset.
+pub const BOOT_HOLD: Duration = Duration::from_secs(3);
+
+/// Safe mode: the board runs on defaults, reading no settings from flash and saving
+/// none, until the next boot. What's stored is left as it was.
+static SAFE_MODE: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+pub fn is_safe_mode() -> bool {
+    SAFE_MODE.lock(|s| s.get())
+}
+
+/// Progress through the console confirmation.
+pub enum Prompt {
+    Typing(usize),
+    Cancelled,
+    Confirmed,
+}
+
+pub fn start_prompt() {
+    warn!(
+        "Factory reset wipes settings, macros, the CC map and presets. Type {} and press Enter to confirm; any other key cancels.",
+        core::str::from_utf8(CONFIRMATION).unwrap()
+    );
+}
+
+/// Takes the next key typed after [`start_prompt`], with `typed` keys of the
+/// confirmation already matched.
+pub fn prompt_key(typed: usize, b: u8) -> Prompt {
+    match CONFIRMATION.get(typed) {
+        Some(&expected) if b == expected => Prompt::Typing(typed + 1),
+        None if b == b'\r' || b == b'\n' => Prompt::Confirmed,
+        _ => Prompt::Cancelled,
+    }
+}
+
+/// Erases stored settings and presets, drops what was loaded from them and shows the
+/// wipe pattern on the LEDs. The board ID and profile fall back to the defaults at the
+/// next boot.
+pub async fn factory_reset() {
+    for partition in WIPED {
+        for sector in 0..partition.sectors() {
+            if let Err(e) = crate::storage::erase(partition, sector) {
+                error!(
+                    "Factory reset: erasing {} failed: {:?}",
+                    partition.name(),
+                    e
+                );
+            }
+            // Erases hold the flash lock; let USB and the scanners run in between
+            Timer::after(Duration::from_millis(1)).await;
+        }
+    }
+    crate::cc_map::load();
+    crate::keys::load_timing();
+    crate::preset::load();
+    crate::leds::show_wipe();
+    info!("Factory reset done");
+}
+
+/// Enters safe mode if the first corner key is held on its own as the board starts,
+/// for when a stored setting (say brightness 0) makes the board unusable. Settings
+/// loaded before the scanner ran are put back to the defaults. Call once the key
+/// scanner is running, before anything else looks at keys held at boot.
+pub async fn check_safe_mode() {
+    // A few scan passes to see what's held
+    Timer::after(Duration::from_millis(100)).await;
+    let held = crate::keys::active_keys();
+    let Some(first) = crate::keys::matrix_keys().next() else {
+        return;
+    };
+    // Both corners is the factory reset gesture
+    if !held.contains(&first) || crate::keys::corners_held() {
+        return;
+    }
+    SAFE_MODE.lock(|s| s.set(true));
+    crate::boot::load();
+    crate::profile::load();
+    crate::cc_map::load();
+    crate::keys::load_timing();
+    crate::preset::load();
+    warn!("Safe mode: running on defaults; stored settings are kept but not loaded or saved until restart");
+}
+
+/// Factory resets if both corner keys are held when the board starts and kept held
+/// for [`BOOT_HOLD`]. Call once the key scanner is running.
+pub async fn check_boot_gesture() {
+    // A few scan passes to see what's held
+    Timer::after(Duration::from_millis(100)).await;
+    if !crate::keys::corners_held() {
+        return;
+    }
+    warn!("Corner keys held at boot: keep holding to factory reset");
+    Timer::after(BOOT_HOLD).await;
+    if crate::keys::corners_held() {
+        factory_reset().await;
+    } else {
+        info!("Factory reset cancelled");
+    }
+}
@@ -0,0 +1,135 @@
+//! A SEGGER RTT up channel for defmt frames, and detection of a debug probe reading it.
+//!
+//! A probe finds the control block by its ID and moves the channel's read offset as it
+//! reads. Nothing tells the board a probe has attached or gone, so it's judged from
+//! that offset: a probe is attached while frames keep being read, and gone once a
+//! frame has sat unread for [`UNREAD_TIMEOUT`].
+
+use crate::capacities::RTT_BUFFER;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use embassy_time::{Duration, Instant, Timer};
+use log::info;
+
+/// How often the read offset is checked.
+const POLL: Duration = Duration::from_millis(100);
+/// A frame unread this long means nothing is reading.
+const UNREAD_TIMEOUT: Duration = Duration::from_secs(1);
+/// Drop frames that don't fit rather than wait for the probe.
+const MODE_NON_BLOCKING_SKIP: usize = 0;
+
+static ATTACHED: AtomicBool = AtomicBool::new(false);
+
+#[repr(C)]
+struct Channel {
+    name: *const u8,
+    buffer: *mut u8,
+    size: usize,
+    write: AtomicUsize,
+    /// Moved by the probe.
+    read: AtomicUsize,
+    flags: AtomicUsize,
+}
+
+#[repr(C)]
+struct Header {
+    id: [u8; 16],
+    max_up_channels: usize,
+    max_down_channels: usize,
+    up_channel: Channel,
+}
+
+// Only the logger writes the buffer, inside its critical section
+unsafe impl Sync for Header {}
+
+struct Buffer(UnsafeCell<[u8; RTT_BUFFER]>);
+
+unsafe impl Sync for Buffer {}
+
+static BUFFER: Buffer = Buffer(UnsafeCell::new([0; RTT_BUFFER]));
+// probe-rs decodes the channel named "defmt" as defmt frames
+static NAME: [u8; 6] = *b"defmt\0";
+
+#[no_mangle]
+static _SEGGER_RTT: Header = Header {
+    id: *b"SEGGER RTT\0\0\0\0\0\0",
+    max_up_channels: 1,
+    max_down_channels: 0,
+    up_channel: Channel {
+        name: NAME.as_ptr(),
+        buffer: BUFFER.0.get() as *mut u8,
+        size: RTT_BUFFER,
+        write: AtomicUsize::new(0),
+        read: AtomicUsize::new(0),
+        flags: AtomicUsize::new(MODE_NON_BLOCKING_SKIP),
+    },
+};
+
+/// Copies a whole frame into the channel, or returns false if it doesn't fit.
+///
+/// Only call from inside the defmt logger's critical section.
+pub fn write_frame(frame: &[u8]) -> bool {
+    let channel = &_SEGGER_RTT.up_channel;
+    let read = channel.read.load(Ordering::Acquire);
+    let write = channel.write.load(Ordering::Acquire);
+    // One byte stays empty so a full buffer doesn't look like an empty one
+    let free = if read > write {
+        read - write - 1
+    } else {
+        RTT_BUFFER - write + read - 1
+    };
+    if frame.len() > free {
+        return false;
+    }
+    let (first, second) = frame.split_at(frame.len().min(RTT_BUFFER - write));
+    // The probe only reads between its read offset and the write offset, which doesn't
+    // move until the copy is done
+    unsafe {
+        let buffer = channel.buffer;
+        core::ptr::copy_nonoverlapping(first.as_ptr(), buffer.add(write), first.len());
+        core::ptr::copy_nonoverlapping(second.as_ptr(), buffer, second.len());
+    }
+    channel
+        .write
+        .store((write + frame.len()) % RTT_BUFFER, Ordering::Release);
+    true
+}
+
+/// Whether a debug probe has been reading the channel.
+pub fn attached() -> bool {
+    ATTACHED.load(Ordering::Relaxed)
+}
+
+/// Watches the read offset to tell when a probe attaches or goes away.
+#[embassy_executor::task]
+pub async fn probe_task() {
+    let channel = &_SEGGER_RTT.up_channel;
+    let mut last_read = channel.read.load(Ordering::Acquire);
+    // When the oldest frame still unread was first seen waiting
+    let mut unread_since: Option<Instant> = None;
+    loop {
+        Timer::after(POLL).await;
+        let read = channel.read.load(Ordering::Acquire);
+        let write = channel.write.load(Ordering::Acquire);
+        let attached = if read != last_read {
+            last_read = read;
+            unread_since = None;
+            true
+        } else if read != write {
+            let since = *unread_since.get_or_insert_with(Instant::now);
+            attached() && since.elapsed() < UNREAD_TIMEOUT
+        } else {
+            // Nothing to read, so nothing to judge by
+            unread_since = None;
+            attached()
+        };
+        if attached != ATTACHED.load(Ordering::Relaxed) {
+            ATTACHED.store(attached, Ordering::Relaxed);
+            if attached {
+                info!("Debug probe attached: defmt logs go to RTT");
+            } else {
+                info!("Debug probe gone: defmt logs go to the second serial port");
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::layouts::CurrentLayout;
+use crate::midi::{decode_packet, encode_packet, event_messages, MidiEvent, ToU7};
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::Vec;
+use lattice_board_core::layout::{Coordinate, Layout};
+use log::{error, info};
+
+/// Pipeline stages timed by [`midi_loopback`].
+const STAGES: [&str; 5] = ["event", "queue", "encode", "decode", "track"];
+
+/// Total and worst time spent in each stage.
+#[derive(Default)]
+struct Timings {
+    total: [Duration; STAGES.len()],
+    max: [Duration; STAGES.len()],
+    runs: u32,
+}
+
+impl Timings {
+    fn record(&mut self, stage: usize, since: Instant) -> Instant {
+        let now = Instant::now();
+        let spent = now - since;
+        self.total[stage] += spent;
+        self.max[stage] = self.max[stage].max(spent);
+        now
+    }
+}
+
+/// Presses and releases the center key and its neighbours through the whole MIDI
+/// path without touching USB: `get_midi_event`, a queue, packet encoding, the
+/// receive parser and the remote voice tracker. Checks each note shows up in (and
+/// leaves) the tracked voices with the channel, note and bend that were sent, and
+/// logs the time spent per stage. The looped notes briefly light their keys.
+///
+/// The notes take their channels from the live MPE allocator, so the test refuses to
+/// run while anything is sounding rather than hand out a playing note's channel.
+pub fn midi_loopback() {
+    if notes_active() {
+        error!("Self-test: release all keys and stop playback first");
+        return;
+    }
+    let center = CurrentLayout::center_coord();
+    let queue: Channel<NoopRawMutex, MidiEvent, 1> = Channel::new();
+    let mut timings = Timings::default();
+    let mut passed = 0;
+    let mut tested = 0;
+
+    for coord in core::iter::once(center).chain(center.neighbors()) {
+        for is_note_on in [true, false] {
+            tested += 1;
+            let start = Instant::now();
+            let Some(event) =
+                crate::tuning::get_midi_event::<CurrentLayout>(coord, 100.to_u7(), is_note_on)
+            else {
+                error!("Self-test: no event for {:?} (on: {})", coord, is_note_on);
+                continue;
+            };
+            let t = timings.record(0, start);
+
+            let _ = queue.try_send(event);
+            let Ok(event) = queue.try_receive() else {
+                error!("Self-test: event lost in the queue");
+                continue;
+            };
+            let t = timings.record(1, t);
+
+            let packets: Vec<[u8; 4], 4> = event_messages(event)
+                .iter()
+                .filter_map(encode_packet)
+                .collect();
+            let mut t = timings.record(2, t);
+
+            for packet in &packets {
+                let Some(message) = decode_packet(packet) else {
+                    error!("Self-test: couldn't parse {:?}", packet);
+                    continue;
+                };
+                t = timings.record(3, t);
+                crate::midi::process_remote_midi(&message);
+                t = timings.record(4, t);
+            }
+            timings.runs += 1;
+
+            if voice_matches(event, is_note_on) {
+                passed += 1;
+            } else {
+                error!("Self-test: voices don't match {:?}", event);
+            }
+        }
+    }
+
+    info!("MIDI self-test: {}/{} passed", passed, tested);
+    for (stage, name) in STAGES.iter().enumerate() {
+        info!(
+            "  {}: avg {}us, max {}us",
+            name,
+            timings.total[stage].as_micros() / timings.runs.max(1) as u64,
+            timings.max[stage].as_micros()
+        );
+    }
+}
+
+/// Chords played by [`release_benchmark`].
+const BENCH_ROUNDS: usize = 50;
+/// How long the chord is held in the rounds that don't release it at once.
+const BENCH_HOLD: Duration = Duration::from_millis(20);
+/// How long a round waits for its releases to be sent before moving on.
+const BENCH_ROUND_TIMEOUT: Duration = Duration::from_secs(1);
+
+static BENCH: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Has the benchmark task run [`release_benchmark`].
+pub fn start_release_benchmark() {
+    BENCH.signal(());
+}
+
+#[embassy_executor::task]
+pub async fn release_benchmark_task() {
+    loop {
+        BENCH.wait().await;
+        release_benchmark().await;
+    }
+}
+
+/// Plays the center key and its neighbours as a chord through the same path as
+/// scanned keys, [`BENCH_ROUNDS`] times, and logs how many releases were sent and
+/// their latency from key to USB: median, 99th percentile and worst case. Every other
+/// round releases the chord as soon as it's pressed, the way a quick tap does, so
+/// releases overtake their own note ons. Starts the release latency stats afresh.
+async fn release_benchmark() {
+    if notes_active() || crate::arp::is_enabled() || crate::euclid::is_enabled() {
+        error!("Release benchmark: release all keys and stop the arp and Euclid first");
+        return;
+    }
+    let center = CurrentLayout::center_coord();
+    let chord: Vec<Coordinate, 7> = core::iter::once(center).chain(center.neighbors()).collect();
+    let expected = BENCH_ROUNDS * chord.len();
+    crate::stats::clear_release_latency();
+    info!(
+        "Release benchmark: {} rounds of {} keys",
+        BENCH_ROUNDS,
+        chord.len()
+    );
+
+    for round in 0..BENCH_ROUNDS {
+        for &coord in &chord {
+            if !crate::keys::play(coord, 100, true) {
+                error!("Release benchmark: the key scanner isn't running");
+                return;
+            }
+        }
+        if round % 2 == 1 {
+            Timer::after(BENCH_HOLD).await;
+        }
+        for &coord in &chord {
+            crate::keys::play(coord, 0, false);
+        }
+        let sent = (round + 1) * chord.len();
+        let start = Instant::now();
+        while (crate::stats::release_latency().count() as usize) < sent
+            && start.elapsed() < BENCH_ROUND_TIMEOUT
+        {
+            Timer::after(Duration::from_millis(1)).await;
+        }
+    }
+
+    let latency = crate::stats::release_latency();
+    info!(
+        "Release benchmark: {}/{} releases sent",
+        latency.count(),
+        expected
+    );
+    match (latency.percentile(50), latency.percentile(99)) {
+        (Some(p50), Some(p99)) => info!(
+            "Release benchmark: p50 {}us, p99 {}us, max {}us",
+            p50,
+            p99,
+            latency.max_us()
+        ),
+        _ => info!("Release benchmark: nothing was sent; is the MIDI port open?"),
+    }
+}
+
+/// Whether any key, voice or MPE channel is in use.
+fn notes_active() -> bool {
+    !crate::keys::active_keys().is_empty()
+        || !crate::tuning::active_voices().is_empty()
+        || crate::tuning::mpe_usage_mask() != 0
+}
+
+/// Whether the remote voices show `event`: sounding with its bend after a note on,
+/// gone after a note off.
+fn voice_matches(event: MidiEvent, is_note_on: bool) -> bool {
+    let (channel, note, bend) = match event {
+        MidiEvent::NoteOn { channel, note, .. } => (channel, note, 8192),
+        MidiEvent::MpeNoteOn {
+            channel,
+            note,
+            pitch_bend,
+            ..
+        } => (channel, note, pitch_bend),
+        MidiEvent::NoteOff { channel, note, .. } => (channel, note, 8192),
+        MidiEvent::PitchBendChange { .. }
+        | MidiEvent::ControlChange { .. }
+        | MidiEvent::ControlChange14 { .. }
+        | MidiEvent::Nrpn { .. }
+        | MidiEvent::Rpn { .. }
+        | MidiEvent::Thru(_)
+        | MidiEvent::TransferReply(_)
+        | MidiEvent::ConfigReply(_) => return false,
+    };
+    let voice = crate::midi::remote_voices()
+        .into_iter()
+        .find(|v| v.channel == channel && v.note == note);
+    match voice {
+        Some(voice) => is_note_on && voice.pitch_bend == bend,
+        None => !is_note_on,
+    }
+}
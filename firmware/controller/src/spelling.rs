@@ -0,0 +1,65 @@
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use lattice_board_core::layout::{Coordinate, Layout};
+use lattice_board_core::spelling::{Convention, NoteName, Policy, Spelled};
+
+/// How note names are written on the dashboard and in the layout dump.
+static CONVENTION: Mutex<CriticalSectionRawMutex, Cell<Convention>> =
+    Mutex::new(Cell::new(Convention::English));
+
+pub fn convention() -> Convention {
+    CONVENTION.lock(|c| c.get())
+}
+
+pub fn cycle_convention(delta: i8) {
+    CONVENTION.lock(|c| {
+        let i = (c.get() as i32 + delta as i32).rem_euclid(Convention::ALL.len() as i32);
+        c.set(Convention::ALL[i as usize]);
+    });
+}
+
+/// Which enharmonic name keys get.
+static POLICY: Mutex<CriticalSectionRawMutex, Cell<Policy>> = Mutex::new(Cell::new(Policy::Chain));
+/// Tonic for [`Policy::Key`], in fifths from C.
+static TONIC: Mutex<CriticalSectionRawMutex, Cell<i16>> = Mutex::new(Cell::new(0));
+
+/// Tonics to pick from, Db to F#.
+const TONICS: core::ops::RangeInclusive<i16> = -5..=6;
+
+pub fn policy() -> Policy {
+    POLICY.lock(|p| p.get())
+}
+
+pub fn cycle_policy(delta: i8) {
+    POLICY.lock(|p| {
+        let i = (p.get() as i32 + delta as i32).rem_euclid(Policy::ALL.len() as i32);
+        p.set(Policy::ALL[i as usize]);
+    });
+}
+
+pub fn tonic() -> i16 {
+    TONIC.lock(|t| t.get())
+}
+
+pub fn cycle_tonic(delta: i8) {
+    TONIC.lock(|t| {
+        let count = TONICS.end() - TONICS.start() + 1;
+        let i = (t.get() - TONICS.start() + delta as i16).rem_euclid(count);
+        t.set(TONICS.start() + i);
+    });
+}
+
+/// The tonic's name, without an octave.
+pub fn tonic_name() -> Spelled {
+    NoteName::from_offsets(0, tonic())
+        .spelled(convention())
+        .without_octave()
+}
+
+/// The name of the key at `coord`, as the UI shows it.
+pub fn key_name<L: Layout>(coord: Coordinate) -> Spelled {
+    let (octaves, fifths) = crate::tuning::calculate_fifths_offsets::<L>(coord);
+    let (octaves, fifths) = policy().respell(tonic(), octaves, fifths);
+    NoteName::from_offsets(octaves, fifths).spelled(convention())
+}
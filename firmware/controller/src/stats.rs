@@ -0,0 +1,209 @@
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant};
+use lattice_board_core::jitter::Histogram;
+use lattice_board_core::sparkline::Trace;
+use portable_atomic::{AtomicI32, AtomicU32, Ordering};
+
+/// Time from a key release being detected to its NoteOff leaving the USB endpoint.
+static RELEASE_LATENCY: Mutex<CriticalSectionRawMutex, RefCell<Histogram>> =
+    Mutex::new(RefCell::new(Histogram::new()));
+
+pub fn record_release_latency(latency: Duration) {
+    let us = latency.as_micros().min(u32::MAX as u64) as u32;
+    RELEASE_LATENCY.lock(|h| h.borrow_mut().record(us));
+}
+
+/// Release latency samples so far.
+pub fn release_latency() -> Histogram {
+    RELEASE_LATENCY.lock(|h| *h.borrow())
+}
+
+pub fn clear_release_latency() {
+    RELEASE_LATENCY.lock(|h| h.borrow_mut().clear());
+}
+
+/// How long after it should have left each event's USB write completed: after its
+/// deadline for held-back events, after the send loop picked it up for the rest.
+static SEND_JITTER: Mutex<CriticalSectionRawMutex, RefCell<Histogram>> =
+    Mutex::new(RefCell::new(Histogram::new()));
+
+pub fn record_send_jitter(late: Duration) {
+    let us = late.as_micros().min(u32::MAX as u64) as u32;
+    SEND_JITTER.lock(|h| h.borrow_mut().record(us));
+}
+
+/// Send jitter samples so far.
+pub fn send_jitter() -> Histogram {
+    SEND_JITTER.lock(|h| *h.borrow())
+}
+
+/// Events the send schedule merged into a newer one, or dropped, because it was full.
+static SCHEDULE_MERGES: AtomicU32 = AtomicU32::new(0);
+static SCHEDULE_DROPS: AtomicU32 = AtomicU32::new(0);
+
+pub fn record_schedule_merge() {
+    SCHEDULE_MERGES.add(1, Ordering::Relaxed);
+}
+
+pub fn record_schedule_drop() {
+    SCHEDULE_DROPS.add(1, Ordering::Relaxed);
+}
+
+/// Returns (merged, dropped) events since the last reset.
+pub fn schedule_overloads() -> (u32, u32) {
+    (
+        SCHEDULE_MERGES.load(Ordering::Relaxed),
+        SCHEDULE_DROPS.load(Ordering::Relaxed),
+    )
+}
+
+/// Periods kept by the performance graphs, and how long each is.
+pub const GRAPH_LEN: usize = 60;
+pub const GRAPH_PERIOD: Duration = Duration::from_millis(100);
+
+/// Measurements graphed on the stats page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Graph {
+    /// Time between the starts of key matrix passes, in us.
+    ScanPeriod,
+    /// Time to render and write an LED frame, in us.
+    LedFrame,
+    /// Events waiting to be sent over USB.
+    QueueDepth,
+}
+
+impl Graph {
+    pub const ALL: [Graph; 3] = [Graph::ScanPeriod, Graph::LedFrame, Graph::QueueDepth];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Graph::ScanPeriod => "Scan period",
+            Graph::LedFrame => "LED frame",
+            Graph::QueueDepth => "MIDI queue",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            Graph::ScanPeriod | Graph::LedFrame => "us",
+            Graph::QueueDepth => "",
+        }
+    }
+
+    /// Least full-scale value, so a quiet trace isn't drawn as if it were busy.
+    pub fn floor(self) -> u32 {
+        match self {
+            Graph::ScanPeriod => 2_000,
+            Graph::LedFrame => 10_000,
+            Graph::QueueDepth => 8,
+        }
+    }
+}
+
+const NEW_TRACE: Trace<GRAPH_LEN> = Trace::new(GRAPH_PERIOD.as_ticks());
+static GRAPHS: Mutex<CriticalSectionRawMutex, RefCell<[Trace<GRAPH_LEN>; 3]>> =
+    Mutex::new(RefCell::new([NEW_TRACE; 3]));
+
+pub fn record_graph(graph: Graph, value: u32) {
+    let now = Instant::now().as_ticks();
+    GRAPHS.lock(|g| g.borrow_mut()[graph as usize].record(now, value));
+}
+
+pub fn record_duration(graph: Graph, duration: Duration) {
+    record_graph(graph, duration.as_micros().min(u32::MAX as u64) as u32);
+}
+
+/// The last [`GRAPH_LEN`] periods of `graph`, up to now.
+pub fn graph(graph: Graph) -> Trace<GRAPH_LEN> {
+    let now = Instant::now().as_ticks();
+    GRAPHS.lock(|g| {
+        let trace = &mut g.borrow_mut()[graph as usize];
+        trace.advance(now);
+        *trace
+    })
+}
+
+/// Log lines (or defmt frames) dropped because their pipe was full.
+static LOG_DROPS: AtomicU32 = AtomicU32::new(0);
+
+pub fn record_log_drop() {
+    LOG_DROPS.add(1, Ordering::Relaxed);
+}
+
+pub fn log_drops() -> u32 {
+    LOG_DROPS.load(Ordering::Relaxed)
+}
+
+/// Bends re-sent by the drift guard, see [`crate::drift`].
+static DRIFT_CORRECTIONS: AtomicU32 = AtomicU32::new(0);
+
+pub fn record_drift_correction() {
+    DRIFT_CORRECTIONS.add(1, Ordering::Relaxed);
+}
+
+pub fn drift_corrections() -> u32 {
+    DRIFT_CORRECTIONS.load(Ordering::Relaxed)
+}
+
+/// Bends and CCs not sent for repeating the value last sent.
+static REPEATS_SUPPRESSED: AtomicU32 = AtomicU32::new(0);
+
+pub fn record_repeat_suppressed() {
+    REPEATS_SUPPRESSED.add(1, Ordering::Relaxed);
+}
+
+pub fn repeats_suppressed() -> u32 {
+    REPEATS_SUPPRESSED.load(Ordering::Relaxed)
+}
+
+/// Last chip temperature reading in milli-degrees Celsius; `i32::MIN` until first read.
+static TEMPERATURE_MC: AtomicI32 = AtomicI32::new(i32::MIN);
+
+pub fn record_temperature(celsius: f32) {
+    TEMPERATURE_MC.store((celsius * 1000.0) as i32, Ordering::Relaxed);
+}
+
+/// Last chip temperature in degrees Celsius, if it has been measured yet.
+pub fn temperature_c() -> Option<f32> {
+    match TEMPERATURE_MC.load(Ordering::Relaxed) {
+        i32::MIN => None,
+        mc => Some(mc as f32 / 1000.0),
+    }
+}
+
+/// Last VSYS reading in millivolts, and the lowest since the stats were reset; 0 and
+/// `u32::MAX` until first read, or on boards that can't measure it.
+static VSYS_MV: AtomicU32 = AtomicU32::new(0);
+static VSYS_LOWEST_MV: AtomicU32 = AtomicU32::new(u32::MAX);
+
+pub fn record_vsys(volts: f32) {
+    let mv = (volts * 1000.0) as u32;
+    VSYS_MV.store(mv, Ordering::Relaxed);
+    if mv < VSYS_LOWEST_MV.load(Ordering::Relaxed) {
+        VSYS_LOWEST_MV.store(mv, Ordering::Relaxed);
+    }
+}
+
+/// Last and lowest VSYS in volts, if it has been measured.
+pub fn vsys() -> Option<(f32, f32)> {
+    match VSYS_MV.load(Ordering::Relaxed) {
+        0 => None,
+        mv => {
+            let lowest = VSYS_LOWEST_MV.load(Ordering::Relaxed).min(mv);
+            Some((mv as f32 / 1000.0, lowest as f32 / 1000.0))
+        }
+    }
+}
+
+pub fn reset() {
+    clear_release_latency();
+    SCHEDULE_MERGES.store(0, Ordering::Relaxed);
+    SCHEDULE_DROPS.store(0, Ordering::Relaxed);
+    SEND_JITTER.lock(|h| h.borrow_mut().clear());
+    LOG_DROPS.store(0, Ordering::Relaxed);
+    DRIFT_CORRECTIONS.store(0, Ordering::Relaxed);
+    REPEATS_SUPPRESSED.store(0, Ordering::Relaxed);
+    VSYS_LOWEST_MV.store(u32::MAX, Ordering::Relaxed);
+}
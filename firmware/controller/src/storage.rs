@@ -0,0 +1,60 @@
+use core::cell::RefCell;
+use embassy_rp::flash::{Blocking, Error, Flash};
+use embassy_rp::peripherals::FLASH;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use lattice_board_core::storage::{Partition, FLASH_SIZE, SECTOR_SIZE};
+use log::info;
+
+/// Flash access for user data. Reads and writes go through a [`Partition`] and are
+/// bounds-checked against it, so one feature can't write into another's sectors.
+static FLASH_HANDLE: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<Option<Flash<'static, FLASH, Blocking, FLASH_SIZE>>>,
+> = Mutex::new(RefCell::new(None));
+
+const _: () = assert!(embassy_rp::flash::ERASE_SIZE == SECTOR_SIZE);
+
+pub fn init(flash: FLASH) {
+    FLASH_HANDLE.lock(|f| *f.borrow_mut() = Some(Flash::new_blocking(flash)));
+}
+
+fn with_flash<R>(f: impl FnOnce(&mut Flash<'static, FLASH, Blocking, FLASH_SIZE>) -> R) -> R {
+    FLASH_HANDLE.lock(|flash| f(flash.borrow_mut().as_mut().expect("flash not initialized")))
+}
+
+pub fn unique_id() -> Result<[u8; 8], Error> {
+    let mut uid = [0u8; 8];
+    with_flash(|flash| flash.blocking_unique_id(&mut uid))?;
+    Ok(uid)
+}
+
+/// Reads `buf.len()` bytes at `at` in `partition`.
+pub fn read(partition: Partition, at: usize, buf: &mut [u8]) -> Result<(), Error> {
+    let offset = partition.range(at, buf.len()).ok_or(Error::OutOfBounds)?;
+    with_flash(|flash| flash.blocking_read(offset, buf))
+}
+
+/// Writes `bytes` at `at` in `partition`, which must have been erased.
+pub fn write(partition: Partition, at: usize, bytes: &[u8]) -> Result<(), Error> {
+    let offset = partition.range(at, bytes.len()).ok_or(Error::OutOfBounds)?;
+    with_flash(|flash| flash.blocking_write(offset, bytes))
+}
+
+/// Erases sector `index` of `partition`.
+pub fn erase(partition: Partition, index: usize) -> Result<(), Error> {
+    let offset = partition.sector(index).ok_or(Error::OutOfBounds)?;
+    with_flash(|flash| flash.blocking_erase(offset, offset + SECTOR_SIZE as u32))
+}
+
+/// Logs the partition map.
+pub fn log_partitions() {
+    for p in Partition::ALL {
+        info!(
+            "{:>10}: 0x{:06X}, {} sectors",
+            p.name(),
+            p.offset(),
+            p.sectors()
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use crate::midi::{MidiEvent, MidiSender};
+use crate::player;
+use crate::tuning::{TuningMode, TuningSpec};
+use lattice_board_core::config::{self, ConfigKey, ConfigValue};
+use lattice_board_core::nrpn::Data;
+use lattice_board_core::sysex::{cmd, parse_message};
+use log::info;
+use smart_leds::RGB8;
+
+/// Largest SysEx message accepted from the host.
+pub const SYSEX_BUFFER_SIZE: usize = 512;
+
+/// Full scale of [`ConfigKey::Brightness`].
+const BRIGHTNESS_MAX: f32 = 16383.0;
+
+/// A setting's current value; `None` for a palette index past the last anchor.
+fn read_config(key: ConfigKey, index: u8) -> Option<ConfigValue> {
+    let led = crate::leds::led_config();
+    let tuning = crate::tuning::spec();
+    let values = match key {
+        ConfigKey::Brightness => [(led.brightness * BRIGHTNESS_MAX + 0.5) as u16, 0, 0],
+        ConfigKey::TuningMode => [tuning.mode as u16, 0, 0],
+        ConfigKey::FifthSize => [((tuning.fifth_size - 600.0) * 10.0 + 0.5) as u16, 0, 0],
+        ConfigKey::PitchBendRange => [(tuning.mpe_pbr * 10.0 + 0.5) as u16, 0, 0],
+        ConfigKey::LedColor => {
+            let rgb = led.rgb_anchors.get(index as usize)?;
+            [rgb.r as u16, rgb.g as u16, rgb.b as u16]
+        }
+    };
+    Some(ConfigValue { key, index, values })
+}
+
+/// Applies a setting from the host, kept in the range its dashboard setting has.
+/// Returns false if the value can't be applied.
+fn write_config(value: ConfigValue) -> bool {
+    let [v, g, b] = value.values;
+    let retune = |spec: TuningSpec| crate::audition::retune(|| crate::tuning::set_spec(spec));
+    let tuning = crate::tuning::spec();
+    match value.key {
+        ConfigKey::Brightness => crate::leds::update_config(|c| {
+            c.brightness = (v as f32 / BRIGHTNESS_MAX).min(1.0);
+        }),
+        ConfigKey::TuningMode => {
+            let mode = match v {
+                0 => TuningMode::Standard,
+                1 => TuningMode::Fifths,
+                2 if crate::tuning::scale_notes().is_some() => TuningMode::Scale,
+                _ => return false,
+            };
+            retune(TuningSpec { mode, ..tuning });
+        }
+        ConfigKey::FifthSize => retune(TuningSpec {
+            fifth_size: (600.0 + v as f32 / 10.0).min(800.0),
+            ..tuning
+        }),
+        ConfigKey::PitchBendRange => retune(TuningSpec {
+            mpe_pbr: (v as f32 / 10.0).clamp(0.1, 96.0),
+            ..tuning
+        }),
+        ConfigKey::LedColor => {
+            let index = value.index as usize;
+            if index >= 12 {
+                return false;
+            }
+            let rgb = RGB8::new(v.min(255) as u8, g.min(255) as u8, b.min(255) as u8);
+            crate::leds::update_config(|c| c.rgb_anchors[index] = rgb);
+        }
+    }
+    true
+}
+
+/// Sends a setting's value back to the host.
+fn reply_config(key: ConfigKey, index: u8, queue: &MidiSender) {
+    let Some(value) = read_config(key, index) else {
+        info!("No {:?} {}", key, index);
+        return;
+    };
+    if queue.try_send(MidiEvent::ConfigReply(value)).is_err() {
+        info!("Config reply dropped: {:?}", value);
+    }
+}
+
+/// Dispatches a complete SysEx message received from the host; replies go out on `queue`.
+pub fn handle_sysex(msg: &[u8], queue: &MidiSender) {
+    let Some((command, payload)) = parse_message(msg) else {
+        return;
+    };
+
+    match command {
+        cmd::PLAYER_CLEAR => player::clear(),
+        cmd::PLAYER_APPEND => player::append(payload),
+        cmd::PLAYER_PLAY => player::play(),
+        cmd::PLAYER_STOP => player::stop(),
+        cmd::SET_BOARD if crate::lock::is_locked() => info!("SET_BOARD ignored while locked"),
+        cmd::SET_BOARD => match payload {
+            [id] => crate::util::store_board_id(*id),
+            _ => info!("SET_BOARD expects a single ID byte"),
+        },
+        cmd::SET_PARAM if crate::lock::is_locked() => info!("SET_PARAM ignored while locked"),
+        cmd::SET_PARAM => match *payload {
+            [param_msb, param_lsb, value_msb, ref fine @ ..] if fine.len() <= 1 => {
+                let param = (param_msb as u16) << 7 | param_lsb as u16;
+                let value = (value_msb as u16) << 7;
+                crate::nrpn::apply(param, Data::Entry(value));
+                if let [value_lsb] = *fine {
+                    crate::nrpn::apply(param, Data::Fine(value | value_lsb as u16));
+                }
+            }
+            _ => info!("SET_PARAM expects a parameter number, a value and an optional LSB"),
+        },
+        cmd::GET_CONFIG => match config::decode_get(payload) {
+            Some((key, index)) => reply_config(key, index, queue),
+            None => info!("GET_CONFIG expects a known key and, for colors, an index"),
+        },
+        cmd::SET_CONFIG if crate::lock::is_locked() => info!("SET_CONFIG ignored while locked"),
+        cmd::SET_CONFIG => match ConfigValue::decode(payload) {
+            Some(value) => {
+                if !write_config(value) {
+                    info!("SET_CONFIG value not applied: {:?}", value);
+                }
+                // The value as it now stands, clamped or not applied
+                reply_config(value.key, value.index, queue);
+            }
+            None => info!("SET_CONFIG expects a known key, an index for colors and its values"),
+        },
+        cmd::SELF_TEST => crate::selftest::midi_loopback(),
+        cmd::PRESS | cmd::RELEASE => {
+            let (row, col, velocity) = match *payload {
+                [row, col] => (row, col, 100),
+                [row, col, velocity] => (row, col, velocity),
+                _ => {
+                    info!("PRESS/RELEASE expect row, col and an optional velocity");
+                    return;
+                }
+            };
+            let is_pressed = command == cmd::PRESS;
+            if !crate::keys::inject(row as usize, col as usize, velocity, is_pressed) {
+                info!("No key at R{} C{}", row, col);
+            }
+        }
+        cmd::TRANSFER_BEGIN | cmd::TRANSFER_CHUNK | cmd::TRANSFER_END | cmd::TRANSFER_STATUS => {
+            crate::transfer::handle(command, payload, queue)
+        }
+        _ => info!("Unknown SysEx command {:#04x}", command),
+    }
+}
@@ -0,0 +1,107 @@
+use core::cell::Cell;
+use embassy_rp::adc::{Adc, Async, Channel};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use lattice_board_core::battery::BatteryMonitor;
+use lattice_board_core::supply::SupplyLimiter;
+use log::{info, warn};
+
+// VSYS sits on GPIO29 (ADC3) on the Pico, which the prototype and the 5x25 board
+// already use (LED data, a row line), so it's only measured on the 7x32 board. On
+// battery builds it's the battery's voltage, watched for its level instead of for
+// sags from the LEDs.
+
+const SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+/// VSYS is sampled faster than the temperature, as a sag from an LED flash is quick.
+const VSYS_PERIOD: Duration = Duration::from_millis(100);
+/// VSYS reaches GPIO29 through a 3:1 divider.
+const VSYS_DIVIDER: f32 = 3.0;
+/// Degrees above the limit at which brightness reaches its floor.
+const DERATE_SPAN_C: f32 = 15.0;
+const DERATE_FLOOR: f32 = 0.25;
+
+/// RP2040 datasheet conversion for the internal temperature sensor.
+fn raw_to_celsius(raw: u16) -> f32 {
+    let volts = raw as f32 * 3.3 / 4096.0;
+    27.0 - (volts - 0.706) / 0.001721
+}
+
+/// Brightness factor for the given chip temperature: 1.0 up to `limit_c`, then falling
+/// linearly to a floor. A limit of 0 disables derating.
+pub fn derate(temp_c: Option<f32>, limit_c: u8) -> f32 {
+    match temp_c {
+        Some(t) if limit_c > 0 && t > limit_c as f32 => {
+            (1.0 - (t - limit_c as f32) / DERATE_SPAN_C).max(DERATE_FLOOR)
+        }
+        _ => 1.0,
+    }
+}
+
+/// Brightness factor for the supply: below 1.0 while VSYS has been sagging.
+static SUPPLY_CAP: Mutex<CriticalSectionRawMutex, Cell<f32>> = Mutex::new(Cell::new(1.0));
+
+pub fn supply_cap() -> f32 {
+    SUPPLY_CAP.lock(|c| c.get())
+}
+
+fn raw_to_vsys(raw: u16) -> f32 {
+    raw as f32 * 3.3 / 4096.0 * VSYS_DIVIDER
+}
+
+/// Samples the chip temperature, and VSYS if the board has `vsys` free to measure it.
+#[embassy_executor::task]
+pub async fn telemetry_task(
+    mut adc: Adc<'static, Async>,
+    mut temp_sensor: Channel<'static>,
+    mut vsys: Option<Channel<'static>>,
+) {
+    let mut warned = false;
+    let mut limiter = SupplyLimiter::new();
+    let mut battery = BatteryMonitor::new();
+    let mut next_temp = Instant::now();
+    loop {
+        if Instant::now() >= next_temp {
+            next_temp += SAMPLE_PERIOD;
+            if let Ok(raw) = adc.read(&mut temp_sensor).await {
+                let temp = raw_to_celsius(raw);
+                crate::stats::record_temperature(temp);
+
+                let limit = crate::leds::led_config().thermal_limit_c;
+                let hot = limit > 0 && temp > limit as f32;
+                if hot && !warned {
+                    warn!("Chip at {:.1}C, derating LED brightness", temp);
+                }
+                warned = hot;
+            }
+        }
+
+        let Some(vsys) = vsys.as_mut() else {
+            Timer::at(next_temp).await;
+            continue;
+        };
+        if let Ok(raw) = adc.read(vsys).await {
+            let volts = raw_to_vsys(raw);
+            crate::stats::record_vsys(volts);
+            if cfg!(feature = "battery") {
+                let level = battery.update(volts);
+                crate::power::set_battery_level(level, volts).await;
+                Timer::after(VSYS_PERIOD).await;
+                continue;
+            }
+            let before = limiter.cap();
+            let cap = limiter.update(volts);
+            SUPPLY_CAP.lock(|c| c.set(cap));
+            if before == 1.0 && cap < 1.0 {
+                warn!("VSYS sagged to {:.2}V, capping LED brightness", volts);
+            } else if before < 1.0 && cap == 1.0 {
+                let lowest = crate::stats::vsys().map_or(volts, |(_, lowest)| lowest);
+                info!(
+                    "VSYS recovered (lowest {:.2}V), LED brightness uncapped",
+                    lowest
+                );
+            }
+        }
+        Timer::after(VSYS_PERIOD).await;
+    }
+}
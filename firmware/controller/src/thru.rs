@@ -0,0 +1,64 @@
+use crate::midi::{MidiEvent, MidiSender};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use lattice_board_core::midi_stream::Message;
+use lattice_board_core::thru::{Class, ThruFilter};
+
+/// Soft-thru: incoming messages sent back out over USB, so the board can sit in the
+/// middle of a MIDI chain. Off for every class until enabled.
+static FILTER: Mutex<CriticalSectionRawMutex, Cell<ThruFilter>> =
+    Mutex::new(Cell::new(ThruFilter::new()));
+/// Class and channel shown and toggled on the dashboard.
+static SELECTED_CLASS: Mutex<CriticalSectionRawMutex, Cell<Class>> =
+    Mutex::new(Cell::new(Class::Notes));
+static SELECTED_CHANNEL: Mutex<CriticalSectionRawMutex, Cell<u8>> = Mutex::new(Cell::new(0));
+
+pub fn filter() -> ThruFilter {
+    FILTER.lock(|f| f.get())
+}
+
+fn update_filter(f: impl FnOnce(&mut ThruFilter)) {
+    FILTER.lock(|cell| {
+        let mut filter = cell.get();
+        f(&mut filter);
+        cell.set(filter);
+    });
+}
+
+pub fn selected_class() -> Class {
+    SELECTED_CLASS.lock(|c| c.get())
+}
+
+pub fn cycle_class(delta: i8) {
+    SELECTED_CLASS.lock(|c| {
+        let i = (c.get() as i32 + delta as i32).rem_euclid(Class::ALL.len() as i32);
+        c.set(Class::ALL[i as usize]);
+    });
+}
+
+pub fn toggle_class() {
+    let class = selected_class();
+    update_filter(|f| f.toggle(class));
+}
+
+/// Channel 0-15.
+pub fn selected_channel() -> u8 {
+    SELECTED_CHANNEL.lock(|c| c.get())
+}
+
+pub fn cycle_channel(delta: i8) {
+    SELECTED_CHANNEL.lock(|c| c.set((c.get() as i8 + delta).rem_euclid(16) as u8));
+}
+
+pub fn toggle_channel() {
+    let channel = selected_channel();
+    update_filter(|f| f.toggle_channel(channel));
+}
+
+/// Queues a received message for sending back out if the filter passes it.
+pub fn forward(message: Message, sender: &MidiSender) {
+    if filter().passes(message.as_bytes()[0]) {
+        let _ = sender.try_send(MidiEvent::Thru(message));
+    }
+}
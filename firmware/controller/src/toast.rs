@@ -0,0 +1,75 @@
+//! Toasts: a dashboard line naming the setting that changed last, whatever changed it
+//! (MIDI, NRPN, the encoder, key combos, the console), so a change made away from the
+//! dashboard doesn't have to be looked for among its rows. It dims, then goes.
+
+use crate::fields::{Field, FIELDS};
+use core::fmt::Write;
+use embassy_time::{Duration, Instant};
+use heapless::String;
+use lattice_board_core::screen::ChangeWatch;
+
+/// How long a toast shows, then how long dimmed.
+const SHOW: Duration = Duration::from_millis(2000);
+const DIM: Duration = Duration::from_millis(1000);
+
+struct Toast {
+    field: Field,
+    /// Other settings that changed along with it, e.g. from a preset.
+    others: usize,
+    at: Instant,
+}
+
+/// Settings as last seen, and the toast for the latest change.
+pub struct Toasts {
+    watch: ChangeWatch<{ FIELDS.len() }>,
+    latest: Option<Toast>,
+}
+
+impl Toasts {
+    pub const fn new() -> Self {
+        Self {
+            watch: ChangeWatch::new(),
+            latest: None,
+        }
+    }
+
+    /// Looks for settings that changed since the last look. The first in dashboard
+    /// order gets the toast.
+    pub fn update(&mut self) {
+        let mut changed = None;
+        let mut count = 0;
+        for (i, &field) in FIELDS.iter().enumerate() {
+            let mut value: String<24> = String::new();
+            let _ = field.write_value(&mut value);
+            if self.watch.update(i, value.as_bytes()) {
+                changed.get_or_insert(field);
+                count += 1;
+            }
+        }
+        if let Some(field) = changed {
+            self.latest = Some(Toast {
+                field,
+                others: count - 1,
+                at: Instant::now(),
+            });
+        }
+    }
+
+    /// Writes the toast with the setting's value as it is now, if one is showing.
+    /// Returns whether it's dimmed.
+    pub fn write(&self, out: &mut impl Write) -> bool {
+        let Some(toast) = self.latest.as_ref() else {
+            return false;
+        };
+        let age = toast.at.elapsed();
+        if age >= SHOW + DIM {
+            return false;
+        }
+        let _ = write!(out, "* {}: ", toast.field.label());
+        let _ = toast.field.write_value(out);
+        if toast.others > 0 {
+            let _ = write!(out, " (+{} more)", toast.others);
+        }
+        age >= SHOW
+    }
+}
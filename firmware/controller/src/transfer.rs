@@ -0,0 +1,99 @@
+use crate::midi::{MidiEvent, MidiSender};
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use heapless::Vec;
+use lattice_board_core::sequence::ENCODED_EVENT_SIZE;
+use lattice_board_core::sysex::cmd;
+use lattice_board_core::transfer::{target, Data, TransferReceiver, MAX_CHUNK};
+use log::info;
+
+static RECEIVER: Mutex<CriticalSectionRawMutex, RefCell<TransferReceiver>> =
+    Mutex::new(RefCell::new(TransferReceiver::new()));
+/// Text of a scale file as it comes in.
+static SCALE_TEXT: Mutex<CriticalSectionRawMutex, RefCell<Vec<u8, SCALE_TEXT_LEN>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+/// Longest scale file taken, enough for the most notes a scale has with comments.
+const SCALE_TEXT_LEN: usize = 4096;
+/// Start of a player event cut off at the end of the last chunk.
+static PLAYER_CARRY: Mutex<CriticalSectionRawMutex, RefCell<Vec<u8, ENCODED_EVENT_SIZE>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+fn accepts(t: u8) -> bool {
+    t == target::PLAYER || t == target::SCALE
+}
+
+/// Handles a `TRANSFER_*` command, queueing the ACK/NACK reply on `queue`.
+pub fn handle(command: u8, payload: &[u8], queue: &MidiSender) {
+    let mut buf = [0u8; MAX_CHUNK];
+    let (reply, started, data, finished) = RECEIVER.lock(|r| {
+        let mut rx = r.borrow_mut();
+        match command {
+            cmd::TRANSFER_BEGIN => {
+                let (reply, started) = rx.begin(payload, accepts);
+                (reply, started, None, None)
+            }
+            cmd::TRANSFER_CHUNK => {
+                let (reply, data) = rx.chunk(payload, &mut buf);
+                (reply, None, data, None)
+            }
+            cmd::TRANSFER_END => {
+                let (reply, finished) = rx.end();
+                (reply, None, None, finished)
+            }
+            _ => (rx.status(), None, None, None),
+        }
+    });
+
+    if started == Some(target::PLAYER) {
+        crate::player::clear();
+        PLAYER_CARRY.lock(|c| c.borrow_mut().clear());
+    }
+    if started == Some(target::SCALE) {
+        SCALE_TEXT.lock(|t| t.borrow_mut().clear());
+    }
+    if let Some(data) = data {
+        write(data);
+    }
+    if let Some(t) = finished {
+        info!("Transfer to target {} complete", t);
+    }
+    if finished == Some(target::SCALE) {
+        load_scale();
+    }
+    if queue.try_send(MidiEvent::TransferReply(reply)).is_err() {
+        info!("Transfer reply dropped: {:?}", reply);
+    }
+}
+
+/// Writes accepted chunk data to its target. Targets so far take data in order, so
+/// the offset isn't needed.
+fn write(data: Data) {
+    if data.target == target::PLAYER {
+        // Chunks needn't end on event boundaries
+        let mut carry = PLAYER_CARRY.lock(|c| c.take());
+        for &b in data.bytes {
+            let _ = carry.push(b);
+            if carry.is_full() {
+                crate::player::append(&carry);
+                carry.clear();
+            }
+        }
+        PLAYER_CARRY.lock(|c| *c.borrow_mut() = carry);
+    } else if data.target == target::SCALE {
+        SCALE_TEXT.lock(|t| {
+            let mut text = t.borrow_mut();
+            // Longer files are cut off, and most likely refused as incomplete
+            let room = text.capacity() - text.len();
+            let _ = text.extend_from_slice(&data.bytes[..data.bytes.len().min(room)]);
+        });
+    }
+}
+
+fn load_scale() {
+    let text = SCALE_TEXT.lock(|t| t.take());
+    match crate::tuning::load_scale(&text) {
+        Ok(notes) => info!("Scale loaded: {} notes", notes),
+        Err(e) => info!("Scale refused: {:?}", e),
+    }
+}
@@ -0,0 +1,86 @@
+use crate::clock;
+use crate::layouts::CurrentLayout;
+use crate::midi::{MidiSender, ToU7};
+use crate::tuning::get_midi_event;
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use lattice_board_core::rng::Rng;
+
+/// How fast held notes repeat while the tremolo key is held.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rate {
+    /// Repeats per generator step (a sixteenth note), following the clock.
+    Synced(u8),
+    /// Repeats per second.
+    Free(f32),
+}
+
+pub const RATES: [(&str, Rate); 5] = [
+    ("1/16", Rate::Synced(1)),
+    ("1/32", Rate::Synced(2)),
+    ("8 Hz", Rate::Free(8.0)),
+    ("12 Hz", Rate::Free(12.0)),
+    ("16 Hz", Rate::Free(16.0)),
+];
+
+/// Velocity of the repeats, and how far it varies either way.
+const VELOCITY: u8 = 96;
+const VELOCITY_SPREAD: i8 = 12;
+
+static RATE: Mutex<CriticalSectionRawMutex, Cell<usize>> = Mutex::new(Cell::new(1));
+/// Whether the tremolo key is held.
+static HELD: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+pub fn rate_name() -> &'static str {
+    RATES[RATE.lock(|r| r.get()) % RATES.len()].0
+}
+
+pub fn cycle_rate(delta: i8) {
+    RATE.lock(|r| {
+        let len = RATES.len() as i32;
+        r.set((r.get() as i32 + delta as i32).rem_euclid(len) as usize);
+    });
+}
+
+pub fn set_held(held: bool) {
+    HELD.lock(|h| h.set(held));
+}
+
+fn period() -> Duration {
+    match RATES[RATE.lock(|r| r.get()) % RATES.len()].1 {
+        Rate::Synced(per_step) => clock::step_duration() / per_step as u32,
+        Rate::Free(hz) => Duration::from_micros((1_000_000.0 / hz) as u64),
+    }
+}
+
+/// Mandolin-style tremolo: while the tremolo key is held, every held note is played
+/// again each period, at a slightly varying velocity.
+#[embassy_executor::task]
+pub async fn tremolo_task(sender: MidiSender) {
+    let mut rng = Rng::new(Instant::now().as_ticks() as u32);
+    let mut next = Instant::now();
+
+    loop {
+        next += period();
+        Timer::at(next).await;
+
+        if !HELD.lock(|h| h.get()) {
+            next = Instant::now();
+            continue;
+        }
+        let keys = crate::keys::active_keys();
+        for &coord in keys
+            .iter()
+            .filter(|&&c| !crate::glide::on_strip::<CurrentLayout>(c))
+        {
+            let velocity = (VELOCITY as i16 + rng.span(VELOCITY_SPREAD) as i16) as u8;
+            let off = get_midi_event::<CurrentLayout>(coord, 0.to_u7(), false);
+            let on = get_midi_event::<CurrentLayout>(coord, velocity.to_u7(), true);
+            for event in [off, on].into_iter().flatten() {
+                sender.send(event).await;
+            }
+        }
+    }
+}
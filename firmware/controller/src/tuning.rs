@@ -1,37 +1,110 @@
 use crate::midi::{index_to_channel, MidiEvent};
+use crate::modulation::Target;
 use crate::mpe::MpeVoiceAllocator;
 use core::cell::{Cell, RefCell};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
 use heapless::Vec;
 use lattice_board_core::layout::{Coordinate, Layout};
-use micromath::F32Ext;
+use lattice_board_core::scala::{self, ScalaError, Scale};
+use lattice_board_core::tuning;
 use wmidi::{Channel, Note, U7};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TuningMode {
     Standard,
     Fifths,
+    /// Keys play the loaded Scala scale, stepping through it by the semitones they'd
+    /// be in 12-EDO.
+    Scale,
 }
 
+/// Tuning at power-up, and for presets that don't store one.
+pub const DEFAULT_SPEC: TuningSpec = TuningSpec {
+    mode: TuningMode::Fifths,
+    fifth_size: 697.0,
+    mpe_pbr: 1.0,
+};
+
 pub static CURRENT_TUNING_MODE: Mutex<CriticalSectionRawMutex, Cell<TuningMode>> =
-    Mutex::new(Cell::new(TuningMode::Fifths));
+    Mutex::new(Cell::new(DEFAULT_SPEC.mode));
+
+static FIFTH_SIZE: Mutex<CriticalSectionRawMutex, Cell<f32>> =
+    Mutex::new(Cell::new(DEFAULT_SPEC.fifth_size));
+static MPE_PBR: Mutex<CriticalSectionRawMutex, Cell<f32>> =
+    Mutex::new(Cell::new(DEFAULT_SPEC.mpe_pbr));
 
-static FIFTH_SIZE: Mutex<CriticalSectionRawMutex, Cell<f32>> = Mutex::new(Cell::new(697.0));
-static MPE_PBR: Mutex<CriticalSectionRawMutex, Cell<f32>> = Mutex::new(Cell::new(1.0));
+pub use lattice_board_core::tuning::PITCH_ANCHOR_CENTS;
 
-pub const PITCH_ANCHOR_CENTS: f32 = 6000.0;
+/// Scale loaded for [`TuningMode::Scale`], until power off.
+static SCALE: Mutex<CriticalSectionRawMutex, RefCell<Option<Scale>>> =
+    Mutex::new(RefCell::new(None));
 
 static MPE_ALLOCATOR: Mutex<CriticalSectionRawMutex, RefCell<MpeVoiceAllocator>> =
     Mutex::new(RefCell::new(MpeVoiceAllocator::new()));
-static ACTIVE_CHANNELS: Mutex<CriticalSectionRawMutex, RefCell<Vec<(Coordinate, Channel), 16>>> =
+static ACTIVE_CHANNELS: Mutex<CriticalSectionRawMutex, RefCell<Vec<ActiveVoice, 16>>> =
     Mutex::new(RefCell::new(Vec::new()));
 
+/// A key voiced on its own MPE channel, as sent in its note on.
+#[derive(Clone, Copy)]
+pub struct ActiveVoice {
+    pub coord: Coordinate,
+    pub channel: Channel,
+    pub note: u8,
+    pub pitch_bend: u16,
+    pub since: Instant,
+    /// Cents added to the key's pitch by the bend keys.
+    pub inflection: f32,
+}
+
+/// Everything that decides how the keys sound.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TuningSpec {
+    pub mode: TuningMode,
+    pub fifth_size: f32,
+    pub mpe_pbr: f32,
+}
+
+impl TuningSpec {
+    /// Whether voices held across a change from `self` to `to` can follow it by pitch
+    /// bend alone: in Fifths mode the notes sent don't depend on the fifth, and MPE
+    /// voices re-bend. Plain notes (a 700c fifth) have no voice to bend.
+    pub fn voices_follow(&self, to: &TuningSpec) -> bool {
+        self.mode == to.mode
+            && match self.mode {
+                TuningMode::Fifths => true,
+                // Always voiced on MPE channels
+                TuningMode::Scale => true,
+                TuningMode::Standard => self.fifth_size != 700.0 && to.fifth_size != 700.0,
+            }
+    }
+}
+
+pub fn spec() -> TuningSpec {
+    TuningSpec {
+        mode: get_mode(),
+        fifth_size: get_fifth_size(),
+        mpe_pbr: get_mpe_pbr(),
+    }
+}
+
+pub fn set_spec(spec: TuningSpec) {
+    CURRENT_TUNING_MODE.lock(|m| m.set(spec.mode));
+    FIFTH_SIZE.lock(|f| f.set(spec.fifth_size));
+    if MPE_PBR.lock(|p| p.replace(spec.mpe_pbr)) != spec.mpe_pbr {
+        crate::mpe::reannounce();
+    }
+}
+
+/// Steps to the next mode, skipping Scale until a scale is loaded.
 pub fn toggle_mode() -> TuningMode {
+    let loaded = SCALE.lock(|s| s.borrow().is_some());
     CURRENT_TUNING_MODE.lock(|m| {
         let new_mode = match m.get() {
             TuningMode::Standard => TuningMode::Fifths,
-            TuningMode::Fifths => TuningMode::Standard,
+            TuningMode::Fifths if loaded => TuningMode::Scale,
+            TuningMode::Fifths | TuningMode::Scale => TuningMode::Standard,
         };
         m.set(new_mode);
         new_mode
@@ -42,14 +115,32 @@ pub fn get_mode() -> TuningMode {
     CURRENT_TUNING_MODE.lock(|m| m.get())
 }
 
+/// Loads a Scala `.scl` scale and switches to playing it, returning its note count.
+pub fn load_scale(text: &[u8]) -> Result<usize, ScalaError> {
+    let scale = scala::parse(text)?;
+    SCALE.lock(|s| *s.borrow_mut() = Some(scale));
+    crate::audition::retune(|| CURRENT_TUNING_MODE.lock(|m| m.set(TuningMode::Scale)));
+    Ok(scale.notes())
+}
+
+/// Notes per period of the loaded scale, if there is one.
+pub fn scale_notes() -> Option<usize> {
+    SCALE.lock(|s| s.borrow().as_ref().map(|s| s.notes()))
+}
+
 pub fn get_fifth_size() -> f32 {
     FIFTH_SIZE.lock(|f| f.get())
 }
 
+/// The fifth notes are played with: the setting plus any LFO on it, kept in range.
+pub fn effective_fifth_size() -> f32 {
+    (get_fifth_size() + crate::modulation::offset(Target::Fifth)).clamp(600.0, 800.0)
+}
+
 pub fn adjust_fifth_size(delta: f32) {
     FIFTH_SIZE.lock(|f| {
         let current = f.get();
-        f.set((current + delta).max(600.0).min(800.0));
+        f.set((current + delta).clamp(600.0, 800.0));
     });
 }
 
@@ -58,27 +149,52 @@ pub fn get_mpe_pbr() -> f32 {
 }
 
 pub fn adjust_mpe_pbr(delta: f32) {
-    MPE_PBR.lock(|f| {
+    let changed = MPE_PBR.lock(|f| {
         let current = f.get();
-        f.set((current + delta).max(0.1).min(96.0));
+        f.set((current + delta).clamp(0.1, 96.0));
+        f.get() != current
     });
+    if changed {
+        crate::mpe::reannounce();
+    }
 }
 
 const FIFTHS_CENTER_CHANNEL: u8 = 4;
 const FIFTHS_CENTER_PITCH: u8 = 60;
 
-/// - x + 1, y - 1 (UP-RIGHT) is a Perfect Fifth.
-/// - x + 0, y - 2 (UP UP) is an Octave.
 pub fn calculate_fifths_offsets<L: Layout>(coord: Coordinate) -> (i16, i16) {
-    let center = L::center_coord();
-    let dx_raw = coord.x as i16 - center.x as i16;
-    let dy_raw = coord.y as i16 - center.y as i16;
+    tuning::fifths_offsets::<L>(coord)
+}
 
-    let octaves = (-dy_raw).div_euclid(2);
-    let shift = (-dy_raw).rem_euclid(2);
-    let fifths = 2 * dx_raw - 2 * octaves - shift;
+/// Whether notes are voiced on MPE channels of their own: in Scale mode, and in
+/// Standard mode off a 700c fifth.
+pub fn uses_mpe() -> bool {
+    match get_mode() {
+        TuningMode::Standard => get_fifth_size() != 700.0,
+        TuningMode::Scale => true,
+        TuningMode::Fifths => false,
+    }
+}
 
-    (octaves, fifths)
+/// Channel and note a key plays when it isn't voiced on an MPE channel: its 12-EDO
+/// note on Ch1, or in Fifths mode a channel per octave and a note per fifth.
+pub fn fixed_note<L: Layout>(coord: Coordinate) -> (Channel, u8) {
+    if get_mode() == TuningMode::Fifths {
+        let (oc, fifths) = calculate_fifths_offsets::<L>(coord);
+        // Spec: Channel increases with physical octaves
+        let ch_idx = (FIFTHS_CENTER_CHANNEL as i16 + oc).clamp(0, 15) as u8;
+        // Spec: Pitch increases with physical fifths
+        let pitch_idx = (FIFTHS_CENTER_PITCH as i16 + fifths).clamp(0, 127) as u8;
+        (index_to_channel(ch_idx).unwrap_or(Channel::Ch1), pitch_idx)
+    } else {
+        // Plain notes can't follow a modulated fifth, and their note off has to name
+        // the same note
+        let plain_cents = tuning::key_pitch_cents::<L>(coord, 700.0);
+        (
+            Channel::Ch1,
+            ((plain_cents / 100.0 + 0.5) as u8).clamp(0, 127),
+        )
+    }
 }
 
 pub fn get_midi_event<L: Layout>(
@@ -88,14 +204,15 @@ pub fn get_midi_event<L: Layout>(
 ) -> Option<MidiEvent> {
     let mode = get_mode();
     match mode {
-        TuningMode::Standard => {
+        TuningMode::Standard | TuningMode::Scale => {
+            let plain = !uses_mpe();
             if is_note_on {
                 let target_cents = get_key_pitch::<L>(coord);
-                if get_fifth_size() == 700.0 {
-                    let midi_note = ((target_cents / 100.0 + 0.5) as u8).clamp(0, 127);
+                if plain {
+                    let (channel, midi_note) = fixed_note::<L>(coord);
                     if let Ok(note) = Note::try_from(midi_note) {
                         return Some(MidiEvent::NoteOn {
-                            channel: Channel::Ch1,
+                            channel,
                             note,
                             velocity,
                         });
@@ -104,13 +221,16 @@ pub fn get_midi_event<L: Layout>(
                 }
                 let channel_opt = MPE_ALLOCATOR.lock(|alloc| alloc.borrow_mut().alloc());
                 if let Some(channel) = channel_opt {
-                    let _ = ACTIVE_CHANNELS.lock(|chans| chans.borrow_mut().push((coord, channel)));
-                    let exact_note_val = target_cents / 100.0;
-                    let midi_note = ((exact_note_val + 0.5) as u8).clamp(0, 127);
-                    let bend_cents = target_cents - (midi_note as f32 * 100.0);
-                    let mpe_pbr = get_mpe_pbr();
-                    let bend_units_offset = (bend_cents / 100.0) * (8192.0 / mpe_pbr);
-                    let bend_val = (8192.0 + bend_units_offset).clamp(0.0, 16383.0) as u16;
+                    let (midi_note, bend_val) = tuning::mpe_note(target_cents, get_mpe_pbr());
+                    let voice = ActiveVoice {
+                        coord,
+                        channel,
+                        note: midi_note,
+                        pitch_bend: bend_val,
+                        since: Instant::now(),
+                        inflection: 0.0,
+                    };
+                    let _ = ACTIVE_CHANNELS.lock(|chans| chans.borrow_mut().push(voice));
                     if let Ok(note) = Note::try_from(midi_note) {
                         Some(MidiEvent::MpeNoteOn {
                             channel,
@@ -132,19 +252,19 @@ pub fn get_midi_event<L: Layout>(
                 let found_data = ACTIVE_CHANNELS.lock(|chans| {
                     let mut c = chans.borrow_mut();
                     let mut found = None;
-                    for (i, (co, _)) in c.iter().enumerate() {
-                        if *co == coord {
+                    for (i, voice) in c.iter().enumerate() {
+                        if voice.coord == coord {
                             found = Some(i);
                             break;
                         }
                     }
                     found.map(|idx| c.swap_remove(idx))
                 });
-                if let Some((_, channel)) = found_data {
+                if let Some(ActiveVoice { channel, note, .. }) = found_data {
                     MPE_ALLOCATOR.lock(|a| a.borrow_mut().free(channel));
-                    let target_cents = get_key_pitch::<L>(coord);
-                    let midi_note = ((target_cents / 100.0 + 0.5) as u8).clamp(0, 127);
-                    if let Ok(note) = Note::try_from(midi_note) {
+                    // The note sent with the note on, which a modulated fifth may since
+                    // have moved away from
+                    if let Ok(note) = Note::try_from(note) {
                         Some(MidiEvent::NoteOff {
                             channel,
                             note,
@@ -153,12 +273,11 @@ pub fn get_midi_event<L: Layout>(
                     } else {
                         None
                     }
-                } else if get_fifth_size() == 700.0 {
-                    let target_cents = get_key_pitch::<L>(coord);
-                    let midi_note = ((target_cents / 100.0 + 0.5) as u8).clamp(0, 127);
+                } else if plain {
+                    let (channel, midi_note) = fixed_note::<L>(coord);
                     if let Ok(note) = Note::try_from(midi_note) {
                         Some(MidiEvent::NoteOff {
-                            channel: Channel::Ch1,
+                            channel,
                             note,
                             velocity,
                         })
@@ -171,14 +290,8 @@ pub fn get_midi_event<L: Layout>(
             }
         }
         TuningMode::Fifths => {
-            let (oc, fifths) = calculate_fifths_offsets::<L>(coord);
-            // Spec: Channel increases with physical octaves
-            let ch_idx = (FIFTHS_CENTER_CHANNEL as i16 + oc).clamp(0, 15) as u8;
-            // Spec: Pitch increases with physical fifths
-            let pitch_idx = (FIFTHS_CENTER_PITCH as i16 + fifths).clamp(0, 127) as u8;
-
+            let (channel, pitch_idx) = fixed_note::<L>(coord);
             if let Ok(note) = Note::try_from(pitch_idx) {
-                let channel = index_to_channel(ch_idx).unwrap_or(Channel::Ch1);
                 if is_note_on {
                     Some(MidiEvent::NoteOn {
                         channel,
@@ -199,13 +312,186 @@ pub fn get_midi_event<L: Layout>(
     }
 }
 
+/// What a held key is sending, for display.
+pub struct SentNote {
+    pub channel: Channel,
+    pub note: u8,
+    /// Pitch bend (8192 = none) for keys voiced on their own MPE channel.
+    pub pitch_bend: Option<u16>,
+}
+
+/// Channel, note and bend that `get_midi_event` sends for the key at `coord`.
+/// In Standard mode this is only exact while the key is held, since its MPE channel
+/// is looked up from the active voices.
+pub fn sent_note<L: Layout>(coord: Coordinate) -> SentNote {
+    match get_mode() {
+        TuningMode::Standard | TuningMode::Scale => {
+            let voice = ACTIVE_CHANNELS.lock(|chans| {
+                chans
+                    .borrow()
+                    .iter()
+                    .find(|voice| voice.coord == coord)
+                    .copied()
+            });
+            match voice {
+                Some(voice) => SentNote {
+                    channel: voice.channel,
+                    note: voice.note,
+                    pitch_bend: Some(voice.pitch_bend),
+                },
+                None => SentNote {
+                    channel: Channel::Ch1,
+                    note: ((get_key_pitch::<L>(coord) / 100.0 + 0.5) as u8).clamp(0, 127),
+                    pitch_bend: None,
+                },
+            }
+        }
+        TuningMode::Fifths => {
+            let (oc, fifths) = calculate_fifths_offsets::<L>(coord);
+            let ch_idx = (FIFTHS_CENTER_CHANNEL as i16 + oc).clamp(0, 15) as u8;
+            SentNote {
+                channel: index_to_channel(ch_idx).unwrap_or(Channel::Ch1),
+                note: (FIFTHS_CENTER_PITCH as i16 + fifths).clamp(0, 127) as u8,
+                pitch_bend: None,
+            }
+        }
+    }
+}
+
+/// Deviation of a key from its 12-EDO pitch at the current fifth size, in cents.
+pub fn cents_from_12edo<L: Layout>(coord: Coordinate) -> f32 {
+    get_key_pitch::<L>(coord) - tuning::key_pitch_cents::<L>(coord, 700.0)
+}
+
+/// Keys currently holding an MPE channel.
+pub fn active_voices() -> Vec<ActiveVoice, 16> {
+    ACTIVE_CHANNELS.lock(|chans| chans.borrow().clone())
+}
+
+/// Key and channel of the most recently started MPE voice.
+pub fn latest_voice() -> Option<(Coordinate, Channel)> {
+    ACTIVE_CHANNELS.lock(|chans| {
+        chans
+            .borrow()
+            .iter()
+            .max_by_key(|voice| voice.since)
+            .map(|voice| (voice.coord, voice.channel))
+    })
+}
+
+/// Moves the voice of `coord` on `channel` by `cents`, returning its new bend, or
+/// `None` if that voice has since ended.
+pub fn inflect_voice<L: Layout>(coord: Coordinate, channel: Channel, cents: f32) -> Option<u16> {
+    let pbr = get_mpe_pbr();
+    ACTIVE_CHANNELS.lock(|chans| {
+        let mut chans = chans.borrow_mut();
+        let voice = chans
+            .iter_mut()
+            .find(|voice| voice.coord == coord && voice.channel == channel)?;
+        voice.inflection += cents;
+        let pitch = get_key_pitch::<L>(coord) + voice.inflection;
+        voice.pitch_bend = tuning::bend_for(voice.note, pitch, pbr);
+        Some(voice.pitch_bend)
+    })
+}
+
+/// Takes an MPE channel for a voice played outside `get_midi_event`, like the glide
+/// strip's.
+pub fn claim_mpe_channel() -> Option<Channel> {
+    MPE_ALLOCATOR.lock(|alloc| alloc.borrow_mut().alloc())
+}
+
+pub fn release_mpe_channel(channel: Channel) {
+    MPE_ALLOCATOR.lock(|alloc| alloc.borrow_mut().free(channel));
+}
+
+/// Keeps `channel` out of MPE allocation while something else plays on it, or gives
+/// it back, announcing the zone again if its member channels changed.
+pub fn reserve_mpe_channel(channel: Channel, reserved: bool) {
+    if MPE_ALLOCATOR.lock(|alloc| alloc.borrow_mut().reserve(channel, reserved)) {
+        crate::mpe::reannounce();
+    }
+}
+
+/// Channels MPE notes are allocated on, as announced to the host.
+pub fn mpe_member_channels() -> u16 {
+    MPE_ALLOCATOR.lock(|alloc| alloc.borrow().members())
+}
+
+/// The MPE allocator's channel usage, bit `i` for channel `i + 1`.
+pub fn mpe_usage_mask() -> u16 {
+    MPE_ALLOCATOR.lock(|alloc| alloc.borrow().usage_mask())
+}
+
 pub fn get_key_pitch<L: Layout>(coord: Coordinate) -> f32 {
-    let (oc, fifths) = calculate_fifths_offsets::<L>(coord);
-    // Absolute pitch calculation for standard 12-TET behavior
-    // 1 Octave (oc) = 1200 cents
-    // 1 Fifth step (fifths) = dynamic fifth size (default 700)
-    PITCH_ANCHOR_CENTS + (oc as f32 * 1200.0) + (fifths as f32 * get_fifth_size())
-        - (fifths.div_euclid(2) as f32 * 1200.0)
+    pitch_at::<L>(coord, effective_fifth_size())
+}
+
+/// Pitch of a key in cents: from the loaded scale in Scale mode, otherwise from the
+/// lattice at `fifth_size`.
+fn pitch_at<L: Layout>(coord: Coordinate, fifth_size: f32) -> f32 {
+    let scaled = (get_mode() == TuningMode::Scale)
+        .then(|| {
+            SCALE.lock(|s| {
+                s.borrow()
+                    .as_ref()
+                    .map(|scale| tuning::scale_pitch_cents::<L>(coord, scale))
+            })
+        })
+        .flatten();
+    scaled.unwrap_or_else(|| tuning::key_pitch_cents::<L>(coord, fifth_size))
+}
+
+/// Pitch of the last note played, and the interval to it from the one before, in cents.
+static LAST_PITCH: Mutex<CriticalSectionRawMutex, Cell<Option<f32>>> = Mutex::new(Cell::new(None));
+static LAST_INTERVAL: Mutex<CriticalSectionRawMutex, Cell<Option<f32>>> =
+    Mutex::new(Cell::new(None));
+
+/// Notes a key played, for the interval readout.
+pub fn note_played<L: Layout>(coord: Coordinate) {
+    let pitch = get_key_pitch::<L>(coord);
+    crate::practice::note_played(pitch);
+    if let Some(previous) = LAST_PITCH.lock(|l| l.replace(Some(pitch))) {
+        LAST_INTERVAL.lock(|l| l.set(Some(pitch - previous)));
+    }
+}
+
+/// The interval between the last two notes played, in cents (negative going down).
+pub fn last_interval() -> Option<f32> {
+    LAST_INTERVAL.lock(|l| l.get())
+}
+
+/// Re-bends the held MPE voices to the current fifth size, returning the channels
+/// whose bend changed with their new bend.
+pub fn retune_voices<L: Layout>() -> Vec<(Channel, u16), 16> {
+    let pbr = get_mpe_pbr();
+    ACTIVE_CHANNELS.lock(|chans| {
+        let mut changed = Vec::new();
+        for voice in chans.borrow_mut().iter_mut() {
+            let pitch = get_key_pitch::<L>(voice.coord) + voice.inflection;
+            let bend = tuning::bend_for(voice.note, pitch, pbr);
+            if bend != voice.pitch_bend {
+                voice.pitch_bend = bend;
+                let _ = changed.push((voice.channel, bend));
+            }
+        }
+        changed
+    })
+}
+
+/// Each held MPE voice's channel with the bend that puts it on pitch now.
+pub fn voice_targets<L: Layout>() -> Vec<(Channel, u16), 16> {
+    let pbr = get_mpe_pbr();
+    ACTIVE_CHANNELS.lock(|chans| {
+        chans
+            .borrow()
+            .iter()
+            .map(|voice| {
+                let pitch = get_key_pitch::<L>(voice.coord) + voice.inflection;
+                (voice.channel, tuning::bend_for(voice.note, pitch, pbr))
+            })
+            .collect()
+    })
 }
 
 pub fn find_closest_keys<L: Layout>(
@@ -214,46 +500,10 @@ pub fn find_closest_keys<L: Layout>(
     rows: usize,
     cols: usize,
     bias_note: Option<u8>,
-) -> Vec<Coordinate, 4> {
-    let mut candidates: Vec<Coordinate, 4> = Vec::new();
-    let mut min_dist = max_dist;
-    for r in 0..rows {
-        for c in 0..cols {
-            if let Some(coord) = L::key_to_coord(r, c) {
-                let pitch = get_key_pitch::<L>(coord);
-                let mut dist = (pitch - target_cents).abs();
-                if let Some(note) = bias_note {
-                    if L::coord_to_midi(coord) == note {
-                        dist -= 20.0;
-                    }
-                }
-                if dist < min_dist {
-                    min_dist = dist;
-                }
-            }
-        }
-    }
-    if min_dist >= max_dist {
-        return candidates;
-    }
-    for r in 0..rows {
-        for c in 0..cols {
-            if let Some(coord) = L::key_to_coord(r, c) {
-                let pitch = get_key_pitch::<L>(coord);
-                let mut dist = (pitch - target_cents).abs();
-                if let Some(note) = bias_note {
-                    if L::coord_to_midi(coord) == note {
-                        dist -= 20.0;
-                    }
-                }
-                if dist <= min_dist + 1.0 {
-                    let _ = candidates.push(coord);
-                    if candidates.is_full() {
-                        return candidates;
-                    }
-                }
-            }
-        }
-    }
-    candidates
+) -> Vec<Coordinate, { tuning::MAX_CANDIDATES }> {
+    let fifth_size = get_fifth_size();
+    let found = tuning::closest_keys_by::<L>(target_cents, max_dist, rows, cols, bias_note, |c| {
+        pitch_at::<L>(c, fifth_size)
+    });
+    Vec::from_slice(found.as_slice()).unwrap_or_default()
 }
@@ -1,5 +1,11 @@
+use crate::commands::{apply_keys, Control};
+use crate::dashboard::{item, voice_scroll, DashboardCache, DashboardWriter, Page, TerminalSize};
+use crate::fields::{Field, FIELDS};
 use crate::layouts::CurrentLayout;
+use crate::reset::Prompt;
+use crate::toast::Toasts;
 use core::cell::RefCell;
+use core::fmt::Write;
 use core::pin::pin;
 use embassy_futures::select::{select, Either};
 use embassy_rp::peripherals;
@@ -9,6 +15,10 @@ use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::blocking_mutex::Mutex;
 use embassy_time::{Duration, Timer};
 use embassy_usb::class::cdc_acm::CdcAcmClass;
+use lattice_board_core::interval::nearest_just;
+use lattice_board_core::layout::Layout;
+use lattice_board_core::pitch::{write_pitch_classes, PITCH_CLASS_NAMES};
+use lattice_board_core::screen::{Arrow, Input, InputFilter, QUERY_SIZE};
 use log::info;
 
 #[derive(PartialEq, Copy, Clone)]
@@ -20,10 +30,16 @@ enum SerialState {
 static SERIAL_STATE: Mutex<CriticalSectionRawMutex, RefCell<SerialState>> =
     Mutex::new(RefCell::new(SerialState::Log));
 
-pub static LOG_PIPE: embassy_sync::pipe::Pipe<CriticalSectionRawMutex, 1024> =
-    embassy_sync::pipe::Pipe::new();
+pub static LOG_PIPE: embassy_sync::pipe::Pipe<
+    CriticalSectionRawMutex,
+    { crate::capacities::LOG_PIPE },
+> = embassy_sync::pipe::Pipe::new();
+
+/// Dashboard rows other than the two lists (status lines and section titles).
+const DASHBOARD_FIXED_ROWS: usize = 19;
+/// Re-query the terminal size every this many dashboard ticks to catch resizes.
+const SIZE_POLL_TICKS: u32 = 20;
 
-const CURSOR_HOME: &[u8] = b"\x1B[H";
 const CLEAR_SCREEN: &[u8] = b"\x1B[2J";
 const HIDE_CURSOR: &[u8] = b"\x1B[?25l";
 const SHOW_CURSOR: &[u8] = b"\x1B[?25h";
@@ -50,6 +66,18 @@ async fn serial_process(
 ) -> Result<(), ()> {
     let mut buf = [0u8; 64];
     let mut log_buf = [0u8; 64];
+    let mut dashboard = DashboardCache::new();
+    let mut input = InputFilter::new();
+    let mut size = TerminalSize::DEFAULT;
+    let mut ticks: u32 = 0;
+    // Dashboard field selected for arrow-key editing
+    let mut field = 0;
+    let mut page = Page::Main;
+    // 'Q' was pressed and the next digit picks the slot to store the macro in
+    let mut awaiting_slot = false;
+    // '!' was pressed and this much of the factory reset confirmation has been typed
+    let mut reset_typed = None;
+    let mut toasts = Toasts::new();
 
     loop {
         let mut result_n = None;
@@ -85,14 +113,55 @@ async fn serial_process(
         }
 
         if let Some(n) = result_n {
-            let data = &buf[..n];
+            // Strip escape sequences, picking up size reports on the way
+            let mut keys: heapless::Vec<u8, 64> = heapless::Vec::new();
+            let mut resized = false;
             let mut state = SERIAL_STATE.lock(|s| *s.borrow());
+            for &b in &buf[..n] {
+                match input.feed(b) {
+                    Input::Byte(b) => {
+                        crate::power::note_activity();
+                        if b == b'\r'
+                            && state == SerialState::Dashboard
+                            && !crate::lock::is_locked()
+                        {
+                            FIELDS[field].activate();
+                        }
+                        let _ = keys.push(b);
+                    }
+                    Input::Arrow(arrow) if state == SerialState::Dashboard => {
+                        crate::power::note_activity();
+                        match arrow {
+                            Arrow::Up => field = (field + FIELDS.len() - 1) % FIELDS.len(),
+                            Arrow::Down => field = (field + 1) % FIELDS.len(),
+                            _ if crate::lock::is_locked() => {}
+                            Arrow::Left => FIELDS[field].adjust(-1),
+                            Arrow::Right => FIELDS[field].adjust(1),
+                        }
+                    }
+                    Input::Size { rows, cols } => {
+                        let reported = TerminalSize { rows, cols };
+                        resized |= reported != size;
+                        size = reported;
+                    }
+                    Input::Arrow(_) | Input::Escape => {}
+                }
+            }
+            let data = &keys[..];
+
+            if resized && state == SerialState::Dashboard {
+                let _ = class.write_packet(CLEAR_SCREEN).await;
+                dashboard.invalidate();
+            }
 
             for &b in data {
-                if b == b'D' || b == b'd' {
+                let control = crate::commands::control(b);
+                if control == Some(Control::Mode) {
                     state = if state == SerialState::Log {
                         let _ = class.write_packet(CLEAR_SCREEN).await;
                         let _ = class.write_packet(HIDE_CURSOR).await;
+                        let _ = class.write_packet(QUERY_SIZE).await;
+                        dashboard.invalidate();
                         SerialState::Dashboard
                     } else {
                         let _ = class.write_packet(SHOW_CURSOR).await;
@@ -100,51 +169,94 @@ async fn serial_process(
                         SerialState::Log
                     };
                     SERIAL_STATE.lock(|s| *s.borrow_mut() = state);
+                } else if control == Some(Control::Page) && state == SerialState::Dashboard {
+                    page = page.next();
+                    let _ = class.write_packet(CLEAR_SCREEN).await;
+                    dashboard.invalidate();
+                } else if control == Some(Control::Help) && state == SerialState::Dashboard {
+                    page = if page == Page::Help {
+                        Page::Main
+                    } else {
+                        Page::Help
+                    };
+                    let _ = class.write_packet(CLEAR_SCREEN).await;
+                    dashboard.invalidate();
                 }
             }
 
             if state == SerialState::Log {
                 let _ = class.write_packet(data).await;
+                let controls = || data.iter().filter_map(|&b| crate::commands::control(b));
+                if controls().any(|c| c == Control::LayoutDump) {
+                    write_layout_dump(class).await;
+                }
+                if controls().any(|c| c == Control::Verify) {
+                    crate::storage::log_partitions();
+                    crate::util::log_settings_health();
+                    crate::capacities::log_report();
+                }
+                if controls().any(|c| c == Control::SettleTest) {
+                    crate::keys::settle_test::start();
+                }
+                if controls().any(|c| c == Control::PracticeLog) {
+                    crate::practice::log_report();
+                }
+                if controls().any(|c| c == Control::ReleaseBenchmark) {
+                    crate::selftest::start_release_benchmark();
+                }
             }
 
-            crate::leds::LED_CONFIG.lock(|c| {
-                let mut config = c.borrow_mut();
-                let clamp_u8 =
-                    |v: u8, delta: i16| -> u8 { ((v as i16 + delta).max(0).min(255)) as u8 };
-                for &b in data {
-                    let sel = config.selected_anchor;
-                    let mut rgb = config.rgb_anchors[sel];
-                    match b {
-                        b'[' => config.selected_anchor = (config.selected_anchor + 11) % 12,
-                        b']' => config.selected_anchor = (config.selected_anchor + 1) % 12,
-                        b'r' => rgb.r = clamp_u8(rgb.r, -5),
-                        b'R' => rgb.r = clamp_u8(rgb.r, 5),
-                        b'g' => rgb.g = clamp_u8(rgb.g, -5),
-                        b'G' => rgb.g = clamp_u8(rgb.g, 5),
-                        b'b' => rgb.b = clamp_u8(rgb.b, -5),
-                        b'B' => rgb.b = clamp_u8(rgb.b, 5),
-                        b'L' => config.brightness = (config.brightness + 0.05).min(1.0),
-                        b'l' => config.brightness = (config.brightness - 0.05).max(0.0),
-                        b'+' | b'=' => config.brightness = (config.brightness + 0.01).min(1.0),
-                        b'-' | b'_' => config.brightness = (config.brightness - 0.01).max(0.0),
-                        b'H' => config.hue_offset = (config.hue_offset + 1.0) % 360.0,
-                        b'h' => config.hue_offset = (config.hue_offset - 1.0 + 360.0) % 360.0,
-                        b't' | b'T' => {
-                            let _ = crate::tuning::toggle_mode();
+            let mut commands: heapless::Vec<u8, 64> = heapless::Vec::new();
+            for &b in data {
+                let control = crate::commands::control(b);
+                if control == Some(Control::Lock) {
+                    crate::lock::toggle();
+                    continue;
+                }
+                if crate::lock::is_locked() {
+                    continue;
+                }
+                if let Some(typed) = reset_typed {
+                    reset_typed = match crate::reset::prompt_key(typed, b) {
+                        Prompt::Typing(typed) => Some(typed),
+                        Prompt::Cancelled => {
+                            info!("Factory reset cancelled");
+                            None
+                        }
+                        Prompt::Confirmed => {
+                            crate::reset::factory_reset().await;
+                            None
                         }
-                        b'(' => crate::tuning::adjust_fifth_size(-1.0),
-                        b')' => crate::tuning::adjust_fifth_size(1.0),
-                        b'{' => crate::tuning::adjust_fifth_size(-0.1),
-                        b'}' => crate::tuning::adjust_fifth_size(0.1),
-                        b',' => crate::tuning::adjust_mpe_pbr(-1.0),
-                        b'.' => crate::tuning::adjust_mpe_pbr(1.0),
-                        b'<' => crate::tuning::adjust_mpe_pbr(-0.1),
-                        b'>' => crate::tuning::adjust_mpe_pbr(0.1),
-                        _ => {}
+                    };
+                    continue;
+                }
+                if awaiting_slot {
+                    awaiting_slot = false;
+                    if let Some(slot) = macro_slot(b) {
+                        crate::recorder::store(slot);
                     }
-                    config.rgb_anchors[sel] = rgb;
+                    continue;
                 }
-            });
+                match control {
+                    Some(Control::Record) => crate::recorder::toggle(),
+                    Some(Control::StoreMacro) => awaiting_slot = true,
+                    Some(Control::FactoryReset) if state == SerialState::Log => {
+                        crate::reset::start_prompt();
+                        reset_typed = Some(0);
+                    }
+                    Some(Control::PlayMacro) => {
+                        if let Some(recorded) = macro_slot(b).and_then(crate::recorder::load) {
+                            apply_keys(&recorded);
+                        }
+                    }
+                    Some(_) => {}
+                    None => {
+                        crate::recorder::record(b);
+                        let _ = commands.push(b);
+                    }
+                }
+            }
+            apply_keys(&commands);
         }
 
         if let Some(n) = result_log {
@@ -155,9 +267,24 @@ async fn serial_process(
         }
 
         if result_tick {
+            // Watched in log mode too, so going back to the dashboard doesn't toast
+            // older changes
+            toasts.update();
             let state = SERIAL_STATE.lock(|s| *s.borrow());
             if state == SerialState::Dashboard {
-                draw_dashboard(class).await;
+                match page {
+                    Page::Main => {
+                        draw_dashboard(class, &mut dashboard, size, FIELDS[field], &toasts).await
+                    }
+                    Page::Channels => draw_channels(class, &mut dashboard, size).await,
+                    Page::Stats => draw_stats(class, &mut dashboard, size).await,
+                    Page::Practice => draw_practice(class, &mut dashboard, size).await,
+                    Page::Help => draw_help(class, &mut dashboard, size).await,
+                }
+                ticks = ticks.wrapping_add(1);
+                if ticks.is_multiple_of(SIZE_POLL_TICKS) {
+                    let _ = class.write_packet(QUERY_SIZE).await;
+                }
             }
         }
 
@@ -165,65 +292,555 @@ async fn serial_process(
     }
 }
 
-async fn draw_dashboard(class: &mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>) {
-    use core::fmt::Write;
-    let mut out: heapless::String<1024> = heapless::String::new();
-
-    let (b, h, sel, anchors, mode, size, pbr) = crate::leds::LED_CONFIG.lock(|cfg| {
-        let cfg = cfg.borrow();
-        let m = crate::tuning::get_mode();
-        let s = crate::tuning::get_fifth_size();
-        let p = crate::tuning::get_mpe_pbr();
-        (
-            cfg.brightness,
-            cfg.hue_offset,
-            cfg.selected_anchor,
-            cfg.rgb_anchors,
-            m,
-            s,
-            p,
-        )
-    });
-
-    let active_keys = crate::keys::ACTIVE_KEYS.lock(|c| c.borrow().clone());
+/// Macro slot selected by a digit key ('1' is slot 0).
+fn macro_slot(b: u8) -> Option<usize> {
+    (b'1'..=b'8').contains(&b).then(|| (b - b'1') as usize)
+}
 
-    let _ = class.write_packet(CURSOR_HOME).await;
-    let rgb = anchors[sel];
-    let _ = write!(
+/// The last interval played, in cents and as the nearest just ratio.
+fn write_interval(out: &mut impl Write) -> core::fmt::Result {
+    let Some(cents) = crate::tuning::last_interval() else {
+        return write!(out, "--");
+    };
+    let just = nearest_just(cents);
+    write!(
         out,
-        "Lattice Board Controller v0.1.0\x1B[K\r\n\
-         -------------------------------\x1B[K\r\n\
-         Brightness: {:.2} | Hue: {:.0} | Mode: {:?}\x1B[K\r\n\
-         Fifth: {:.1}c | PBR: {:.1}\x1B[K\r\n\
-         RGB: Idx {} | R{} G{} B{}\x1B[K\r\n\r\n\
-         Held Keys:\x1B[K\r\n",
-        b, h, mode, size, pbr, sel, rgb.r, rgb.g, rgb.b
+        "{:+.1}c ~ {}/{}",
+        cents, just.numerator, just.denominator
+    )?;
+    if just.octaves > 0 {
+        write!(out, " +{} oct", just.octaves)?;
+    }
+    write!(out, " ({:+.1}c)", just.error)
+}
+
+async fn draw_dashboard(
+    class: &mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>,
+    cache: &mut DashboardCache,
+    term: TerminalSize,
+    field: Field,
+    toasts: &Toasts,
+) {
+    let cfg = crate::leds::led_config();
+    let (b, transpose, h, sel, anchors, release, frame) = (
+        cfg.brightness,
+        cfg.transpose,
+        cfg.hue_rotation,
+        cfg.selected_anchor,
+        cfg.rgb_anchors,
+        cfg.release_ms,
+        cfg.frame_ms,
     );
+    let mode = crate::tuning::get_mode();
+    let size = crate::tuning::get_fifth_size();
+    let pbr = crate::tuning::get_mpe_pbr();
+
+    let active_keys = crate::keys::active_keys();
+    let euclid = crate::euclid::get_config();
+    let bpm = crate::clock::get_bpm();
+    let walk = crate::walk::get_config();
+    let arp = crate::arp::get_config();
+    let (playing, player_events) = crate::player::status();
+    let release_latency = crate::stats::release_latency();
+    let (merged, dropped) = crate::stats::schedule_overloads();
+    let jitter = crate::stats::send_jitter();
+    let throttled = if crate::midi::is_busy() {
+        " (throttled)"
+    } else {
+        ""
+    };
+
+    let rgb = anchors[sel];
+    // Share the rows left below the status lines between the two lists
+    let spare = (term.rows as usize).saturating_sub(DASHBOARD_FIXED_ROWS + 1);
+    let key_rows = (spare / 3).max(1);
+    let voice_rows = spare.saturating_sub(key_rows).max(1);
 
-    if active_keys.is_empty() {
-        let _ = write!(out, " (None)\x1B[K\r\n");
+    let mut out = DashboardWriter::new(class, cache, term);
+    out.line(format_args!(
+        "Lattice Board Controller v0.1.0 | {}{}",
+        crate::profile::name(crate::profile::active()),
+        if crate::lock::is_locked() {
+            " [LOCKED]"
+        } else {
+            ""
+        }
+    ))
+    .await;
+    out.line(format_args!("-------------------------------"))
+        .await;
+    out.line(format_args!(
+        "Brightness: {:.2} | Red: {} | Hue rot: {:.0} | Release: {}ms | Mode: {:?} ({})",
+        b,
+        PITCH_CLASS_NAMES[transpose as usize % 12],
+        h,
+        release,
+        mode,
+        crate::compare::slot()
+    ))
+    .await;
+    let mut interval: heapless::String<40> = heapless::String::new();
+    let _ = write_interval(&mut interval);
+    out.line(format_args!(
+        "Fifth: {:.1}c | PBR: {:.1} | Frame: {}ms{} | Interval: {}",
+        size, pbr, frame, throttled, interval
+    ))
+    .await;
+    out.line(format_args!(
+        "RGB: Idx {} | R{} G{} B{}",
+        sel, rgb.r, rgb.g, rgb.b
+    ))
+    .await;
+    let mut landmarks: heapless::String<48> = heapless::String::new();
+    let _ = write_pitch_classes(&mut landmarks, cfg.landmarks);
+    out.line(format_args!(
+        "Landmarks: {} | Guides: {:?} | Octave gradient: {:.2}",
+        landmarks, cfg.guides, cfg.octave_gradient
+    ))
+    .await;
+    out.line(format_args!(
+        "Euclid: {} {}/{} | BPM: {:.0}",
+        if euclid.enabled { "On" } else { "Off" },
+        euclid.pulses,
+        euclid.steps,
+        bpm
+    ))
+    .await;
+    out.line(format_args!(
+        "Walk: {} Step {} | Scale: {} | Arp: {} {} {} {}% {} oct",
+        if walk.enabled { "On" } else { "Off" },
+        walk.step_size,
+        crate::walk::scale_name(walk.scale),
+        if arp.enabled { "On" } else { "Off" },
+        arp.pattern.name(),
+        crate::arp::rate_name(arp.rate),
+        arp.gate,
+        arp.octaves
+    ))
+    .await;
+    out.line(format_args!(
+        "Player: {} | {} events",
+        if playing { "Playing" } else { "Stopped" },
+        player_events
+    ))
+    .await;
+    match (
+        release_latency.percentile(50),
+        release_latency.percentile(99),
+    ) {
+        (Some(p50), Some(p99)) => {
+            out.line(format_args!(
+                "Release latency: p50 {}us | p99 {}us | max {}us ({} sent)",
+                p50,
+                p99,
+                release_latency.max_us(),
+                release_latency.count()
+            ))
+            .await
+        }
+        _ => {
+            out.line(format_args!("Release latency: nothing released yet"))
+                .await
+        }
+    }
+    out.line(format_args!(
+        "Overload: {} merged, {} dropped",
+        merged, dropped
+    ))
+    .await;
+    match (jitter.percentile(50), jitter.percentile(99)) {
+        (Some(p50), Some(p99)) => {
+            out.line(format_args!(
+                "Send jitter: p50 {}us | p99 {}us | max {}us ({} sent)",
+                p50,
+                p99,
+                jitter.max_us(),
+                jitter.count()
+            ))
+            .await
+        }
+        _ => {
+            out.line(format_args!("Send jitter: nothing sent yet"))
+                .await
+        }
+    }
+    match crate::stats::temperature_c() {
+        Some(temp) => {
+            let derate = crate::telemetry::derate(Some(temp), cfg.thermal_limit_c);
+            out.line(format_args!(
+                "Temp: {:.1}C | Limit: {}C | LED derate: {:.0}%",
+                temp,
+                cfg.thermal_limit_c,
+                derate * 100.0
+            ))
+            .await;
+        }
+        None => out.line(format_args!("Temp: --")).await,
+    }
+    let mut expansion: heapless::String<64> = heapless::String::new();
+    let _ = crate::expansion::write_summary(&mut expansion);
+    out.line(format_args!("Expansion: {}", expansion)).await;
+    let mut value: heapless::String<24> = heapless::String::new();
+    let _ = field.write_value(&mut value);
+    out.line(format_args!(
+        "> {}: {} (Up/Down select, Left/Right adjust, Enter toggle)",
+        field.label(),
+        value
+    ))
+    .await;
+    // Kept short so the dimming is never cut off before it's turned back off
+    let mut toast: heapless::String<48> = heapless::String::new();
+    if toasts.write(&mut toast) {
+        out.line(format_args!("\x1B[2m{}\x1B[22m", toast)).await;
     } else {
-        for k in active_keys {
-            let (octaves, fifths) = crate::tuning::calculate_fifths_offsets::<CurrentLayout>(k);
-            let _ = write!(out, "Oc:{} F:{} | ", octaves, fifths);
+        out.line(format_args!("{}", toast)).await;
+    }
+
+    out.line(format_args!("Held Keys:")).await;
+    let keys = active_keys.iter().map(|&k| {
+        let name = crate::spelling::key_name::<CurrentLayout>(k);
+        let cents = crate::tuning::cents_from_12edo::<CurrentLayout>(k);
+        let sent = crate::tuning::sent_note::<CurrentLayout>(k);
+        let mut entry = item(format_args!(
+            "{} {:+.0}c Ch{} N{}",
+            name,
+            cents,
+            crate::midi::channel_to_index(sent.channel) + 1,
+            sent.note
+        ));
+        if let Some(bend) = sent.pitch_bend {
+            let _ = write!(entry, " b{:+}", bend as i32 - 8192);
+        }
+        entry
+    });
+    out.list(keys, 0, key_rows).await;
+
+    out.line(format_args!("")).await;
+    out.line(format_args!("Remote MIDI:")).await;
+    let remote_voices = crate::midi::remote_voices();
+    let scroll = voice_scroll(remote_voices.len());
+    let voices = remote_voices.iter().map(|voice| {
+        item(format_args!(
+            "Ch{} N{}",
+            crate::midi::channel_to_index(voice.channel) + 1,
+            u8::from(voice.note)
+        ))
+    });
+    out.list(voices, scroll, voice_rows).await;
+
+    out.finish().await;
+}
+
+/// Dashboard page listing all 16 channels: what the MPE allocator has marked in use,
+/// and the key voiced on each. A channel marked in use without a voice has leaked.
+async fn draw_channels(
+    class: &mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>,
+    cache: &mut DashboardCache,
+    term: TerminalSize,
+) {
+    let mask = crate::tuning::mpe_usage_mask();
+    let voices = crate::tuning::active_voices();
+    let now = embassy_time::Instant::now();
+
+    let mut out = DashboardWriter::new(class, cache, term);
+    out.line(format_args!("MPE Channels (c: next page)")).await;
+    out.line(format_args!("-------------------------------"))
+        .await;
+    out.line(format_args!(
+        "Mode: {:?} | Usage mask: {:016b} | In use: {}/15",
+        crate::tuning::get_mode(),
+        mask,
+        mask.count_ones()
+    ))
+    .await;
+    out.line(format_args!("")).await;
+
+    for idx in 0..16u8 {
+        let allocated = mask & (1 << idx) != 0;
+        let voice = voices
+            .iter()
+            .find(|v| crate::midi::channel_to_index(v.channel) == idx as usize);
+        let state = match (idx, allocated, voice) {
+            (0, _, _) => "Master",
+            (_, true, Some(_)) => "Held",
+            (_, true, None) => "LEAKED",
+            (_, false, Some(_)) => "Unallocated",
+            (_, false, None) => "Free",
+        };
+        match voice {
+            Some(v) => {
+                out.line(format_args!(
+                    "Ch{:<2} {:<11} ({}, {}) {} N{} Bend {:+} Age {:.1}s",
+                    idx + 1,
+                    state,
+                    v.coord.x,
+                    v.coord.y,
+                    crate::spelling::key_name::<CurrentLayout>(v.coord),
+                    v.note,
+                    v.pitch_bend as i32 - 8192,
+                    (now - v.since).as_millis() as f32 / 1000.0
+                ))
+                .await;
+            }
+            None => out.line(format_args!("Ch{:<2} {}", idx + 1, state)).await,
+        }
+    }
+
+    out.finish().await;
+}
+
+/// Performance graphs, one column per period, newest on the right.
+async fn draw_stats(
+    class: &mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>,
+    cache: &mut DashboardCache,
+    term: TerminalSize,
+) {
+    use crate::stats::{Graph, GRAPH_LEN, GRAPH_PERIOD};
+
+    let mut out = DashboardWriter::new(class, cache, term);
+    out.line(format_args!("Performance (c: next page)")).await;
+    out.line(format_args!("-------------------------------"))
+        .await;
+    out.line(format_args!(
+        "Last {:.1}s, {}ms per column",
+        (GRAPH_PERIOD * GRAPH_LEN as u32).as_millis() as f32 / 1000.0,
+        GRAPH_PERIOD.as_millis()
+    ))
+    .await;
+    for graph in Graph::ALL {
+        let trace = crate::stats::graph(graph);
+        let mut line: heapless::String<GRAPH_LEN> = heapless::String::new();
+        let _ = trace.draw(graph.floor(), &mut line);
+        out.line(format_args!("")).await;
+        out.line(format_args!(
+            "{} (peak {}{})",
+            graph.name(),
+            trace.max(),
+            graph.unit()
+        ))
+        .await;
+        out.line(format_args!("|{}|", line)).await;
+    }
+
+    out.line(format_args!("")).await;
+    out.line(format_args!(
+        "Log lines dropped (console pipe full): {} | Bend corrections: {} | Repeats dropped: {}",
+        crate::stats::log_drops(),
+        crate::stats::drift_corrections(),
+        crate::stats::repeats_suppressed()
+    ))
+    .await;
+    match crate::stats::vsys() {
+        Some((volts, lowest)) => {
+            if cfg!(feature = "battery") {
+                out.line(format_args!(
+                    "VSYS: {:.2}V | Lowest: {:.2}V | Battery: {}",
+                    volts,
+                    lowest,
+                    crate::power::battery_level().name()
+                ))
+                .await
+            } else {
+                out.line(format_args!(
+                    "VSYS: {:.2}V | Lowest: {:.2}V | LED cap: {:.0}%",
+                    volts,
+                    lowest,
+                    crate::telemetry::supply_cap() * 100.0
+                ))
+                .await
+            }
+        }
+        None => {
+            out.line(format_args!("VSYS: not measured on this board"))
+                .await
         }
-        let _ = write!(out, "\x1B[K\r\n");
     }
+    let mut used: heapless::String<96> = heapless::String::new();
+    for (i, (index, presses)) in crate::wear::most_used::<5>().into_iter().enumerate() {
+        let sep = if i > 0 { " | " } else { "" };
+        let _ = write!(used, "{}{} {}", sep, MatrixKey(index), presses);
+    }
+    out.line(format_args!("Most used: {}", used)).await;
+    let mut climbing: heapless::String<96> = heapless::String::new();
+    for (i, index) in crate::wear::climbing::<6>().into_iter().enumerate() {
+        let sep = if i > 0 { ", " } else { "" };
+        let _ = write!(climbing, "{}{}", sep, MatrixKey(index));
+    }
+    if climbing.is_empty() {
+        let _ = climbing.write_str("none");
+    }
+    out.line(format_args!("Bouncing more than usual: {}", climbing))
+        .await;
+
+    out.finish().await;
+}
 
-    let _ = write!(out, "\r\nRemote MIDI:\x1B[K\r\n");
-    crate::midi::REMOTE_VOICES.lock(|v| {
-        for voice in v.borrow().iter() {
+/// The session's practice log.
+async fn draw_practice(
+    class: &mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>,
+    cache: &mut DashboardCache,
+    term: TerminalSize,
+) {
+    let (session, minutes) = crate::practice::session();
+    let mut classes: heapless::String<48> = heapless::String::new();
+    let _ = write_pitch_classes(&mut classes, session.pitch_classes);
+
+    let mut out = DashboardWriter::new(class, cache, term);
+    out.line(format_args!(
+        "Practice (c: main page, ^: new session, @: heatmap)"
+    ))
+    .await;
+    out.line(format_args!("-------------------------------"))
+        .await;
+    out.line(format_args!("Session: {} min", minutes)).await;
+    out.line(format_args!(
+        "Playing: {} min (pauses over {}s left out)",
+        session.playing_minutes(),
+        lattice_board_core::practice::BREAK_MS / 1000
+    ))
+    .await;
+    out.line(format_args!("Notes: {}", session.notes)).await;
+    out.line(format_args!(
+        "Pitch classes: {}/12 ({})",
+        session.pitch_classes.count_ones(),
+        classes
+    ))
+    .await;
+    // Notes per pitch class, six to a line
+    for half in [0..6, 6..12] {
+        let mut counts: heapless::String<64> = heapless::String::new();
+        for pc in half {
             let _ = write!(
-                out,
-                "Ch{} N{} | ",
-                crate::midi::channel_to_index(voice.channel) + 1,
-                u8::from(voice.note)
+                counts,
+                "{:>3}: {:<6}",
+                PITCH_CLASS_NAMES[pc], session.counts[pc]
             );
         }
-    });
-    let _ = write!(out, "\x1B[K\r\n");
+        out.line(format_args!("{}", counts.trim_end())).await;
+    }
+    out.finish().await;
+}
+
+/// A key by its matrix index, shown by name where it has one.
+struct MatrixKey(usize);
+
+impl core::fmt::Display for MatrixKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let cols = crate::layouts::cols().max(1);
+        let (row, col) = (self.0 / cols, self.0 % cols);
+        match CurrentLayout::key_to_coord(row, col) {
+            Some(coord) => write!(f, "{}", crate::spelling::key_name::<CurrentLayout>(coord)),
+            None => write!(f, "r{}c{}", row, col),
+        }
+    }
+}
+
+/// Lists the console keys and key combos, from the tables that handle them.
+async fn draw_help(
+    class: &mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>,
+    cache: &mut DashboardCache,
+    term: TerminalSize,
+) {
+    const COLUMN: usize = 32;
+    let mut out = DashboardWriter::new(class, cache, term);
+    out.line(format_args!("Help (?: back)")).await;
+    out.line(format_args!("-------------------------------"))
+        .await;
+    out.line(format_args!(
+        "Hold both corner keys {}s: performance lock | At power-up: hold them {}s to factory reset, or hold key 2-{} to pick a profile",
+        crate::lock::COMBO_HOLD.as_secs(),
+        crate::reset::BOOT_HOLD.as_secs(),
+        lattice_board_core::storage::PROFILES + 1
+    ))
+    .await;
+    out.line(format_args!("")).await;
+
+    let entries = || {
+        crate::commands::CONTROLS
+            .iter()
+            .map(|c| (c.keys, c.help))
+            .chain(crate::commands::COMMANDS.iter().map(|c| (c.keys, c.help)))
+    };
+    let columns = (term.cols as usize / COLUMN).max(1);
+    let rows = entries().count().div_ceil(columns);
+    // Column-major, so related keys read down a column
+    for row in 0..rows {
+        let mut line: heapless::String<{ crate::dashboard::LINE_LEN }> = heapless::String::new();
+        for (keys, help) in entries().skip(row).step_by(rows) {
+            let entry = crate::commands::help_entry(keys, help);
+            let _ = write!(line, "{:<width$}", entry, width = COLUMN);
+        }
+        out.line(format_args!("{}", line)).await;
+    }
+
+    out.finish().await;
+}
 
-    for chunk in out.as_bytes().chunks(64) {
+/// Writes the detected board's layout for host tools (configurator, simulator): a
+/// `layout` line with the board and key geometry, one `key` line per key, then `end`.
+async fn write_layout_dump(class: &mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>) {
+    let mut line: heapless::String<160> = heapless::String::new();
+    let _ = write!(
+        line,
+        "\r\nlayout board={:?} rows={} cols={} ",
+        crate::layouts::board(),
+        crate::layouts::rows(),
+        crate::layouts::cols()
+    );
+    let _ = match CurrentLayout::geometry() {
+        Some(geometry) => geometry.write_descriptor(&mut line),
+        None => line.write_str("shape=unknown"),
+    };
+    let _ = line.write_str("\r\n");
+    write_all(class, line.as_bytes()).await;
+
+    let matrix = crate::keys::matrix();
+    for row in 0..crate::layouts::rows() {
+        for col in 0..crate::layouts::cols() {
+            let Some(coord) = CurrentLayout::key_to_coord(row, col) else {
+                continue;
+            };
+            line.clear();
+            let _ = write!(
+                line,
+                "key row={} col={} x={} y={} midi={} name={}",
+                row,
+                col,
+                coord.x,
+                coord.y,
+                CurrentLayout::coord_to_midi(coord),
+                crate::spelling::key_name::<CurrentLayout>(coord)
+            );
+            if let Some(led) = CurrentLayout::coord_to_led(coord) {
+                let _ = write!(line, " led={}", led);
+            }
+            if let Some(pos) = CurrentLayout::physical_position(coord) {
+                let _ = write!(line, " x_mm={:.2} y_mm={:.2}", pos.x_mm, pos.y_mm);
+            }
+            let (bounce, bounce_max) = crate::keys::bounce_us(row, col);
+            let index = crate::keys::key_index(row, col);
+            let wear = crate::wear::lifetime(index);
+            let _ = write!(
+                line,
+                " bounce_us={} bounce_max_us={} presses={} bounces={} pressed={}",
+                bounce,
+                bounce_max,
+                wear.presses,
+                wear.bounces,
+                matrix.is_pressed(index) as u8
+            );
+            let _ = line.write_str("\r\n");
+            write_all(class, line.as_bytes()).await;
+        }
+    }
+    write_all(class, b"end\r\n").await;
+}
+
+async fn write_all(
+    class: &mut CdcAcmClass<'static, Driver<'static, peripherals::USB>>,
+    bytes: &[u8],
+) {
+    for chunk in bytes.chunks(64) {
         let _ = class.write_packet(chunk).await;
     }
 }
@@ -0,0 +1,592 @@
+use crate::midi::{channel_to_index, MidiEvent, MidiSender};
+use core::convert::Infallible;
+use embassy_executor::Spawner;
+use embassy_rp::clocks::ClockConfig;
+use embassy_rp::gpio::{Level, Pull};
+use embassy_rp::pac;
+use embassy_rp::peripherals::PIO1;
+use embassy_rp::pio::{Config, Direction, FifoJoin, Pin, Pio, PioPin, ShiftConfig, ShiftDirection};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use fixed::types::U24F8;
+use lattice_board_core::midi_stream::StreamParser;
+use lattice_board_core::usb_host::{
+    self, MidiIn, Pid, Symbols, CONFIGURATION, CONFIGURATION_LEN, DEVICE, J, K, MAX_DATA, SE0,
+};
+use lattice_board_core::usb_midi::payload;
+use log::{info, warn};
+use wmidi::MidiMessage;
+
+// Host port builds (the usb-host feature) run a second USB port in host mode on PIO1,
+// for a MIDI controller such as a fader box to plug straight into the board. Its CCs
+// are mapped to parameters like the host's (MIDI learn included), and the unmapped
+// ones are merged into the outgoing stream; its other messages are left out.
+//
+// The port runs at full speed only, with the system clock at 120 MHz so a bit is 10
+// PIO cycles. One state machine sends, another samples the reply, and the CPU codes
+// and decodes packets in between (see lattice_board_core::usb_host). Interrupts are
+// masked from each token to its handshake, as a device waits only a few bit times for
+// it; SOFs and decoding run with them on.
+
+/// Line states sent in one burst: a token, and the 8 bytes of a SETUP or OUT packet.
+const TX_WORDS: usize = 12;
+/// Bytes of line states (4 to a byte) a 64 byte packet can take, with bit stuffing.
+const SAMPLES: usize = 192;
+/// Idle bit times between the packets of a burst.
+const GAP_BITS: usize = 3;
+/// Raised by the sender once a burst is out: the receiver starts on 4, the CPU
+/// waits on 5.
+const TX_DONE: usize = 5;
+/// How long a device has to start its reply once the burst is out, and to finish it:
+/// a 64 byte packet with bit stuffing takes about 52us.
+const REPLY_TIMEOUT: Duration = Duration::from_micros(3);
+const PACKET_TIMEOUT: Duration = Duration::from_micros(60);
+/// A burst of [`TX_WORDS`] takes about 17us.
+const SEND_TIMEOUT: Duration = Duration::from_micros(25);
+
+const FRAME: Duration = Duration::from_millis(1);
+/// How long the bus is held in reset, then given to recover, before enumerating.
+const RESET: Duration = Duration::from_millis(20);
+const RESET_RECOVERY_FRAMES: usize = 20;
+/// How long a newly attached device's pull-up must hold before it's enumerated.
+const ATTACH_DEBOUNCE: Duration = Duration::from_millis(100);
+const LINE_POLL: Duration = Duration::from_millis(10);
+/// Transactions without a reply before the device is given up on.
+const TRIES: u8 = 3;
+/// NAKed frames before a control transfer is given up on.
+const NAK_LIMIT: u16 = 500;
+/// Address given to the device.
+const ADDRESS: u8 = 1;
+/// Configuration descriptors longer than this are read cut short.
+const CONFIGURATION_BUF: usize = 256;
+
+/// The clocks for a host port build: the system clock at 120 MHz, so a full-speed
+/// bit is a whole number of PIO cycles.
+pub fn config() -> embassy_rp::config::Config {
+    let mut clocks = ClockConfig::crystal(12_000_000);
+    if let Some(pll) = clocks.xosc.as_mut().and_then(|x| x.sys_pll.as_mut()) {
+        // 12 MHz * 120 / 6 / 2
+        pll.fbdiv = 120;
+    }
+    embassy_rp::config::Config::new(clocks)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Error {
+    /// No reply, or a damaged one, after [`TRIES`].
+    Timeout,
+    Stall,
+    /// A reply that doesn't fit the transaction.
+    Protocol,
+    /// The device has no USB-MIDI input.
+    NotMidi,
+    Detached,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Reply {
+    Ack,
+    Nak,
+    Stall,
+    /// A data packet of this many bytes.
+    Data(Pid, usize),
+    /// No reply, or a damaged one.
+    None,
+}
+
+struct Port {
+    pio: Pio<'static, PIO1>,
+    rx_config: Config<'static, PIO1>,
+    dp: Pin<'static, PIO1>,
+    dm: Pin<'static, PIO1>,
+    /// The host's handshake, sent as soon as a data packet ends.
+    ack: Symbols<2>,
+    /// How DATA0 and DATA1 packets start, to tell them apart before they end.
+    data_leads: [u32; 2],
+    ticker: Ticker,
+    frame: u16,
+    address: u8,
+    /// Packet size of the device's control endpoint.
+    max_packet0: u8,
+}
+
+/// Sets up the host port on PIO1 with its D+ and D- pins (consecutive, D+ first) and
+/// starts it.
+pub fn start(
+    spawner: Spawner,
+    mut pio: Pio<'static, PIO1>,
+    pins: (impl PioPin, impl PioPin),
+    sender: MidiSender,
+) {
+    let mut dp = pio.common.make_pio_pin(pins.0);
+    let mut dm = pio.common.make_pio_pin(pins.1);
+    // Stand-ins for the host's 15k pull-downs, so a free port reads SE0
+    dp.set_pull(Pull::Down);
+    dm.set_pull(Pull::Down);
+
+    // Sends the symbol count less one, then the symbols, 2 bits (D+, D-) each,
+    // 10 cycles apart; drives the bus only while sending
+    let tx_program = embassy_rp::pio::program::pio_asm!(
+        "pull block",
+        "mov x, osr",
+        "out null, 32",
+        "set pins, 0b01",
+        "set pindirs, 0b11",
+        "bit:",
+        "out pins, 2 [8]",
+        "jmp x-- bit",
+        "set pindirs, 0b00",
+        "irq nowait 4",
+        "irq nowait 5",
+    );
+    // From the first K after a burst, samples both lines every 10 cycles, in the
+    // middle of the bit: each change of D+ restarts the count, so the sampling
+    // follows the device's clock
+    let rx_program = embassy_rp::pio::program::pio_asm!(
+        "wait 1 irq 4",
+        "wait 0 pin 0 [1]",
+        "fall:",
+        "nop [1]",
+        "low_sample:",
+        "in pins, 2",
+        "set x, 2",
+        "low_poll:",
+        "jmp pin rise",
+        "jmp x-- low_poll",
+        "jmp pin rise",
+        "jmp low_sample",
+        "rise:",
+        "nop [2]",
+        "high_sample:",
+        "in pins, 2",
+        "set x, 2",
+        "high_poll:",
+        "jmp pin high_more",
+        "jmp fall",
+        "high_more:",
+        "jmp x-- high_poll",
+        "jmp pin high_sample [1]",
+        "jmp fall",
+    );
+    let tx_program = pio.common.load_program(&tx_program.program);
+    let rx_program = pio.common.load_program(&rx_program.program);
+
+    let mut tx_config = Config::default();
+    tx_config.use_program(&tx_program, &[]);
+    tx_config.set_out_pins(&[&dp, &dm]);
+    tx_config.set_set_pins(&[&dp, &dm]);
+    tx_config.clock_divider = U24F8::from_num(1);
+    tx_config.shift_out = ShiftConfig {
+        threshold: 32,
+        direction: ShiftDirection::Right,
+        auto_fill: true,
+    };
+    tx_config.fifo_join = FifoJoin::TxOnly;
+    pio.sm0.set_pin_dirs(Direction::In, &[&dp, &dm]);
+    pio.sm0.set_config(&tx_config);
+    pio.sm0.set_enable(true);
+
+    // Each byte of 4 samples lands in the top of its word, the first in the low bits
+    let mut rx_config = Config::default();
+    rx_config.use_program(&rx_program, &[]);
+    rx_config.set_in_pins(&[&dp, &dm]);
+    rx_config.set_jmp_pin(&dp);
+    rx_config.clock_divider = U24F8::from_num(1);
+    rx_config.shift_in = ShiftConfig {
+        threshold: 8,
+        direction: ShiftDirection::Right,
+        auto_fill: true,
+    };
+    rx_config.fifo_join = FifoJoin::RxOnly;
+
+    let mut ack = Symbols::new();
+    ack.packet(&[Pid::Ack.byte()]);
+    let port = Port {
+        pio,
+        rx_config,
+        dp,
+        dm,
+        ack,
+        data_leads: [Pid::Data0, Pid::Data1].map(usb_host::leading_states),
+        ticker: Ticker::every(FRAME),
+        frame: 0,
+        address: 0,
+        max_packet0: 8,
+    };
+    spawner.spawn(host_task(port, sender)).unwrap();
+}
+
+/// Waits for a controller, enumerates it and passes its MIDI on until it's unplugged.
+#[embassy_executor::task]
+async fn host_task(mut port: Port, sender: MidiSender) {
+    loop {
+        port.wait_attach().await;
+        match port.enumerate().await {
+            Ok(midi) => {
+                info!("Host port: MIDI controller attached");
+                let Err(error) = port.poll(midi, &sender).await;
+                info!("Host port: controller gone ({:?})", error);
+            }
+            Err(Error::NotMidi) => {
+                warn!("Host port: the device isn't a MIDI controller");
+                port.wait_detach().await;
+            }
+            Err(error) => {
+                warn!("Host port: enumeration failed ({:?}), retrying", error);
+                Timer::after(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// A USB-MIDI packet from the controller: CCs mapped to a parameter set it, and the
+/// others are merged into the outgoing stream.
+fn merge(packet: &[u8], parser: &mut StreamParser, sender: &MidiSender) {
+    let Some(bytes) = payload(packet) else {
+        return;
+    };
+    for message in bytes.iter().filter_map(|&b| parser.feed(b)) {
+        let Ok(MidiMessage::ControlChange(channel, control, value)) =
+            MidiMessage::try_from(message.as_bytes())
+        else {
+            continue;
+        };
+        let (control, value) = (u8::from(control), u8::from(value));
+        crate::power::note_activity();
+        if crate::cc_map::handle_controller(channel_to_index(channel) as u8, control, value) {
+            continue;
+        }
+        let event = MidiEvent::ControlChange {
+            channel,
+            control,
+            value,
+        };
+        if sender.try_send(event).is_err() {
+            warn!("Host port: MIDI channel full, dropping CC{}", control);
+        }
+    }
+}
+
+impl Port {
+    /// The bus state, from the pins' input levels.
+    fn line(&self) -> u8 {
+        let levels = pac::SIO.gpio_in(0).read();
+        let level = |pin: &Pin<'static, PIO1>| (levels >> pin.pin()) as u8 & 1;
+        level(&self.dp) | level(&self.dm) << 1
+    }
+
+    /// Waits for a full-speed device's pull-up on D+.
+    async fn wait_attach(&mut self) {
+        let mut warned = false;
+        loop {
+            match self.line() {
+                J => {
+                    Timer::after(ATTACH_DEBOUNCE).await;
+                    if self.line() == J {
+                        return;
+                    }
+                }
+                K if !warned => {
+                    warn!("Host port: low-speed devices aren't supported");
+                    warned = true;
+                }
+                SE0 => warned = false,
+                _ => {}
+            }
+            Timer::after(LINE_POLL).await;
+        }
+    }
+
+    async fn wait_detach(&mut self) {
+        while self.line() != SE0 {
+            Timer::after(LINE_POLL).await;
+        }
+    }
+
+    /// Holds the bus in reset, which leaves the device at address 0.
+    async fn reset(&mut self) {
+        let pins = [&self.dp, &self.dm];
+        self.pio.sm0.set_pins(Level::Low, &pins);
+        self.pio.sm0.set_pin_dirs(Direction::Out, &pins);
+        Timer::after(RESET).await;
+        self.pio.sm0.set_pin_dirs(Direction::In, &pins);
+        self.address = 0;
+        self.max_packet0 = 8;
+        self.ticker.reset();
+    }
+
+    /// Starts the next frame with its SOF, which also keeps the device from
+    /// suspending.
+    async fn frame(&mut self) -> Result<(), Error> {
+        self.ticker.next().await;
+        if self.line() == SE0 {
+            return Err(Error::Detached);
+        }
+        self.frame = (self.frame + 1) & 0x7FF;
+        let mut sof = Symbols::<TX_WORDS>::new();
+        sof.idle(GAP_BITS);
+        sof.packet(&usb_host::sof(self.frame));
+        // It fits the sender's FIFO and nothing replies, so interrupts can stay on
+        self.start_send(&sof);
+        self.wait_sent();
+        Ok(())
+    }
+
+    /// Starts sending a burst, the receiver starting on the first K after it. One
+    /// longer than the sender's FIFO (8 words) has to be sent with interrupts masked.
+    fn start_send<const W: usize>(&mut self, symbols: &Symbols<W>) {
+        let Some(count) = symbols.len().checked_sub(1) else {
+            return;
+        };
+        let rx = &mut self.pio.sm1;
+        rx.set_enable(false);
+        // Back to the start of its program
+        rx.set_config(&self.rx_config);
+        rx.restart();
+        rx.clear_fifos();
+        rx.set_enable(true);
+        self.pio.irq_flags.clear(TX_DONE);
+
+        let tx = self.pio.sm0.tx();
+        for word in core::iter::once(count as u32).chain(symbols.words().iter().copied()) {
+            while !tx.try_push(word) {}
+        }
+    }
+
+    /// Waits until the burst being sent is out.
+    fn wait_sent(&mut self) {
+        let deadline = Instant::now() + SEND_TIMEOUT;
+        while !self.pio.irq_flags.check(TX_DONE as u8) && Instant::now() < deadline {}
+        self.pio.irq_flags.clear(TX_DONE);
+    }
+
+    /// Samples the reply to the burst being sent, returning how many bytes of
+    /// `samples` it took and whether it's a data packet. One is acknowledged at once,
+    /// as the device gives up on the handshake after a few bit times; its CRC is only
+    /// checked after, by [`Self::reply`].
+    fn capture(&mut self, samples: &mut [u8; SAMPLES]) -> Option<(usize, bool)> {
+        self.wait_sent();
+        let mut len = 0;
+        let start = Instant::now();
+        let mut deadline = start + REPLY_TIMEOUT;
+        loop {
+            match self.pio.sm1.rx().try_pull() {
+                Some(word) => {
+                    let sample = samples.get_mut(len)?;
+                    *sample = (word >> 24) as u8;
+                    len += 1;
+                    if usb_host::has_se0(*sample) {
+                        break;
+                    }
+                    if len == 1 {
+                        deadline = start + PACKET_TIMEOUT;
+                    }
+                }
+                None if Instant::now() > deadline => return None,
+                None => {}
+            }
+        }
+        let lead = samples.first_chunk().map(|&lead| u32::from_le_bytes(lead));
+        let is_data = lead.is_some_and(|lead| self.data_leads.contains(&lead));
+        if is_data {
+            // It fits the sender's FIFO; the caller waits for it to go out
+            let ack = self.ack;
+            self.start_send(&ack);
+        }
+        Some((len, is_data))
+    }
+
+    /// Reads a captured reply; a damaged data packet is dropped, though the device
+    /// has moved on.
+    fn reply(samples: &[u8], is_data: bool, data: &mut [u8; MAX_DATA]) -> Reply {
+        let mut packet = [0u8; 1 + MAX_DATA + 2];
+        let Some(n) = usb_host::decode(samples, &mut packet) else {
+            return Reply::None;
+        };
+        match (packet.first().and_then(|&pid| Pid::from_byte(pid)), n) {
+            (Some(Pid::Ack), 1) => Reply::Ack,
+            (Some(Pid::Nak), 1) => Reply::Nak,
+            (Some(Pid::Stall), 1) => Reply::Stall,
+            (Some(pid @ (Pid::Data0 | Pid::Data1)), 3..)
+                if is_data && usb_host::crc16_ok(&packet[1..n]) =>
+            {
+                data[..n - 3].copy_from_slice(&packet[1..n - 2]);
+                Reply::Data(pid, n - 3)
+            }
+            _ => Reply::None,
+        }
+    }
+
+    /// One transaction in the current frame: the token, the data of a SETUP or OUT,
+    /// and the reply. Interrupts are masked from the burst to the handshake only: a
+    /// few microseconds for a NAK, up to about 75 for a full 64 byte packet.
+    fn transaction(
+        &mut self,
+        pid: Pid,
+        endpoint: u8,
+        out: Option<(Pid, &[u8])>,
+        data: &mut [u8; MAX_DATA],
+    ) -> Reply {
+        let mut symbols = Symbols::<TX_WORDS>::new();
+        symbols.idle(GAP_BITS);
+        symbols.packet(&usb_host::token(pid, self.address, endpoint));
+        if let Some((data_pid, payload)) = out {
+            symbols.idle(GAP_BITS);
+            if !symbols.data(data_pid, payload) {
+                return Reply::None;
+            }
+        }
+        let mut samples = [0u8; SAMPLES];
+        let captured = critical_section::with(|_| {
+            self.start_send(&symbols);
+            self.capture(&mut samples)
+        });
+        self.wait_sent();
+        match captured {
+            Some((len, is_data)) => Self::reply(&samples[..len], is_data, data),
+            None => Reply::None,
+        }
+    }
+
+    /// A transaction, one a frame until it gets a reply other than a NAK.
+    async fn retry(
+        &mut self,
+        pid: Pid,
+        endpoint: u8,
+        out: Option<(Pid, &[u8])>,
+        data: &mut [u8; MAX_DATA],
+    ) -> Result<Reply, Error> {
+        let mut tries = 0;
+        let mut naks = 0;
+        loop {
+            self.frame().await?;
+            match self.transaction(pid, endpoint, out, data) {
+                Reply::None => {
+                    tries += 1;
+                    if tries == TRIES {
+                        return Err(Error::Timeout);
+                    }
+                }
+                Reply::Nak => {
+                    naks += 1;
+                    if naks == NAK_LIMIT {
+                        return Err(Error::Timeout);
+                    }
+                }
+                Reply::Stall => return Err(Error::Stall),
+                reply => return Ok(reply),
+            }
+        }
+    }
+
+    /// A control transfer on endpoint 0: the SETUP, the data stage of a request that
+    /// reads into `data`, and the status stage. Returns the bytes read.
+    async fn control(&mut self, request: [u8; 8], data: &mut [u8]) -> Result<usize, Error> {
+        let mut packet = [0u8; MAX_DATA];
+        let setup = Some((Pid::Data0, &request[..]));
+        if self.retry(Pid::Setup, 0, setup, &mut packet).await? != Reply::Ack {
+            return Err(Error::Protocol);
+        }
+        if request[0] & 0x80 == 0 {
+            // Status: an empty IN
+            return match self.retry(Pid::In, 0, None, &mut packet).await? {
+                Reply::Data(_, 0) => Ok(0),
+                _ => Err(Error::Protocol),
+            };
+        }
+
+        let length = (u16::from_le_bytes([request[6], request[7]]) as usize).min(data.len());
+        let mut read = 0;
+        let mut toggle = Pid::Data1;
+        while read < length {
+            let Reply::Data(pid, n) = self.retry(Pid::In, 0, None, &mut packet).await? else {
+                return Err(Error::Protocol);
+            };
+            // A resend of a packet already taken
+            if pid != toggle {
+                continue;
+            }
+            toggle = toggle.toggled();
+            let taken = n.min(length - read);
+            data[read..read + taken].copy_from_slice(&packet[..taken]);
+            read += taken;
+            if n < self.max_packet0 as usize {
+                break;
+            }
+        }
+        // Status: an empty OUT
+        match self
+            .retry(Pid::Out, 0, Some((Pid::Data1, &[])), &mut packet)
+            .await?
+        {
+            Reply::Ack => Ok(read),
+            _ => Err(Error::Protocol),
+        }
+    }
+
+    /// Resets the device, addresses it and sets up its configuration, returning its
+    /// MIDI input.
+    async fn enumerate(&mut self) -> Result<MidiIn, Error> {
+        self.reset().await;
+        for _ in 0..RESET_RECOVERY_FRAMES {
+            self.frame().await?;
+        }
+
+        let mut device = [0u8; 8];
+        let n = self
+            .control(usb_host::get_descriptor(DEVICE, 8), &mut device)
+            .await?;
+        self.max_packet0 = usb_host::control_packet_size(&device[..n]).ok_or(Error::Protocol)?;
+        self.control(usb_host::set_address(ADDRESS), &mut [])
+            .await?;
+        self.address = ADDRESS;
+        // The device has 2 ms to take up its address
+        for _ in 0..2 {
+            self.frame().await?;
+        }
+
+        let mut descriptors = [0u8; CONFIGURATION_BUF];
+        let request = usb_host::get_descriptor(CONFIGURATION, CONFIGURATION_LEN as u16);
+        let n = self
+            .control(request, &mut descriptors[..CONFIGURATION_LEN])
+            .await?;
+        let (total, value) = usb_host::configuration(&descriptors[..n]).ok_or(Error::Protocol)?;
+        let total = (total as usize).min(CONFIGURATION_BUF);
+        let request = usb_host::get_descriptor(CONFIGURATION, total as u16);
+        let n = self.control(request, &mut descriptors[..total]).await?;
+        let midi = usb_host::find_midi_in(&descriptors[..n]).ok_or(Error::NotMidi)?;
+        self.control(usb_host::set_configuration(value), &mut [])
+            .await?;
+        Ok(midi)
+    }
+
+    /// Reads the controller's MIDI every frame, until it stops answering.
+    async fn poll(&mut self, midi: MidiIn, sender: &MidiSender) -> Result<Infallible, Error> {
+        let mut packet = [0u8; MAX_DATA];
+        let mut toggle = Pid::Data0;
+        let mut parser = StreamParser::new();
+        let mut tries = 0;
+        loop {
+            self.frame().await?;
+            match self.transaction(Pid::In, midi.endpoint, None, &mut packet) {
+                Reply::Data(pid, n) => {
+                    tries = 0;
+                    if pid == toggle {
+                        toggle = toggle.toggled();
+                        for chunk in packet[..n].chunks_exact(4) {
+                            merge(chunk, &mut parser, sender);
+                        }
+                    }
+                }
+                Reply::Nak => tries = 0,
+                Reply::Stall => return Err(Error::Stall),
+                Reply::Ack => return Err(Error::Protocol),
+                Reply::None => {
+                    tries += 1;
+                    if tries == TRIES {
+                        return Err(Error::Timeout);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,31 @@
-use embassy_rp::flash::Blocking;
-use embassy_rp::flash::Flash;
-use embassy_rp::peripherals::FLASH;
-use heapless::String;
+use crate::storage;
+use embassy_rp::flash::Error;
+use heapless::{String, Vec};
+use lattice_board_core::banks::{self, BankState, HEADER_LEN};
+use lattice_board_core::recording::{decode_slot, encode_slot, MACRO_LEN, MACRO_SLOTS, SLOT_SIZE};
+use lattice_board_core::storage::{
+    macros_banks, macros_sector, preset_banks, Partition, SECTOR_SIZE,
+};
+use log::{error, info, warn};
 
-pub fn read_unique_id(flash: FLASH) -> String<32> {
-    let mut flash = Flash::<_, Blocking, { 2 * 1024 * 1024 }>::new_blocking(flash);
-    let mut uid = [0u8; 8];
-    flash.blocking_unique_id(&mut uid).unwrap();
+/// Settings that must survive reflashing alternate between two banks (see [`banks`]).
+const SETTINGS_BANKS: [Partition; 2] = [Partition::Settings0, Partition::Settings1];
+/// Largest settings payload.
+const SETTINGS_LEN: usize = 16;
+/// Where the CC map starts in the macros sector, after the macro slots.
+const CC_MAP_AT: usize = MACRO_SLOTS * SLOT_SIZE;
+/// Room for the CC map.
+pub const CC_MAP_LEN: usize = 64;
+/// Macro slots and the CC map, laid out as the macros sector held them before they
+/// were banked.
+const MACROS_LEN: usize = CC_MAP_AT + CC_MAP_LEN;
+/// Largest preset payload.
+pub const PRESET_LEN: usize = 512;
+/// Board ID record written to the last sector before settings were banked.
+const LEGACY_BOARD_ID_MAGIC: [u8; 4] = *b"LBID";
+
+pub fn read_unique_id() -> String<32> {
+    let uid = storage::unique_id().unwrap();
 
     let mut hex_uid = String::new();
     for &b in &uid {
@@ -15,3 +34,345 @@ pub fn read_unique_id(flash: FLASH) -> String<32> {
     }
     hex_uid
 }
+
+type SettingsBank = [u8; HEADER_LEN + SETTINGS_LEN];
+
+fn read_settings_banks() -> [SettingsBank; 2] {
+    let mut banks = [[0xFF; HEADER_LEN + SETTINGS_LEN]; 2];
+    for (bank, partition) in banks.iter_mut().zip(SETTINGS_BANKS) {
+        if storage::read(partition, 0, bank).is_err() {
+            // Read as corrupt rather than erased
+            bank[0] = 0;
+        }
+    }
+    banks
+}
+
+/// What the settings banks hold. Fields added later go at the end, read as 0 from
+/// records saved before them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Settings {
+    /// Board ID for boards without strap pins; 0 for none.
+    pub board_id: u8,
+    /// Profile used at boot unless another is picked.
+    pub profile: u8,
+    /// Key debounce hold in us.
+    pub debounce_us: u16,
+    /// Column settle time in us; 0 for the default.
+    pub settle_us: u8,
+    /// Profile always loaded at boot, from 1; 0 for the one used last.
+    pub boot_profile: u8,
+    /// Startup steps turned off (see [`crate::boot`]).
+    pub boot_skip: u8,
+}
+
+const _: () = assert!(Settings::LEN <= SETTINGS_LEN);
+
+impl Settings {
+    const LEN: usize = 7;
+
+    fn encode(self) -> [u8; Self::LEN] {
+        let [debounce_lo, debounce_hi] = self.debounce_us.to_le_bytes();
+        [
+            self.board_id,
+            self.profile,
+            debounce_lo,
+            debounce_hi,
+            self.settle_us,
+            self.boot_profile,
+            self.boot_skip,
+        ]
+    }
+
+    fn decode(payload: &[u8]) -> Self {
+        let field = |i: usize| payload.get(i).copied().unwrap_or(0);
+        Self {
+            board_id: field(0),
+            profile: field(1),
+            debounce_us: u16::from_le_bytes([field(2), field(3)]),
+            settle_us: field(4),
+            boot_profile: field(5),
+            boot_skip: field(6),
+        }
+    }
+}
+
+/// Settings from the newest valid bank, or the defaults (always, in safe mode).
+pub fn stored_settings() -> Settings {
+    if crate::reset::is_safe_mode() {
+        return Settings::default();
+    }
+    let stored = read_settings_banks();
+    match banks::read([&stored[0], &stored[1]]) {
+        Some(payload) => Settings::decode(payload),
+        None => {
+            let legacy = &stored[0];
+            Settings {
+                board_id: if legacy[..4] == LEGACY_BOARD_ID_MAGIC {
+                    legacy[4]
+                } else {
+                    0
+                },
+                ..Settings::default()
+            }
+        }
+    }
+}
+
+/// Board ID saved with [`store_board_id`], if any.
+pub fn stored_board_id() -> Option<u8> {
+    let id = stored_settings().board_id;
+    (id != 0).then_some(id)
+}
+
+/// Saves the board ID used at the next boot when no strap pins are fitted; 0 clears it.
+pub fn store_board_id(id: u8) {
+    let settings = Settings {
+        board_id: id,
+        ..stored_settings()
+    };
+    match store_settings(&settings.encode()) {
+        Ok(()) => info!("Stored board ID {}, applies after restart", id),
+        Err(e) => error!("Storing board ID failed: {:?}", e),
+    }
+}
+
+/// Saves the profile used at the next boot.
+pub fn store_profile(profile: u8) {
+    let settings = Settings {
+        profile,
+        ..stored_settings()
+    };
+    if let Err(e) = store_settings(&settings.encode()) {
+        error!("Storing profile failed: {:?}", e);
+    }
+}
+
+/// Saves the key scan timings. False if they weren't written.
+pub fn store_key_timing(debounce_us: u16, settle_us: u8) -> bool {
+    if crate::reset::is_safe_mode() {
+        warn!("Safe mode: key timing not saved");
+        return false;
+    }
+    let settings = Settings {
+        debounce_us,
+        settle_us,
+        ..stored_settings()
+    };
+    match store_settings(&settings.encode()) {
+        Ok(()) => true,
+        Err(e) => {
+            error!("Storing key timing failed: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Saves what happens at boot.
+pub fn store_boot(boot_profile: u8, boot_skip: u8) {
+    let settings = Settings {
+        boot_profile,
+        boot_skip,
+        ..stored_settings()
+    };
+    if let Err(e) = store_settings(&settings.encode()) {
+        error!("Storing boot settings failed: {:?}", e);
+    }
+}
+
+/// Writes `payload` to the settings bank not holding the newest record.
+fn store_settings(payload: &[u8; Settings::LEN]) -> Result<(), Error> {
+    if crate::reset::is_safe_mode() {
+        warn!("Safe mode: settings not saved");
+        return Ok(());
+    }
+    let stored = read_settings_banks();
+    let (bank, seq) = banks::next_write(stored.map(|b| banks::check(&b)));
+    let mut record: SettingsBank = [0xFF; HEADER_LEN + SETTINGS_LEN];
+    let len = banks::encode(seq, payload, &mut record).ok_or(Error::OutOfBounds)?;
+    let partition = SETTINGS_BANKS[bank];
+    storage::erase(partition, 0)?;
+    storage::write(partition, 0, &record[..len])
+}
+
+/// Logs the state of both settings banks (`config verify`). Saves alternate between
+/// the banks, so each has been erased about half as many times as there were saves.
+pub fn log_settings_health() {
+    let stored = read_settings_banks();
+    let states = stored.map(|b| banks::check(&b));
+    let newest = banks::newest(states);
+    for (i, state) in states.iter().enumerate() {
+        let current = if newest == Some(i) { " (current)" } else { "" };
+        match state {
+            BankState::Erased => info!("Settings bank {}: erased", i),
+            BankState::Corrupt => error!("Settings bank {}: corrupt", i),
+            BankState::Valid { seq, len } => {
+                info!(
+                    "Settings bank {}: save {}, {} bytes{}",
+                    i, seq, len, current
+                )
+            }
+        }
+    }
+    let saves = newest.map_or(0, |i| match states[i] {
+        BankState::Valid { seq, .. } => seq,
+        _ => 0,
+    });
+    info!(
+        "{} settings saves, about {} erase cycles per bank",
+        saves,
+        saves.div_ceil(2)
+    );
+}
+
+type PresetBank = [u8; HEADER_LEN + PRESET_LEN];
+
+/// The active profile's preset alternates between the first two of its preset
+/// sectors, as the settings do between their banks.
+fn preset_sectors() -> [usize; 2] {
+    preset_banks(crate::profile::active() as usize)
+}
+
+fn read_preset_banks() -> [PresetBank; 2] {
+    let mut banks = [[0xFF; HEADER_LEN + PRESET_LEN]; 2];
+    for (bank, sector) in banks.iter_mut().zip(preset_sectors()) {
+        if storage::read(Partition::Presets, sector * SECTOR_SIZE, bank).is_err() {
+            bank[0] = 0;
+        }
+    }
+    banks
+}
+
+/// Reads the active profile's preset into `out`, returning its length.
+pub fn stored_preset(out: &mut [u8; PRESET_LEN]) -> Option<usize> {
+    if crate::reset::is_safe_mode() {
+        return None;
+    }
+    let stored = read_preset_banks();
+    let payload = banks::read([&stored[0], &stored[1]])?;
+    out[..payload.len()].copy_from_slice(payload);
+    Some(payload.len())
+}
+
+/// Saves the active profile's preset. False if it wasn't written.
+pub fn store_preset(payload: &[u8]) -> bool {
+    if crate::reset::is_safe_mode() {
+        warn!("Safe mode: preset not saved");
+        return false;
+    }
+    let stored = read_preset_banks();
+    let (bank, seq) = banks::next_write(stored.map(|b| banks::check(&b)));
+    let mut record: PresetBank = [0xFF; HEADER_LEN + PRESET_LEN];
+    let Some(len) = banks::encode(seq, payload, &mut record) else {
+        error!("Preset of {} bytes doesn't fit", payload.len());
+        return false;
+    };
+    let sector = preset_sectors()[bank];
+    let stored = storage::erase(Partition::Presets, sector)
+        .and_then(|()| storage::write(Partition::Presets, sector * SECTOR_SIZE, &record[..len]));
+    if let Err(e) = stored {
+        error!("Storing preset failed: {:?}", e);
+        return false;
+    }
+    true
+}
+
+/// Macro saved in `slot` with [`store_macro`], if any.
+pub fn stored_macro(slot: usize) -> Option<Vec<u8, MACRO_LEN>> {
+    if slot >= MACRO_SLOTS {
+        return None;
+    }
+    let macros = stored_macros(&read_macros_banks()).ok()?;
+    let record = macros[slot * SLOT_SIZE..(slot + 1) * SLOT_SIZE]
+        .try_into()
+        .ok()?;
+    Vec::from_slice(decode_slot(record)?).ok()
+}
+
+/// Saves a macro in `slot`, keeping the other slots.
+pub fn store_macro(slot: usize, keys: &[u8]) {
+    if slot >= MACRO_SLOTS {
+        return;
+    }
+    match patch_macros(slot * SLOT_SIZE, &encode_slot(keys)) {
+        Ok(()) => info!("Stored macro {} ({} keys)", slot + 1, keys.len()),
+        Err(e) => error!("Storing macro failed: {:?}", e),
+    }
+}
+
+/// Reads the CC map saved with [`store_cc_map`] into `out`.
+pub fn stored_cc_map(out: &mut [u8; CC_MAP_LEN]) -> bool {
+    if crate::reset::is_safe_mode() {
+        return false;
+    }
+    match stored_macros(&read_macros_banks()) {
+        Ok(macros) => {
+            out.copy_from_slice(&macros[CC_MAP_AT..]);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Saves the CC map, keeping the macros.
+pub fn store_cc_map(map: &[u8]) {
+    if map.len() > CC_MAP_LEN {
+        return;
+    }
+    if crate::reset::is_safe_mode() {
+        warn!("Safe mode: CC map not saved");
+        return;
+    }
+    if let Err(e) = patch_macros(CC_MAP_AT, map) {
+        error!("Storing CC map failed: {:?}", e);
+    }
+}
+
+type MacrosBank = [u8; HEADER_LEN + MACROS_LEN];
+
+/// The active profile's macros and CC map alternate between the two preset sectors
+/// after its preset banks, as the settings do between their banks.
+fn macros_sectors() -> [usize; 2] {
+    macros_banks(crate::profile::active() as usize)
+}
+
+fn read_macros_banks() -> [MacrosBank; 2] {
+    let mut banks = [[0xFF; HEADER_LEN + MACROS_LEN]; 2];
+    for (bank, sector) in banks.iter_mut().zip(macros_sectors()) {
+        if storage::read(Partition::Presets, sector * SECTOR_SIZE, bank).is_err() {
+            bank[0] = 0;
+        }
+    }
+    banks
+}
+
+/// The macros and CC map in the newest of `stored`, or else in the sector they were
+/// kept in before they were banked.
+fn stored_macros(stored: &[MacrosBank; 2]) -> Result<[u8; MACROS_LEN], Error> {
+    let mut macros = [0xFF; MACROS_LEN];
+    match banks::read([&stored[0], &stored[1]]) {
+        Some(payload) => macros[..payload.len()].copy_from_slice(payload),
+        None => {
+            let (partition, sector) = macros_sector(crate::profile::active() as usize);
+            storage::read(partition, sector * SECTOR_SIZE, &mut macros)?;
+        }
+    }
+    Ok(macros)
+}
+
+/// Rewrites `bytes` at `at` in the active profile's macros and CC map, keeping the
+/// rest, into the macros bank not holding the newest record.
+fn patch_macros(at: usize, bytes: &[u8]) -> Result<(), Error> {
+    let stored = read_macros_banks();
+    let mut macros = stored_macros(&stored)?;
+    macros
+        .get_mut(at..at + bytes.len())
+        .ok_or(Error::OutOfBounds)?
+        .copy_from_slice(bytes);
+    let (bank, seq) = banks::next_write(stored.map(|b| banks::check(&b)));
+    let mut record: MacrosBank = [0xFF; HEADER_LEN + MACROS_LEN];
+    let len = banks::encode(seq, &macros, &mut record).ok_or(Error::OutOfBounds)?;
+    let sector = macros_sectors()[bank];
+    storage::erase(Partition::Presets, sector)?;
+    storage::write(Partition::Presets, sector * SECTOR_SIZE, &record[..len])
+}
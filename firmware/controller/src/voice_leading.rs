@@ -0,0 +1,90 @@
+use crate::keys::ACTIVE_KEYS;
+use crate::layouts::{cols, rows, CurrentLayout};
+use crate::tuning::get_key_pitch;
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use heapless::Vec;
+use lattice_board_core::harmony::{nearest_move, suggestions};
+use lattice_board_core::layout::Coordinate;
+use log::info;
+
+/// Keys lit as suggestions at most.
+const MAX_SUGGESTED: usize = 32;
+
+/// Voice-leading assistant: lights the keys the held notes could move to for the
+/// chords of the walk scale they lead to most smoothly.
+static ENABLED: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+static SUGGESTED: Mutex<CriticalSectionRawMutex, RefCell<Vec<Coordinate, MAX_SUGGESTED>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+pub fn is_enabled() -> bool {
+    ENABLED.lock(|e| e.get())
+}
+
+pub fn toggle() {
+    let enabled = ENABLED.lock(|e| {
+        e.set(!e.get());
+        e.get()
+    });
+    if !enabled {
+        SUGGESTED.lock(|s| s.borrow_mut().clear());
+    }
+    info!("Voice leading {}", if enabled { "on" } else { "off" });
+}
+
+/// Keys to light as suggestions.
+pub fn suggested() -> Vec<Coordinate, MAX_SUGGESTED> {
+    SUGGESTED.lock(|s| s.borrow().clone())
+}
+
+/// Pitch class of the 12-EDO note nearest the key.
+fn pitch_class(coord: Coordinate) -> u8 {
+    let semitones = (get_key_pitch::<CurrentLayout>(coord) / 100.0 + 0.5) as i32;
+    semitones.rem_euclid(12) as u8
+}
+
+/// Redoes the suggestions whenever the held keys change, off the LED task, which
+/// only draws them.
+#[embassy_executor::task]
+pub async fn voice_leading_task() {
+    let mut keys_rx = ACTIVE_KEYS.receiver().unwrap();
+    loop {
+        let keys = keys_rx.changed().await;
+        let mut found = Vec::new();
+        if is_enabled() {
+            let held = keys
+                .iter()
+                .fold(0u16, |mask, &c| mask | 1 << pitch_class(c));
+            for chord in suggestions(held, crate::walk::scale_mask())
+                .into_iter()
+                .flatten()
+            {
+                // Each held key's nearest move onto the chord, as the nearest keys
+                for &key in keys.iter() {
+                    let step = nearest_move(pitch_class(key), chord.mask());
+                    if step == 0 {
+                        continue;
+                    }
+                    let target = get_key_pitch::<CurrentLayout>(key) + step as f32 * 100.0;
+                    let closest = crate::tuning::find_closest_keys::<CurrentLayout>(
+                        target,
+                        50.0,
+                        rows(),
+                        cols(),
+                        None,
+                    );
+                    for c in closest {
+                        if !found.contains(&c) {
+                            let _ = found.push(c);
+                        }
+                    }
+                }
+                // Each chord searches the board; let other tasks in between
+                embassy_futures::yield_now().await;
+            }
+        }
+        SUGGESTED.lock(|s| *s.borrow_mut() = found);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::clock;
+use crate::layouts::{cols, rows, CurrentLayout};
+use crate::midi::{MidiSender, ToU7};
+use crate::tuning::{get_key_pitch, get_midi_event};
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Instant, Timer};
+use heapless::Vec;
+use lattice_board_core::layout::{Coordinate, Layout};
+use lattice_board_core::rng::Rng;
+use log::info;
+
+/// Number of recent walk positions kept lit on the board.
+pub const TRAIL_LEN: usize = 8;
+
+const MAX_STEP_SIZE: u8 = 3;
+
+/// Pitch-class masks the walk is constrained to (bit 0 = C).
+pub const SCALES: [(&str, u16); 4] = [
+    ("Chromatic", 0xFFF),
+    ("Major", 0xAB5),
+    ("Pentatonic", 0x295),
+    ("Whole tone", 0x555),
+];
+
+/// "Drunk walk" generator settings.
+/// Each clock step moves up to `step_size` lattice steps in x and y from the
+/// previous position, restarting from the most recently pressed key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WalkConfig {
+    pub enabled: bool,
+    pub step_size: u8,
+    pub scale: usize,
+}
+
+static WALK_CONFIG: Mutex<CriticalSectionRawMutex, Cell<WalkConfig>> =
+    Mutex::new(Cell::new(WalkConfig {
+        enabled: false,
+        step_size: 1,
+        scale: 0,
+    }));
+
+static TRAIL: Mutex<CriticalSectionRawMutex, RefCell<Vec<Coordinate, TRAIL_LEN>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+pub fn get_config() -> WalkConfig {
+    WALK_CONFIG.lock(|c| c.get())
+}
+
+pub fn toggle() {
+    let enabled = WALK_CONFIG.lock(|c| {
+        let mut cfg = c.get();
+        cfg.enabled = !cfg.enabled;
+        c.set(cfg);
+        cfg.enabled
+    });
+    info!("Walk {}", if enabled { "on" } else { "off" });
+}
+
+pub fn adjust_step_size(delta: i8) {
+    WALK_CONFIG.lock(|c| {
+        let mut cfg = c.get();
+        cfg.step_size = (cfg.step_size as i8 + delta).clamp(1, MAX_STEP_SIZE as i8) as u8;
+        c.set(cfg);
+    });
+}
+
+pub fn cycle_scale(delta: i8) {
+    WALK_CONFIG.lock(|c| {
+        let mut cfg = c.get();
+        cfg.scale = (cfg.scale as i32 + delta as i32).rem_euclid(SCALES.len() as i32) as usize;
+        c.set(cfg);
+    });
+}
+
+pub fn scale_name(scale: usize) -> &'static str {
+    SCALES[scale % SCALES.len()].0
+}
+
+/// Pitch classes of the selected scale.
+pub fn scale_mask() -> u16 {
+    SCALES[get_config().scale % SCALES.len()].1
+}
+
+/// Recent walk positions, oldest first.
+pub fn trail() -> Vec<Coordinate, TRAIL_LEN> {
+    TRAIL.lock(|t| t.borrow().clone())
+}
+
+fn is_on_board(coord: Coordinate) -> bool {
+    (0..rows()).any(|r| (0..cols()).any(|c| CurrentLayout::key_to_coord(r, c) == Some(coord)))
+}
+
+fn in_scale(coord: Coordinate, mask: u16) -> bool {
+    let semitones = (get_key_pitch::<CurrentLayout>(coord) / 100.0 + 0.5) as i32;
+    mask & (1 << semitones.rem_euclid(12)) != 0
+}
+
+/// Picks a random neighbour within `step_size` that is on the board and in the scale.
+/// Gives up after a few attempts so a sparse mask can't stall the task.
+fn choose_step(rng: &mut Rng, from: Coordinate, cfg: WalkConfig) -> Option<Coordinate> {
+    let mask = SCALES[cfg.scale % SCALES.len()].1;
+    for _ in 0..16 {
+        let dx = rng.span(cfg.step_size as i8);
+        let dy = rng.span(cfg.step_size as i8);
+        if dx == 0 && dy == 0 {
+            continue;
+        }
+        let coord = Coordinate {
+            x: from.x.saturating_add(dx),
+            y: from.y.saturating_add(dy),
+        };
+        if is_on_board(coord) && in_scale(coord, mask) {
+            return Some(coord);
+        }
+    }
+    None
+}
+
+#[embassy_executor::task]
+pub async fn walk_task(sender: MidiSender) {
+    let mut rng = Rng::new(Instant::now().as_ticks() as u32);
+    let mut position = CurrentLayout::center_coord();
+    let mut anchor: Option<Coordinate> = None;
+    let mut next = Instant::now();
+
+    loop {
+        next += clock::step_duration();
+        Timer::at(next).await;
+
+        let cfg = get_config();
+        if !cfg.enabled {
+            TRAIL.lock(|t| t.borrow_mut().clear());
+            next = Instant::now();
+            continue;
+        }
+
+        // Restart from the most recently pressed key
+        let last_pressed = crate::keys::active_keys().last().copied();
+        if last_pressed != anchor {
+            anchor = last_pressed;
+            if let Some(coord) = last_pressed {
+                position = coord;
+            }
+        }
+
+        let Some(coord) = choose_step(&mut rng, position, cfg) else {
+            continue;
+        };
+        position = coord;
+
+        TRAIL.lock(|t| {
+            let mut trail = t.borrow_mut();
+            if trail.is_full() {
+                trail.remove(0);
+            }
+            let _ = trail.push(coord);
+        });
+
+        if let Some(event) = get_midi_event::<CurrentLayout>(coord, 100.to_u7(), true) {
+            sender.send(event).await;
+            // Gate is half a step
+            Timer::after(clock::step_duration() / 2).await;
+            if let Some(event) = get_midi_event::<CurrentLayout>(coord, 0.to_u7(), false) {
+                sender.send(event).await;
+            }
+        }
+    }
+}
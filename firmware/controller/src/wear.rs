@@ -0,0 +1,161 @@
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Timer};
+use heapless::Vec;
+use lattice_board_core::banks::{self, HEADER_LEN};
+use lattice_board_core::storage::{Partition, SECTOR_SIZE};
+use lattice_board_core::wear::{self, KeyWear, WEAR_KEYS, WEAR_LEN};
+use log::{error, info};
+
+/// Edges closer together than this are one burst of bounce, in us: a press this soon
+/// after the key's release is chatter rather than playing.
+pub const CHATTER_US: u64 = 5_000;
+/// Counts are written out at most this often, since each save erases a sector.
+const SAVE_PERIOD: Duration = Duration::from_secs(600);
+/// How often a due save checks whether the board has gone quiet.
+const IDLE_POLL: Duration = Duration::from_secs(1);
+
+/// Counts stored before this boot.
+static STORED: Mutex<CriticalSectionRawMutex, RefCell<[KeyWear; WEAR_KEYS]>> =
+    Mutex::new(RefCell::new([KeyWear::ZERO; WEAR_KEYS]));
+/// Counts since boot.
+static SESSION: Mutex<CriticalSectionRawMutex, RefCell<[KeyWear; WEAR_KEYS]>> =
+    Mutex::new(RefCell::new([KeyWear::ZERO; WEAR_KEYS]));
+static UNSAVED: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+type WearBank = [u8; HEADER_LEN + WEAR_LEN];
+
+fn read_banks() -> [WearBank; 2] {
+    let mut banks = [[0xFF; HEADER_LEN + WEAR_LEN]; 2];
+    for (i, bank) in banks.iter_mut().enumerate() {
+        if crate::storage::read(Partition::KeyWear, i * SECTOR_SIZE, bank).is_err() {
+            bank[0] = 0;
+        }
+    }
+    banks
+}
+
+/// Reads the stored counts. Call once at boot.
+pub fn load() {
+    let stored = read_banks();
+    let counts =
+        banks::read([&stored[0], &stored[1]]).map_or([KeyWear::ZERO; WEAR_KEYS], wear::decode);
+    STORED.lock(|s| *s.borrow_mut() = counts);
+}
+
+/// Counts a press of the key at matrix `index`.
+pub fn record_press(index: usize) {
+    record(
+        index,
+        KeyWear {
+            presses: 1,
+            bounces: 0,
+        },
+    );
+}
+
+/// Counts a press of the key at matrix `index` that bounced or chattered.
+pub fn record_bounce(index: usize) {
+    record(
+        index,
+        KeyWear {
+            presses: 0,
+            bounces: 1,
+        },
+    );
+}
+
+fn record(index: usize, wear: KeyWear) {
+    SESSION.lock(|s| {
+        if let Some(key) = s.borrow_mut().get_mut(index) {
+            *key = key.plus(wear);
+        }
+    });
+    UNSAVED.lock(|u| u.set(true));
+}
+
+/// Counts of the key at matrix `index` over its life, this session included.
+pub fn lifetime(index: usize) -> KeyWear {
+    let stored = STORED.lock(|s| s.borrow().get(index).copied().unwrap_or_default());
+    let session = SESSION.lock(|s| s.borrow().get(index).copied().unwrap_or_default());
+    stored.plus(session)
+}
+
+/// Matrix indexes of the `N` keys pressed most over their life, most first.
+pub fn most_used<const N: usize>() -> Vec<(usize, u32), N> {
+    let mut top: Vec<(usize, u32), N> = Vec::new();
+    for index in 0..WEAR_KEYS {
+        let presses = lifetime(index).presses;
+        if presses == 0 {
+            continue;
+        }
+        let at = top
+            .iter()
+            .position(|&(_, p)| presses > p)
+            .unwrap_or(top.len());
+        if at < N {
+            if top.is_full() {
+                top.pop();
+            }
+            let _ = top.insert(at, (index, presses));
+        }
+    }
+    top
+}
+
+/// Matrix indexes of keys bouncing more this session than they used to.
+pub fn climbing<const N: usize>() -> Vec<usize, N> {
+    let stored = STORED.lock(|s| *s.borrow());
+    let session = SESSION.lock(|s| *s.borrow());
+    stored
+        .iter()
+        .zip(session.iter())
+        .enumerate()
+        .filter(|(_, (&before, &now))| wear::bounce_climbing(before, now))
+        .map(|(i, _)| i)
+        .take(N)
+        .collect()
+}
+
+fn save() {
+    let mut counts = STORED.lock(|s| *s.borrow());
+    SESSION.lock(|s| {
+        for (key, session) in counts.iter_mut().zip(s.borrow().iter()) {
+            *key = key.plus(*session);
+        }
+    });
+    let mut payload = [0u8; WEAR_LEN];
+    wear::encode(&counts, &mut payload);
+
+    let stored = read_banks();
+    let (bank, seq) = banks::next_write(stored.map(|b| banks::check(&b)));
+    let mut record: WearBank = [0xFF; HEADER_LEN + WEAR_LEN];
+    let len = banks::encode(seq, &payload, &mut record).expect("wear record too long");
+    let result = crate::storage::erase(Partition::KeyWear, bank).and_then(|()| {
+        crate::storage::write(Partition::KeyWear, bank * SECTOR_SIZE, &record[..len])
+    });
+    match result {
+        Ok(()) => {
+            UNSAVED.lock(|u| u.set(false));
+            info!("Saved key wear counts");
+        }
+        Err(e) => error!("Saving key wear counts failed: {:?}", e),
+    }
+}
+
+/// Writes new counts out every [`SAVE_PERIOD`], once no keys are down: an erase
+/// stalls the board for a moment.
+#[embassy_executor::task]
+pub async fn wear_task() {
+    loop {
+        Timer::after(SAVE_PERIOD).await;
+        if !UNSAVED.lock(|u| u.get()) {
+            continue;
+        }
+        while !crate::keys::matrix().is_empty() || crate::midi::is_busy() {
+            Timer::after(IDLE_POLL).await;
+        }
+        save();
+    }
+}
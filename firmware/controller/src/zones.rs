@@ -0,0 +1,462 @@
+use crate::layouts::CurrentLayout;
+use crate::midi::{MidiEvent, MidiSender, ToU7};
+use crate::tuning::{get_key_pitch, get_mpe_pbr};
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use heapless::Vec;
+use lattice_board_core::layout::{Coordinate, Layout};
+use lattice_board_core::matrix::MATRIX_KEYS;
+use lattice_board_core::tuning::mpe_note;
+use lattice_board_core::zones::{drum_note, Rect, StoredChord, Zone, ZoneKind, MAX_CHORD, PADS};
+use log::{error, info, warn};
+use wmidi::{Channel, Note, U7};
+
+/// The zone of the active preset.
+static ZONE: Mutex<CriticalSectionRawMutex, RefCell<Zone>> =
+    Mutex::new(RefCell::new(Zone::new(Coordinate { x: 0, y: 0 })));
+/// Pads whose chords are sounding, one bit per pad.
+static SOUNDING: Mutex<CriticalSectionRawMutex, Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// A note a pad's chord is sounding.
+#[derive(Clone, Copy)]
+struct PadNote {
+    pad: u8,
+    channel: Channel,
+    note: Note,
+    /// On an MPE channel of its own, given back with the note.
+    mpe: bool,
+}
+
+/// Notes of the sounding pads. Pads play their chords apart from the keys, which
+/// may be held as well and sound the same notes.
+static PAD_NOTES: Mutex<CriticalSectionRawMutex, RefCell<Vec<PadNote, { PADS * MAX_CHORD }>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+/// Bend key steps, in divisions of the octave (72 for twelfth tones).
+pub const BEND_DIVISIONS: [u8; 5] = [24, 31, 48, 72, 96];
+/// Index into [`BEND_DIVISIONS`]; 72 unless set.
+pub const DEFAULT_BEND_DIVISIONS: u8 = 3;
+static BEND_DIVISION: Mutex<CriticalSectionRawMutex, Cell<u8>> =
+    Mutex::new(Cell::new(DEFAULT_BEND_DIVISIONS));
+/// An MPE voice, by its key and channel.
+type Voice = (Coordinate, Channel);
+/// The voice each bend key moved, to move back when it's released.
+static BENT: Mutex<CriticalSectionRawMutex, Cell<[Option<Voice>; 2]>> =
+    Mutex::new(Cell::new([None; 2]));
+
+/// The General MIDI percussion channel, which drum pads play on.
+const DRUM_CHANNEL: Channel = Channel::Ch10;
+
+/// Velocities drum pads can play at, whatever the key reports.
+pub const DRUM_VELOCITIES: [u8; 4] = [40, 70, 100, 127];
+/// Index into [`DRUM_VELOCITIES`]; 100 unless set.
+pub const DEFAULT_DRUM_VELOCITY: u8 = 2;
+static DRUM_VELOCITY: Mutex<CriticalSectionRawMutex, Cell<u8>> =
+    Mutex::new(Cell::new(DEFAULT_DRUM_VELOCITY));
+
+pub fn zone() -> Zone {
+    ZONE.lock(|z| *z.borrow())
+}
+
+/// Replaces the zone, or sets the default one (see [`default_rect`]). A zone whose
+/// corners aren't all keys of this board is moved there too, keeping its pads.
+pub fn set(zone: Option<Zone>) {
+    let keys = board_keys();
+    let zone = match zone {
+        Some(zone) if corners_are_keys(zone.rect, &keys) => zone,
+        Some(zone) => {
+            warn!("Zone: stored rectangle doesn't fit this board, using the default");
+            Zone {
+                rect: default_rect(&keys),
+                ..zone
+            }
+        }
+        None => Zone {
+            rect: default_rect(&keys),
+            ..Zone::new(Coordinate { x: 0, y: 0 })
+        },
+    };
+    release_all();
+    ZONE.lock(|z| *z.borrow_mut() = zone);
+    reserve_drum_channel(zone.kind);
+}
+
+/// Every key of the board.
+fn board_keys() -> Vec<Coordinate, MATRIX_KEYS> {
+    crate::keys::matrix_keys().take(MATRIX_KEYS).collect()
+}
+
+/// Whether a key is at each corner of `rect`, so it can be picked out and played to
+/// its edges.
+fn corners_are_keys(rect: Rect, keys: &[Coordinate]) -> bool {
+    rect.corners().iter().all(|corner| keys.contains(corner))
+}
+
+/// The largest square zone, from 4 by 4 down, with a key at each corner, as near the
+/// board's lowest corner as it fits.
+fn default_rect(keys: &[Coordinate]) -> Rect {
+    (1..=4)
+        .rev()
+        .find_map(|size| {
+            keys.iter()
+                .map(|&min| Rect {
+                    min,
+                    width: size,
+                    height: size,
+                })
+                .filter(|&rect| corners_are_keys(rect, keys))
+                .min_by_key(|rect| (rect.min.y, rect.min.x))
+        })
+        .unwrap_or(Zone::new(Coordinate { x: 0, y: 0 }).rect)
+}
+
+/// Moves the zone to the rectangle between the two held keys at its opposite
+/// corners, keeping its kind and pads. Refused unless exactly two keys are held, a key
+/// is at each corner, the rectangle has at most [`PADS`] keys and no pad is sounding.
+pub fn select_rect() {
+    let held = crate::keys::pressed_keys();
+    let &[a, b] = held.as_slice() else {
+        info!("Zone: hold the keys at two opposite corners");
+        return;
+    };
+    let rect = Rect::spanning(a, b);
+    if rect.pads() > PADS {
+        info!("Zone: {}x{} is over {} keys", rect.width, rect.height, PADS);
+    } else if !corners_are_keys(rect, &board_keys()) {
+        info!("Zone: every corner has to be a key");
+    } else if SOUNDING.lock(|s| s.get()) != 0 {
+        info!("Zone: release the pads first");
+    } else {
+        ZONE.lock(|z| z.borrow_mut().rect = rect);
+        crate::preset::store();
+        info!(
+            "Zone: {}x{} from ({}, {})",
+            rect.width, rect.height, rect.min.x, rect.min.y
+        );
+    }
+}
+
+/// Keeps MPE notes off the percussion channel while drum pads play on it.
+fn reserve_drum_channel(kind: ZoneKind) {
+    crate::tuning::reserve_mpe_channel(DRUM_CHANNEL, kind == ZoneKind::Drums);
+}
+
+pub fn cycle_kind(delta: i8) {
+    let kind = ZONE.lock(|z| {
+        let mut zone = z.borrow_mut();
+        let i = (zone.kind as i32 + delta as i32).rem_euclid(ZoneKind::ALL.len() as i32);
+        zone.kind = ZoneKind::ALL[i as usize];
+        zone.kind
+    });
+    reserve_drum_channel(kind);
+    crate::preset::store();
+}
+
+pub fn drum_velocity() -> u8 {
+    DRUM_VELOCITIES[DRUM_VELOCITY.lock(|v| v.get()) as usize % DRUM_VELOCITIES.len()]
+}
+
+/// Index of the drum velocity, as stored in presets.
+pub fn drum_velocity_index() -> u8 {
+    DRUM_VELOCITY.lock(|v| v.get())
+}
+
+pub fn set_drum_velocity_index(index: u8) {
+    if (index as usize) < DRUM_VELOCITIES.len() {
+        DRUM_VELOCITY.lock(|v| v.set(index));
+    }
+}
+
+pub fn cycle_drum_velocity(delta: i8) {
+    DRUM_VELOCITY.lock(|v| {
+        let len = DRUM_VELOCITIES.len() as i32;
+        v.set((v.get() as i32 + delta as i32).rem_euclid(len) as u8);
+    });
+    crate::preset::store();
+}
+
+pub fn bend_divisions() -> u8 {
+    BEND_DIVISIONS[BEND_DIVISION.lock(|d| d.get()) as usize % BEND_DIVISIONS.len()]
+}
+
+/// Index of the bend step, as stored in presets.
+pub fn bend_divisions_index() -> u8 {
+    BEND_DIVISION.lock(|d| d.get())
+}
+
+pub fn set_bend_divisions_index(index: u8) {
+    if (index as usize) < BEND_DIVISIONS.len() {
+        BEND_DIVISION.lock(|d| d.set(index));
+    }
+}
+
+pub fn cycle_bend_divisions(delta: i8) {
+    BEND_DIVISION.lock(|d| {
+        let len = BEND_DIVISIONS.len() as i32;
+        d.set((d.get() as i32 + delta as i32).rem_euclid(len) as u8);
+    });
+    crate::preset::store();
+}
+
+/// Keys whose notes the sounding pads are playing, for the LEDs.
+pub fn sounding_keys() -> Vec<Coordinate, { PADS * MAX_CHORD }> {
+    let zone = zone();
+    let sounding = SOUNDING.lock(|s| s.get());
+    let mut keys = Vec::new();
+    for (_, chord) in zone
+        .chords
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| sounding & 1 << i != 0)
+    {
+        for &key in chord.keys() {
+            let _ = keys.push(key);
+        }
+    }
+    keys
+}
+
+/// Handles a key of the zone. Returns `None` for keys outside it, which play as usual,
+/// otherwise whether the key's events could be queued.
+pub fn key_changed<L: Layout>(
+    coord: Coordinate,
+    is_pressed: bool,
+    velocity: U7,
+    sender: &MidiSender,
+) -> Option<bool> {
+    let zone = zone();
+    let pad = zone.pad(coord)?;
+    crate::power::note_activity();
+    Some(match zone.kind {
+        ZoneKind::Off => true,
+        ZoneKind::ChordPads => chord_pad::<L>(&zone, pad, is_pressed, velocity, sender),
+        ZoneKind::Drums => drum_pad(pad, is_pressed, sender),
+        ZoneKind::BendKeys => bend_key::<L>(pad, is_pressed, sender),
+        ZoneKind::TremoloKey => {
+            crate::tremolo::set_held(is_pressed);
+            true
+        }
+    })
+}
+
+/// Bends the newest MPE voice by a step while key `pad` is held: down for the first,
+/// up for the second.
+fn bend_key<L: Layout>(pad: usize, is_pressed: bool, sender: &MidiSender) -> bool {
+    let step = 1200.0 / bend_divisions() as f32;
+    let (voice, cents) = if is_pressed {
+        let voice = crate::tuning::latest_voice();
+        BENT.lock(|b| {
+            let mut bent = b.get();
+            bent[pad] = voice;
+            b.set(bent);
+        });
+        (voice, if pad == 0 { -step } else { step })
+    } else {
+        let voice = BENT.lock(|b| {
+            let mut bent = b.get();
+            let voice = bent[pad].take();
+            b.set(bent);
+            voice
+        });
+        (voice, if pad == 0 { step } else { -step })
+    };
+    let Some((coord, channel)) = voice else {
+        return true;
+    };
+    match crate::tuning::inflect_voice::<L>(coord, channel, cents) {
+        Some(value) => sender
+            .try_send(MidiEvent::PitchBendChange { channel, value })
+            .is_ok(),
+        None => true,
+    }
+}
+
+/// Plays the drum of `pad` on the General MIDI percussion channel.
+fn drum_pad(pad: usize, is_pressed: bool, sender: &MidiSender) -> bool {
+    let Ok(note) = Note::try_from(drum_note(pad)) else {
+        return true;
+    };
+    let channel = DRUM_CHANNEL;
+    let queued = if is_pressed {
+        let velocity = drum_velocity().to_u7();
+        sender
+            .try_send(MidiEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+            })
+            .is_ok()
+    } else {
+        let velocity = 0.to_u7();
+        crate::midi::try_send_release(MidiEvent::NoteOff {
+            channel,
+            note,
+            velocity,
+        })
+        .or_else(|event| sender.try_send(event))
+        .is_ok()
+    };
+    if !queued {
+        error!("MIDI Channel Full! Dropping Event");
+    }
+    queued
+}
+
+/// Plays or releases the chord of `pad`. Pressing a pad while holding two or more
+/// other keys captures them into it instead.
+///
+/// Pads send their notes themselves rather than pressing the chord's keys, so a key
+/// held while its pad sounds (or the other way round) keeps its note until both let
+/// go of it.
+fn chord_pad<L: Layout>(
+    zone: &Zone,
+    pad: usize,
+    is_pressed: bool,
+    velocity: U7,
+    sender: &MidiSender,
+) -> bool {
+    let bit = 1 << pad;
+    if !is_pressed {
+        let was_sounding = SOUNDING.lock(|s| {
+            let sounding = s.get();
+            s.set(sounding & !bit);
+            sounding & bit != 0
+        });
+        return !was_sounding || release_pad::<L>(Some(pad), velocity, sender);
+    }
+    let held = crate::keys::active_keys();
+    if held.len() >= 2 && !crate::lock::is_locked() {
+        ZONE.lock(|z| z.borrow_mut().chords[pad] = StoredChord::from_keys(&held));
+        crate::preset::store();
+        info!(
+            "Captured {} keys into pad {}",
+            held.len().min(MAX_CHORD),
+            pad + 1
+        );
+        return true;
+    }
+    SOUNDING.lock(|s| s.set(s.get() | bit));
+    let mut queued = true;
+    for &key in zone.chords[pad].keys() {
+        queued &= pad_note_on::<L>(pad, key, velocity, sender);
+    }
+    if !queued {
+        error!("MIDI Channel Full! Dropping Event");
+    }
+    queued
+}
+
+/// Starts the note of `key` for `pad`: on an MPE channel of its own when keys are
+/// voiced that way, otherwise on the channel and note the key itself plays.
+fn pad_note_on<L: Layout>(pad: usize, key: Coordinate, velocity: U7, sender: &MidiSender) -> bool {
+    let (event, sounding) = if crate::tuning::uses_mpe() {
+        // Out of channels, the note doesn't sound, as for a key
+        let Some(channel) = crate::tuning::claim_mpe_channel() else {
+            return true;
+        };
+        let (note, pitch_bend) = mpe_note(get_key_pitch::<L>(key), get_mpe_pbr());
+        let Ok(note) = Note::try_from(note) else {
+            crate::tuning::release_mpe_channel(channel);
+            return true;
+        };
+        let event = MidiEvent::MpeNoteOn {
+            channel,
+            note,
+            velocity,
+            pitch_bend,
+        };
+        (event, (channel, note, true))
+    } else {
+        let (channel, note) = crate::tuning::fixed_note::<L>(key);
+        let Ok(note) = Note::try_from(note) else {
+            return true;
+        };
+        let event = MidiEvent::NoteOn {
+            channel,
+            note,
+            velocity,
+        };
+        (event, (channel, note, false))
+    };
+    let (channel, note, mpe) = sounding;
+    if sender.try_send(event).is_err() {
+        if mpe {
+            crate::tuning::release_mpe_channel(channel);
+        }
+        return false;
+    }
+    crate::tuning::note_played::<L>(key);
+    PAD_NOTES.lock(|n| {
+        // Can't fail: a pad sounds at most once, with at most MAX_CHORD notes
+        let _ = n.borrow_mut().push(PadNote {
+            pad: pad as u8,
+            channel,
+            note,
+            mpe,
+        });
+    });
+    true
+}
+
+/// Ends the notes of `pad`, or of every pad, except for ones a held key or another
+/// pad still sounds. Returns false if a release had to be dropped.
+fn release_pad<L: Layout>(pad: Option<usize>, velocity: U7, sender: &MidiSender) -> bool {
+    let mine = |n: &PadNote| pad.is_none_or(|pad| n.pad as usize == pad);
+    let released = PAD_NOTES.lock(|n| {
+        let mut notes = n.borrow_mut();
+        let released: Vec<PadNote, { PADS * MAX_CHORD }> =
+            notes.iter().copied().filter(mine).collect();
+        notes.retain(|n| !mine(n));
+        released
+    });
+    let mut queued = true;
+    for PadNote {
+        channel, note, mpe, ..
+    } in released
+    {
+        if mpe {
+            crate::tuning::release_mpe_channel(channel);
+        } else if holds_note(channel, note) || key_holds_note::<L>(channel, note) {
+            continue;
+        }
+        queued &= crate::midi::try_send_release(MidiEvent::NoteOff {
+            channel,
+            note,
+            velocity,
+        })
+        .or_else(|event| sender.try_send(event))
+        .is_ok();
+    }
+    if !queued {
+        error!("MIDI Channel Full! Dropping Event");
+    }
+    queued
+}
+
+/// Ends every pad's notes, before the zone changes under them.
+fn release_all() {
+    SOUNDING.lock(|s| s.set(0));
+    if let Some(sender) = crate::keys::sender() {
+        release_pad::<CurrentLayout>(None, 0.to_u7(), &sender);
+    }
+}
+
+/// Whether a sounding pad plays `note` on `channel`, outside MPE, so a key sending the
+/// same note mustn't end it.
+pub fn holds_note(channel: Channel, note: Note) -> bool {
+    PAD_NOTES.lock(|n| {
+        n.borrow()
+            .iter()
+            .any(|n| !n.mpe && n.channel == channel && n.note == note)
+    })
+}
+
+/// Whether a held key, outside MPE, is sounding `note` on `channel`. Keys of the glide
+/// strip play on a channel of their own.
+fn key_holds_note<L: Layout>(channel: Channel, note: Note) -> bool {
+    crate::keys::active_keys()
+        .into_iter()
+        .filter(|&key| !crate::glide::on_strip::<L>(key))
+        .any(|key| crate::tuning::fixed_note::<L>(key) == (channel, note as u8))
+}
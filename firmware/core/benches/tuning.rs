@@ -0,0 +1,82 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lattice_board_core::layout::{Coordinate, Layout};
+use lattice_board_core::pitch::{Pitch, PitchClass};
+use lattice_board_core::tuning::{closest_keys, fifths_offsets, key_pitch_cents};
+
+const ROWS: usize = 5;
+const COLS: usize = 25;
+
+/// Plain 5x25 grid, the size of the full board.
+struct Grid;
+
+impl Layout for Grid {
+    fn key_to_coord(row: usize, col: usize) -> Option<Coordinate> {
+        Some(Coordinate {
+            x: col as i8,
+            y: row as i8,
+        })
+    }
+
+    fn led_to_coord(_idx: usize) -> Option<Coordinate> {
+        None
+    }
+
+    fn coord_to_led(_coord: Coordinate) -> Option<usize> {
+        None
+    }
+
+    fn center_coord() -> Coordinate {
+        Coordinate { x: 12, y: 2 }
+    }
+}
+
+fn all_keys() -> impl Iterator<Item = Coordinate> {
+    (0..ROWS).flat_map(|r| (0..COLS).filter_map(move |c| Grid::key_to_coord(r, c)))
+}
+
+fn bench_fifths_offsets(c: &mut Criterion) {
+    c.bench_function("fifths_offsets (full board)", |b| {
+        b.iter(|| {
+            for coord in all_keys() {
+                black_box(fifths_offsets::<Grid>(black_box(coord)));
+            }
+        })
+    });
+}
+
+fn bench_closest_keys(c: &mut Criterion) {
+    c.bench_function("closest_keys (local key)", |b| {
+        b.iter(|| closest_keys::<Grid>(black_box(6700.0), 200.0, ROWS, COLS, None, 697.0))
+    });
+    c.bench_function("closest_keys (biased remote voice)", |b| {
+        b.iter(|| closest_keys::<Grid>(black_box(6650.0), 200.0, ROWS, COLS, Some(67), 697.0))
+    });
+}
+
+fn bench_pitch_conversion(c: &mut Criterion) {
+    c.bench_function("key_pitch_cents (full board)", |b| {
+        b.iter(|| {
+            for coord in all_keys() {
+                black_box(key_pitch_cents::<Grid>(black_box(coord), 697.0));
+            }
+        })
+    });
+    c.bench_function("Pitch::from_midi + to_f32", |b| {
+        b.iter(|| {
+            for note in 0..128u8 {
+                black_box(Pitch::from_midi(black_box(note)).to_f32());
+            }
+        })
+    });
+    c.bench_function("PitchClass::from_f32", |b| {
+        b.iter(|| PitchClass::from_f32(black_box(7.02)))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_fifths_offsets,
+    bench_closest_keys,
+    bench_pitch_conversion
+);
+criterion_main!(benches);
@@ -0,0 +1,97 @@
+//! Arpeggiator patterns: the order held notes are played in, one at a time. Notes are
+//! numbered from the lowest up, repeated an octave higher for each extra octave of the
+//! range, so with three notes over two octaves 3 is the lowest note an octave up.
+
+use crate::rng::Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArpPattern {
+    Up,
+    Down,
+    /// Up then back down, the top and bottom notes played once each time.
+    UpDown,
+    Random,
+}
+
+impl ArpPattern {
+    pub const ALL: [ArpPattern; 4] = [
+        ArpPattern::Up,
+        ArpPattern::Down,
+        ArpPattern::UpDown,
+        ArpPattern::Random,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArpPattern::Up => "Up",
+            ArpPattern::Down => "Down",
+            ArpPattern::UpDown => "Up-down",
+            ArpPattern::Random => "Random",
+        }
+    }
+}
+
+/// Where an arpeggio has got to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Arpeggio {
+    step: usize,
+}
+
+impl Arpeggio {
+    pub const fn new() -> Self {
+        Self { step: 0 }
+    }
+
+    /// Starts again from the beginning of the pattern.
+    pub fn restart(&mut self) {
+        self.step = 0;
+    }
+
+    /// The next note to play out of `notes`, or `None` if there are none. Notes can
+    /// come and go between calls; the pattern carries on from where it was.
+    pub fn next(&mut self, pattern: ArpPattern, notes: usize, rng: &mut Rng) -> Option<usize> {
+        if notes == 0 {
+            return None;
+        }
+        let step = self.step;
+        self.step = self.step.wrapping_add(1);
+        Some(match pattern {
+            ArpPattern::Up => step % notes,
+            ArpPattern::Down => notes - 1 - step % notes,
+            ArpPattern::UpDown if notes == 1 => 0,
+            ArpPattern::UpDown => {
+                let at = step % (2 * (notes - 1));
+                if at < notes {
+                    at
+                } else {
+                    2 * (notes - 1) - at
+                }
+            }
+            ArpPattern::Random => rng.below(notes as u32) as usize,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(pattern: ArpPattern, notes: usize, count: usize) -> Vec<usize> {
+        let mut arp = Arpeggio::new();
+        let mut rng = Rng::new(1);
+        (0..count)
+            .map(|_| arp.next(pattern, notes, &mut rng).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!(play(ArpPattern::Up, 3, 5), [0, 1, 2, 0, 1]);
+        assert_eq!(play(ArpPattern::Down, 3, 5), [2, 1, 0, 2, 1]);
+        assert_eq!(play(ArpPattern::UpDown, 4, 8), [0, 1, 2, 3, 2, 1, 0, 1]);
+        assert_eq!(play(ArpPattern::UpDown, 1, 3), [0, 0, 0]);
+        assert!(play(ArpPattern::Random, 3, 20).iter().all(|&n| n < 3));
+        let mut arp = Arpeggio::new();
+        assert_eq!(arp.next(ArpPattern::Up, 0, &mut Rng::new(1)), None);
+    }
+}
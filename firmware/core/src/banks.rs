@@ -0,0 +1,176 @@
+//! Settings records written alternately to two flash banks.
+//!
+//! Each save goes to the bank not holding the newest record, with a sequence number
+//! one higher and a CRC32 over the record, so a save cut short by power loss leaves
+//! the previous record intact in the other bank.
+//!
+//! Record layout: magic `LBCF`, sequence number (u32 LE), payload length (u16 LE),
+//! two reserved 0xFF bytes, CRC32 (u32 LE) over the sequence number, length and
+//! payload, then the payload.
+
+/// Bytes before the payload.
+pub const HEADER_LEN: usize = 16;
+
+const MAGIC: [u8; 4] = *b"LBCF";
+
+/// CRC-32 (IEEE 802.3, as used by zip and Ethernet).
+pub fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn record_crc(header: &[u8], payload: &[u8]) -> u32 {
+    !crc32_update(crc32_update(!0, &header[4..10]), payload)
+}
+
+/// Writes a record of `payload` with sequence number `seq` into `out`, returning its
+/// length, or `None` if `out` is too small.
+pub fn encode(seq: u32, payload: &[u8], out: &mut [u8]) -> Option<usize> {
+    let len = HEADER_LEN + payload.len();
+    let record = out.get_mut(..len)?;
+    record[..4].copy_from_slice(&MAGIC);
+    record[4..8].copy_from_slice(&seq.to_le_bytes());
+    record[8..10].copy_from_slice(&u16::try_from(payload.len()).ok()?.to_le_bytes());
+    record[10..12].copy_from_slice(&[0xFF, 0xFF]);
+    let crc = record_crc(record, payload);
+    record[12..16].copy_from_slice(&crc.to_le_bytes());
+    record[HEADER_LEN..].copy_from_slice(payload);
+    Some(len)
+}
+
+/// What a bank holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BankState {
+    /// Never written since the last erase.
+    Erased,
+    /// A record with a bad header or CRC, e.g. from an interrupted save.
+    Corrupt,
+    Valid {
+        seq: u32,
+        len: usize,
+    },
+}
+
+/// Checks the record at the start of `bank`, which must hold at least the header and
+/// as much of the payload as a record can have.
+pub fn check(bank: &[u8]) -> BankState {
+    let Some(header) = bank.get(..HEADER_LEN) else {
+        return BankState::Corrupt;
+    };
+    if header.iter().all(|&b| b == 0xFF) {
+        return BankState::Erased;
+    }
+    let field = |range: core::ops::Range<usize>| {
+        let mut bytes = [0u8; 4];
+        bytes[..range.len()].copy_from_slice(&header[range]);
+        u32::from_le_bytes(bytes)
+    };
+    let len = field(8..10) as usize;
+    match bank.get(HEADER_LEN..HEADER_LEN + len) {
+        Some(payload) if header[..4] == MAGIC && record_crc(header, payload) == field(12..16) => {
+            BankState::Valid {
+                seq: field(4..8),
+                len,
+            }
+        }
+        _ => BankState::Corrupt,
+    }
+}
+
+/// The bank holding the newest valid record, if any.
+pub fn newest(banks: [BankState; 2]) -> Option<usize> {
+    match banks {
+        [BankState::Valid { seq: a, .. }, BankState::Valid { seq: b, .. }] => {
+            Some(if b > a { 1 } else { 0 })
+        }
+        [BankState::Valid { .. }, _] => Some(0),
+        [_, BankState::Valid { .. }] => Some(1),
+        _ => None,
+    }
+}
+
+/// The bank the next save goes to, and its sequence number.
+pub fn next_write(banks: [BankState; 2]) -> (usize, u32) {
+    match newest(banks).map(|i| (i, banks[i])) {
+        Some((i, BankState::Valid { seq, .. })) => (1 - i, seq.wrapping_add(1)),
+        _ => (0, 1),
+    }
+}
+
+/// The payload of the newest valid record in `banks`.
+pub fn read(banks: [&[u8]; 2]) -> Option<&[u8]> {
+    let states = banks.map(check);
+    match newest(states).map(|i| (i, states[i])) {
+        Some((i, BankState::Valid { len, .. })) => banks[i].get(HEADER_LEN..HEADER_LEN + len),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(seq: u32, payload: &[u8]) -> [u8; 32] {
+        let mut bank = [0xFF; 32];
+        encode(seq, payload, &mut bank).unwrap();
+        bank
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(check(&[0xFF; 32]), BankState::Erased);
+        assert_eq!(
+            check(&record(7, b"abc")),
+            BankState::Valid { seq: 7, len: 3 }
+        );
+
+        let mut torn = record(7, b"abc");
+        torn[HEADER_LEN + 2] = 0xFF;
+        assert_eq!(check(&torn), BankState::Corrupt);
+        let mut bad_magic = record(7, b"abc");
+        bad_magic[0] = b'X';
+        assert_eq!(check(&bad_magic), BankState::Corrupt);
+        assert_eq!(
+            check(&record(1, b"abc")[..HEADER_LEN + 2]),
+            BankState::Corrupt
+        );
+        assert_eq!(encode(1, &[0; 17], &mut [0; 32]), None);
+    }
+
+    #[test]
+    fn test_alternating_saves() {
+        let mut banks = [[0xFF; 32]; 2];
+        for (save, payload) in [b"one", b"two", b"six"].iter().enumerate() {
+            let (bank, seq) = next_write(banks.map(|b| check(&b)));
+            assert_eq!((bank, seq), (save % 2, save as u32 + 1));
+            banks[bank] = record(seq, *payload);
+            assert_eq!(read([&banks[0], &banks[1]]), Some(&payload[..]));
+        }
+
+        // A save torn by power loss falls back to the other bank
+        let (bank, _) = next_write(banks.map(|b| check(&b)));
+        banks[bank][HEADER_LEN] ^= 1;
+        assert_eq!(read([&banks[0], &banks[1]]), Some(&b"six"[..]));
+        // ...and the next save overwrites the broken bank again
+        assert_eq!(next_write(banks.map(|b| check(&b))), (bank, 4));
+    }
+}
@@ -0,0 +1,127 @@
+//! Battery level from VSYS, for battery builds. Readings are averaged, as a flash of
+//! the LEDs pulls VSYS down for a moment, and a level is only left for a better one
+//! once the voltage is clear of its threshold, so the warning doesn't flicker.
+//!
+//! The thresholds are for a LiPo cell as seen on VSYS, behind the Pico's input diode.
+
+/// Below this the battery is low and the board warns, in volts.
+pub const LOW_V: f32 = 3.4;
+/// Below this the board is close to browning out and stops its voices, in volts.
+pub const CRITICAL_V: f32 = 3.2;
+/// How far above a threshold the voltage has to come back to leave its level.
+pub const HYSTERESIS_V: f32 = 0.1;
+/// Weight of each new reading in the average.
+const SMOOTHING: f32 = 0.05;
+
+/// Worst last, so a drop is a move up the order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BatteryLevel {
+    Ok,
+    Low,
+    Critical,
+}
+
+impl BatteryLevel {
+    pub fn name(self) -> &'static str {
+        match self {
+            BatteryLevel::Ok => "OK",
+            BatteryLevel::Low => "Low",
+            BatteryLevel::Critical => "Critical",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatteryMonitor {
+    volts: Option<f32>,
+    level: BatteryLevel,
+}
+
+impl Default for BatteryMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatteryMonitor {
+    pub const fn new() -> Self {
+        Self {
+            volts: None,
+            level: BatteryLevel::Ok,
+        }
+    }
+
+    pub fn level(&self) -> BatteryLevel {
+        self.level
+    }
+
+    /// The averaged voltage, once there has been a reading.
+    pub fn volts(&self) -> Option<f32> {
+        self.volts
+    }
+
+    /// Feeds a VSYS reading and returns the new level. The first reading is taken as
+    /// it is, so a board switched on with a flat battery knows at once.
+    pub fn update(&mut self, volts: f32) -> BatteryLevel {
+        let average = self
+            .volts
+            .map_or(volts, |average| average + (volts - average) * SMOOTHING);
+        self.volts = Some(average);
+        let level_at = |margin: f32| {
+            if average < CRITICAL_V + margin {
+                BatteryLevel::Critical
+            } else if average < LOW_V + margin {
+                BatteryLevel::Low
+            } else {
+                BatteryLevel::Ok
+            }
+        };
+        let worse = level_at(0.0);
+        let better = level_at(HYSTERESIS_V);
+        if worse > self.level {
+            self.level = worse;
+        } else if better < self.level {
+            self.level = better;
+        }
+        self.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_battery_levels() {
+        let mut monitor = BatteryMonitor::new();
+        assert_eq!(monitor.volts(), None);
+        assert_eq!(monitor.update(3.9), BatteryLevel::Ok);
+        // A short sag from the LEDs doesn't count
+        assert_eq!(monitor.update(3.0), BatteryLevel::Ok);
+        for _ in 0..200 {
+            monitor.update(3.35);
+        }
+        assert_eq!(monitor.level(), BatteryLevel::Low);
+        // Just over the threshold isn't enough to clear the warning
+        for _ in 0..200 {
+            monitor.update(LOW_V + HYSTERESIS_V / 2.0);
+        }
+        assert_eq!(monitor.level(), BatteryLevel::Low);
+        for _ in 0..200 {
+            monitor.update(3.1);
+        }
+        assert_eq!(monitor.level(), BatteryLevel::Critical);
+        // Charging clears both levels once well above them
+        for _ in 0..400 {
+            monitor.update(4.2);
+        }
+        assert_eq!(monitor.level(), BatteryLevel::Ok);
+    }
+
+    #[test]
+    fn test_flat_at_power_up() {
+        let mut monitor = BatteryMonitor::new();
+        assert_eq!(monitor.update(3.1), BatteryLevel::Critical);
+        assert_eq!(monitor.volts(), Some(3.1));
+    }
+}
@@ -0,0 +1,180 @@
+/// Bytes a stored map takes: a marker, then channel and CC per parameter.
+pub const fn stored_len(params: usize) -> usize {
+    1 + 2 * params
+}
+
+/// First byte of a stored map; erased flash reads 0xFF.
+const MAP_MARKER: u8 = 0x43;
+const UNMAPPED: u8 = 0xFF;
+
+/// An incoming controller: channel 0-15 and CC number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CcSource {
+    pub channel: u8,
+    pub cc: u8,
+}
+
+/// Which CC controls each of `N` parameters, with MIDI learn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CcMap<const N: usize> {
+    sources: [Option<CcSource>; N],
+    learning: Option<usize>,
+}
+
+impl<const N: usize> CcMap<N> {
+    pub const fn new() -> Self {
+        Self {
+            sources: [None; N],
+            learning: None,
+        }
+    }
+
+    /// Binds the next CC [`Self::control`] sees to `param`.
+    pub fn learn(&mut self, param: usize) {
+        self.learning = (param < N).then_some(param);
+    }
+
+    /// Parameter waiting for a CC, if any.
+    pub fn learning(&self) -> Option<usize> {
+        self.learning
+    }
+
+    pub fn source(&self, param: usize) -> Option<CcSource> {
+        self.sources.get(param).copied().flatten()
+    }
+
+    /// Unbinds `param` and stops learning it.
+    pub fn clear(&mut self, param: usize) {
+        if let Some(source) = self.sources.get_mut(param) {
+            *source = None;
+        }
+        if self.learning == Some(param) {
+            self.learning = None;
+        }
+    }
+
+    /// Handles an incoming CC: binds it while learning (taking it from any other
+    /// parameter), otherwise returns the parameter it controls.
+    pub fn control(&mut self, source: CcSource) -> Control {
+        if let Some(param) = self.learning.take() {
+            for s in self.sources.iter_mut().filter(|s| **s == Some(source)) {
+                *s = None;
+            }
+            self.sources[param] = Some(source);
+            return Control::Learned(param);
+        }
+        match self.sources.iter().position(|&s| s == Some(source)) {
+            Some(param) => Control::Set(param),
+            None => Control::Unmapped,
+        }
+    }
+
+    /// Flash image of the map; its length is [`stored_len`]`(N)`.
+    pub fn encode(&self, out: &mut [u8]) {
+        out[0] = MAP_MARKER;
+        for (pair, source) in out[1..].chunks_exact_mut(2).zip(&self.sources) {
+            let (channel, cc) = source.map_or((UNMAPPED, UNMAPPED), |s| (s.channel, s.cc));
+            pair.copy_from_slice(&[channel, cc]);
+        }
+    }
+
+    /// A map written by [`Self::encode`], or `None` if there is none.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < stored_len(N) || bytes[0] != MAP_MARKER {
+            return None;
+        }
+        let mut map = Self::new();
+        for (source, pair) in map.sources.iter_mut().zip(bytes[1..].chunks_exact(2)) {
+            *source = (pair[0] < 16 && pair[1] < 128).then_some(CcSource {
+                channel: pair[0],
+                cc: pair[1],
+            });
+        }
+        Some(map)
+    }
+}
+
+impl<const N: usize> Default for CcMap<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What an incoming CC did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// It was bound to this parameter.
+    Learned(usize),
+    /// It sets this parameter.
+    Set(usize),
+    Unmapped,
+}
+
+/// A 7-bit CC value scaled onto `min..=max`.
+pub fn scale(value: u8, min: f32, max: f32) -> f32 {
+    min + (max - min) * value.min(127) as f32 / 127.0
+}
+
+/// A 14-bit controller value scaled onto `min..=max`.
+pub fn scale14(value: u16, min: f32, max: f32) -> f32 {
+    min + (max - min) * value.min(16383) as f32 / 16383.0
+}
+
+/// `value` within `min..=max` as a 14-bit controller value, for feedback to the host.
+pub fn unscale(value: f32, min: f32, max: f32) -> u16 {
+    (((value - min) / (max - min)).clamp(0.0, 1.0) * 16383.0 + 0.5) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOD_WHEEL: CcSource = CcSource { channel: 0, cc: 1 };
+    const CUTOFF: CcSource = CcSource { channel: 2, cc: 74 };
+
+    #[test]
+    fn test_learn() {
+        let mut map = CcMap::<4>::new();
+        assert_eq!(map.control(MOD_WHEEL), Control::Unmapped);
+
+        map.learn(2);
+        assert_eq!(map.learning(), Some(2));
+        assert_eq!(map.control(MOD_WHEEL), Control::Learned(2));
+        assert_eq!(map.control(MOD_WHEEL), Control::Set(2));
+        assert_eq!(map.control(CUTOFF), Control::Unmapped);
+
+        // Learning a bound CC moves it
+        map.learn(0);
+        map.control(MOD_WHEEL);
+        assert_eq!(map.source(2), None);
+        assert_eq!(map.control(MOD_WHEEL), Control::Set(0));
+
+        map.clear(0);
+        assert_eq!(map.control(MOD_WHEEL), Control::Unmapped);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let mut map = CcMap::<4>::new();
+        map.learn(1);
+        map.control(CUTOFF);
+        let mut bytes = [0; stored_len(4)];
+        map.encode(&mut bytes);
+        assert_eq!(CcMap::<4>::decode(&bytes), Some(map));
+        assert_eq!(CcMap::<4>::decode(&[0xFF; stored_len(4)]), None);
+    }
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(scale(0, 20.0, 300.0), 20.0);
+        assert_eq!(scale(127, 20.0, 300.0), 300.0);
+        assert_eq!(scale(200, 0.0, 1.0), 1.0);
+        assert_eq!(unscale(300.0, 20.0, 300.0), 16383);
+        assert_eq!(unscale(0.5, 0.0, 1.0), 8192);
+        assert_eq!(scale14(16383, 20.0, 300.0), 300.0);
+        assert_eq!(unscale(scale14(8192, 20.0, 300.0), 20.0, 300.0), 8192);
+        for v in [0, 1, 64, 126, 127] {
+            assert_eq!(unscale(scale(v, 20.0, 300.0), 20.0, 300.0) >> 7, v as u16);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Hue/saturation/value colors, for editing palette colors the way they're seen
+//! rather than as red, green and blue.
+
+/// A color as hue (degrees, 0..360), saturation and value (0..=255 each).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hsv {
+    pub h: u16,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl Hsv {
+    pub fn from_rgb([r, g, b]: [u8; 3]) -> Self {
+        let (r, g, b) = (r as i32, g as i32, b as i32);
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+        let h = if delta == 0 {
+            0
+        } else if max == r {
+            60 * (g - b) / delta
+        } else if max == g {
+            120 + 60 * (b - r) / delta
+        } else {
+            240 + 60 * (r - g) / delta
+        };
+        Self {
+            h: h.rem_euclid(360) as u16,
+            s: if max == 0 {
+                0
+            } else {
+                (delta * 255 / max) as u8
+            },
+            v: max as u8,
+        }
+    }
+
+    pub fn to_rgb(self) -> [u8; 3] {
+        let (h, s, v) = (self.h as i32 % 360, self.s as i32, self.v as i32);
+        let rem = h % 60;
+        let p = (v * (255 - s) / 255) as u8;
+        let q = (v * (255 - s * rem / 60) / 255) as u8;
+        let t = (v * (255 - s * (60 - rem) / 60) / 255) as u8;
+        let v = v as u8;
+        match h / 60 {
+            0 => [v, t, p],
+            1 => [q, v, p],
+            2 => [p, v, t],
+            3 => [p, q, v],
+            4 => [t, p, v],
+            _ => [v, p, q],
+        }
+    }
+
+    /// Hue turned by `degrees`, wrapping around.
+    pub fn rotated(self, degrees: i16) -> Self {
+        Self {
+            h: (self.h as i32 + degrees as i32).rem_euclid(360) as u16,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hsv() {
+        let red = Hsv::from_rgb([255, 0, 0]);
+        assert_eq!(
+            red,
+            Hsv {
+                h: 0,
+                s: 255,
+                v: 255
+            }
+        );
+        assert_eq!(red.rotated(120).to_rgb(), [0, 255, 0]);
+        assert_eq!(red.rotated(-120).to_rgb(), [0, 0, 255]);
+        assert_eq!(Hsv::from_rgb([40, 40, 40]), Hsv { h: 0, s: 0, v: 40 });
+
+        // Palette colors survive the round trip to within rounding
+        for rgb in [[225, 35, 0], [0, 165, 130], [100, 0, 200], [20, 20, 245]] {
+            let back = Hsv::from_rgb(rgb).to_rgb();
+            for (a, b) in rgb.iter().zip(back) {
+                assert!(a.abs_diff(b) <= 4, "{:?} came back as {:?}", rgb, back);
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Settings a host-side editor reads and writes over SysEx, as plain values rather
+//! than the steps NRPN takes.
+//!
+//! `GET_CONFIG` carries a key (and for [`ConfigKey::LedColor`] the palette index); the
+//! board answers with `CONFIG_VALUE`. `SET_CONFIG` takes the same payload as
+//! `CONFIG_VALUE`, so an editor can send back what it read, and is answered with the
+//! value as applied. Values are 14-bit, MSB first.
+
+use crate::sysex::{cmd, MANUFACTURER_ID, SYSEX_END, SYSEX_START};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigKey {
+    /// 0 for off to 16383 for full.
+    Brightness = 0,
+    /// 0 Standard, 1 Fifths, 2 Scale.
+    TuningMode = 1,
+    /// Tenths of a cent above 600.
+    FifthSize = 2,
+    /// MPE pitch bend range in tenths of a semitone.
+    PitchBendRange = 3,
+    /// A palette anchor, by index: red, green and blue, 0-255.
+    LedColor = 4,
+}
+
+impl ConfigKey {
+    pub fn from_u8(key: u8) -> Option<Self> {
+        match key {
+            0 => Some(ConfigKey::Brightness),
+            1 => Some(ConfigKey::TuningMode),
+            2 => Some(ConfigKey::FifthSize),
+            3 => Some(ConfigKey::PitchBendRange),
+            4 => Some(ConfigKey::LedColor),
+            _ => None,
+        }
+    }
+
+    fn indexed(self) -> bool {
+        self == ConfigKey::LedColor
+    }
+
+    /// How many values the key has.
+    fn values(self) -> usize {
+        if self == ConfigKey::LedColor {
+            3
+        } else {
+            1
+        }
+    }
+}
+
+/// A setting's value, read from the board or to be written to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConfigValue {
+    pub key: ConfigKey,
+    /// Which one of an indexed setting; 0 for the others.
+    pub index: u8,
+    /// The values, unused ones 0.
+    pub values: [u16; 3],
+}
+
+/// The key and index a `GET_CONFIG` payload asks for.
+pub fn decode_get(payload: &[u8]) -> Option<(ConfigKey, u8)> {
+    let (&key, rest) = payload.split_first()?;
+    let key = ConfigKey::from_u8(key)?;
+    match (key.indexed(), rest) {
+        (false, []) => Some((key, 0)),
+        (true, &[index]) => Some((key, index)),
+        _ => None,
+    }
+}
+
+impl ConfigValue {
+    /// Reads a `SET_CONFIG` or `CONFIG_VALUE` payload.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let (key, rest) = payload.split_first()?;
+        let key = ConfigKey::from_u8(*key)?;
+        let (index, rest) = match key.indexed() {
+            true => rest.split_first().map(|(&i, rest)| (i, rest))?,
+            false => (0, rest),
+        };
+        if rest.len() != 2 * key.values() {
+            return None;
+        }
+        let mut values = [0; 3];
+        for (value, pair) in values.iter_mut().zip(rest.chunks(2)) {
+            *value = (pair[0] as u16 & 0x7F) << 7 | (pair[1] as u16 & 0x7F);
+        }
+        Some(Self { key, index, values })
+    }
+
+    /// The value as a `CONFIG_VALUE` message.
+    pub fn encode(self, out: &mut [u8; 12]) -> &[u8] {
+        out[..4].copy_from_slice(&[
+            SYSEX_START,
+            MANUFACTURER_ID,
+            cmd::CONFIG_VALUE,
+            self.key as u8,
+        ]);
+        let mut len = 4;
+        if self.key.indexed() {
+            out[len] = self.index & 0x7F;
+            len += 1;
+        }
+        for &value in &self.values[..self.key.values()] {
+            out[len] = (value >> 7) as u8 & 0x7F;
+            out[len + 1] = value as u8 & 0x7F;
+            len += 2;
+        }
+        out[len] = SYSEX_END;
+        &out[..len + 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysex::parse_message;
+
+    #[test]
+    fn test_round_trip() {
+        let color = ConfigValue {
+            key: ConfigKey::LedColor,
+            index: 7,
+            values: [255, 128, 0],
+        };
+        let fifth = ConfigValue {
+            key: ConfigKey::FifthSize,
+            index: 0,
+            values: [970, 0, 0],
+        };
+        for value in [color, fifth] {
+            let mut buf = [0; 12];
+            let (command, payload) = parse_message(value.encode(&mut buf)).unwrap();
+            assert_eq!(command, cmd::CONFIG_VALUE);
+            assert_eq!(ConfigValue::decode(payload), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode_get(&[2]), Some((ConfigKey::FifthSize, 0)));
+        assert_eq!(decode_get(&[4, 11]), Some((ConfigKey::LedColor, 11)));
+        // Colors need an index, the others take none
+        assert_eq!(decode_get(&[4]), None);
+        assert_eq!(decode_get(&[0, 1]), None);
+        assert_eq!(decode_get(&[9]), None);
+        // Every value has to be there
+        assert_eq!(ConfigValue::decode(&[0, 0x7F]), None);
+        assert_eq!(
+            ConfigValue::decode(&[0, 0x7F, 0x7F]).map(|v| v.values[0]),
+            Some(16383)
+        );
+    }
+}
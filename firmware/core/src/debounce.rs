@@ -0,0 +1,157 @@
+//! Key debouncing by elapsed time rather than by scan passes, so a setting means the
+//! same whatever the scan rate.
+
+/// One key's settled state and the bounce in progress.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Debouncer {
+    pressed: bool,
+    raw: bool,
+    /// First and last edge since the level last settled.
+    edges: Option<(u64, u64)>,
+    /// First and last edge of the burst being measured for [`Debouncer::bounce`].
+    burst: Option<(u64, u64)>,
+    changed_at: u64,
+}
+
+/// A debounced change of a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Settled {
+    pub pressed: bool,
+    /// Time from the first edge to the last, in the caller's ticks.
+    pub bounce: u64,
+}
+
+impl Debouncer {
+    pub const fn new() -> Self {
+        Self {
+            pressed: false,
+            raw: false,
+            edges: None,
+            burst: None,
+            changed_at: 0,
+        }
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// When the key last changed, for telling chatter from playing.
+    pub fn changed_at(&self) -> u64 {
+        self.changed_at
+    }
+
+    /// Feeds a reading taken at `now`. The key changes once its level has held for
+    /// `hold` since the last edge, so with no hold it changes on the first edge.
+    /// Bounces that settle back to where they started are ignored.
+    pub fn update(&mut self, raw: bool, now: u64, hold: u64) -> Option<Settled> {
+        if raw != self.raw {
+            self.raw = raw;
+            let first = self.edges.map_or(now, |(first, _)| first);
+            self.edges = Some((first, now));
+            let first = self.burst.map_or(now, |(first, _)| first);
+            self.burst = Some((first, now));
+        }
+        let (first, last) = self.edges?;
+        if now.saturating_sub(last) < hold {
+            return None;
+        }
+        self.edges = None;
+        if raw == self.pressed {
+            return None;
+        }
+        self.pressed = raw;
+        self.changed_at = now;
+        Some(Settled {
+            pressed: raw,
+            bounce: last - first,
+        })
+    }
+
+    /// Ends the burst of edges fed to [`Debouncer::update`] once none has come for
+    /// `quiet`, returning how long it lasted and the level it ended at. Kept apart
+    /// from the hold, so it measures the switch's own bounce even when every edge
+    /// counts as a change.
+    pub fn bounce(&mut self, now: u64, quiet: u64) -> Option<Settled> {
+        let (first, last) = self.burst?;
+        if now.saturating_sub(last) < quiet {
+            return None;
+        }
+        self.burst = None;
+        Some(Settled {
+            pressed: self.raw,
+            bounce: last - first,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debounce() {
+        let mut key = Debouncer::new();
+        // Without a hold every edge counts
+        assert_eq!(
+            key.update(true, 10, 0),
+            Some(Settled {
+                pressed: true,
+                bounce: 0
+            })
+        );
+        assert_eq!(key.update(true, 20, 0), None);
+
+        // A bouncy release settles once the level holds
+        let mut key = Debouncer::new();
+        key.update(true, 0, 0);
+        assert_eq!(key.update(false, 100, 50), None);
+        assert_eq!(key.update(true, 110, 50), None);
+        assert_eq!(key.update(false, 130, 50), None);
+        assert_eq!(key.update(false, 170, 50), None);
+        assert_eq!(
+            key.update(false, 180, 50),
+            Some(Settled {
+                pressed: false,
+                bounce: 30
+            })
+        );
+        assert!(!key.is_pressed());
+        assert_eq!(key.changed_at(), 180);
+
+        // A glitch that settles back is no change
+        assert_eq!(key.update(true, 200, 50), None);
+        assert_eq!(key.update(false, 205, 50), None);
+        assert_eq!(key.update(false, 300, 50), None);
+        assert!(!key.is_pressed());
+    }
+
+    #[test]
+    fn test_bounce_without_hold() {
+        let mut key = Debouncer::new();
+        assert_eq!(key.bounce(0, 50), None);
+        // Every edge is a change, but the burst is measured as a whole
+        assert!(key.update(true, 100, 0).is_some());
+        assert!(key.update(false, 110, 0).is_some());
+        assert!(key.update(true, 125, 0).is_some());
+        assert_eq!(key.bounce(150, 50), None);
+        assert_eq!(
+            key.bounce(175, 50),
+            Some(Settled {
+                pressed: true,
+                bounce: 25
+            })
+        );
+        assert_eq!(key.bounce(500, 50), None);
+
+        // A clean release is a burst of one edge
+        assert!(key.update(false, 600, 0).is_some());
+        assert_eq!(
+            key.bounce(700, 50),
+            Some(Settled {
+                pressed: false,
+                bounce: 0
+            })
+        );
+    }
+}
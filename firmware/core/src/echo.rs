@@ -0,0 +1,78 @@
+use crate::release::NoteKey;
+
+/// Recently sent note ons, so the same notes coming back from the host (or through
+/// soft-thru) can be told apart from notes the host plays itself.
+pub struct EchoFilter<const N: usize> {
+    /// Sent notes with their send time in ms; the oldest is overwritten when full.
+    sent: [Option<(NoteKey, u64)>; N],
+    next: usize,
+}
+
+impl<const N: usize> EchoFilter<N> {
+    pub const fn new() -> Self {
+        Self {
+            sent: [None; N],
+            next: 0,
+        }
+    }
+
+    /// Notes a note on sent at `now_ms`.
+    pub fn record(&mut self, key: NoteKey, now_ms: u64) {
+        let slot = match self
+            .sent
+            .iter()
+            .position(|s| s.is_some_and(|(k, _)| k == key))
+        {
+            Some(i) => i,
+            None => {
+                let i = self.next;
+                self.next = (self.next + 1) % N;
+                i
+            }
+        };
+        self.sent[slot] = Some((key, now_ms));
+    }
+
+    /// Whether a note on received at `now_ms` is the echo of one sent at most
+    /// `window_ms` before. Each sent note matches one echo.
+    pub fn is_echo(&mut self, key: NoteKey, now_ms: u64, window_ms: u32) -> bool {
+        let found = self.sent.iter().position(|s| {
+            s.is_some_and(|(k, at)| k == key && now_ms.saturating_sub(at) <= window_ms as u64)
+        });
+        if let Some(i) = found {
+            self.sent[i] = None;
+        }
+        found.is_some()
+    }
+}
+
+impl<const N: usize> Default for EchoFilter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo() {
+        let mut filter = EchoFilter::<2>::new();
+        filter.record((1, 60), 1000);
+        assert!(!filter.is_echo((2, 60), 1010, 50));
+        assert!(filter.is_echo((1, 60), 1010, 50));
+        // Only one echo per note sent
+        assert!(!filter.is_echo((1, 60), 1020, 50));
+
+        filter.record((1, 62), 2000);
+        assert!(!filter.is_echo((1, 62), 2051, 50));
+
+        // The oldest note makes room
+        filter.record((0, 1), 3000);
+        filter.record((0, 2), 3000);
+        filter.record((0, 3), 3000);
+        assert!(!filter.is_echo((0, 1), 3001, 50));
+        assert!(filter.is_echo((0, 3), 3001, 50));
+    }
+}
@@ -0,0 +1,163 @@
+//! Intervals and chords on the chain of fifths.
+//!
+//! Intervals are counted in fifths, so they keep their meaning in any tuning of the
+//! chain: a major third is four fifths up, less two octaves, whatever the fifth size.
+//! Chords are pitch-class masks (bit 0 = C) in 12-EDO, like scale masks.
+
+/// Chords [`suggestions`] returns at most.
+pub const SUGGESTIONS: usize = 3;
+
+/// How much a key `fifths` fifths from the bass stands out as a chord tone: octaves
+/// and fifths fully, thirds and sixths half, anything else not at all.
+pub fn interval_level(fifths: i16) -> f32 {
+    match fifths {
+        -1..=1 => 1.0,
+        -4 | -3 | 3 | 4 => 0.5,
+        _ => 0.0,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+}
+
+impl Quality {
+    pub const ALL: [Quality; 4] = [
+        Quality::Major,
+        Quality::Minor,
+        Quality::Diminished,
+        Quality::Augmented,
+    ];
+
+    /// Semitones of the chord tones above the root.
+    pub fn intervals(self) -> [u8; 3] {
+        match self {
+            Quality::Major => [0, 4, 7],
+            Quality::Minor => [0, 3, 7],
+            Quality::Diminished => [0, 3, 6],
+            Quality::Augmented => [0, 4, 8],
+        }
+    }
+}
+
+/// A triad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chord {
+    /// Pitch class, 0 = C.
+    pub root: u8,
+    pub quality: Quality,
+}
+
+impl Chord {
+    pub fn mask(self) -> u16 {
+        self.quality
+            .intervals()
+            .iter()
+            .fold(0, |mask, i| mask | 1 << ((self.root + i) % 12))
+    }
+}
+
+/// The triads with all their tones in `scale`.
+pub fn chords_in(scale: u16) -> impl Iterator<Item = Chord> {
+    (0..12)
+        .flat_map(|root| Quality::ALL.map(|quality| Chord { root, quality }))
+        .filter(move |chord| chord.mask() & !scale == 0)
+}
+
+/// The smallest move in semitones from pitch class `pc` onto a pitch class in `mask`,
+/// upward on ties; 0 for an empty mask.
+pub fn nearest_move(pc: u8, mask: u16) -> i8 {
+    let has = |d: i8| mask & 1 << (pc as i8 + d).rem_euclid(12) != 0;
+    (0..=6).flat_map(|d| [d, -d]).find(|&d| has(d)).unwrap_or(0)
+}
+
+/// Semitones the `held` pitch classes move in all, each to its nearest tone of `to`.
+pub fn distance(held: u16, to: Chord) -> u32 {
+    (0..12)
+        .filter(|pc| held & 1 << pc != 0)
+        .map(|pc| nearest_move(pc, to.mask()).unsigned_abs() as u32)
+        .sum()
+}
+
+/// The chords of `scale` the `held` pitch classes lead to most smoothly, nearest first.
+/// Chords needing no move, which `held` already outlines, aren't suggested.
+pub fn suggestions(held: u16, scale: u16) -> [Option<Chord>; SUGGESTIONS] {
+    let mut best: [Option<(u32, Chord)>; SUGGESTIONS] = [None; SUGGESTIONS];
+    for chord in chords_in(scale) {
+        let d = distance(held, chord);
+        let duplicate = best.iter().flatten().any(|(_, c)| c.mask() == chord.mask());
+        if d == 0 || duplicate {
+            continue;
+        }
+        // Keep the list sorted, earlier chords first among equals
+        if let Some(i) = best.iter().position(|b| b.is_none_or(|(bd, _)| d < bd)) {
+            best[i..].rotate_right(1);
+            best[i] = Some((d, chord));
+        }
+    }
+    best.map(|b| b.map(|(_, chord)| chord))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_level() {
+        // Octave, fifth and fourth
+        assert_eq!(interval_level(0), 1.0);
+        assert_eq!(interval_level(1), 1.0);
+        assert_eq!(interval_level(-1), 1.0);
+        // Major and minor thirds
+        assert_eq!(interval_level(4), 0.5);
+        assert_eq!(interval_level(-3), 0.5);
+        // Whole tone, tritone, and the diminished fourth a major third's enharmonic
+        // is in 12-EDO but not in other tunings
+        assert_eq!(interval_level(2), 0.0);
+        assert_eq!(interval_level(6), 0.0);
+        assert_eq!(interval_level(-8), 0.0);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        const C_MAJOR: u16 = 0xAB5;
+        let chord = |root, quality| Some(Chord { root, quality });
+        let a_minor = Chord {
+            root: 9,
+            quality: Quality::Minor,
+        };
+        assert_eq!(nearest_move(5, a_minor.mask()), -1);
+        // D is as far from C as from E
+        assert_eq!(nearest_move(2, a_minor.mask()), 2);
+        assert_eq!(nearest_move(6, 0), 0);
+        assert_eq!(chords_in(C_MAJOR).count(), 7);
+
+        // From C major: E minor moves C to B, A minor moves G to A, then F major
+        // (E to F, G up to A) before G major as F comes first
+        let c = chord(0, Quality::Major).unwrap().mask();
+        assert_eq!(
+            suggestions(c, C_MAJOR),
+            [
+                chord(4, Quality::Minor),
+                chord(9, Quality::Minor),
+                chord(5, Quality::Major)
+            ]
+        );
+        // A dyad isn't suggested the chords it's already part of
+        let c_e = 1 << 0 | 1 << 4;
+        assert!(suggestions(c_e, C_MAJOR)
+            .iter()
+            .flatten()
+            .all(|s| s.mask() & c_e != c_e));
+        assert_eq!(suggestions(0, 0xFFF), [None; SUGGESTIONS]);
+        // Augmented triads are listed once: D augmented, from any of its roots
+        assert_eq!(
+            suggestions(c_e, 0x555),
+            [chord(2, Quality::Augmented), None, None]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Naming a played interval by the nearest just ratio, to hear how far a tuning
+//! strays from it.
+
+/// Just ratios up to an octave, with their size in cents.
+const JUST_RATIOS: [(u8, u8, f32); 19] = [
+    (1, 1, 0.0),
+    (16, 15, 111.73),
+    (10, 9, 182.40),
+    (9, 8, 203.91),
+    (7, 6, 266.87),
+    (6, 5, 315.64),
+    (5, 4, 386.31),
+    (9, 7, 435.08),
+    (4, 3, 498.04),
+    (11, 8, 551.32),
+    (7, 5, 582.51),
+    (10, 7, 617.49),
+    (3, 2, 701.96),
+    (8, 5, 813.69),
+    (5, 3, 884.36),
+    (7, 4, 968.83),
+    (9, 5, 1017.60),
+    (15, 8, 1088.27),
+    (2, 1, 1200.0),
+];
+
+/// The just ratio nearest an interval's size, up to compound octaves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JustInterval {
+    pub numerator: u8,
+    pub denominator: u8,
+    /// Whole octaves added to the ratio.
+    pub octaves: u8,
+    /// How much larger the interval is than the just one, in cents.
+    pub error: f32,
+}
+
+/// The just interval nearest `cents`, up or down.
+pub fn nearest_just(cents: f32) -> JustInterval {
+    let size = if cents < 0.0 { -cents } else { cents };
+    let octaves = (size / 1200.0) as u32;
+    let within = size - octaves as f32 * 1200.0;
+    let distance = |c: f32| {
+        let d = within - c;
+        if d < 0.0 {
+            -d
+        } else {
+            d
+        }
+    };
+    let (numerator, denominator, just) = JUST_RATIOS
+        .into_iter()
+        .min_by(|a, b| distance(a.2).total_cmp(&distance(b.2)))
+        .unwrap_or(JUST_RATIOS[0]);
+    JustInterval {
+        numerator,
+        denominator,
+        octaves: octaves.min(u8::MAX as u32) as u8,
+        error: within - just,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_just() {
+        // A 12-EDO fifth is two cents flat of 3/2
+        let fifth = nearest_just(700.0);
+        assert_eq!(
+            (fifth.numerator, fifth.denominator, fifth.octaves),
+            (3, 2, 0)
+        );
+        assert!((fifth.error + 1.96).abs() < 0.01);
+        // Downward and compound intervals name the same ratio
+        let down = nearest_just(-1900.0);
+        assert_eq!((down.numerator, down.denominator, down.octaves), (3, 2, 1));
+        // A slightly narrow octave is an octave, not a unison
+        let octave = nearest_just(1195.0);
+        assert_eq!(
+            (octave.numerator, octave.denominator, octave.octaves),
+            (2, 1, 0)
+        );
+        assert!((octave.error + 5.0).abs() < 0.01);
+        assert_eq!(nearest_just(0.0).numerator, 1);
+    }
+}
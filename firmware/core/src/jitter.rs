@@ -0,0 +1,86 @@
+//! Distribution of how late events leave, as counts in fixed buckets, so percentiles
+//! can be reported without keeping every sample.
+
+/// Upper bound of each bucket in microseconds. Samples past the last one go in a
+/// final bucket of their own.
+pub const BUCKET_LIMITS_US: [u32; 15] = [
+    50, 100, 200, 300, 500, 750, 1_000, 1_500, 2_000, 3_000, 5_000, 7_500, 10_000, 20_000, 50_000,
+];
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Histogram {
+    counts: [u32; BUCKET_LIMITS_US.len() + 1],
+    max_us: u32,
+}
+
+impl Histogram {
+    pub const fn new() -> Self {
+        Self {
+            counts: [0; BUCKET_LIMITS_US.len() + 1],
+            max_us: 0,
+        }
+    }
+
+    pub fn record(&mut self, us: u32) {
+        let bucket = BUCKET_LIMITS_US.partition_point(|&limit| limit < us);
+        self.counts[bucket] = self.counts[bucket].saturating_add(1);
+        self.max_us = self.max_us.max(us);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.counts.iter().fold(0, |sum, &c| sum.saturating_add(c))
+    }
+
+    pub fn max_us(&self) -> u32 {
+        self.max_us
+    }
+
+    /// Bound under which `percent` of the samples fall: the limit of the bucket
+    /// reaching that share, or the largest sample if that is lower. `None` before the
+    /// first sample.
+    pub fn percentile(&self, percent: u32) -> Option<u32> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let needed = (count as u64 * percent.min(100) as u64)
+            .div_ceil(100)
+            .max(1);
+        let mut seen = 0u64;
+        let bucket = self.counts.iter().position(|&c| {
+            seen += c as u64;
+            seen >= needed
+        })?;
+        let limit = BUCKET_LIMITS_US.get(bucket).copied().unwrap_or(u32::MAX);
+        Some(limit.min(self.max_us))
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut h = Histogram::new();
+        assert_eq!(h.percentile(50), None);
+        for _ in 0..98 {
+            h.record(80);
+        }
+        h.record(1_200);
+        h.record(70_000);
+        assert_eq!(h.count(), 100);
+        assert_eq!(h.percentile(50), Some(100));
+        assert_eq!(h.percentile(99), Some(1_500));
+        assert_eq!(h.percentile(100), Some(70_000));
+
+        // Never above the largest sample
+        h.clear();
+        h.record(60);
+        assert_eq!(h.percentile(99), Some(60));
+    }
+}
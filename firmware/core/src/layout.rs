@@ -11,9 +11,129 @@ pub struct Coordinate {
     pub y: i8,
 }
 
+/// Steps to the six keys touching a key, in rotational order. With the stagger the
+/// grid is hexagonal: besides the four orthogonal steps, (x + 1, y - 1) and
+/// (x - 1, y + 1) (a fifth up or down) are also adjacent.
+pub const NEIGHBOR_OFFSETS: [(i8, i8); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+impl Coordinate {
+    /// Coordinate `steps` times `offset` away, if it stays in range.
+    fn offset(self, (dx, dy): (i8, i8), steps: i16) -> Option<Coordinate> {
+        let x = self.x as i16 + dx as i16 * steps;
+        let y = self.y as i16 + dy as i16 * steps;
+        Some(Coordinate {
+            x: i8::try_from(x).ok()?,
+            y: i8::try_from(y).ok()?,
+        })
+    }
+
+    /// The (up to six) adjacent keys' coordinates. Not all of them need exist on a board.
+    pub fn neighbors(self) -> impl Iterator<Item = Coordinate> {
+        NEIGHBOR_OFFSETS
+            .into_iter()
+            .filter_map(move |step| self.offset(step, 1))
+    }
+
+    /// Number of steps between two keys, moving only between adjacent keys.
+    pub fn distance(self, other: Coordinate) -> u16 {
+        let dx = other.x as i16 - self.x as i16;
+        let dy = other.y as i16 - self.y as i16;
+        (dx.unsigned_abs() + dy.unsigned_abs() + (dx + dy).unsigned_abs()) / 2
+    }
+
+    /// Every coordinate exactly `radius` steps away, walking once around the ring
+    /// (6 * `radius` of them, or just `self` for radius 0).
+    pub fn ring(self, radius: u8) -> impl Iterator<Item = Coordinate> {
+        let radius = radius as i16;
+        let sides = if radius == 0 { 1 } else { 6 };
+        (0..sides).flat_map(move |side| {
+            // Start at a corner, then walk towards the next one
+            let corner = self.offset(NEIGHBOR_OFFSETS[side], radius);
+            let along = NEIGHBOR_OFFSETS[(side + 2) % 6];
+            (0..radius.max(1)).filter_map(move |step| corner?.offset(along, step))
+        })
+    }
+}
+
+/// Where a key sits on the board, in millimetres from the center key. Axes follow
+/// the PCB: x to the right, y towards the player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalPosition {
+    pub x_mm: f32,
+    pub y_mm: f32,
+    /// Rotation of the key cap, counterclockwise.
+    pub rotation_deg: f32,
+}
+
+/// Outline of a key cap, for drawing the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyShape {
+    Square,
+    /// Flat sides facing the (x + 1) and (x - 1) neighbors.
+    Hex,
+}
+
+impl KeyShape {
+    pub const fn name(self) -> &'static str {
+        match self {
+            KeyShape::Square => "square",
+            KeyShape::Hex => "hex",
+        }
+    }
+}
+
+/// Physical placement of a board's keys. The switches sit on a regular (rotated,
+/// sheared) lattice, so two step vectors describe every key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geometry {
+    /// Offset in mm of one step along x, i.e. (x + 1, y).
+    pub step_x: (f32, f32),
+    /// Offset in mm of one step along y, i.e. (x, y + 1).
+    pub step_y: (f32, f32),
+    pub rotation_deg: f32,
+    pub key_shape: KeyShape,
+    /// Cap width across the flats.
+    pub key_size_mm: f32,
+}
+
+impl Geometry {
+    /// Position of `coord` relative to `origin`.
+    pub fn position(&self, coord: Coordinate, origin: Coordinate) -> PhysicalPosition {
+        let dx = (coord.x as i16 - origin.x as i16) as f32;
+        let dy = (coord.y as i16 - origin.y as i16) as f32;
+        PhysicalPosition {
+            x_mm: dx * self.step_x.0 + dy * self.step_y.0,
+            y_mm: dx * self.step_x.1 + dy * self.step_y.1,
+            rotation_deg: self.rotation_deg,
+        }
+    }
+
+    /// Writes the geometry as space-separated `name=value` pairs, for the layout dump.
+    pub fn write_descriptor(&self, out: &mut impl core::fmt::Write) -> core::fmt::Result {
+        write!(
+            out,
+            "shape={} size={:.2} step_x={:.3},{:.3} step_y={:.3},{:.3} rotation={:.2}",
+            self.key_shape.name(),
+            self.key_size_mm,
+            self.step_x.0,
+            self.step_x.1,
+            self.step_y.0,
+            self.step_y.1,
+            self.rotation_deg
+        )
+    }
+}
+
 /// Logical index of an LED on the strip.
 pub type LedIndex = usize;
 
+/// Entry of a layout's LED matrix: the LED index at that (row, col), or [`NO_LED`].
+/// u16 so boards aren't capped at 255 LEDs.
+pub type LedMatrixEntry = u16;
+
+/// LED matrix entry for a position without an LED.
+pub const NO_LED: LedMatrixEntry = LedMatrixEntry::MAX;
+
 /// The interface that every board variant must implement.
 ///
 /// This trait decouples the physical hardware (Matix Rows/Cols, LED Index)
@@ -32,6 +152,17 @@ pub trait Layout: Sync {
     /// Returns the logical Coordinate that corresponds to Middle C (MIDI 60).
     fn center_coord() -> Coordinate;
 
+    /// Physical placement of the keys, if known.
+    fn geometry() -> Option<&'static Geometry> {
+        None
+    }
+
+    /// Where the key at `coord` sits, relative to the center key. Doesn't check that
+    /// the key exists. `None` if the layout has no geometry.
+    fn physical_position(coord: Coordinate) -> Option<PhysicalPosition> {
+        Some(Self::geometry()?.position(coord, Self::center_coord()))
+    }
+
     /// Convert a Coordinate to a generic MIDI pitch (0-127).
     /// Default implementation maps `center_coord()` to 60.
     fn coord_to_midi(coord: Coordinate) -> u8 {
@@ -57,9 +188,9 @@ pub trait Layout: Sync {
 }
 
 /// Helper to generate a reverse lookup table from a matrix at compile time.
+/// Entries equal to [`NO_LED`] or past `NUM_LEDS` are skipped.
 pub const fn build_reversed_lookup<const ROWS: usize, const COLS: usize, const NUM_LEDS: usize>(
-    matrix: [[u8; COLS]; ROWS],
-    no_led: u8,
+    matrix: [[LedMatrixEntry; COLS]; ROWS],
 ) -> [Coordinate; NUM_LEDS] {
     let mut lookup = [Coordinate { x: 0, y: 0 }; NUM_LEDS];
     let mut r = 0;
@@ -67,7 +198,7 @@ pub const fn build_reversed_lookup<const ROWS: usize, const COLS: usize, const N
         let mut c = 0;
         while c < COLS {
             let led_idx = matrix[r][c];
-            if led_idx != no_led {
+            if led_idx != NO_LED {
                 let idx = led_idx as usize;
                 if idx < NUM_LEDS {
                     lookup[idx] = Coordinate {
@@ -82,3 +213,84 @@ pub const fn build_reversed_lookup<const ROWS: usize, const COLS: usize, const N
     }
     lookup
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: Coordinate = Coordinate { x: 0, y: 0 };
+
+    #[test]
+    fn test_neighbors_are_one_step_away() {
+        let neighbors: Vec<_> = ORIGIN.neighbors().collect();
+        assert_eq!(neighbors.len(), 6);
+        assert!(neighbors.iter().all(|&n| ORIGIN.distance(n) == 1));
+        // The fifth is adjacent, the minor third (x + 1, y + 1) is not
+        assert!(neighbors.contains(&Coordinate { x: 1, y: -1 }));
+        assert!(!neighbors.contains(&Coordinate { x: 1, y: 1 }));
+
+        let corner = Coordinate { x: 127, y: -128 };
+        assert_eq!(corner.neighbors().count(), 3);
+    }
+
+    #[test]
+    fn test_distance() {
+        let a = Coordinate { x: 2, y: -1 };
+        let b = Coordinate { x: -1, y: 3 };
+        assert_eq!(a.distance(b), b.distance(a));
+        assert_eq!(a.distance(a), 0);
+        // Two fifths up is two steps, a minor third is two (right, then down-right)
+        assert_eq!(ORIGIN.distance(Coordinate { x: 2, y: -2 }), 2);
+        assert_eq!(ORIGIN.distance(Coordinate { x: 1, y: 1 }), 2);
+    }
+
+    #[test]
+    fn test_rings() {
+        assert_eq!(ORIGIN.ring(0).collect::<Vec<_>>(), [ORIGIN]);
+        for radius in 1..4 {
+            let ring: Vec<_> = ORIGIN.ring(radius).collect();
+            assert_eq!(ring.len(), 6 * radius as usize);
+            assert!(ring.iter().all(|&c| ORIGIN.distance(c) == radius as u16));
+            // Consecutive coordinates touch, so the ring is walked in order
+            for pair in ring.windows(2) {
+                assert_eq!(pair[0].distance(pair[1]), 1);
+            }
+            for (i, c) in ring.iter().enumerate() {
+                assert!(!ring[i + 1..].contains(c));
+            }
+        }
+    }
+
+    #[test]
+    fn test_geometry_position() {
+        let geometry = Geometry {
+            step_x: (16.0, -6.0),
+            step_y: (8.0, 15.0),
+            rotation_deg: 21.0,
+            key_shape: KeyShape::Square,
+            key_size_mm: 16.5,
+        };
+        let origin = Coordinate { x: 3, y: 2 };
+        let at = |x, y| geometry.position(Coordinate { x, y }, origin);
+        assert_eq!((at(3, 2).x_mm, at(3, 2).y_mm), (0.0, 0.0));
+        // A fifth is one step right and one up
+        let fifth = at(4, 1);
+        assert_eq!((fifth.x_mm, fifth.y_mm), (8.0, -21.0));
+        assert_eq!(fifth.rotation_deg, 21.0);
+
+        let mut out = String::new();
+        geometry.write_descriptor(&mut out).unwrap();
+        assert_eq!(
+            out,
+            "shape=square size=16.50 step_x=16.000,-6.000 step_y=8.000,15.000 rotation=21.00"
+        );
+    }
+
+    #[test]
+    fn test_reversed_lookup_beyond_u8() {
+        let matrix = [[NO_LED, 300], [0, 1]];
+        let lookup = build_reversed_lookup::<2, 2, 301>(matrix);
+        assert_eq!(lookup[300], Coordinate { x: 1, y: 0 });
+        assert_eq!(lookup[1], Coordinate { x: 1, y: 1 });
+    }
+}
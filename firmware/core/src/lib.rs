@@ -1,4 +1,43 @@
 #![cfg_attr(not(test), no_std)]
 
+pub mod arp;
+pub mod banks;
+pub mod battery;
+pub mod cc_map;
+pub mod color;
+pub mod config;
+pub mod debounce;
+pub mod echo;
+pub mod harmony;
+pub mod interval;
+pub mod jitter;
 pub mod layout;
+pub mod matrix;
+pub mod midi_stream;
+pub mod modulation;
+pub mod nrpn;
 pub mod pitch;
+pub mod practice;
+pub mod recording;
+pub mod release;
+pub mod repeat;
+pub mod rhythm;
+pub mod rng;
+pub mod scala;
+pub mod schedule;
+pub mod screen;
+pub mod sequence;
+pub mod settle;
+pub mod sparkline;
+pub mod spelling;
+pub mod storage;
+pub mod supply;
+pub mod sysex;
+pub mod thru;
+pub mod transfer;
+pub mod tuning;
+pub mod usb_host;
+pub mod usb_midi;
+pub mod velocity;
+pub mod wear;
+pub mod zones;
@@ -0,0 +1,68 @@
+//! The pressed state of every matrix position as one plain value, so a reader gets
+//! the whole matrix from a single moment.
+
+/// Matrix positions held; enough for every board.
+pub const MATRIX_KEYS: usize = 256;
+
+/// One bit per matrix position, row by row.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyMatrix {
+    bits: [u32; MATRIX_KEYS / 32],
+}
+
+impl KeyMatrix {
+    pub const EMPTY: Self = Self {
+        bits: [0; MATRIX_KEYS / 32],
+    };
+
+    /// Positions past [`MATRIX_KEYS`] are ignored.
+    pub fn set(&mut self, index: usize, pressed: bool) {
+        let Some(word) = self.bits.get_mut(index / 32) else {
+            return;
+        };
+        let bit = 1 << (index % 32);
+        if pressed {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    pub fn is_pressed(&self, index: usize) -> bool {
+        self.bits
+            .get(index / 32)
+            .is_some_and(|word| word & (1 << (index % 32)) != 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.bits.iter().map(|word| word.count_ones()).sum()
+    }
+
+    /// Indexes of the pressed positions, in order.
+    pub fn pressed(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..MATRIX_KEYS).filter(|&i| self.is_pressed(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_matrix() {
+        let mut matrix = KeyMatrix::EMPTY;
+        assert!(matrix.is_empty());
+        matrix.set(3, true);
+        matrix.set(40, true);
+        matrix.set(MATRIX_KEYS, true);
+        assert!(matrix.is_pressed(40));
+        assert!(!matrix.is_pressed(MATRIX_KEYS));
+        assert_eq!(matrix.pressed().collect::<Vec<_>>(), [3, 40]);
+        matrix.set(3, false);
+        assert_eq!(matrix.count(), 1);
+    }
+}
@@ -0,0 +1,203 @@
+/// A complete MIDI message out of [`StreamParser`]: status and up to two data bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Message {
+    bytes: [u8; 3],
+    len: u8,
+}
+
+impl Message {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+/// Data bytes following `status`, or `None` for bytes that don't start a message.
+fn data_len(status: u8) -> Option<u8> {
+    match status {
+        0x80..=0xBF | 0xE0..=0xEF => Some(2),
+        0xC0..=0xDF => Some(1),
+        0xF1 | 0xF3 => Some(1),
+        0xF2 => Some(2),
+        0xF6 | 0xF8..=0xFF => Some(0),
+        _ => None,
+    }
+}
+
+/// Splits a MIDI byte stream (UART, or USB packet payloads) into messages.
+///
+/// Handles running status for channel messages, real-time bytes anywhere (even
+/// between a status and its data), and 1-data-byte messages. SysEx contents are
+/// skipped; SysEx is assembled separately. Stray data bytes are dropped.
+#[derive(Clone, Debug, Default)]
+pub struct StreamParser {
+    /// Status of the message being received; kept after channel messages for
+    /// running status.
+    status: Option<u8>,
+    data: [u8; 2],
+    received: u8,
+    in_sysex: bool,
+}
+
+impl StreamParser {
+    pub const fn new() -> Self {
+        Self {
+            status: None,
+            data: [0; 2],
+            received: 0,
+            in_sysex: false,
+        }
+    }
+
+    /// Takes the next byte, returning a message once one is complete.
+    pub fn feed(&mut self, byte: u8) -> Option<Message> {
+        if byte >= 0xF8 {
+            // Real-time: passes through without touching the message in progress
+            return Some(Message {
+                bytes: [byte, 0, 0],
+                len: 1,
+            });
+        }
+        if byte & 0x80 != 0 {
+            self.received = 0;
+            self.in_sysex = byte == 0xF0;
+            self.status = data_len(byte).map(|_| byte);
+            return match self.status {
+                Some(0xF6) => {
+                    self.status = None;
+                    Some(Message {
+                        bytes: [byte, 0, 0],
+                        len: 1,
+                    })
+                }
+                _ => None,
+            };
+        }
+        if self.in_sysex {
+            return None;
+        }
+
+        let status = self.status?;
+        let needed = data_len(status)?;
+        self.data[self.received as usize] = byte;
+        self.received += 1;
+        if self.received < needed {
+            return None;
+        }
+        self.received = 0;
+        if status >= 0xF0 {
+            // System common messages cancel running status
+            self.status = None;
+        }
+        Some(Message {
+            bytes: [status, self.data[0], self.data[1]],
+            len: 1 + needed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut parser = StreamParser::new();
+        bytes
+            .iter()
+            .filter_map(|&b| parser.feed(b))
+            .map(|m| m.as_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_complete_messages() {
+        assert_eq!(
+            parse(&[0x90, 60, 100, 0x80, 60, 0, 0xE1, 0x00, 0x40]),
+            [
+                [0x90, 60, 100].to_vec(),
+                [0x80, 60, 0].to_vec(),
+                [0xE1, 0x00, 0x40].to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_running_status() {
+        assert_eq!(
+            parse(&[0x93, 60, 100, 64, 100, 60, 0]),
+            [
+                [0x93, 60, 100].to_vec(),
+                [0x93, 64, 100].to_vec(),
+                [0x93, 60, 0].to_vec()
+            ]
+        );
+        assert_eq!(
+            parse(&[0xC2, 5, 6, 7]),
+            [[0xC2, 5].to_vec(), [0xC2, 6].to_vec(), [0xC2, 7].to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_two_byte_messages() {
+        assert_eq!(
+            parse(&[0xC0, 12, 0xD5, 90, 0xB0, 1, 2]),
+            [
+                [0xC0, 12].to_vec(),
+                [0xD5, 90].to_vec(),
+                [0xB0, 1, 2].to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_real_time_interleaved() {
+        // Clock between status and data, and between the data bytes
+        assert_eq!(
+            parse(&[0x90, 0xF8, 60, 0xFE, 100, 0xFA, 62, 0xFC, 100]),
+            [
+                [0xF8].to_vec(),
+                [0xFE].to_vec(),
+                [0x90, 60, 100].to_vec(),
+                [0xFA].to_vec(),
+                [0xFC].to_vec(),
+                [0x90, 62, 100].to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_system_common() {
+        // Song position, then data that no longer has a running status
+        assert_eq!(
+            parse(&[0xF2, 0x10, 0x01, 0x40, 0xF3, 3, 0xF6, 0xF1, 0x21]),
+            [
+                [0xF2, 0x10, 0x01].to_vec(),
+                [0xF3, 3].to_vec(),
+                [0xF6].to_vec(),
+                [0xF1, 0x21].to_vec()
+            ]
+        );
+        // Undefined system common bytes cancel the message in progress
+        assert_eq!(parse(&[0x90, 60, 0xF4, 100, 0xF5]), Vec::<Vec<u8>>::new());
+    }
+
+    #[test]
+    fn test_sysex_skipped() {
+        assert_eq!(
+            parse(&[0x90, 60, 100, 0xF0, 0x7D, 0x20, 0xF8, 1, 0xF7, 60, 0, 0x80, 60, 0]),
+            [
+                [0x90, 60, 100].to_vec(),
+                [0xF8].to_vec(),
+                [0x80, 60, 0].to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stray_data_and_interrupted_messages() {
+        // Data before any status, and a status cut short by a new one
+        assert_eq!(
+            parse(&[1, 2, 3, 0x90, 60, 0xB0, 7, 100]),
+            [[0xB0, 7, 100].to_vec()]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+/// LFO waveforms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Triangle,
+    Sine,
+    /// Rising ramp that jumps back at the end of each period.
+    Saw,
+    Square,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 4] = [Shape::Triangle, Shape::Sine, Shape::Saw, Shape::Square];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shape::Triangle => "Triangle",
+            Shape::Sine => "Sine",
+            Shape::Saw => "Saw",
+            Shape::Square => "Square",
+        }
+    }
+
+    /// Value at `phase` (in cycles, only the fraction counts), from -1.0 to 1.0.
+    /// Every shape starts its cycle at 0 or -1 and rises first.
+    pub fn value(self, phase: f32) -> f32 {
+        let mut p = phase - (phase as i32) as f32;
+        if p < 0.0 {
+            p += 1.0;
+        }
+        match self {
+            Shape::Triangle => {
+                if p < 0.25 {
+                    4.0 * p
+                } else if p < 0.75 {
+                    2.0 - 4.0 * p
+                } else {
+                    4.0 * p - 4.0
+                }
+            }
+            // Parabolic half-waves: within 6% of a sine, without libm
+            Shape::Sine => {
+                if p < 0.5 {
+                    8.0 * p * (1.0 - 2.0 * p)
+                } else {
+                    let q = p - 0.5;
+                    -8.0 * q * (1.0 - 2.0 * q)
+                }
+            }
+            Shape::Saw => 2.0 * p - 1.0,
+            Shape::Square => {
+                if p < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+        }
+    }
+}
+
+/// A beat-synced LFO.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lfo {
+    pub shape: Shape,
+    pub period_beats: f32,
+    /// Peak offset, in the modulated setting's units.
+    pub depth: f32,
+}
+
+impl Lfo {
+    /// Offset `beats` into the clock.
+    pub fn value(&self, beats: f32) -> f32 {
+        self.depth * self.shape.value(beats / self.period_beats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shapes() {
+        for shape in Shape::ALL {
+            for i in -40..40 {
+                let v = shape.value(i as f32 * 0.05);
+                assert!((-1.0..=1.0).contains(&v), "{:?} {}", shape, v);
+            }
+        }
+        assert_eq!(Shape::Triangle.value(0.25), 1.0);
+        assert_eq!(Shape::Triangle.value(0.75), -1.0);
+        assert_eq!(Shape::Sine.value(0.25), 1.0);
+        assert_eq!(Shape::Sine.value(1.75), -1.0);
+        assert_eq!(Shape::Saw.value(0.0), -1.0);
+        assert_eq!(Shape::Saw.value(-0.25), 0.5);
+        assert_eq!(Shape::Square.value(0.6), -1.0);
+    }
+
+    #[test]
+    fn test_lfo() {
+        let lfo = Lfo {
+            shape: Shape::Triangle,
+            period_beats: 4.0,
+            depth: 3.0,
+        };
+        assert_eq!(lfo.value(0.0), 0.0);
+        assert_eq!(lfo.value(1.0), 3.0);
+        assert_eq!(lfo.value(7.0), -3.0);
+    }
+}
@@ -0,0 +1,124 @@
+//! Incoming NRPNs: the CC 99/98 parameter select and CC 6/38 data entry sequence, and
+//! data increment/decrement (CC 96/97), put back together from one channel's CCs.
+
+/// Controllers taking part in an NRPN.
+pub mod cc {
+    pub const DATA_MSB: u8 = 6;
+    pub const DATA_LSB: u8 = 38;
+    pub const INCREMENT: u8 = 96;
+    pub const DECREMENT: u8 = 97;
+    pub const PARAM_LSB: u8 = 98;
+    pub const PARAM_MSB: u8 = 99;
+    pub const RPN_LSB: u8 = 100;
+    pub const RPN_MSB: u8 = 101;
+}
+
+/// What arrived for the selected parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Data {
+    /// Data entry MSB, as a 14-bit value with the LSB cleared.
+    Entry(u16),
+    /// Data entry LSB, refining the last entry.
+    Fine(u16),
+    /// Data increment (1) or decrement (-1).
+    Step(i8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NrpnInput {
+    /// 14-bit parameter number.
+    pub param: u16,
+    pub data: Data,
+}
+
+/// One channel's NRPN state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NrpnParser {
+    param_msb: Option<u8>,
+    param_lsb: Option<u8>,
+    data_msb: u8,
+}
+
+impl NrpnParser {
+    pub const fn new() -> Self {
+        Self {
+            param_msb: None,
+            param_lsb: None,
+            data_msb: 0,
+        }
+    }
+
+    /// Parameter selected, once both halves arrived. Selecting an RPN, or the null
+    /// parameter 127/127, deselects it.
+    pub fn param(&self) -> Option<u16> {
+        match (self.param_msb?, self.param_lsb?) {
+            (0x7F, 0x7F) => None,
+            (msb, lsb) => Some((msb as u16) << 7 | lsb as u16),
+        }
+    }
+
+    /// Feeds a CC; returns the data it carries for the selected parameter, if any.
+    pub fn feed(&mut self, control: u8, value: u8) -> Option<NrpnInput> {
+        let value = value & 0x7F;
+        let data = match control {
+            cc::PARAM_MSB => {
+                self.param_msb = Some(value);
+                self.data_msb = 0;
+                return None;
+            }
+            cc::PARAM_LSB => {
+                self.param_lsb = Some(value);
+                self.data_msb = 0;
+                return None;
+            }
+            cc::RPN_MSB | cc::RPN_LSB => {
+                *self = Self::new();
+                return None;
+            }
+            cc::DATA_MSB => {
+                self.data_msb = value;
+                Data::Entry((value as u16) << 7)
+            }
+            cc::DATA_LSB => Data::Fine((self.data_msb as u16) << 7 | value as u16),
+            cc::INCREMENT => Data::Step(1),
+            cc::DECREMENT => Data::Step(-1),
+            _ => return None,
+        };
+        Some(NrpnInput {
+            param: self.param()?,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nrpn_parser() {
+        let mut parser = NrpnParser::new();
+        // Data entry before a parameter is selected goes nowhere
+        assert_eq!(parser.feed(cc::DATA_MSB, 10), None);
+        assert_eq!(parser.feed(cc::PARAM_MSB, 1), None);
+        assert_eq!(parser.feed(cc::DATA_MSB, 10), None);
+        assert_eq!(parser.feed(cc::PARAM_LSB, 3), None);
+        let input = |data| Some(NrpnInput { param: 131, data });
+        assert_eq!(parser.feed(cc::DATA_MSB, 65), input(Data::Entry(65 << 7)));
+        assert_eq!(parser.feed(cc::DATA_LSB, 5), input(Data::Fine(65 << 7 | 5)));
+        assert_eq!(parser.feed(cc::INCREMENT, 0), input(Data::Step(1)));
+        assert_eq!(parser.feed(cc::DECREMENT, 0), input(Data::Step(-1)));
+        // Other CCs pass by
+        assert_eq!(parser.feed(7, 100), None);
+        assert_eq!(parser.param(), Some(131));
+        // The null parameter and RPNs deselect it
+        parser.feed(cc::PARAM_MSB, 0x7F);
+        parser.feed(cc::PARAM_LSB, 0x7F);
+        assert_eq!(parser.feed(cc::INCREMENT, 0), None);
+        parser.feed(cc::PARAM_MSB, 0);
+        parser.feed(cc::PARAM_LSB, 2);
+        assert_eq!(parser.param(), Some(2));
+        parser.feed(cc::RPN_MSB, 0);
+        assert_eq!(parser.param(), None);
+    }
+}
@@ -8,6 +8,30 @@ pub struct PitchClass(pub u32);
 const MICRO_CENTS_PER_SEMITONE: u32 = 100_000_000;
 const MICRO_CENTS_PER_OCTAVE: u32 = 12 * MICRO_CENTS_PER_SEMITONE;
 
+/// Names of the twelve pitch classes, starting at C.
+pub const PITCH_CLASS_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Writes the pitch classes set in `mask` (bit 0 = C) as space-separated names,
+/// or "None".
+pub fn write_pitch_classes(out: &mut impl core::fmt::Write, mask: u16) -> core::fmt::Result {
+    let mut first = true;
+    for (pc, name) in PITCH_CLASS_NAMES.iter().enumerate() {
+        if mask & (1 << pc) != 0 {
+            if !first {
+                out.write_char(' ')?;
+            }
+            out.write_str(name)?;
+            first = false;
+        }
+    }
+    if first {
+        out.write_str("None")?;
+    }
+    Ok(())
+}
+
 impl PitchClass {
     /// Creates a new PitchClass from a floating point semitone value (0.0 - 11.999...).
     pub fn from_f32(val: f32) -> Self {
@@ -71,6 +95,17 @@ impl Pitch {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_write_pitch_classes() {
+        let mut out = String::new();
+        write_pitch_classes(&mut out, 0x081).unwrap();
+        assert_eq!(out, "C G");
+
+        let mut out = String::new();
+        write_pitch_classes(&mut out, 0).unwrap();
+        assert_eq!(out, "None");
+    }
+
     #[test]
     fn test_pitch_class_normalization() {
         // Basic range
@@ -0,0 +1,91 @@
+//! Practice log: how much of a session was spent playing, and what it covered.
+
+/// Gaps between notes longer than this are a break rather than playing.
+pub const BREAK_MS: u64 = 30_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Session {
+    pub notes: u32,
+    /// Pitch classes played, bit 0 for C.
+    pub pitch_classes: u16,
+    /// Notes played of each pitch class, C first.
+    pub counts: [u32; 12],
+    /// Time spent playing: the gaps between notes, breaks left out.
+    pub playing_ms: u64,
+    last_note_ms: Option<u64>,
+}
+
+impl Session {
+    pub const fn new() -> Self {
+        Self {
+            notes: 0,
+            pitch_classes: 0,
+            counts: [0; 12],
+            playing_ms: 0,
+            last_note_ms: None,
+        }
+    }
+
+    /// Records a note of `pitch_class` (0 for C) played at `now_ms`.
+    pub fn note(&mut self, now_ms: u64, pitch_class: u8) {
+        self.notes = self.notes.saturating_add(1);
+        self.pitch_classes |= 1 << (pitch_class % 12);
+        let count = &mut self.counts[(pitch_class % 12) as usize];
+        *count = count.saturating_add(1);
+        if let Some(last) = self.last_note_ms {
+            let gap = now_ms.saturating_sub(last);
+            if gap <= BREAK_MS {
+                self.playing_ms += gap;
+            }
+        }
+        self.last_note_ms = Some(now_ms);
+    }
+
+    pub fn playing_minutes(&self) -> u32 {
+        (self.playing_ms / 60_000) as u32
+    }
+
+    /// How often `pitch_class` was played next to the most played one, 0 to 1.
+    pub fn heat(&self, pitch_class: u8) -> f32 {
+        let most = self.counts.iter().copied().max().unwrap_or(0);
+        match most {
+            0 => 0.0,
+            most => self.counts[(pitch_class % 12) as usize] as f32 / most as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::new();
+        session.note(1_000, 0);
+        // The first note starts the clock
+        assert_eq!(session.playing_ms, 0);
+        session.note(61_000 - BREAK_MS, 7);
+        session.note(61_000, 12);
+        assert_eq!(session.playing_ms, 60_000);
+        assert_eq!(session.playing_minutes(), 1);
+        // A break doesn't count
+        session.note(61_001 + BREAK_MS, 4);
+        assert_eq!(session.playing_ms, 60_000);
+        assert_eq!(session.notes, 4);
+        assert_eq!(session.pitch_classes, 0b1001_0001);
+    }
+
+    #[test]
+    fn test_heat() {
+        let mut session = Session::new();
+        assert_eq!(session.heat(0), 0.0);
+        for (i, pitch_class) in [0, 7, 0, 12, 4, 0].into_iter().enumerate() {
+            session.note(i as u64 * 1_000, pitch_class);
+        }
+        assert_eq!(session.counts[0], 4);
+        assert_eq!(session.heat(0), 1.0);
+        assert_eq!(session.heat(7), 0.25);
+        assert_eq!(session.heat(2), 0.0);
+    }
+}
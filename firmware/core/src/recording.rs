@@ -0,0 +1,116 @@
+/// Number of stored command macros.
+pub const MACRO_SLOTS: usize = 8;
+/// Bytes each slot takes in flash.
+pub const SLOT_SIZE: usize = 128;
+/// Longest macro: a slot minus its marker and length bytes.
+pub const MACRO_LEN: usize = SLOT_SIZE - 2;
+
+/// First byte of a written slot; erased flash reads 0xFF.
+const SLOT_MARKER: u8 = 0x4D;
+
+/// Collects console command bytes into a macro.
+pub struct Recorder {
+    buf: [u8; MACRO_LEN],
+    len: usize,
+    recording: bool,
+}
+
+impl Recorder {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MACRO_LEN],
+            len: 0,
+            recording: false,
+        }
+    }
+
+    /// Starts a new recording, dropping the previous one.
+    pub fn start(&mut self) {
+        self.len = 0;
+        self.recording = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Records a command byte. Returns false (and stops recording) once the macro is full.
+    pub fn push(&mut self, b: u8) -> bool {
+        if !self.recording {
+            return true;
+        }
+        if self.len == MACRO_LEN {
+            self.recording = false;
+            return false;
+        }
+        self.buf[self.len] = b;
+        self.len += 1;
+        true
+    }
+
+    /// The bytes recorded so far.
+    pub fn recorded(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Flash image of a slot holding `bytes`, cut off at [`MACRO_LEN`].
+pub fn encode_slot(bytes: &[u8]) -> [u8; SLOT_SIZE] {
+    let len = bytes.len().min(MACRO_LEN);
+    let mut slot = [0xFF; SLOT_SIZE];
+    slot[0] = SLOT_MARKER;
+    slot[1] = len as u8;
+    slot[2..2 + len].copy_from_slice(&bytes[..len]);
+    slot
+}
+
+/// The macro stored in a slot, or `None` if it was never written.
+pub fn decode_slot(slot: &[u8; SLOT_SIZE]) -> Option<&[u8]> {
+    let len = slot[1] as usize;
+    (slot[0] == SLOT_MARKER && len <= MACRO_LEN).then(|| &slot[2..2 + len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorder() {
+        let mut recorder = Recorder::new();
+        recorder.push(b'x');
+        assert_eq!(recorder.recorded(), b"");
+
+        recorder.start();
+        for &b in b"tLL" {
+            assert!(recorder.push(b));
+        }
+        recorder.stop();
+        recorder.push(b'x');
+        assert_eq!(recorder.recorded(), b"tLL");
+
+        recorder.start();
+        for _ in 0..MACRO_LEN {
+            assert!(recorder.push(b'+'));
+        }
+        assert!(!recorder.push(b'+'));
+        assert!(!recorder.is_recording());
+        assert_eq!(recorder.recorded().len(), MACRO_LEN);
+    }
+
+    #[test]
+    fn test_slot_round_trip() {
+        assert_eq!(decode_slot(&encode_slot(b"(((ee")), Some(&b"(((ee"[..]));
+        assert_eq!(decode_slot(&encode_slot(b"")), Some(&b""[..]));
+        assert_eq!(decode_slot(&[0xFF; SLOT_SIZE]), None);
+    }
+}
@@ -0,0 +1,166 @@
+/// A note identified by (channel index 0-15, note number).
+pub type NoteKey = (u8, u8);
+
+/// Small fixed-capacity set of notes.
+struct NoteSet<const N: usize> {
+    items: [NoteKey; N],
+    len: usize,
+}
+
+impl<const N: usize> NoteSet<N> {
+    const fn new() -> Self {
+        Self {
+            items: [(0, 0); N],
+            len: 0,
+        }
+    }
+
+    fn contains(&self, key: NoteKey) -> bool {
+        self.items[..self.len].contains(&key)
+    }
+
+    /// Returns false if the set is full.
+    fn insert(&mut self, key: NoteKey) -> bool {
+        if self.contains(key) {
+            return true;
+        }
+        if self.len == N {
+            return false;
+        }
+        self.items[self.len] = key;
+        self.len += 1;
+        true
+    }
+
+    /// Returns true if the key was present.
+    fn remove(&mut self, key: NoteKey) -> bool {
+        if let Some(i) = self.items[..self.len].iter().position(|&k| k == key) {
+            self.len -= 1;
+            self.items[i] = self.items[self.len];
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Keeps prioritised NoteOffs from overtaking their own NoteOn.
+///
+/// NoteOffs may be sent ahead of the main event queue. If one arrives for a note whose
+/// NoteOn hasn't been sent yet while the main queue still holds events, the NoteOff is
+/// held back until that NoteOn has gone and then goes right behind it, so a quick tap
+/// is still heard and the note never gets stuck. `T` is whatever the caller needs to
+/// send the held-back NoteOff later.
+pub struct ReleaseGuard<T, const N: usize> {
+    sounding: NoteSet<N>,
+    held: [Option<(NoteKey, T)>; N],
+}
+
+impl<T: Copy, const N: usize> ReleaseGuard<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            sounding: NoteSet::new(),
+            held: [None; N],
+        }
+    }
+
+    /// Call before sending a NoteOn. Returns the NoteOff held back for it, to send
+    /// right after it.
+    pub fn on_note_on(&mut self, key: NoteKey) -> Option<T> {
+        let held = self
+            .held
+            .iter_mut()
+            .find(|h| matches!(h, Some((k, _)) if *k == key));
+        if let Some((_, release)) = held.and_then(Option::take) {
+            return Some(release);
+        }
+        // If full we simply stop tracking; NoteOffs for untracked notes still go out
+        let _ = self.sounding.insert(key);
+        None
+    }
+
+    /// Call before sending a NoteOff. `queue_busy` is true when the NoteOff skipped ahead
+    /// of a non-empty main queue. Returns `release` back if it should go now, or `None`
+    /// if it's held back for its NoteOn.
+    pub fn on_note_off(&mut self, key: NoteKey, release: T, queue_busy: bool) -> Option<T> {
+        if self.sounding.remove(key) || !queue_busy {
+            return Some(release);
+        }
+        // Its NoteOn may still be queued (send it anyway if we can't hold it)
+        match self.held.iter_mut().find(|h| h.is_none()) {
+            Some(slot) => {
+                *slot = Some((key, release));
+                None
+            }
+            None => Some(release),
+        }
+    }
+
+    /// Call whenever the main queue is empty: any NoteOn a held-back NoteOff was
+    /// waiting for has been processed by now. Returns those NoteOffs one per call, to
+    /// send anyway.
+    pub fn on_queue_drained(&mut self) -> Option<T> {
+        self.held
+            .iter_mut()
+            .find_map(Option::take)
+            .map(|(_, release)| release)
+    }
+}
+
+impl<T: Copy, const N: usize> Default for ReleaseGuard<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_order_release() {
+        let mut guard = ReleaseGuard::<char, 4>::new();
+        assert_eq!(guard.on_note_on((0, 60)), None);
+        assert_eq!(guard.on_note_off((0, 60), 'a', true), Some('a'));
+    }
+
+    #[test]
+    fn test_overtaking_release_follows_its_note_on() {
+        let mut guard = ReleaseGuard::<char, 4>::new();
+        // NoteOff jumps ahead of its own NoteOn still in the main queue
+        assert_eq!(guard.on_note_off((0, 60), 'a', true), None);
+        // The tap still plays: the release goes right behind its NoteOn
+        assert_eq!(guard.on_note_on((0, 60)), Some('a'));
+        // Next press plays normally
+        assert_eq!(guard.on_note_on((0, 60)), None);
+        assert_eq!(guard.on_note_off((0, 60), 'b', true), Some('b'));
+    }
+
+    #[test]
+    fn test_stray_release_with_idle_queue_is_sent() {
+        let mut guard = ReleaseGuard::<char, 4>::new();
+        assert_eq!(guard.on_note_off((3, 64), 'a', false), Some('a'));
+        assert_eq!(guard.on_note_on((3, 64)), None);
+    }
+
+    #[test]
+    fn test_drain_sends_held_releases() {
+        let mut guard = ReleaseGuard::<char, 4>::new();
+        assert_eq!(guard.on_note_off((0, 60), 'a', true), None);
+        assert_eq!(guard.on_note_off((0, 62), 'b', true), None);
+        let mut drained = [guard.on_queue_drained(), guard.on_queue_drained()];
+        drained.sort();
+        assert_eq!(drained, [Some('a'), Some('b')]);
+        assert_eq!(guard.on_queue_drained(), None);
+        assert_eq!(guard.on_note_on((0, 60)), None);
+    }
+
+    #[test]
+    fn test_full_guard_never_drops_release() {
+        let mut guard = ReleaseGuard::<char, 1>::new();
+        assert_eq!(guard.on_note_off((0, 60), 'a', true), None);
+        // No room to hold a second release back, so it goes now
+        assert_eq!(guard.on_note_off((0, 61), 'b', true), Some('b'));
+        assert_eq!(guard.on_note_on((0, 60)), Some('a'));
+    }
+}
@@ -0,0 +1,89 @@
+/// A continuous value on a channel: a CC number, or [`BEND`] for pitch bend.
+pub type ValueKey = (u8, u8);
+
+/// Stands in for the controller number of pitch bend, past the CC range.
+pub const BEND: u8 = 0x80;
+
+/// The last value sent for recent controllers, so a modulation source updating
+/// faster than its value changes doesn't resend the same value over and over.
+pub struct RepeatFilter<const N: usize> {
+    /// Controllers with the value last sent and when, in ms; the oldest is
+    /// overwritten when full.
+    sent: [Option<(ValueKey, u16, u64)>; N],
+    next: usize,
+}
+
+impl<const N: usize> RepeatFilter<N> {
+    pub const fn new() -> Self {
+        Self {
+            sent: [None; N],
+            next: 0,
+        }
+    }
+
+    fn find(&self, key: ValueKey) -> Option<usize> {
+        self.sent
+            .iter()
+            .position(|s| s.is_some_and(|(k, _, _)| k == key))
+    }
+
+    /// Notes `value` sent for `key` at `now_ms`.
+    pub fn record(&mut self, key: ValueKey, value: u16, now_ms: u64) {
+        let slot = self.find(key).unwrap_or_else(|| {
+            let i = self.next;
+            self.next = (self.next + 1) % N;
+            i
+        });
+        self.sent[slot] = Some((key, value, now_ms));
+    }
+
+    /// Whether `value` is what was last sent for `key`, at most `window_ms` before
+    /// `now_ms`. Repeats don't restart the window, so an unchanging value still goes
+    /// out once per window.
+    pub fn is_repeat(&self, key: ValueKey, value: u16, now_ms: u64, window_ms: u32) -> bool {
+        self.find(key).is_some_and(|i| {
+            self.sent[i].is_some_and(|(_, v, at)| {
+                v == value && now_ms.saturating_sub(at) <= window_ms as u64
+            })
+        })
+    }
+
+    /// Forgets what was sent for `key`, for a send that may not have arrived.
+    pub fn forget(&mut self, key: ValueKey) {
+        if let Some(i) = self.find(key) {
+            self.sent[i] = None;
+        }
+    }
+}
+
+impl<const N: usize> Default for RepeatFilter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeat() {
+        let mut filter = RepeatFilter::<2>::new();
+        filter.record((0, BEND), 8192, 1000);
+        assert!(filter.is_repeat((0, BEND), 8192, 1010, 50));
+        assert!(!filter.is_repeat((0, BEND), 8200, 1010, 50));
+        assert!(!filter.is_repeat((1, BEND), 8192, 1010, 50));
+        // Once the window passes the value goes out again
+        assert!(!filter.is_repeat((0, BEND), 8192, 1051, 50));
+
+        filter.forget((0, BEND));
+        assert!(!filter.is_repeat((0, BEND), 8192, 1010, 50));
+
+        // The oldest controller makes room
+        filter.record((0, 1), 64, 2000);
+        filter.record((0, 7), 100, 2000);
+        filter.record((0, 11), 127, 2000);
+        assert!(!filter.is_repeat((0, 1), 64, 2001, 50));
+        assert!(filter.is_repeat((0, 11), 127, 2001, 50));
+    }
+}
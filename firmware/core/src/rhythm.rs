@@ -0,0 +1,60 @@
+/// Maximum number of steps a rhythm pattern can hold (one bit per step).
+pub const MAX_STEPS: u8 = 32;
+
+/// Computes an Euclidean rhythm: `pulses` onsets spread as evenly as possible over `steps`.
+/// Returns a bitmask where bit `i` is set if step `i` is a pulse. The first step is always
+/// a pulse (unless `pulses` is 0).
+///
+/// `steps` is clamped to 1..=MAX_STEPS and `pulses` to 0..=steps.
+pub fn euclidean(pulses: u8, steps: u8) -> u32 {
+    let steps = steps.clamp(1, MAX_STEPS) as u32;
+    let pulses = (pulses as u32).min(steps);
+
+    let mut pattern = 0u32;
+    for i in 0..steps {
+        if (i * pulses) % steps < pulses {
+            pattern |= 1 << i;
+        }
+    }
+    pattern
+}
+
+/// Returns true if step `step` of `pattern` is a pulse.
+pub fn is_pulse(pattern: u32, step: u8) -> bool {
+    step < MAX_STEPS && (pattern >> step) & 1 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_euclidean_known_patterns() {
+        // Tresillo: x..x..x.
+        assert_eq!(euclidean(3, 8), 0b0100_1001);
+        // x.x.xx.x (a rotation of the cinquillo)
+        assert_eq!(euclidean(5, 8), 0b1011_0101);
+        // Four on the floor
+        assert_eq!(euclidean(4, 16), 0x1111);
+    }
+
+    #[test]
+    fn test_euclidean_edge_cases() {
+        assert_eq!(euclidean(0, 8), 0);
+        assert_eq!(euclidean(8, 8), 0xFF);
+        // More pulses than steps saturates
+        assert_eq!(euclidean(12, 8), 0xFF);
+        // Full 32 step pattern doesn't overflow
+        assert_eq!(euclidean(32, 32), u32::MAX);
+        assert_eq!(euclidean(7, 13).count_ones(), 7);
+    }
+
+    #[test]
+    fn test_is_pulse() {
+        let p = euclidean(3, 8);
+        assert!(is_pulse(p, 0));
+        assert!(!is_pulse(p, 1));
+        assert!(is_pulse(p, 3));
+        assert!(!is_pulse(p, 40));
+    }
+}
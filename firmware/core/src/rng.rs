@@ -0,0 +1,67 @@
+/// Small xorshift32 pseudo-random generator for generative modes.
+/// Not suitable for anything security related.
+#[derive(Clone, Copy, Debug)]
+pub struct Rng(u32);
+
+impl Rng {
+    /// Creates a generator from a seed. A zero seed is replaced, since xorshift
+    /// would otherwise only ever produce zeros.
+    pub fn new(seed: u32) -> Self {
+        Self(if seed == 0 { 0x9E37_79B9 } else { seed })
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// Returns a value in `0..n`. Returns 0 if `n` is 0.
+    pub fn below(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        self.next_u32() % n
+    }
+
+    /// Returns a value in `-span..=span`.
+    pub fn span(&mut self, span: i8) -> i8 {
+        let span = span.max(0) as u32;
+        (self.below(2 * span + 1) as i32 - span as i32) as i8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_zero_seed() {
+        let mut rng = Rng::new(0);
+        assert_ne!(rng.next_u32(), 0);
+    }
+
+    #[test]
+    fn test_rng_ranges() {
+        let mut rng = Rng::new(1234);
+        for _ in 0..1000 {
+            assert!(rng.below(7) < 7);
+            let s = rng.span(2);
+            assert!((-2..=2).contains(&s));
+        }
+        assert_eq!(rng.below(0), 0);
+        assert_eq!(rng.span(0), 0);
+    }
+
+    #[test]
+    fn test_rng_deterministic() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..10 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+}
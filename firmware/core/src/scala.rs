@@ -0,0 +1,179 @@
+//! Scala `.scl` scales: a description line, the number of notes, then each note's pitch
+//! above the 1/1, in cents (with a `.`) or as a ratio. The last note is the period the
+//! scale repeats at, usually the octave. Lines starting with `!` are comments.
+
+use core::f64::consts::LN_2;
+
+/// Most notes a scale can have.
+pub const MAX_NOTES: usize = 128;
+
+/// Why a scale file was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalaError {
+    /// The note count is missing or isn't a number.
+    BadCount,
+    /// More notes than [`MAX_NOTES`], or none.
+    NoteCount,
+    /// The pitch on this line (from 1) isn't cents or a positive ratio.
+    BadPitch(usize),
+    /// Fewer pitches than the count says.
+    Incomplete,
+    /// The period isn't above the 1/1, so the scale wouldn't rise.
+    BadPeriod,
+}
+
+/// A scale read from a `.scl` file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scale {
+    /// Notes above the 1/1 in cents, the period last.
+    cents: [f32; MAX_NOTES],
+    notes: usize,
+}
+
+impl Scale {
+    /// Notes per period, the period included.
+    pub fn notes(&self) -> usize {
+        self.notes
+    }
+
+    pub fn period_cents(&self) -> f32 {
+        self.cents[self.notes - 1]
+    }
+
+    /// Pitch of scale step `step` above the 1/1 (below for negative steps), in cents.
+    pub fn step_cents(&self, step: i32) -> f32 {
+        let periods = step.div_euclid(self.notes as i32);
+        let degree = step.rem_euclid(self.notes as i32) as usize;
+        let within = match degree {
+            0 => 0.0,
+            d => self.cents[d - 1],
+        };
+        periods as f32 * self.period_cents() + within
+    }
+}
+
+/// `1200 * log2(ratio)`, for `ratio > 0`, without a maths library: the exponent
+/// gives whole octaves and a series the rest.
+fn ratio_cents(ratio: f64) -> f64 {
+    let bits = ratio.to_bits();
+    let octaves = ((bits >> 52) & 0x7FF) as i64 - 1023;
+    // Mantissa in 1..2; ln(m) = 2 atanh(z) with z = (m - 1) / (m + 1) at most 1/3
+    let mantissa = f64::from_bits(bits & ((1 << 52) - 1) | 1023 << 52);
+    let z = (mantissa - 1.0) / (mantissa + 1.0);
+    let mut term = z;
+    let mut atanh = 0.0;
+    for k in 0..16 {
+        atanh += term / (2 * k + 1) as f64;
+        term *= z * z;
+    }
+    1200.0 * (octaves as f64 + 2.0 * atanh / LN_2)
+}
+
+/// Pitch in cents of a note line: its first word, cents if it has a `.`, otherwise a
+/// ratio `n/d` or a whole number.
+fn parse_pitch(line: &[u8]) -> Option<f32> {
+    let word = core::str::from_utf8(line).ok()?.split_whitespace().next()?;
+    if word.contains('.') {
+        return word.parse().ok();
+    }
+    let (numerator, denominator) = match word.split_once('/') {
+        Some((n, d)) => (n.parse::<u64>().ok()?, d.parse::<u64>().ok()?),
+        None => (word.parse::<u64>().ok()?, 1),
+    };
+    if numerator == 0 || denominator == 0 {
+        return None;
+    }
+    Some(ratio_cents(numerator as f64 / denominator as f64) as f32)
+}
+
+/// Reads a `.scl` file.
+pub fn parse(text: &[u8]) -> Result<Scale, ScalaError> {
+    let mut lines = text
+        .split(|&b| b == b'\n')
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.strip_suffix(b"\r").unwrap_or(line)))
+        .filter(|(_, line)| !line.starts_with(b"!"));
+
+    // The description can be anything, even empty
+    lines.next().ok_or(ScalaError::BadCount)?;
+    let (_, count) = lines.next().ok_or(ScalaError::BadCount)?;
+    let notes: usize = core::str::from_utf8(count)
+        .ok()
+        .and_then(|c| c.split_whitespace().next())
+        .and_then(|c| c.parse().ok())
+        .ok_or(ScalaError::BadCount)?;
+    if notes == 0 || notes > MAX_NOTES {
+        return Err(ScalaError::NoteCount);
+    }
+
+    let mut scale = Scale {
+        cents: [0.0; MAX_NOTES],
+        notes,
+    };
+    let mut pitches = lines.filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace));
+    for cents in scale.cents.iter_mut().take(notes) {
+        let (number, line) = pitches.next().ok_or(ScalaError::Incomplete)?;
+        *cents = parse_pitch(line).ok_or(ScalaError::BadPitch(number))?;
+    }
+    if scale.period_cents() <= 0.0 {
+        return Err(ScalaError::BadPeriod);
+    }
+    Ok(scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEANTONE: &[u8] = b"! meanquar.scl\r
+!\r
+1/4-comma meantone scale. Pietro Aaron's temp. (1523). 6/5 beats twice 3/2\r
+ 12\r
+!\r
+ 76.04900\r
+ 193.15686\r
+ 310.26471\r
+ 5/4\r
+ 503.42157\r
+ 579.47057\r
+ 696.57843\r
+ 25/16\r
+ 889.73529\r
+ 1006.84314\r
+ 1082.89214\r
+ 2/1\r
+";
+
+    #[test]
+    fn test_parse() {
+        let scale = parse(MEANTONE).unwrap();
+        assert_eq!(scale.notes(), 12);
+        assert_eq!(scale.period_cents(), 1200.0);
+        assert!((scale.step_cents(4) - 386.3137).abs() < 1e-3);
+        let fifth = scale.step_cents(7);
+        assert!((fifth - 696.578).abs() < 1e-3);
+        // Steps wrap around the period both ways
+        assert_eq!(scale.step_cents(12), 1200.0);
+        assert_eq!(scale.step_cents(-5), fifth - 1200.0);
+        assert_eq!(scale.step_cents(0), 0.0);
+    }
+
+    #[test]
+    fn test_ratio_cents() {
+        assert!((ratio_cents(3.0 / 2.0) - 701.955).abs() < 1e-3);
+        assert!((ratio_cents(4.0) - 2400.0).abs() < 1e-9);
+        assert!((ratio_cents(0.5) + 1200.0).abs() < 1e-9);
+        // Whole numbers are ratios over 1
+        assert_eq!(parse_pitch(b"3 ! tritave"), parse_pitch(b"3/1"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse(b"Empty\n"), Err(ScalaError::BadCount));
+        assert_eq!(parse(b"\nlots\n"), Err(ScalaError::BadCount));
+        assert_eq!(parse(b"\n0\n"), Err(ScalaError::NoteCount));
+        assert_eq!(parse(b"\n2\n100.0\n"), Err(ScalaError::Incomplete));
+        assert_eq!(parse(b"\n2\n100.0\n-3/2\n"), Err(ScalaError::BadPitch(4)));
+        assert_eq!(parse(b"\n1\n1/2\n"), Err(ScalaError::BadPeriod));
+    }
+}
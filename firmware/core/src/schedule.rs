@@ -0,0 +1,259 @@
+//! Outgoing events held back until a deadline.
+//!
+//! Events with the same key (a MIDI channel, say) leave in the order they were
+//! pushed, whatever their deadlines: one pushed behind a later one waits for it.
+//!
+//! When the schedule is full, [`Schedule::offer`] first merges the event into the
+//! last one waiting on its key if it supersedes it (a newer value of the same
+//! controller, say), then makes room by dropping a waiting event that can be spared.
+
+/// How an event behaves in a full schedule.
+pub trait Timed: Copy {
+    /// Events with the same key and merge key replace each other, the newer one
+    /// making the older pointless; `None` never merges.
+    fn merge_key(&self) -> Option<u16>;
+    /// Whether the event can be dropped under overload.
+    fn droppable(&self) -> bool;
+}
+
+/// What [`Schedule::offer`] did with an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Offered<T> {
+    Queued,
+    /// Replaced the last event waiting on its key, which it superseded.
+    Merged,
+    /// Queued in place of this waiting event, which was dropped.
+    Evicted(T),
+    /// No room and nothing to drop for it.
+    Rejected(T),
+}
+
+/// Events waiting for their deadline, in ticks of whatever clock the caller uses.
+pub struct Schedule<T, const N: usize> {
+    entries: [Option<Entry<T>>; N],
+    /// Push counter, keeping equal deadlines in push order.
+    pushed: u32,
+}
+
+#[derive(Clone, Copy)]
+struct Entry<T> {
+    at: u64,
+    seq: u32,
+    key: u8,
+    item: T,
+}
+
+impl<T: Copy, const N: usize> Schedule<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            entries: [None; N],
+            pushed: 0,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(Option::is_none)
+    }
+
+    /// The latest deadline of the events waiting with `key`.
+    pub fn latest(&self, key: u8) -> Option<u64> {
+        self.entries
+            .iter()
+            .flatten()
+            .filter(|e| e.key == key)
+            .map(|e| e.at)
+            .max()
+    }
+
+    /// Queues `item` for `at`, or behind the last event with the same key. Returns the
+    /// item back if the schedule is full.
+    pub fn push(&mut self, at: u64, key: u8, item: T) -> Result<(), T> {
+        let at = self.latest(key).map_or(at, |latest| latest.max(at));
+        let Some(slot) = self.entries.iter_mut().find(|e| e.is_none()) else {
+            return Err(item);
+        };
+        *slot = Some(Entry {
+            at,
+            seq: self.pushed,
+            key,
+            item,
+        });
+        self.pushed = self.pushed.wrapping_add(1);
+        Ok(())
+    }
+
+    fn first(&self) -> Option<(usize, &Entry<T>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| Some((i, e.as_ref()?)))
+            .min_by_key(|(_, e)| (e.at, e.seq.wrapping_sub(self.pushed)))
+    }
+
+    /// Takes the last event pushed with `key` that `matches`, however long it had left
+    /// to wait.
+    pub fn remove_last(&mut self, key: u8, matches: impl Fn(&T) -> bool) -> Option<T> {
+        let pushed = self.pushed;
+        let (i, _) = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| Some((i, e.as_ref()?)))
+            .filter(|(_, e)| e.key == key && matches(&e.item))
+            .max_by_key(|(_, e)| e.seq.wrapping_sub(pushed))?;
+        self.entries[i].take().map(|e| e.item)
+    }
+
+    /// When the next event is due.
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.first().map(|(_, e)| e.at)
+    }
+
+    /// Takes the first event due by `now`.
+    pub fn pop_due(&mut self, now: u64) -> Option<T> {
+        let (i, _) = self.first().filter(|(_, e)| e.at <= now)?;
+        self.entries[i].take().map(|e| e.item)
+    }
+}
+
+impl<T: Timed, const N: usize> Schedule<T, N> {
+    /// [`Schedule::push`], but when the schedule is full merges `item` into the last
+    /// event waiting on `key` or drops a droppable one for it. A droppable `item` is
+    /// rejected rather than pushing out another.
+    pub fn offer(&mut self, at: u64, key: u8, item: T) -> Offered<T> {
+        let item = match self.push(at, key, item) {
+            Ok(()) => return Offered::Queued,
+            Err(item) => item,
+        };
+        let pushed = self.pushed;
+        let last = self
+            .entries
+            .iter_mut()
+            .flatten()
+            .filter(|e| e.key == key)
+            .max_by_key(|e| e.seq.wrapping_sub(pushed));
+        if let Some(last) = last {
+            if item.merge_key().is_some() && last.item.merge_key() == item.merge_key() {
+                last.item = item;
+                return Offered::Merged;
+            }
+        }
+        if item.droppable() {
+            return Offered::Rejected(item);
+        }
+        // The spare event that would have waited longest
+        let victim = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| Some((i, e.as_ref()?)))
+            .filter(|(_, e)| e.item.droppable())
+            .max_by_key(|(_, e)| (e.at, e.seq.wrapping_sub(pushed)))
+            .map(|(i, _)| i);
+        let Some(evicted) = victim.and_then(|i| self.entries[i].take()) else {
+            return Offered::Rejected(item);
+        };
+        match self.push(at, key, item) {
+            Ok(()) => Offered::Evicted(evicted.item),
+            Err(item) => Offered::Rejected(item),
+        }
+    }
+}
+
+impl<T: Copy, const N: usize> Default for Schedule<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadlines_and_key_order() {
+        let mut s = Schedule::<char, 4>::new();
+        s.push(30, 0, 'a').unwrap();
+        s.push(10, 1, 'b').unwrap();
+        // Pushed after 'a' on the same key, so it waits for it
+        s.push(5, 0, 'c').unwrap();
+        s.push(10, 2, 'd').unwrap();
+        assert_eq!(s.push(0, 3, 'e'), Err('e'));
+        assert_eq!(s.next_deadline(), Some(10));
+        assert_eq!(s.pop_due(9), None);
+        assert_eq!(s.pop_due(10), Some('b'));
+        assert_eq!(s.pop_due(10), Some('d'));
+        assert_eq!(s.pop_due(29), None);
+        assert_eq!(s.pop_due(100), Some('a'));
+        assert_eq!(s.pop_due(100), Some('c'));
+        assert!(s.is_empty());
+        assert_eq!(s.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_remove_last() {
+        let mut s = Schedule::<char, 4>::new();
+        s.push(10, 0, 'a').unwrap();
+        s.push(20, 1, 'b').unwrap();
+        s.push(0, 0, 'a').unwrap();
+        s.push(0, 0, 'c').unwrap();
+        assert_eq!(s.remove_last(0, |&e| e == 'b'), None);
+        assert_eq!(s.remove_last(0, |&e| e == 'a'), Some('a'));
+        assert_eq!(s.size(), 3);
+        assert_eq!(s.remove_last(0, |_| true), Some('c'));
+        assert_eq!(s.pop_due(100), Some('a'));
+        assert_eq!(s.pop_due(100), Some('b'));
+        assert!(s.is_empty());
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct Event {
+        name: char,
+        merge: Option<u16>,
+        droppable: bool,
+    }
+
+    impl Timed for Event {
+        fn merge_key(&self) -> Option<u16> {
+            self.merge
+        }
+
+        fn droppable(&self) -> bool {
+            self.droppable
+        }
+    }
+
+    #[test]
+    fn test_overload_policies() {
+        let event = |name, merge, droppable| Event {
+            name,
+            merge,
+            droppable,
+        };
+        let mut s = Schedule::<Event, 3>::new();
+        let bend = event('b', Some(1), true);
+        let note = event('n', None, true);
+        let release = event('r', None, false);
+        assert_eq!(s.offer(10, 0, bend), Offered::Queued);
+        assert_eq!(s.offer(20, 1, note), Offered::Queued);
+        assert_eq!(s.offer(5, 1, release), Offered::Queued);
+
+        // A newer bend replaces the waiting one on its channel, not one elsewhere
+        let newer = event('B', Some(1), true);
+        assert_eq!(s.offer(15, 0, newer), Offered::Merged);
+        assert_eq!(s.offer(15, 1, newer), Offered::Rejected(newer));
+        // Releases push out the spare event that would have waited longest
+        assert_eq!(s.offer(0, 2, release), Offered::Evicted(note));
+        assert_eq!(s.offer(0, 3, release), Offered::Evicted(newer));
+        assert_eq!(s.offer(0, 4, release), Offered::Rejected(release));
+
+        // The release behind the dropped note keeps the note's deadline
+        assert_eq!(s.pop_due(100).map(|e| e.name), Some('r'));
+        assert_eq!(s.next_deadline(), Some(0));
+        assert_eq!(s.latest(1), Some(20));
+    }
+}
@@ -0,0 +1,299 @@
+/// FNV-1a hash, used to detect changed rows without keeping a copy of the screen.
+pub fn line_hash(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811C_9DC5;
+    for &b in bytes {
+        hash ^= b as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+/// Remembers what was drawn on each terminal row so only changed rows are re-sent.
+/// Rows past `ROWS` are never cached and always count as changed.
+pub struct LineCache<const ROWS: usize> {
+    hashes: [Option<u32>; ROWS],
+    rows: usize,
+}
+
+impl<const ROWS: usize> LineCache<ROWS> {
+    pub const fn new() -> Self {
+        Self {
+            hashes: [None; ROWS],
+            rows: 0,
+        }
+    }
+
+    /// Forgets the cached screen, e.g. after it was cleared.
+    pub fn invalidate(&mut self) {
+        self.hashes = [None; ROWS];
+        self.rows = 0;
+    }
+
+    /// Records `line` for `row`. Returns true if it differs from what is on screen.
+    pub fn update(&mut self, row: usize, line: &[u8]) -> bool {
+        let Some(slot) = self.hashes.get_mut(row) else {
+            return true;
+        };
+        let hash = Some(line_hash(line));
+        let changed = *slot != hash;
+        *slot = hash;
+        changed
+    }
+
+    /// Ends a frame of `rows` rows. Returns true if the previous frame was taller,
+    /// so the leftover rows below need clearing.
+    pub fn finish(&mut self, rows: usize) -> bool {
+        let shrunk = rows < self.rows;
+        for slot in self.hashes.iter_mut().skip(rows) {
+            *slot = None;
+        }
+        self.rows = rows;
+        shrunk
+    }
+}
+
+impl<const ROWS: usize> Default for LineCache<ROWS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Watches `N` values for changes by hash, without keeping copies of them.
+pub struct ChangeWatch<const N: usize> {
+    hashes: [Option<u32>; N],
+}
+
+impl<const N: usize> ChangeWatch<N> {
+    pub const fn new() -> Self {
+        Self { hashes: [None; N] }
+    }
+
+    /// Records value `i`. Returns true if it differs from the one last recorded; the
+    /// first value recorded is not a change.
+    pub fn update(&mut self, i: usize, value: &[u8]) -> bool {
+        let Some(slot) = self.hashes.get_mut(i) else {
+            return false;
+        };
+        let hash = line_hash(value);
+        let changed = slot.is_some_and(|h| h != hash);
+        *slot = Some(hash);
+        changed
+    }
+}
+
+impl<const N: usize> Default for ChangeWatch<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Escape sequence asking the terminal for its size: park the cursor in the far corner
+/// and request a cursor position report (`ESC [ rows ; cols R`).
+pub const QUERY_SIZE: &[u8] = b"\x1B[999;999H\x1B[6n";
+
+/// Result of feeding one input byte to [`InputFilter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    /// An ordinary byte to be handled as a command.
+    Byte(u8),
+    /// Part of an escape sequence; ignore.
+    Escape,
+    /// A cursor position report, i.e. the terminal size after [`QUERY_SIZE`].
+    Size { rows: u16, cols: u16 },
+    /// An arrow key.
+    Arrow(Arrow),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrow {
+    Up,
+    Down,
+    Right,
+    Left,
+}
+
+impl Arrow {
+    fn from_final(b: u8) -> Option<Self> {
+        match b {
+            b'A' => Some(Self::Up),
+            b'B' => Some(Self::Down),
+            b'C' => Some(Self::Right),
+            b'D' => Some(Self::Left),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Esc,
+    Csi,
+    /// `ESC O`, used for arrows in application cursor mode
+    Ss3,
+}
+
+/// Separates terminal escape sequences (cursor reports, arrow keys...) from typed
+/// commands, so e.g. the `R` ending a report isn't taken as a keypress.
+/// Arrow keys are decoded; other sequences are swallowed.
+pub struct InputFilter {
+    state: State,
+    params: [u16; 2],
+    param: usize,
+}
+
+impl InputFilter {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Idle,
+            params: [0; 2],
+            param: 0,
+        }
+    }
+
+    pub fn feed(&mut self, b: u8) -> Input {
+        match self.state {
+            State::Idle if b == 0x1B => {
+                self.state = State::Esc;
+                Input::Escape
+            }
+            State::Idle => Input::Byte(b),
+            State::Esc if b == b'[' => {
+                self.state = State::Csi;
+                self.params = [0; 2];
+                self.param = 0;
+                Input::Escape
+            }
+            State::Esc if b == b'O' => {
+                self.state = State::Ss3;
+                Input::Escape
+            }
+            State::Esc => {
+                self.state = State::Idle;
+                Input::Byte(b)
+            }
+            State::Ss3 => {
+                self.state = State::Idle;
+                Arrow::from_final(b).map_or(Input::Escape, Input::Arrow)
+            }
+            State::Csi => match b {
+                b'0'..=b'9' => {
+                    if let Some(p) = self.params.get_mut(self.param) {
+                        *p = p.saturating_mul(10).saturating_add((b - b'0') as u16);
+                    }
+                    Input::Escape
+                }
+                b';' => {
+                    self.param += 1;
+                    Input::Escape
+                }
+                // Final byte ends the sequence
+                0x40..=0x7E => {
+                    self.state = State::Idle;
+                    let [rows, cols] = self.params;
+                    if b == b'R' && self.param == 1 && rows > 0 && cols > 0 {
+                        Input::Size { rows, cols }
+                    } else {
+                        // Modifiers (e.g. Ctrl+Arrow, `ESC [1;5C`) are ignored
+                        Arrow::from_final(b).map_or(Input::Escape, Input::Arrow)
+                    }
+                }
+                _ => Input::Escape,
+            },
+        }
+    }
+}
+
+impl Default for InputFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(filter: &mut InputFilter, bytes: &[u8]) -> Input {
+        let mut last = Input::Escape;
+        for &b in bytes {
+            last = filter.feed(b);
+        }
+        last
+    }
+
+    #[test]
+    fn test_cursor_report_is_parsed() {
+        let mut filter = InputFilter::new();
+        assert_eq!(
+            feed_all(&mut filter, b"\x1B[48;132R"),
+            Input::Size {
+                rows: 48,
+                cols: 132
+            }
+        );
+        // Plain keys pass straight through afterwards
+        assert_eq!(filter.feed(b'R'), Input::Byte(b'R'));
+    }
+
+    #[test]
+    fn test_arrow_keys() {
+        let mut filter = InputFilter::new();
+        // Arrow keys must not leak their final letter as a command
+        assert_eq!(feed_all(&mut filter, b"\x1B[D"), Input::Arrow(Arrow::Left));
+        assert_eq!(
+            feed_all(&mut filter, b"\x1B[1;5C"),
+            Input::Arrow(Arrow::Right)
+        );
+        // Application cursor mode
+        assert_eq!(feed_all(&mut filter, b"\x1BOA"), Input::Arrow(Arrow::Up));
+        assert_eq!(filter.feed(b'd'), Input::Byte(b'd'));
+    }
+
+    #[test]
+    fn test_other_sequences_are_swallowed() {
+        let mut filter = InputFilter::new();
+        // Delete key
+        assert_eq!(feed_all(&mut filter, b"\x1B[3~"), Input::Escape);
+        assert_eq!(filter.feed(b'D'), Input::Byte(b'D'));
+    }
+
+    #[test]
+    fn test_unchanged_rows_are_skipped() {
+        let mut cache = LineCache::<4>::new();
+        assert!(cache.update(0, b"Brightness: 0.05"));
+        assert!(!cache.update(0, b"Brightness: 0.05"));
+        assert!(cache.update(0, b"Brightness: 0.06"));
+        // Rows beyond the cache are always redrawn
+        assert!(cache.update(4, b"x"));
+        assert!(cache.update(4, b"x"));
+    }
+
+    #[test]
+    fn test_invalidate_redraws_everything() {
+        let mut cache = LineCache::<4>::new();
+        cache.update(1, b"Held Keys:");
+        cache.invalidate();
+        assert!(cache.update(1, b"Held Keys:"));
+    }
+
+    #[test]
+    fn test_finish_reports_shrinking_frames() {
+        let mut cache = LineCache::<4>::new();
+        cache.update(2, b"Ch1 N60");
+        assert!(!cache.finish(3));
+        assert!(cache.finish(2));
+        // Row 2 was cleared, so drawing it again counts as a change
+        assert!(cache.update(2, b"Ch1 N60"));
+    }
+
+    #[test]
+    fn test_change_watch() {
+        let mut watch = ChangeWatch::<2>::new();
+        assert!(!watch.update(0, b"696.6c"));
+        assert!(!watch.update(0, b"696.6c"));
+        assert!(watch.update(0, b"696.7c"));
+        assert!(!watch.update(1, b"On"));
+        assert!(!watch.update(2, b"Off"));
+    }
+}
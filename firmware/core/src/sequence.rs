@@ -0,0 +1,95 @@
+/// A pre-flattened MIDI event with the delay since the previous event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SequenceEvent {
+    pub delta_ms: u16,
+    /// Raw channel message: status, data1, data2.
+    pub message: [u8; 3],
+}
+
+/// Size of one encoded event inside a SysEx payload.
+/// Layout: delta MSB (7 bits), delta LSB (7 bits), status without its high bit, data1, data2.
+pub const ENCODED_EVENT_SIZE: usize = 5;
+
+/// Largest delta representable in the 14-bit encoding.
+pub const MAX_DELTA_MS: u16 = 0x3FFF;
+
+impl SequenceEvent {
+    /// Decodes one event from its 7-bit-safe SysEx form.
+    /// Returns None for non-channel-voice statuses or out-of-range bytes.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < ENCODED_EVENT_SIZE || bytes.iter().any(|&b| b > 0x7F) {
+            return None;
+        }
+        let status = bytes[2] | 0x80;
+        if status >= 0xF0 {
+            return None;
+        }
+        Some(Self {
+            delta_ms: ((bytes[0] as u16) << 7) | bytes[1] as u16,
+            message: [status, bytes[3], bytes[4]],
+        })
+    }
+
+    /// Encodes the event into its 7-bit-safe SysEx form. Deltas are clamped to `MAX_DELTA_MS`.
+    pub fn encode(&self) -> [u8; ENCODED_EVENT_SIZE] {
+        let delta = self.delta_ms.min(MAX_DELTA_MS);
+        [
+            (delta >> 7) as u8,
+            (delta & 0x7F) as u8,
+            self.message[0] & 0x7F,
+            self.message[1] & 0x7F,
+            self.message[2] & 0x7F,
+        ]
+    }
+}
+
+/// Iterates over the events encoded in a SysEx payload, skipping invalid ones.
+pub fn decode_events(payload: &[u8]) -> impl Iterator<Item = SequenceEvent> + '_ {
+    payload
+        .chunks_exact(ENCODED_EVENT_SIZE)
+        .filter_map(SequenceEvent::decode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_roundtrip() {
+        let ev = SequenceEvent {
+            delta_ms: 1234,
+            message: [0x91, 60, 100],
+        };
+        assert_eq!(SequenceEvent::decode(&ev.encode()), Some(ev));
+    }
+
+    #[test]
+    fn test_event_delta_clamped() {
+        let ev = SequenceEvent {
+            delta_ms: u16::MAX,
+            message: [0x80, 60, 0],
+        };
+        assert_eq!(
+            SequenceEvent::decode(&ev.encode()).unwrap().delta_ms,
+            MAX_DELTA_MS
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid() {
+        // System messages are not allowed
+        assert_eq!(SequenceEvent::decode(&[0, 0, 0x70, 0, 0]), None);
+        // High bit set in payload
+        assert_eq!(SequenceEvent::decode(&[0x80, 0, 0x10, 0, 0]), None);
+        assert_eq!(SequenceEvent::decode(&[0, 0, 0x10]), None);
+    }
+
+    #[test]
+    fn test_decode_events() {
+        let payload = [0, 0, 0x10, 60, 100, 0, 10, 0x00, 60, 0, 1];
+        let events: Vec<_> = decode_events(&payload).collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].delta_ms, 10);
+        assert_eq!(events[1].message, [0x80, 60, 0]);
+    }
+}
@@ -0,0 +1,107 @@
+//! Column settling: reading the rows once they hold steady, and measuring on the board
+//! how long they keep changing after a column is driven.
+
+/// Readings from a settle test.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SettleTest {
+    /// Columns driven.
+    pub columns: u32,
+    /// Columns where a row changed after the drive, from a key on the new column or
+    /// one on the previous column letting go.
+    pub changed: u32,
+    /// Latest change seen, in us after the drive.
+    pub longest_us: u32,
+}
+
+impl SettleTest {
+    pub const fn new() -> Self {
+        Self {
+            columns: 0,
+            changed: 0,
+            longest_us: 0,
+        }
+    }
+
+    /// Records a driven column and when its rows last changed, if they did.
+    pub fn record(&mut self, last_change_us: Option<u32>) {
+        self.columns += 1;
+        if let Some(us) = last_change_us {
+            self.changed += 1;
+            self.longest_us = self.longest_us.max(us);
+        }
+    }
+
+    /// A settle time with twice the longest change as margin, at least 1 us. `None`
+    /// until a change was seen, as there's nothing to go by.
+    pub fn recommended_us(&self) -> Option<u32> {
+        (self.changed > 0).then(|| (self.longest_us * 2).max(1))
+    }
+}
+
+/// Counts consecutive equal readings of a set of inputs, to read them as soon as they
+/// hold steady instead of after a fixed wait.
+#[derive(Clone, Copy, Debug)]
+pub struct Steady {
+    last: Option<u32>,
+    count: u8,
+    needed: u8,
+}
+
+impl Steady {
+    pub const fn new(needed: u8) -> Self {
+        Self {
+            last: None,
+            count: 0,
+            needed,
+        }
+    }
+
+    /// Feeds a reading; true once `needed` readings in a row were the same.
+    pub fn feed(&mut self, reading: u32) -> bool {
+        if self.last == Some(reading) {
+            self.count = self.count.saturating_add(1);
+        } else {
+            self.last = Some(reading);
+            self.count = 1;
+        }
+        self.count >= self.needed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steady() {
+        let mut steady = Steady::new(3);
+        assert!(!steady.feed(0b01));
+        assert!(!steady.feed(0b11));
+        assert!(!steady.feed(0b11));
+        assert!(steady.feed(0b11));
+        assert!(steady.feed(0b11));
+        // A change starts the count over
+        assert!(!steady.feed(0b10));
+    }
+
+    #[test]
+    fn test_settle_test() {
+        let mut test = SettleTest::new();
+        test.record(None);
+        assert_eq!(test.recommended_us(), None);
+        // Changes faster than the clock resolution still need some settle time
+        test.record(Some(0));
+        assert_eq!(test.recommended_us(), Some(1));
+        test.record(Some(4));
+        test.record(Some(2));
+        assert_eq!(test.recommended_us(), Some(8));
+        assert_eq!(
+            test,
+            SettleTest {
+                columns: 4,
+                changed: 3,
+                longest_us: 4
+            }
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Recent history of a measurement, one peak per fixed period, drawn as a line of
+//! ASCII characters for the dashboard.
+
+use core::fmt::Write;
+
+/// Characters from an empty period up to the graph's scale.
+pub const LEVELS: &[u8] = b" .:-=+*#%@";
+
+/// The last `N` periods of a measurement, in ticks of whatever clock the caller uses.
+#[derive(Clone, Copy, Debug)]
+pub struct Trace<const N: usize> {
+    samples: [u32; N],
+    /// Index of the oldest sample.
+    oldest: usize,
+    /// Peak of the period in progress.
+    peak: u32,
+    period_start: u64,
+    period: u64,
+}
+
+impl<const N: usize> Trace<N> {
+    pub const fn new(period: u64) -> Self {
+        Self {
+            samples: [0; N],
+            oldest: 0,
+            peak: 0,
+            period_start: 0,
+            period,
+        }
+    }
+
+    pub fn record(&mut self, now: u64, value: u32) {
+        self.advance(now);
+        self.peak = self.peak.max(value);
+    }
+
+    /// Closes the periods that ended by `now`. Periods without samples read as 0.
+    pub fn advance(&mut self, now: u64) {
+        let ended = now.saturating_sub(self.period_start) / self.period.max(1);
+        if ended == 0 {
+            return;
+        }
+        for i in ended.saturating_sub(N as u64)..ended {
+            self.samples[self.oldest] = if i == 0 { self.peak } else { 0 };
+            self.oldest = (self.oldest + 1) % N;
+        }
+        self.peak = 0;
+        self.period_start += ended * self.period;
+    }
+
+    /// Oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = u32> + '_ {
+        let (newer, older) = self.samples.split_at(self.oldest);
+        older.iter().chain(newer).copied()
+    }
+
+    pub fn max(&self) -> u32 {
+        self.samples().max().unwrap_or(0)
+    }
+
+    /// Draws one character per period, scaled to the largest sample but to at least
+    /// `floor`, so noise on an idle trace doesn't fill the graph. Any non-zero sample
+    /// shows.
+    pub fn draw(&self, floor: u32, out: &mut impl Write) -> core::fmt::Result {
+        let scale = self.max().max(floor).max(1) as u64;
+        let top = LEVELS.len() as u64 - 1;
+        for v in self.samples() {
+            let level = (v as u64 * top).div_ceil(scale).min(top);
+            out.write_char(LEVELS[level as usize] as char)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        let mut trace = Trace::<4>::new(10);
+        trace.record(3, 5);
+        trace.record(7, 9);
+        trace.record(12, 2);
+        // Two idle periods, then the one in progress isn't shown yet
+        trace.record(41, 7);
+        assert_eq!(trace.samples().collect::<Vec<_>>(), [9, 2, 0, 0]);
+
+        let mut graph = String::new();
+        trace.draw(0, &mut graph).unwrap();
+        assert_eq!(graph, "@:  ");
+        graph.clear();
+        trace.draw(90, &mut graph).unwrap();
+        assert_eq!(graph, "..  ");
+
+        // A long gap clears the trace
+        trace.advance(1000);
+        assert_eq!(trace.max(), 0);
+    }
+}
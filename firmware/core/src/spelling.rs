@@ -0,0 +1,351 @@
+use core::fmt;
+
+/// Letter names in scale order, starting at C.
+const LETTERS: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+/// Fixed-do syllables for the same letters.
+const SOLFEGE: [&str; 7] = ["Do", "Re", "Mi", "Fa", "Sol", "La", "Si"];
+/// 31-EDO steps from C to each letter.
+const EDO31_STEPS: [i16; 7] = [0, 5, 10, 13, 18, 23, 28];
+
+/// How note names are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Convention {
+    /// C D E F G A B with # and b.
+    English,
+    /// H for B and B for Bb, with -is and -es suffixes (Fis, Es, As).
+    German,
+    /// Fixed do: Do Re Mi Fa Sol La Si with # and b.
+    Solfege,
+    /// 31-EDO ups and downs: names with more than one sharp or flat are written as
+    /// the nearest natural or single accidental raised (^) or lowered (v) by
+    /// single steps of 31-EDO, so C## is vD.
+    Ups31,
+}
+
+impl Convention {
+    pub const ALL: [Convention; 4] = [
+        Convention::English,
+        Convention::German,
+        Convention::Solfege,
+        Convention::Ups31,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Convention::English => "English",
+            Convention::German => "German",
+            Convention::Solfege => "Solfege",
+            Convention::Ups31 => "31-EDO ups/downs",
+        }
+    }
+}
+
+/// Which of a pitch's enharmonic names to show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// The key's own place on the chain of fifths, so enharmonic keys keep distinct
+    /// names (F# and Gb).
+    Chain,
+    /// Naturals and sharps (A#, never Bb).
+    Sharps,
+    /// Naturals and flats (Bb, never A#).
+    Flats,
+    /// As a key signature of the tonic's major key would: the major scale, plus flats
+    /// for the notes below it on the chain and a sharp for the one above (in C: Db Eb
+    /// F# Ab Bb).
+    Key,
+}
+
+impl Policy {
+    pub const ALL: [Policy; 4] = [Policy::Chain, Policy::Sharps, Policy::Flats, Policy::Key];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Policy::Chain => "As played",
+            Policy::Sharps => "Sharps",
+            Policy::Flats => "Flats",
+            Policy::Key => "Key",
+        }
+    }
+
+    /// Moves `octaves` and `fifths` (as for [`NoteName::from_offsets`]) to the name
+    /// the policy picks, `tonic` fifths from C being the key for [`Policy::Key`].
+    /// Respelled names are 12-EDO enharmonics of the key: twelve fifths up and an
+    /// octave down.
+    pub fn respell(self, tonic: i16, octaves: i16, fifths: i16) -> (i16, i16) {
+        // Twelve consecutive fifths, one of each pitch class
+        let lowest = match self {
+            Policy::Chain => return (octaves, fifths),
+            Policy::Sharps => -1,
+            Policy::Flats => -6,
+            Policy::Key => tonic - 5,
+        };
+        let respelled = lowest + (fifths - lowest).rem_euclid(12);
+        (octaves - (respelled - fifths) / 12, respelled)
+    }
+}
+
+/// A key's note name, spelled from its place on the chain of fifths rather than from
+/// its sounding pitch, so enharmonic keys (e.g. F# and Gb) keep distinct names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoteName {
+    /// Index into C D E F G A B.
+    pub letter: u8,
+    /// Sharps if positive, flats if negative.
+    pub accidentals: i16,
+    /// Scientific octave number (Middle C is C4), following the letter, so B#3
+    /// sounds with C4 in 12-EDO.
+    pub octave: i16,
+}
+
+impl NoteName {
+    /// Spells the key `octaves` and `fifths` away from Middle C, as returned by
+    /// [`crate::tuning::fifths_offsets`].
+    pub fn from_offsets(octaves: i16, fifths: i16) -> Self {
+        // Pitch is `fifths` fifths plus this many octaves up from Middle C
+        let octaves = octaves - fifths.div_euclid(2);
+        // A fifth spans 4 letter steps, an octave 7
+        let steps = 7 * octaves + 4 * fifths;
+        Self {
+            letter: steps.rem_euclid(7) as u8,
+            // F is one fifth below C, so sharps start 6 fifths up and flats 2 down
+            accidentals: (fifths + 1).div_euclid(7),
+            octave: 4 + steps.div_euclid(7),
+        }
+    }
+
+    pub fn letter_char(&self) -> char {
+        LETTERS[self.letter as usize]
+    }
+
+    /// The name written in `convention`.
+    pub fn spelled(self, convention: Convention) -> Spelled {
+        Spelled {
+            name: self,
+            convention,
+            octave: true,
+        }
+    }
+
+    /// The same pitch in 31-EDO respelled with at most one sharp or flat, and the
+    /// 31-EDO steps it is then raised by.
+    fn edo31_respelled(self) -> (NoteName, i16) {
+        if self.accidentals.abs() <= 1 {
+            return (self, 0);
+        }
+        let step = |n: NoteName| 31 * n.octave + EDO31_STEPS[n.letter as usize] + 2 * n.accidentals;
+        let target = step(self);
+        let mut best = (self, 0, i16::MAX);
+        for octave in self.octave - 1..=self.octave + 1 {
+            for letter in 0..7 {
+                for accidentals in [0, -1, 1] {
+                    let candidate = NoteName {
+                        letter,
+                        accidentals,
+                        octave,
+                    };
+                    let ups = target - step(candidate);
+                    // Fewest ups or downs first, then fewest accidentals
+                    let cost = 2 * ups.abs() + accidentals.abs();
+                    if cost < best.2 {
+                        best = (candidate, ups, cost);
+                    }
+                }
+            }
+        }
+        (best.0, best.1)
+    }
+}
+
+fn write_accidentals(f: &mut fmt::Formatter<'_>, accidentals: i16) -> fmt::Result {
+    use fmt::Write;
+    let symbol = if accidentals > 0 { '#' } else { 'b' };
+    for _ in 0..accidentals.unsigned_abs() {
+        f.write_char(symbol)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for NoteName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write;
+        f.write_char(self.letter_char())?;
+        write_accidentals(f, self.accidentals)?;
+        write!(f, "{}", self.octave)
+    }
+}
+
+/// A [`NoteName`] written in a [`Convention`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Spelled {
+    pub name: NoteName,
+    pub convention: Convention,
+    /// Whether the octave number follows the name.
+    pub octave: bool,
+}
+
+impl Spelled {
+    /// Just the pitch class, without the octave number.
+    pub fn without_octave(self) -> Self {
+        Self {
+            octave: false,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Spelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write;
+        let (name, ups) = match self.convention {
+            Convention::Ups31 => self.name.edo31_respelled(),
+            _ => (self.name, 0),
+        };
+        let symbol = if ups > 0 { "^" } else { "v" };
+        for _ in 0..ups.unsigned_abs() {
+            f.write_str(symbol)?;
+        }
+        match self.convention {
+            Convention::English | Convention::Ups31 => {
+                f.write_char(name.letter_char())?;
+                write_accidentals(f, name.accidentals)?;
+            }
+            Convention::German => {
+                let n = name.accidentals.unsigned_abs() as usize;
+                let suffix = if name.accidentals > 0 { "is" } else { "es" };
+                let mut letter = [0u8; 4];
+                let (stem, suffix, repeats) = match (name.letter_char(), name.accidentals) {
+                    // B is H, and Bb is B
+                    ('B', -1) => ("B", "", 0),
+                    ('B', _) => ("H", suffix, n),
+                    // Es and As rather than Ees and Aes, then Eses and Asas
+                    ('E', a) if a < 0 => ("Es", "es", n - 1),
+                    ('A', a) if a < 0 => ("As", "as", n - 1),
+                    (l, _) => (&*l.encode_utf8(&mut letter), suffix, n),
+                };
+                f.write_str(stem)?;
+                for _ in 0..repeats {
+                    f.write_str(suffix)?;
+                }
+            }
+            Convention::Solfege => {
+                f.write_str(SOLFEGE[name.letter as usize])?;
+                write_accidentals(f, name.accidentals)?;
+            }
+        }
+        if self.octave {
+            write!(f, "{}", name.octave)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(octaves: i16, fifths: i16) -> String {
+        NoteName::from_offsets(octaves, fifths).to_string()
+    }
+
+    #[test]
+    fn test_naturals_and_accidentals() {
+        assert_eq!(name(0, 0), "C4");
+        assert_eq!(name(0, 1), "G4");
+        assert_eq!(name(0, -1), "F4");
+        assert_eq!(name(0, 2), "D4");
+        assert_eq!(name(0, 6), "F#4");
+        assert_eq!(name(0, -6), "Gb3");
+        assert_eq!(name(1, 0), "C5");
+        assert_eq!(name(-1, 5), "B3");
+        assert_eq!(name(0, 13), "F##5");
+    }
+
+    fn spelled(octaves: i16, fifths: i16, convention: Convention) -> String {
+        NoteName::from_offsets(octaves, fifths)
+            .spelled(convention)
+            .to_string()
+    }
+
+    #[test]
+    fn test_policies() {
+        let with = |policy: Policy, tonic, fifths| {
+            let (octaves, fifths) = policy.respell(tonic, 0, fifths);
+            name(octaves, fifths)
+        };
+        assert_eq!(with(Policy::Chain, 0, -2), "Bb3");
+        assert_eq!(with(Policy::Sharps, 0, -2), "A#3");
+        assert_eq!(with(Policy::Sharps, 0, -1), "F4");
+        assert_eq!(with(Policy::Sharps, 0, 12), "C5");
+        assert_eq!(with(Policy::Flats, 0, 6), "Gb4");
+        assert_eq!(with(Policy::Flats, 0, 5), "B4");
+        assert_eq!(with(Policy::Flats, 0, -13), "F3");
+        // C major: flats for the black keys except F#
+        let in_c: Vec<String> = (0..12)
+            .map(|semitones| {
+                let (octaves, fifths) = Policy::Key.respell(0, 0, semitones * 7 % 12);
+                let name = NoteName::from_offsets(octaves, fifths);
+                name.spelled(Convention::English)
+                    .without_octave()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            in_c,
+            ["C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"]
+        );
+        // D major has C# and G#, and Bb for its flat sixth
+        assert_eq!(with(Policy::Key, 2, 7), "C#5");
+        assert_eq!(with(Policy::Key, 2, 8), "G#4");
+        assert_eq!(with(Policy::Key, 2, -2), "Bb3");
+        assert_eq!(with(Policy::Key, 2, 10), "Bb4");
+    }
+
+    #[test]
+    fn test_german() {
+        let german = |fifths| spelled(0, fifths, Convention::German);
+        assert_eq!(german(0), "C4");
+        assert_eq!(german(-2), "B3");
+        assert_eq!(german(5), "H4");
+        assert_eq!(german(12), "His4");
+        assert_eq!(german(-9), "Heses3");
+        assert_eq!(german(6), "Fis4");
+        assert_eq!(german(-3), "Es4");
+        assert_eq!(german(-10), "Eses3");
+        assert_eq!(german(-4), "As3");
+        assert_eq!(german(-11), "Asas3");
+        assert_eq!(german(-6), "Ges3");
+        assert_eq!(german(13), "Fisis5");
+    }
+
+    #[test]
+    fn test_solfege() {
+        assert_eq!(spelled(0, 0, Convention::Solfege), "Do4");
+        assert_eq!(spelled(0, 1, Convention::Solfege), "Sol4");
+        assert_eq!(spelled(0, 6, Convention::Solfege), "Fa#4");
+        assert_eq!(spelled(0, -2, Convention::Solfege), "Sib3");
+    }
+
+    #[test]
+    fn test_ups_and_downs() {
+        let ups = |fifths| spelled(0, fifths, Convention::Ups31);
+        // Single accidentals are kept
+        assert_eq!(ups(6), "F#4");
+        assert_eq!(ups(-6), "Gb3");
+        assert_eq!(ups(12), "B#4");
+        // C## is a step below D, Dbb a step above C
+        assert_eq!(ups(14), "vD5");
+        assert_eq!(ups(-12), "^C3");
+        // E## is a step above F, and B## crosses into the next octave
+        assert_eq!(ups(18), "^F5");
+        assert_eq!(ups(19), "^C6");
+    }
+
+    #[test]
+    fn test_octave_follows_letter() {
+        // B#: 12 fifths up lands a Pythagorean comma above C5, spelled B#4
+        assert_eq!(name(0, 12), "B#4");
+        // Cb: 7 fifths down, a semitone below C4 but still spelled in octave 4
+        assert_eq!(name(0, -7), "Cb4");
+    }
+}
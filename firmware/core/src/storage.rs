@@ -0,0 +1,217 @@
+//! Partition map of the user data kept at the top of flash.
+//!
+//! Partitions are whole sectors, laid out downward from the end of flash in the order
+//! of [`Partition::ALL`]. Their places are fixed, since moving one loses what boards
+//! have stored there: new partitions go at the end of the list, out of the room left
+//! in [`RESERVED`].
+
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+/// Erase unit of the flash.
+pub const SECTOR_SIZE: usize = 4096;
+/// Bytes at the top of flash kept out of the firmware image; must match memory.x.
+pub const RESERVED: usize = 264 * 1024;
+/// Player profiles, each with its own macros, CC map and presets.
+pub const PROFILES: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Partition {
+    /// First settings bank (see [`crate::banks`]).
+    Settings0,
+    /// Console macros and the CC map, as the first profile saved them before they
+    /// were banked in its presets sectors (see [`macros_banks`]).
+    Macros,
+    /// Second settings bank. Below the macros, which were stored before settings
+    /// were banked.
+    Settings1,
+    Presets,
+    Lessons,
+    CrashLog,
+    LedScenes,
+    /// Macros and CC maps of the profiles after the first, one sector each, as saved
+    /// before they were banked.
+    ProfileMacros,
+    /// Per-key press and bounce counts, in two banks of one sector.
+    KeyWear,
+}
+
+impl Partition {
+    /// Top of flash first.
+    pub const ALL: [Partition; 9] = [
+        Partition::Settings0,
+        Partition::Macros,
+        Partition::Settings1,
+        Partition::Presets,
+        Partition::Lessons,
+        Partition::CrashLog,
+        Partition::LedScenes,
+        Partition::ProfileMacros,
+        Partition::KeyWear,
+    ];
+
+    pub const fn sectors(self) -> usize {
+        match self {
+            Partition::Settings0 | Partition::Macros | Partition::Settings1 => 1,
+            Partition::Presets => 16,
+            Partition::Lessons => 32,
+            Partition::CrashLog | Partition::KeyWear => 2,
+            Partition::LedScenes => 8,
+            Partition::ProfileMacros => PROFILES - 1,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Partition::Settings0 => "Settings 0",
+            Partition::Macros => "Macros",
+            Partition::Settings1 => "Settings 1",
+            Partition::Presets => "Presets",
+            Partition::Lessons => "Lessons",
+            Partition::CrashLog => "Crash log",
+            Partition::LedScenes => "LED scenes",
+            Partition::ProfileMacros => "Profile macros",
+            Partition::KeyWear => "Key wear",
+        }
+    }
+
+    /// Bytes in the partition.
+    pub const fn size(self) -> usize {
+        self.sectors() * SECTOR_SIZE
+    }
+
+    /// Start of the partition, from the start of flash.
+    pub const fn offset(self) -> u32 {
+        let mut end = FLASH_SIZE;
+        let mut i = 0;
+        while i < self as usize {
+            end -= Partition::ALL[i].size();
+            i += 1;
+        }
+        (end - self.size()) as u32
+    }
+
+    /// Flash offset of `len` bytes at `at` in the partition, or `None` if they don't
+    /// fit in it.
+    pub fn range(self, at: usize, len: usize) -> Option<u32> {
+        let end = at.checked_add(len)?;
+        (end <= self.size()).then_some(self.offset() + at as u32)
+    }
+
+    /// Flash offset of sector `index` of the partition.
+    pub fn sector(self, index: usize) -> Option<u32> {
+        self.range(index * SECTOR_SIZE, SECTOR_SIZE)
+    }
+}
+
+/// The partition and sector holding `profile`'s macros and CC map. The first profile
+/// keeps the macros sector from before there were profiles.
+pub fn macros_sector(profile: usize) -> (Partition, usize) {
+    match profile {
+        0 => (Partition::Macros, 0),
+        _ => (Partition::ProfileMacros, profile - 1),
+    }
+}
+
+/// Sectors of the presets partition belonging to `profile`: its preset banks, then
+/// its macros banks.
+pub fn presets_sectors(profile: usize) -> core::ops::Range<usize> {
+    let per_profile = Partition::Presets.sectors() / PROFILES;
+    profile * per_profile..(profile + 1) * per_profile
+}
+
+/// Sectors of the presets partition holding the two banks of `profile`'s preset.
+pub fn preset_banks(profile: usize) -> [usize; 2] {
+    let first = presets_sectors(profile).start;
+    [first, first + 1]
+}
+
+/// Sectors of the presets partition holding the two banks of `profile`'s macros and
+/// CC map, after its preset banks. [`macros_sector`] is still read for what was saved
+/// before they were banked.
+pub fn macros_banks(profile: usize) -> [usize; 2] {
+    let first = presets_sectors(profile).start + 2;
+    [first, first + 1]
+}
+
+/// Bytes taken by the partitions.
+pub const USED: usize = {
+    let mut used = 0;
+    let mut i = 0;
+    while i < Partition::ALL.len() {
+        used += Partition::ALL[i].size();
+        i += 1;
+    }
+    used
+};
+
+const _: () = assert!(USED <= RESERVED);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partitions_tile_downward() {
+        let mut end = FLASH_SIZE as u32;
+        for (i, p) in Partition::ALL.iter().enumerate() {
+            assert_eq!(*p as usize, i);
+            assert_eq!(p.offset() + p.size() as u32, end);
+            assert_eq!(p.offset() as usize % SECTOR_SIZE, 0);
+            end = p.offset();
+        }
+        assert_eq!(FLASH_SIZE - end as usize, USED);
+        // Places boards already have data in
+        assert_eq!(
+            Partition::Settings0.offset() as usize,
+            FLASH_SIZE - SECTOR_SIZE
+        );
+        assert_eq!(
+            Partition::Macros.offset() as usize,
+            FLASH_SIZE - 2 * SECTOR_SIZE
+        );
+        assert_eq!(
+            Partition::Settings1.offset() as usize,
+            FLASH_SIZE - 3 * SECTOR_SIZE
+        );
+    }
+
+    #[test]
+    fn test_range() {
+        let p = Partition::CrashLog;
+        assert_eq!(p.range(0, p.size()), Some(p.offset()));
+        assert_eq!(p.range(100, 28), Some(p.offset() + 100));
+        assert_eq!(p.range(1, p.size()), None);
+        assert_eq!(p.range(usize::MAX, 2), None);
+        assert_eq!(p.sector(1), Some(p.offset() + SECTOR_SIZE as u32));
+        assert_eq!(p.sector(2), None);
+    }
+
+    #[test]
+    fn test_profiles_get_their_own_sectors() {
+        let macros: Vec<u32> = (0..PROFILES)
+            .map(|p| {
+                let (partition, sector) = macros_sector(p);
+                partition.sector(sector).unwrap()
+            })
+            .collect();
+        for (i, a) in macros.iter().enumerate() {
+            assert!(macros[i + 1..].iter().all(|b| a != b));
+        }
+        assert_eq!(presets_sectors(0), 0..4);
+        assert_eq!(
+            presets_sectors(PROFILES - 1).end,
+            Partition::Presets.sectors()
+        );
+    }
+
+    #[test]
+    fn test_banks_within_profile_sectors() {
+        for p in 0..PROFILES {
+            let sectors = presets_sectors(p);
+            let banks: Vec<usize> = preset_banks(p).into_iter().chain(macros_banks(p)).collect();
+            assert!(banks.iter().all(|s| sectors.contains(s)));
+            for (i, a) in banks.iter().enumerate() {
+                assert!(banks[i + 1..].iter().all(|b| a != b));
+            }
+        }
+    }
+}
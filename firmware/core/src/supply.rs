@@ -0,0 +1,69 @@
+//! LED brightness cap for a sagging supply. Pulling the LEDs down raises the voltage
+//! again, so the cap steps rather than tracking the voltage directly, which would
+//! oscillate: down quickly while the supply is low, back up slowly once it recovers.
+
+/// Below this the cap steps down, in volts.
+pub const SAG_V: f32 = 4.4;
+/// Above this the cap steps back up, in volts.
+pub const RECOVER_V: f32 = 4.6;
+/// Lowest cap, so the board never goes dark.
+pub const CAP_FLOOR: f32 = 0.25;
+const STEP_DOWN: f32 = 0.1;
+const STEP_UP: f32 = 0.02;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SupplyLimiter {
+    cap: f32,
+}
+
+impl Default for SupplyLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SupplyLimiter {
+    pub const fn new() -> Self {
+        Self { cap: 1.0 }
+    }
+
+    /// Brightness factor, 1.0 when the supply is fine.
+    pub fn cap(&self) -> f32 {
+        self.cap
+    }
+
+    /// Feeds a supply reading and returns the new cap.
+    pub fn update(&mut self, volts: f32) -> f32 {
+        if volts < SAG_V {
+            self.cap = (self.cap - STEP_DOWN).max(CAP_FLOOR);
+        } else if volts > RECOVER_V {
+            self.cap = (self.cap + STEP_UP).min(1.0);
+        }
+        self.cap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supply_limiter() {
+        let mut limiter = SupplyLimiter::new();
+        assert_eq!(limiter.update(4.9), 1.0);
+        assert!((limiter.update(4.2) - 0.9).abs() < 1e-6);
+        // Between the thresholds the cap holds
+        assert!((limiter.update(4.5) - 0.9).abs() < 1e-6);
+        for _ in 0..20 {
+            limiter.update(3.9);
+        }
+        assert_eq!(limiter.cap(), CAP_FLOOR);
+        // Recovery is slower than the fall
+        limiter.update(4.8);
+        assert!((limiter.cap() - (CAP_FLOOR + 0.02)).abs() < 1e-6);
+        for _ in 0..100 {
+            limiter.update(4.8);
+        }
+        assert_eq!(limiter.cap(), 1.0);
+    }
+}
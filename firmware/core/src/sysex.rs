@@ -0,0 +1,239 @@
+/// SysEx start and end bytes.
+pub const SYSEX_START: u8 = 0xF0;
+pub const SYSEX_END: u8 = 0xF7;
+
+/// Manufacturer ID used by the board (0x7D = non-commercial / educational use).
+pub const MANUFACTURER_ID: u8 = 0x7D;
+
+/// Command IDs following the manufacturer ID: `F0 7D <cmd> <payload...> F7`.
+pub mod cmd {
+    /// Clear the event player buffer.
+    pub const PLAYER_CLEAR: u8 = 0x20;
+    /// Append encoded events to the player buffer.
+    pub const PLAYER_APPEND: u8 = 0x21;
+    /// Start playback from the beginning.
+    pub const PLAYER_PLAY: u8 = 0x22;
+    /// Stop playback and release sounding notes.
+    pub const PLAYER_STOP: u8 = 0x23;
+    /// Store the board ID used when no strap pins are fitted (payload: ID, 0 clears).
+    /// An unstrapped prototype needs it once after upgrading to a build with board
+    /// detection, which otherwise takes it for a 5x25.
+    pub const SET_BOARD: u8 = 0x30;
+    /// Loop synthetic key presses through the MIDI path and log the results.
+    pub const SELF_TEST: u8 = 0x40;
+    /// Press a key as if scanned (payload: row, col, optional velocity).
+    pub const PRESS: u8 = 0x41;
+    /// Release a key as if scanned (payload: row, col).
+    pub const RELEASE: u8 = 0x42;
+    /// Start a chunked transfer (payload: target, length); see `transfer`.
+    pub const TRANSFER_BEGIN: u8 = 0x50;
+    /// One chunk of a transfer (payload: sequence number, checksum, packed data).
+    pub const TRANSFER_CHUNK: u8 = 0x51;
+    /// Finish a transfer once every chunk is acknowledged.
+    pub const TRANSFER_END: u8 = 0x52;
+    /// Ask where the current transfer stands, to resume it.
+    pub const TRANSFER_STATUS: u8 = 0x53;
+    /// Board reply: everything up to the sequence number is in.
+    pub const TRANSFER_ACK: u8 = 0x54;
+    /// Board reply: resend from the sequence number (payload also has the reason).
+    pub const TRANSFER_NACK: u8 = 0x55;
+    /// Set a parameter by its NRPN number, as NRPN data entry would (payload: number
+    /// MSB and LSB, value MSB and optional LSB).
+    pub const SET_PARAM: u8 = 0x60;
+    /// Read a setting for a host editor (payload: key, index for indexed ones); see
+    /// `config`.
+    pub const GET_CONFIG: u8 = 0x61;
+    /// Write a setting (payload as `CONFIG_VALUE`).
+    pub const SET_CONFIG: u8 = 0x62;
+    /// Board reply: a setting's value (payload: key, index, 14-bit values).
+    pub const CONFIG_VALUE: u8 = 0x63;
+}
+
+/// Returns true if a USB-MIDI event packet's Code Index Number belongs to a SysEx transfer.
+pub fn is_sysex_packet(packet: &[u8]) -> bool {
+    match packet.first().map(|b| b & 0x0F) {
+        Some(0x4) | Some(0x6) | Some(0x7) => true,
+        // CIN 0x5 is also used for single-byte system common messages
+        Some(0x5) => packet.get(1) == Some(&SYSEX_END),
+        _ => false,
+    }
+}
+
+/// Reassembles SysEx messages from 4-byte USB-MIDI event packets.
+/// Messages longer than `N` bytes are dropped.
+pub struct SysexAssembler<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    active: bool,
+    overflow: bool,
+}
+
+impl<const N: usize> SysexAssembler<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            active: false,
+            overflow: false,
+        }
+    }
+
+    /// Feeds one USB-MIDI event packet. Returns the full message (including
+    /// `F0`/`F7`) once the end of a SysEx has been received.
+    pub fn push_packet(&mut self, packet: &[u8]) -> Option<&[u8]> {
+        if packet.len() < 4 {
+            return None;
+        }
+        let data = match packet[0] & 0x0F {
+            0x4 | 0x7 => &packet[1..4],
+            0x5 => &packet[1..2],
+            0x6 => &packet[1..3],
+            _ => return None,
+        };
+
+        let mut complete = false;
+        for &b in data {
+            if b == SYSEX_START {
+                self.len = 0;
+                self.active = true;
+                self.overflow = false;
+            }
+            if !self.active {
+                continue;
+            }
+            if self.len < N {
+                self.buf[self.len] = b;
+                self.len += 1;
+            } else {
+                self.overflow = true;
+            }
+            if b == SYSEX_END {
+                self.active = false;
+                complete = !self.overflow;
+                break;
+            }
+        }
+
+        if complete {
+            Some(&self.buf[..self.len])
+        } else {
+            None
+        }
+    }
+}
+
+impl<const N: usize> Default for SysexAssembler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// USB-MIDI event packets (cable 0) carrying a complete SysEx message.
+pub fn packets(msg: &[u8]) -> impl Iterator<Item = [u8; 4]> + '_ {
+    let count = msg.len().div_ceil(3);
+    msg.chunks(3).enumerate().map(move |(i, bytes)| {
+        let cin = if i + 1 < count {
+            0x4
+        } else {
+            0x4 + bytes.len() as u8
+        };
+        let mut packet = [cin, 0, 0, 0];
+        packet[1..1 + bytes.len()].copy_from_slice(bytes);
+        packet
+    })
+}
+
+/// Splits a complete SysEx message addressed to this board into (command, payload).
+pub fn parse_message(msg: &[u8]) -> Option<(u8, &[u8])> {
+    if msg.len() < 4
+        || msg[0] != SYSEX_START
+        || msg[1] != MANUFACTURER_ID
+        || msg[msg.len() - 1] != SYSEX_END
+    {
+        return None;
+    }
+    Some((msg[2], &msg[3..msg.len() - 1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assembler_multi_packet() {
+        let mut asm = SysexAssembler::<16>::new();
+        assert_eq!(asm.push_packet(&[0x04, 0xF0, 0x7D, 0x22]), None);
+        assert_eq!(
+            asm.push_packet(&[0x06, 0x01, 0xF7, 0x00]),
+            Some(&[0xF0, 0x7D, 0x22, 0x01, 0xF7][..])
+        );
+    }
+
+    #[test]
+    fn test_assembler_end_variants() {
+        let mut asm = SysexAssembler::<16>::new();
+        assert_eq!(asm.push_packet(&[0x04, 0xF0, 0x7D, 0x22]), None);
+        assert_eq!(
+            asm.push_packet(&[0x05, 0xF7, 0x00, 0x00]),
+            Some(&[0xF0, 0x7D, 0x22, 0xF7][..])
+        );
+
+        assert_eq!(asm.push_packet(&[0x04, 0xF0, 0x7D, 0x21]), None);
+        assert_eq!(
+            asm.push_packet(&[0x07, 0x01, 0x02, 0xF7]),
+            Some(&[0xF0, 0x7D, 0x21, 0x01, 0x02, 0xF7][..])
+        );
+    }
+
+    #[test]
+    fn test_assembler_overflow_drops_message() {
+        let mut asm = SysexAssembler::<4>::new();
+        assert_eq!(asm.push_packet(&[0x04, 0xF0, 0x7D, 0x21]), None);
+        assert_eq!(asm.push_packet(&[0x07, 0x01, 0x02, 0xF7]), None);
+        // Recovers on the next message
+        assert_eq!(
+            asm.push_packet(&[0x07, 0xF0, 0x7D, 0xF7]),
+            Some(&[0xF0, 0x7D, 0xF7][..])
+        );
+    }
+
+    #[test]
+    fn test_packets_round_trip() {
+        for len in 0..8 {
+            let mut msg = vec![SYSEX_START, MANUFACTURER_ID];
+            msg.extend((0..len).map(|i| i as u8));
+            msg.push(SYSEX_END);
+            let mut asm = SysexAssembler::<16>::new();
+            let mut out = None;
+            for packet in packets(&msg) {
+                assert!(is_sysex_packet(&packet));
+                out = asm.push_packet(&packet).map(|m| m.to_vec());
+            }
+            assert_eq!(out, Some(msg));
+        }
+    }
+
+    #[test]
+    fn test_is_sysex_packet() {
+        assert!(is_sysex_packet(&[0x04, 0xF0, 0x7D, 0x21]));
+        assert!(is_sysex_packet(&[0x05, 0xF7, 0, 0]));
+        // Tune request is single-byte system common, not SysEx
+        assert!(!is_sysex_packet(&[0x05, 0xF6, 0, 0]));
+        assert!(!is_sysex_packet(&[0x09, 0x90, 60, 100]));
+    }
+
+    #[test]
+    fn test_parse_message() {
+        assert_eq!(
+            parse_message(&[0xF0, 0x7D, 0x22, 0x01, 0xF7]),
+            Some((0x22, &[0x01][..]))
+        );
+        assert_eq!(
+            parse_message(&[0xF0, 0x7D, 0x22, 0xF7]),
+            Some((0x22, &[][..]))
+        );
+        // Other manufacturers are ignored
+        assert_eq!(parse_message(&[0xF0, 0x41, 0x22, 0xF7]), None);
+        assert_eq!(parse_message(&[0xF0, 0x7D, 0xF7]), None);
+    }
+}
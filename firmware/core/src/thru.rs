@@ -0,0 +1,116 @@
+/// Classes of incoming messages soft-thru can forward.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Class {
+    /// Note on/off and polyphonic aftertouch.
+    Notes,
+    Controllers,
+    /// Clock, start, continue, stop and song position.
+    Clock,
+    /// Program change, channel pressure and pitch bend.
+    Other,
+}
+
+impl Class {
+    pub const ALL: [Class; 4] = [Class::Notes, Class::Controllers, Class::Clock, Class::Other];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Notes => "Notes",
+            Class::Controllers => "CCs",
+            Class::Clock => "Clock",
+            Class::Other => "Other",
+        }
+    }
+
+    /// Class of the message starting with `status`, with its channel (0-15) for
+    /// channel messages. SysEx and the other system messages have none.
+    pub fn of(status: u8) -> Option<(Class, Option<u8>)> {
+        let channel = Some(status & 0x0F);
+        match status {
+            0x80..=0xAF => Some((Class::Notes, channel)),
+            0xB0..=0xBF => Some((Class::Controllers, channel)),
+            0xC0..=0xEF => Some((Class::Other, channel)),
+            0xF2 | 0xF8 | 0xFA..=0xFC => Some((Class::Clock, None)),
+            _ => None,
+        }
+    }
+}
+
+/// Which incoming messages are forwarded: per-class switches, and a channel mask
+/// (bit `i` for channel `i + 1`) that channel messages must also pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThruFilter {
+    classes: u8,
+    pub channels: u16,
+}
+
+impl ThruFilter {
+    /// Forwards nothing until classes are enabled, then any channel.
+    pub const fn new() -> Self {
+        Self {
+            classes: 0,
+            channels: 0xFFFF,
+        }
+    }
+
+    pub fn enabled(&self, class: Class) -> bool {
+        self.classes & (1 << class as u8) != 0
+    }
+
+    pub fn toggle(&mut self, class: Class) {
+        self.classes ^= 1 << class as u8;
+    }
+
+    pub fn channel_enabled(&self, channel: u8) -> bool {
+        self.channels & (1 << (channel & 0x0F)) != 0
+    }
+
+    pub fn toggle_channel(&mut self, channel: u8) {
+        self.channels ^= 1 << (channel & 0x0F);
+    }
+
+    /// Whether the message starting with `status` is forwarded.
+    pub fn passes(&self, status: u8) -> bool {
+        match Class::of(status) {
+            Some((class, channel)) => {
+                self.enabled(class) && channel.is_none_or(|c| self.channel_enabled(c))
+            }
+            None => false,
+        }
+    }
+}
+
+impl Default for ThruFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let mut filter = ThruFilter::new();
+        assert!(!filter.passes(0x90));
+        assert!(!filter.passes(0xF8));
+
+        filter.toggle(Class::Notes);
+        filter.toggle(Class::Clock);
+        assert!(filter.passes(0x90));
+        assert!(filter.passes(0x8F));
+        assert!(filter.passes(0xF8));
+        assert!(!filter.passes(0xB0));
+        assert!(!filter.passes(0xE0));
+        // SysEx never goes through
+        assert!(!filter.passes(0xF0));
+
+        filter.toggle_channel(2);
+        assert!(!filter.passes(0x92));
+        assert!(filter.passes(0x93));
+        // System messages ignore the channel mask
+        filter.channels = 0;
+        assert!(filter.passes(0xFA));
+    }
+}
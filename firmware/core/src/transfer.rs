@@ -0,0 +1,306 @@
+//! Chunked SysEx transfers for bulk data.
+//!
+//! The host sends `TRANSFER_BEGIN` with a target and the total length, then numbered
+//! `TRANSFER_CHUNK`s, then `TRANSFER_END`. The board answers every message with
+//! `TRANSFER_ACK` (the next sequence number it expects) or `TRANSFER_NACK` (where to
+//! resend from, and why), so the host can wait for each ACK before sending on.
+//! After a dropped connection, `TRANSFER_STATUS` tells the host where to resume.
+//!
+//! Chunk data is 8-bit: each group of up to 7 bytes is sent as a byte holding their
+//! top bits (first byte in bit 0) followed by the 7 low bits of each.
+
+use crate::sysex::{cmd, MANUFACTURER_ID, SYSEX_END, SYSEX_START};
+
+/// Data bytes per chunk before packing; packs to 128 SysEx bytes.
+pub const MAX_CHUNK: usize = 112;
+
+/// What a transfer fills.
+pub mod target {
+    /// The event player buffer; the data is encoded events as for `PLAYER_APPEND`.
+    pub const PLAYER: u8 = 0;
+    /// The tuning scale; the data is the text of a Scala `.scl` file.
+    pub const SCALE: u8 = 1;
+}
+
+/// Why a message was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NackReason {
+    /// No transfer has been started.
+    NoTransfer = 1,
+    /// A chunk was skipped; resend from the expected one.
+    OutOfOrder = 2,
+    Checksum = 3,
+    /// More data than announced.
+    TooLong = 4,
+    /// `TRANSFER_END` before all the data arrived.
+    Incomplete = 5,
+    Malformed = 6,
+    /// The board has no such target.
+    UnknownTarget = 7,
+}
+
+/// Board answer to a transfer message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reply {
+    Ack(u16),
+    Nack(u16, NackReason),
+}
+
+impl Reply {
+    /// The reply as a SysEx message.
+    pub fn encode(self, out: &mut [u8; 7]) -> &[u8] {
+        let (command, seq, reason) = match self {
+            Reply::Ack(seq) => (cmd::TRANSFER_ACK, seq, None),
+            Reply::Nack(seq, reason) => (cmd::TRANSFER_NACK, seq, Some(reason as u8)),
+        };
+        *out = [
+            SYSEX_START,
+            MANUFACTURER_ID,
+            command,
+            (seq >> 7) as u8 & 0x7F,
+            seq as u8 & 0x7F,
+            reason.unwrap_or(SYSEX_END),
+            SYSEX_END,
+        ];
+        &out[..if reason.is_some() { 7 } else { 6 }]
+    }
+}
+
+/// Data from an accepted chunk, to be written at `offset` of `target`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Data<'a> {
+    pub target: u8,
+    pub offset: u32,
+    pub bytes: &'a [u8],
+}
+
+/// XOR of the packed chunk bytes.
+pub fn checksum(packed: &[u8]) -> u8 {
+    packed.iter().fold(0, |acc, b| acc ^ b) & 0x7F
+}
+
+/// Packs 8-bit `data` into 7-bit SysEx bytes, returning how many were written.
+pub fn pack7(data: &[u8], out: &mut [u8]) -> usize {
+    let mut len = 0;
+    for group in data.chunks(7) {
+        out[len] = group
+            .iter()
+            .enumerate()
+            .fold(0, |msbs, (i, b)| msbs | (b >> 7) << i);
+        for (o, b) in out[len + 1..].iter_mut().zip(group) {
+            *o = b & 0x7F;
+        }
+        len += 1 + group.len();
+    }
+    len
+}
+
+/// Unpacks bytes written by [`pack7`], returning how many were written, or `None` if
+/// `out` is too small.
+pub fn unpack7(packed: &[u8], out: &mut [u8]) -> Option<usize> {
+    let mut len = 0;
+    for group in packed.chunks(8) {
+        let msbs = group[0];
+        for (i, b) in group[1..].iter().enumerate() {
+            *out.get_mut(len)? = b | ((msbs >> i) & 1) << 7;
+            len += 1;
+        }
+    }
+    Some(len)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Active {
+    target: u8,
+    total: u32,
+    received: u32,
+    next_seq: u16,
+}
+
+/// Board side of a transfer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TransferReceiver {
+    active: Option<Active>,
+}
+
+impl TransferReceiver {
+    pub const fn new() -> Self {
+        Self { active: None }
+    }
+
+    /// `TRANSFER_BEGIN`: target, then the length as three 7-bit bytes, high first.
+    /// Replaces any transfer in progress if `accepts` the target.
+    pub fn begin(&mut self, payload: &[u8], accepts: impl Fn(u8) -> bool) -> (Reply, Option<u8>) {
+        let [target, a, b, c] = *payload else {
+            return (Reply::Nack(0, NackReason::Malformed), None);
+        };
+        if !accepts(target) {
+            return (Reply::Nack(0, NackReason::UnknownTarget), None);
+        }
+        self.active = Some(Active {
+            target,
+            total: (a as u32) << 14 | (b as u32) << 7 | c as u32,
+            received: 0,
+            next_seq: 0,
+        });
+        (Reply::Ack(0), Some(target))
+    }
+
+    /// `TRANSFER_CHUNK`: sequence number (two 7-bit bytes), checksum, packed data.
+    /// A repeated chunk is acknowledged again without returning its data.
+    pub fn chunk<'a>(
+        &mut self,
+        payload: &[u8],
+        out: &'a mut [u8; MAX_CHUNK],
+    ) -> (Reply, Option<Data<'a>>) {
+        let Some(active) = self.active.as_mut() else {
+            return (Reply::Nack(0, NackReason::NoTransfer), None);
+        };
+        let next = active.next_seq;
+        let [hi, lo, sum, ref packed @ ..] = *payload else {
+            return (Reply::Nack(next, NackReason::Malformed), None);
+        };
+        let seq = (hi as u16) << 7 | lo as u16;
+        if seq < next {
+            return (Reply::Ack(next), None);
+        }
+        if seq > next {
+            return (Reply::Nack(next, NackReason::OutOfOrder), None);
+        }
+        if checksum(packed) != sum {
+            return (Reply::Nack(next, NackReason::Checksum), None);
+        }
+        let Some(len) = unpack7(packed, out) else {
+            return (Reply::Nack(next, NackReason::Malformed), None);
+        };
+        if active.received + len as u32 > active.total {
+            return (Reply::Nack(next, NackReason::TooLong), None);
+        }
+        let offset = active.received;
+        active.received += len as u32;
+        active.next_seq += 1;
+        let data = Data {
+            target: active.target,
+            offset,
+            bytes: &out[..len],
+        };
+        (Reply::Ack(active.next_seq), Some(data))
+    }
+
+    /// `TRANSFER_END`: returns the finished target once all the data is in.
+    pub fn end(&mut self) -> (Reply, Option<u8>) {
+        match self.active {
+            None => (Reply::Nack(0, NackReason::NoTransfer), None),
+            Some(active) if active.received < active.total => {
+                (Reply::Nack(active.next_seq, NackReason::Incomplete), None)
+            }
+            Some(active) => {
+                self.active = None;
+                (Reply::Ack(active.next_seq), Some(active.target))
+            }
+        }
+    }
+
+    /// `TRANSFER_STATUS`: the chunk to resume from.
+    pub fn status(&self) -> Reply {
+        match self.active {
+            Some(active) => Reply::Ack(active.next_seq),
+            None => Reply::Nack(0, NackReason::NoTransfer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_payload(seq: u16, data: &[u8]) -> Vec<u8> {
+        let mut packed = [0u8; 128];
+        let len = pack7(data, &mut packed);
+        let mut payload = vec![(seq >> 7) as u8, seq as u8 & 0x7F, checksum(&packed[..len])];
+        payload.extend_from_slice(&packed[..len]);
+        payload
+    }
+
+    #[test]
+    fn test_pack_round_trip() {
+        let data: Vec<u8> = (0..MAX_CHUNK).map(|i| (i * 37) as u8).collect();
+        for len in [0, 1, 7, 8, 15, MAX_CHUNK] {
+            let mut packed = [0u8; 128];
+            let packed_len = pack7(&data[..len], &mut packed);
+            assert!(packed[..packed_len].iter().all(|&b| b < 0x80));
+            let mut out = [0u8; MAX_CHUNK];
+            assert_eq!(unpack7(&packed[..packed_len], &mut out), Some(len));
+            assert_eq!(&out[..len], &data[..len]);
+        }
+        assert_eq!(pack7(&[0u8; MAX_CHUNK], &mut [0u8; 128]), 128);
+        assert_eq!(unpack7(&[0, 1, 2], &mut [0u8; 1]), None);
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut rx = TransferReceiver::new();
+        let mut out = [0u8; MAX_CHUNK];
+        assert_eq!(rx.status(), Reply::Nack(0, NackReason::NoTransfer));
+        assert_eq!(
+            rx.chunk(&chunk_payload(0, &[1]), &mut out).0,
+            Reply::Nack(0, NackReason::NoTransfer)
+        );
+
+        let accepts = |t| t == target::PLAYER;
+        assert_eq!(
+            rx.begin(&[5, 0, 1, 4], accepts),
+            (Reply::Nack(0, NackReason::UnknownTarget), None)
+        );
+        assert_eq!(
+            rx.begin(&[target::PLAYER, 0, 1, 4], accepts),
+            (Reply::Ack(0), Some(0))
+        );
+        let first: Vec<u8> = (0..MAX_CHUNK as u8).map(|b| b ^ 0x80).collect();
+        let (reply, data) = rx.chunk(&chunk_payload(0, &first), &mut out);
+        assert_eq!(reply, Reply::Ack(1));
+        let data = data.unwrap();
+        assert_eq!((data.target, data.offset, data.bytes), (0, 0, &first[..]));
+
+        // Repeats are acknowledged but not applied again; gaps ask for a resend
+        assert_eq!(
+            rx.chunk(&chunk_payload(0, &first), &mut out),
+            (Reply::Ack(1), None)
+        );
+        assert_eq!(
+            rx.chunk(&chunk_payload(2, &[1]), &mut out).0,
+            Reply::Nack(1, NackReason::OutOfOrder)
+        );
+        let mut corrupt = chunk_payload(1, &[1, 2]);
+        corrupt[2] ^= 1;
+        assert_eq!(
+            rx.chunk(&corrupt, &mut out).0,
+            Reply::Nack(1, NackReason::Checksum)
+        );
+
+        assert_eq!(rx.end(), (Reply::Nack(1, NackReason::Incomplete), None));
+        assert_eq!(rx.status(), Reply::Ack(1));
+        assert_eq!(
+            rx.chunk(&chunk_payload(1, &[0; 21]), &mut out).0,
+            Reply::Nack(1, NackReason::TooLong)
+        );
+        let (reply, data) = rx.chunk(&chunk_payload(1, &[9; 20]), &mut out);
+        assert_eq!(reply, Reply::Ack(2));
+        assert_eq!(data.unwrap().offset, MAX_CHUNK as u32);
+        assert_eq!(rx.end(), (Reply::Ack(2), Some(target::PLAYER)));
+        assert_eq!(rx.status(), Reply::Nack(0, NackReason::NoTransfer));
+    }
+
+    #[test]
+    fn test_reply_encoding() {
+        let mut buf = [0; 7];
+        assert_eq!(
+            Reply::Ack(200).encode(&mut buf),
+            [0xF0, 0x7D, cmd::TRANSFER_ACK, 1, 72, 0xF7]
+        );
+        assert_eq!(
+            Reply::Nack(3, NackReason::Checksum).encode(&mut buf),
+            [0xF0, 0x7D, cmd::TRANSFER_NACK, 0, 3, 3, 0xF7]
+        );
+    }
+}
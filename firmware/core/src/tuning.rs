@@ -0,0 +1,266 @@
+use crate::layout::{Coordinate, Layout};
+use crate::scala::Scale;
+
+/// Absolute pitch of the center key (Middle C), in cents.
+pub const PITCH_ANCHOR_CENTS: f32 = 6000.0;
+
+/// Maximum number of enharmonic candidates returned by [`closest_keys`].
+pub const MAX_CANDIDATES: usize = 4;
+
+/// Octave and fifth offsets of a key relative to the layout center.
+/// - x + 1, y - 1 (UP-RIGHT) is a Perfect Fifth.
+/// - x + 0, y - 2 (UP UP) is an Octave.
+pub fn fifths_offsets<L: Layout>(coord: Coordinate) -> (i16, i16) {
+    let center = L::center_coord();
+    let dx_raw = coord.x as i16 - center.x as i16;
+    let dy_raw = coord.y as i16 - center.y as i16;
+
+    let octaves = (-dy_raw).div_euclid(2);
+    let shift = (-dy_raw).rem_euclid(2);
+    let fifths = 2 * dx_raw - 2 * octaves - shift;
+
+    (octaves, fifths)
+}
+
+/// Absolute pitch of a key in cents for the given fifth size.
+pub fn key_pitch_cents<L: Layout>(coord: Coordinate, fifth_size: f32) -> f32 {
+    let (oc, fifths) = fifths_offsets::<L>(coord);
+    // Absolute pitch calculation for standard 12-TET behavior
+    // 1 Octave (oc) = 1200 cents
+    // 1 Fifth step (fifths) = dynamic fifth size (default 700)
+    PITCH_ANCHOR_CENTS + (oc as f32 * 1200.0) + (fifths as f32 * fifth_size)
+        - (fifths.div_euclid(2) as f32 * 1200.0)
+}
+
+/// Pitch of a key from a Scala scale: its number of 12-EDO semitones from the center
+/// key picks the scale step, with the 1/1 on the center key.
+pub fn scale_pitch_cents<L: Layout>(coord: Coordinate, scale: &Scale) -> f32 {
+    let semitones = (key_pitch_cents::<L>(coord, 700.0) - PITCH_ANCHOR_CENTS) / 100.0;
+    // Whole at a 700c fifth; rounded against float error
+    let step = if semitones < 0.0 {
+        semitones - 0.5
+    } else {
+        semitones + 0.5
+    } as i32;
+    PITCH_ANCHOR_CENTS + scale.step_cents(step)
+}
+
+/// Pitch bend value with no bend applied.
+pub const BEND_CENTER: u16 = 8192;
+
+/// Nearest MIDI note to `target_cents`, and the 14-bit pitch bend making up the
+/// rest for a synth bending by `bend_range` semitones.
+pub fn mpe_note(target_cents: f32, bend_range: f32) -> (u8, u16) {
+    let midi_note = ((target_cents / 100.0 + 0.5) as u8).clamp(0, 127);
+    (midi_note, bend_for(midi_note, target_cents, bend_range))
+}
+
+/// Pitch bend that moves `note` to `target_cents`, clamped to the bend range.
+pub fn bend_for(note: u8, target_cents: f32, bend_range: f32) -> u16 {
+    let bend_cents = target_cents - (note as f32 * 100.0);
+    let bend_units_offset = (bend_cents / 100.0) * (BEND_CENTER as f32 / bend_range);
+    (BEND_CENTER as f32 + bend_units_offset).clamp(0.0, 16383.0) as u16
+}
+
+/// Pitch in cents a synth bending by `bend_range` semitones plays for `note` and `bend`.
+pub fn bent_pitch_cents(note: u8, bend: u16, bend_range: f32) -> f32 {
+    let bend_semitones = (bend as f32 - BEND_CENTER as f32) * bend_range / BEND_CENTER as f32;
+    (note as f32 + bend_semitones) * 100.0
+}
+
+/// Keys found by [`closest_keys`], in scan order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidates {
+    keys: [Coordinate; MAX_CANDIDATES],
+    len: usize,
+}
+
+impl Candidates {
+    const fn new() -> Self {
+        Self {
+            keys: [Coordinate { x: 0, y: 0 }; MAX_CANDIDATES],
+            len: 0,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == MAX_CANDIDATES
+    }
+
+    fn push(&mut self, coord: Coordinate) {
+        if !self.is_full() {
+            self.keys[self.len] = coord;
+            self.len += 1;
+        }
+    }
+
+    pub fn as_slice(&self) -> &[Coordinate] {
+        &self.keys[..self.len]
+    }
+}
+
+/// Finds the keys whose pitch is closest to `target_cents` (all enharmonic equivalents
+/// within a cent of the best match), ignoring anything further than `max_dist`.
+/// Keys mapping to `bias_note` are preferred by 20 cents.
+pub fn closest_keys<L: Layout>(
+    target_cents: f32,
+    max_dist: f32,
+    rows: usize,
+    cols: usize,
+    bias_note: Option<u8>,
+    fifth_size: f32,
+) -> Candidates {
+    closest_keys_by::<L>(target_cents, max_dist, rows, cols, bias_note, |coord| {
+        key_pitch_cents::<L>(coord, fifth_size)
+    })
+}
+
+/// [`closest_keys`] for keys tuned by `pitch` rather than by a fifth size.
+pub fn closest_keys_by<L: Layout>(
+    target_cents: f32,
+    max_dist: f32,
+    rows: usize,
+    cols: usize,
+    bias_note: Option<u8>,
+    pitch: impl Fn(Coordinate) -> f32,
+) -> Candidates {
+    let mut candidates = Candidates::new();
+    let distance = |coord: Coordinate| {
+        let pitch = pitch(coord);
+        let mut dist = (pitch - target_cents).abs();
+        if let Some(note) = bias_note {
+            if L::coord_to_midi(coord) == note {
+                dist -= 20.0;
+            }
+        }
+        dist
+    };
+
+    let mut min_dist = max_dist;
+    for r in 0..rows {
+        for c in 0..cols {
+            if let Some(coord) = L::key_to_coord(r, c) {
+                min_dist = min_dist.min(distance(coord));
+            }
+        }
+    }
+    if min_dist >= max_dist {
+        return candidates;
+    }
+    for r in 0..rows {
+        for c in 0..cols {
+            if let Some(coord) = L::key_to_coord(r, c) {
+                if distance(coord) <= min_dist + 1.0 {
+                    candidates.push(coord);
+                    if candidates.is_full() {
+                        return candidates;
+                    }
+                }
+            }
+        }
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plain grid with the center at (2, 2).
+    struct Grid;
+
+    impl Layout for Grid {
+        fn key_to_coord(row: usize, col: usize) -> Option<Coordinate> {
+            Some(Coordinate {
+                x: col as i8,
+                y: row as i8,
+            })
+        }
+
+        fn led_to_coord(_idx: usize) -> Option<Coordinate> {
+            None
+        }
+
+        fn coord_to_led(_coord: Coordinate) -> Option<usize> {
+            None
+        }
+
+        fn center_coord() -> Coordinate {
+            Coordinate { x: 2, y: 2 }
+        }
+    }
+
+    #[test]
+    fn test_fifths_offsets() {
+        assert_eq!(fifths_offsets::<Grid>(Coordinate { x: 2, y: 2 }), (0, 0));
+        // Up-right is a fifth
+        assert_eq!(fifths_offsets::<Grid>(Coordinate { x: 3, y: 1 }), (0, 1));
+        // Two rows up moves up an octave
+        assert_eq!(fifths_offsets::<Grid>(Coordinate { x: 2, y: 0 }).0, 1);
+    }
+
+    #[test]
+    fn test_key_pitch_cents() {
+        assert_eq!(
+            key_pitch_cents::<Grid>(Coordinate { x: 2, y: 2 }, 700.0),
+            PITCH_ANCHOR_CENTS
+        );
+        assert_eq!(
+            key_pitch_cents::<Grid>(Coordinate { x: 3, y: 1 }, 700.0),
+            PITCH_ANCHOR_CENTS + 700.0
+        );
+        // Major second to the right
+        assert_eq!(
+            key_pitch_cents::<Grid>(Coordinate { x: 3, y: 2 }, 697.0),
+            PITCH_ANCHOR_CENTS + 194.0
+        );
+    }
+
+    #[test]
+    fn test_closest_keys() {
+        let found = closest_keys::<Grid>(PITCH_ANCHOR_CENTS + 700.0, 200.0, 5, 5, None, 700.0);
+        assert!(found.as_slice().contains(&Coordinate { x: 3, y: 1 }));
+
+        let none = closest_keys::<Grid>(0.0, 200.0, 5, 5, None, 700.0);
+        assert!(none.as_slice().is_empty());
+    }
+
+    #[test]
+    fn test_scale_pitch_cents() {
+        let scale = crate::scala::parse(b"Pelog-ish\n3\n150.0\n520.0\n1200.0\n").unwrap();
+        let center = Coordinate { x: 2, y: 2 };
+        assert_eq!(
+            scale_pitch_cents::<Grid>(center, &scale),
+            PITCH_ANCHOR_CENTS
+        );
+        // A major second is two semitones, so two steps up
+        assert_eq!(
+            scale_pitch_cents::<Grid>(Coordinate { x: 3, y: 2 }, &scale),
+            PITCH_ANCHOR_CENTS + 520.0
+        );
+        // A fifth, seven semitones: two periods and a step
+        assert_eq!(
+            scale_pitch_cents::<Grid>(Coordinate { x: 3, y: 1 }, &scale),
+            PITCH_ANCHOR_CENTS + 2550.0
+        );
+        // Down a fifth: three periods down, two steps up
+        assert_eq!(
+            scale_pitch_cents::<Grid>(Coordinate { x: 1, y: 3 }, &scale),
+            PITCH_ANCHOR_CENTS - 3600.0 + 520.0
+        );
+    }
+
+    #[test]
+    fn test_mpe_note_round_trip() {
+        for fifth_size in [696.0, 700.0, 702.0] {
+            for x in 0..5 {
+                let pitch = key_pitch_cents::<Grid>(Coordinate { x, y: 1 }, fifth_size);
+                let (note, bend) = mpe_note(pitch, 2.0);
+                assert!((bent_pitch_cents(note, bend, 2.0) - pitch).abs() < 0.1);
+            }
+        }
+        assert_eq!(mpe_note(6000.0, 2.0), (60, BEND_CENTER));
+        assert_eq!(bend_for(60, 6100.0, 2.0), BEND_CENTER + 4096);
+        assert_eq!(bend_for(60, 7000.0, 2.0), 16383);
+    }
+}
@@ -0,0 +1,571 @@
+//! The USB host side of the host port: packets with their CRCs, their line coding as
+//! the PIO sends and samples it, and the requests and descriptors a MIDI controller
+//! is enumerated by. The port runs at full speed only.
+//!
+//! Line states are two bits, D+ in bit 0 and D- in bit 1, so a word of them drives or
+//! samples both pins at once. Packets are NRZI coded (a 0 is a change of state, a 1
+//! keeps it) with a 0 stuffed in after six 1s, and start with a SYNC and end with an
+//! EOP (two bit times of SE0, then J).
+
+/// Both lines low: a reset, an EOP or no device.
+pub const SE0: u8 = 0b00;
+/// Idle state of a full-speed bus: D+ high.
+pub const J: u8 = 0b01;
+pub const K: u8 = 0b10;
+
+/// The SYNC pattern, sent first: seven 0s then a 1.
+const SYNC: u8 = 0x80;
+/// 1s after which the sender stuffs in a 0.
+const MAX_ONES: u8 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pid {
+    Out,
+    In,
+    Sof,
+    Setup,
+    Data0,
+    Data1,
+    Ack,
+    Nak,
+    Stall,
+}
+
+impl Pid {
+    const ALL: [Pid; 9] = [
+        Pid::Out,
+        Pid::In,
+        Pid::Sof,
+        Pid::Setup,
+        Pid::Data0,
+        Pid::Data1,
+        Pid::Ack,
+        Pid::Nak,
+        Pid::Stall,
+    ];
+
+    /// The PID byte: the 4-bit PID, then its complement as a check.
+    pub fn byte(self) -> u8 {
+        let pid = match self {
+            Pid::Out => 0x1,
+            Pid::In => 0x9,
+            Pid::Sof => 0x5,
+            Pid::Setup => 0xD,
+            Pid::Data0 => 0x3,
+            Pid::Data1 => 0xB,
+            Pid::Ack => 0x2,
+            Pid::Nak => 0xA,
+            Pid::Stall => 0xE,
+        };
+        pid | (!pid << 4)
+    }
+
+    /// Reads a PID byte, `None` if its check fails or it's one the host doesn't use.
+    pub fn from_byte(byte: u8) -> Option<Pid> {
+        Pid::ALL.into_iter().find(|pid| pid.byte() == byte)
+    }
+
+    /// The other data PID, for the next packet of an endpoint.
+    pub fn toggled(self) -> Pid {
+        match self {
+            Pid::Data0 => Pid::Data1,
+            Pid::Data1 => Pid::Data0,
+            pid => pid,
+        }
+    }
+}
+
+/// CRC5 of a token's 11 bits of address and endpoint (or frame number), sent
+/// inverted.
+pub fn crc5(bits: u16) -> u8 {
+    let mut crc = 0x1F;
+    for i in 0..11 {
+        let bit = (bits >> i) as u8 & 1;
+        crc = if (crc ^ bit) & 1 != 0 {
+            (crc >> 1) ^ 0x14
+        } else {
+            crc >> 1
+        };
+    }
+    !crc & 0x1F
+}
+
+/// Runs CRC16 over `data` from `crc`, bits least significant first like the wire.
+fn crc16_update(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        for i in 0..8 {
+            let bit = (byte >> i) as u16 & 1;
+            crc = if (crc ^ bit) & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC16 of a data packet's payload, as sent after it (low byte first).
+pub fn crc16(data: &[u8]) -> u16 {
+    !crc16_update(0xFFFF, data)
+}
+
+/// Whether a received payload followed by its CRC16 is intact: running the CRC over
+/// both leaves a fixed residual.
+pub fn crc16_ok(data_and_crc: &[u8]) -> bool {
+    data_and_crc.len() >= 2 && crc16_update(0xFFFF, data_and_crc) == 0xB001
+}
+
+/// A token packet to `address` and `endpoint`.
+pub fn token(pid: Pid, address: u8, endpoint: u8) -> [u8; 3] {
+    let bits = (address as u16 & 0x7F) | ((endpoint as u16 & 0xF) << 7);
+    with_crc5(pid, bits)
+}
+
+/// The start of frame packet of `frame` (11 bits), which keeps a device from
+/// suspending.
+pub fn sof(frame: u16) -> [u8; 3] {
+    with_crc5(Pid::Sof, frame & 0x7FF)
+}
+
+fn with_crc5(pid: Pid, bits: u16) -> [u8; 3] {
+    let field = bits | (crc5(bits) as u16) << 11;
+    [pid.byte(), field as u8, (field >> 8) as u8]
+}
+
+/// Line states of what the host sends, 16 to a word with the first in the low bits,
+/// as the PIO shifts them out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Symbols<const W: usize> {
+    words: [u32; W],
+    len: usize,
+    level: u8,
+    ones: u8,
+}
+
+impl<const W: usize> Default for Symbols<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize> Symbols<W> {
+    pub const fn new() -> Self {
+        Self {
+            words: [0; W],
+            len: 0,
+            level: J,
+            ones: 0,
+        }
+    }
+
+    /// Line states so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The words holding them, the last one padded.
+    pub fn words(&self) -> &[u32] {
+        &self.words[..self.len.div_ceil(16)]
+    }
+
+    fn push(&mut self, state: u8) -> bool {
+        let Some(word) = self.words.get_mut(self.len / 16) else {
+            return false;
+        };
+        if self.len.is_multiple_of(16) {
+            *word = 0;
+        }
+        *word |= (state as u32) << (2 * (self.len % 16));
+        self.len += 1;
+        true
+    }
+
+    fn bit(&mut self, one: bool) -> bool {
+        if !one {
+            self.level ^= J | K;
+            self.ones = 0;
+            return self.push(self.level);
+        }
+        if !self.push(self.level) {
+            return false;
+        }
+        self.ones += 1;
+        if self.ones == MAX_ONES {
+            return self.bit(false);
+        }
+        true
+    }
+
+    /// `bits` of idle bus, for the gap packets need between them.
+    pub fn idle(&mut self, bits: usize) -> bool {
+        (0..bits).all(|_| self.push(J))
+    }
+
+    /// Appends a packet: SYNC, `bytes` and EOP. False if it didn't fit.
+    pub fn packet(&mut self, bytes: &[u8]) -> bool {
+        self.level = J;
+        self.ones = 0;
+        let coded = core::iter::once(&SYNC)
+            .chain(bytes)
+            .all(|&byte| (0..8).all(|i| self.bit(byte >> i & 1 != 0)));
+        self.level = J;
+        coded && self.push(SE0) && self.push(SE0) && self.push(J)
+    }
+
+    /// Appends a data packet: `pid`, `payload` and its CRC16. False if it didn't fit.
+    pub fn data(&mut self, pid: Pid, payload: &[u8]) -> bool {
+        let crc = crc16(payload).to_le_bytes();
+        let mut bytes = [0u8; 1 + MAX_DATA + 2];
+        let Some(packet) = bytes.get_mut(..payload.len() + 3) else {
+            return false;
+        };
+        packet[0] = pid.byte();
+        packet[1..=payload.len()].copy_from_slice(payload);
+        packet[payload.len() + 1..].copy_from_slice(&crc);
+        self.packet(packet)
+    }
+}
+
+/// Payload of a full-speed bulk or control packet at most.
+pub const MAX_DATA: usize = 64;
+
+/// The first 16 line states of a packet with `pid`, its SYNC and PID, packed 4 to a
+/// byte like [`decode`] takes them: how a reply is told apart before it has ended.
+pub fn leading_states(pid: Pid) -> u32 {
+    let mut symbols = Symbols::<2>::new();
+    symbols.packet(&[pid.byte()]);
+    symbols.words()[0]
+}
+
+/// Whether 4 sampled line states (one byte of samples) hold an SE0, so the EOP. Quick
+/// enough to run on every byte as it's sampled.
+pub fn has_se0(samples: u8) -> bool {
+    (samples | samples >> 1) & 0x55 != 0x55
+}
+
+/// Decodes a packet from its line states as sampled from the start of its SYNC, 4 to
+/// a byte with the first in the low bits, into `out`: the SYNC is checked, the NRZI
+/// undone and the stuffed bits dropped, up to the EOP. Bits short of a whole byte
+/// before the EOP are dropped, as the CRC catches a damaged packet. Returns the bytes
+/// decoded, or `None` if the SYNC or the stuffing was wrong or it didn't fit.
+pub fn decode(samples: &[u8], out: &mut [u8]) -> Option<usize> {
+    let states = samples
+        .iter()
+        .flat_map(|&byte| (0..4).map(move |i| byte >> (2 * i) & 0b11));
+    let mut level = J;
+    let mut ones = 0;
+    // The SYNC is decoded like the rest, into its own byte
+    let mut bits = 0usize;
+    let mut byte = 0u8;
+    let mut len = 0;
+    for state in states {
+        if state == SE0 {
+            break;
+        }
+        let one = state == level;
+        level = state;
+        if ones == MAX_ONES {
+            // A stuffed 0, unless the stuffing is broken
+            if one {
+                return None;
+            }
+            ones = 0;
+            continue;
+        }
+        ones = if one { ones + 1 } else { 0 };
+        byte = byte >> 1 | (one as u8) << 7;
+        bits += 1;
+        if bits.is_multiple_of(8) {
+            if bits == 8 {
+                if byte != SYNC {
+                    return None;
+                }
+            } else {
+                *out.get_mut(len)? = byte;
+                len += 1;
+            }
+        }
+    }
+    Some(len)
+}
+
+/// Standard requests, by `bRequest`.
+pub const SET_ADDRESS: u8 = 5;
+pub const GET_DESCRIPTOR: u8 = 6;
+pub const SET_CONFIGURATION: u8 = 9;
+/// Descriptor types.
+pub const DEVICE: u8 = 1;
+pub const CONFIGURATION: u8 = 2;
+const INTERFACE: u8 = 4;
+const ENDPOINT: u8 = 5;
+/// Length of a device descriptor; its first 8 bytes give the control endpoint's
+/// packet size.
+pub const DEVICE_LEN: usize = 18;
+/// Length of a configuration descriptor alone, without what follows it.
+pub const CONFIGURATION_LEN: usize = 9;
+
+/// The 8 bytes of a SETUP packet's data.
+pub fn setup(request_type: u8, request: u8, value: u16, index: u16, length: u16) -> [u8; 8] {
+    let [value_lo, value_hi] = value.to_le_bytes();
+    let [index_lo, index_hi] = index.to_le_bytes();
+    let [length_lo, length_hi] = length.to_le_bytes();
+    [
+        request_type,
+        request,
+        value_lo,
+        value_hi,
+        index_lo,
+        index_hi,
+        length_lo,
+        length_hi,
+    ]
+}
+
+/// Reads descriptor `kind` (index 0), up to `length` bytes.
+pub fn get_descriptor(kind: u8, length: u16) -> [u8; 8] {
+    setup(0x80, GET_DESCRIPTOR, (kind as u16) << 8, 0, length)
+}
+
+pub fn set_address(address: u8) -> [u8; 8] {
+    setup(0x00, SET_ADDRESS, address as u16, 0, 0)
+}
+
+pub fn set_configuration(configuration: u8) -> [u8; 8] {
+    setup(0x00, SET_CONFIGURATION, configuration as u16, 0, 0)
+}
+
+/// Packet size of a device's control endpoint, from the start of its device
+/// descriptor.
+pub fn control_packet_size(device: &[u8]) -> Option<u8> {
+    match device {
+        [_, DEVICE, _, _, _, _, _, size, ..] if matches!(size, 8 | 16 | 32 | 64) => Some(*size),
+        _ => None,
+    }
+}
+
+/// Length of a configuration with its interfaces and endpoints, and its value for
+/// SET_CONFIGURATION, from its configuration descriptor.
+pub fn configuration(descriptor: &[u8]) -> Option<(u16, u8)> {
+    match descriptor {
+        [_, CONFIGURATION, lo, hi, _, value, ..] => Some((u16::from_le_bytes([*lo, *hi]), *value)),
+        _ => None,
+    }
+}
+
+/// The endpoint a MIDI controller sends on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MidiIn {
+    /// Endpoint number, without the direction bit.
+    pub endpoint: u8,
+    pub max_packet: u16,
+}
+
+/// The bulk IN endpoint of the first USB-MIDI streaming interface (audio class,
+/// MIDI streaming subclass) of a configuration, read with its interfaces and
+/// endpoints.
+pub fn find_midi_in(configuration: &[u8]) -> Option<MidiIn> {
+    let mut in_midi = false;
+    let mut rest = configuration;
+    while let [len, kind, ..] = *rest {
+        let descriptor = rest.get(..len as usize)?;
+        match (kind, descriptor) {
+            (INTERFACE, [_, _, _, _, _, class, subclass, ..]) => {
+                in_midi = *class == 1 && *subclass == 3;
+            }
+            (ENDPOINT, [_, _, address, attributes, lo, hi, ..])
+                if in_midi && address & 0x80 != 0 && attributes & 0b11 == 2 =>
+            {
+                return Some(MidiIn {
+                    endpoint: address & 0xF,
+                    max_packet: u16::from_le_bytes([*lo, *hi]) & 0x7FF,
+                });
+            }
+            _ => {}
+        }
+        if len == 0 {
+            return None;
+        }
+        rest = &rest[len as usize..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unpacks line states 4 to a byte, as the PIO samples them.
+    fn samples(symbols: &Symbols<8>) -> Vec<u8> {
+        let bytes: Vec<u8> = symbols
+            .words()
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect();
+        bytes[..symbols.len().div_ceil(4)].to_vec()
+    }
+
+    #[test]
+    fn test_pids() {
+        assert_eq!(Pid::Setup.byte(), 0x2D);
+        assert_eq!(Pid::Data1.byte(), 0x4B);
+        assert_eq!(Pid::Nak.byte(), 0x5A);
+        for pid in Pid::ALL {
+            assert_eq!(Pid::from_byte(pid.byte()), Some(pid));
+        }
+        // A PID whose check bits don't match
+        assert_eq!(Pid::from_byte(0x2C), None);
+        assert_eq!(Pid::Data0.toggled(), Pid::Data1);
+        assert_eq!(Pid::Data1.toggled(), Pid::Data0);
+    }
+
+    #[test]
+    fn test_crcs() {
+        // As seen on the wire: a SETUP to a new device, and the spec's CRC5 example
+        assert_eq!(token(Pid::Setup, 0, 0), [0x2D, 0x00, 0x10]);
+        assert_eq!(token(Pid::In, 0x15, 0xE)[2] >> 3, 0b11101);
+        let get_device = get_descriptor(DEVICE, 64);
+        assert_eq!(get_device, [0x80, 0x06, 0x00, 0x01, 0x00, 0x00, 0x40, 0x00]);
+        assert_eq!(crc16(&get_device).to_le_bytes(), [0xDD, 0x94]);
+        // A zero length packet's CRC
+        assert_eq!(crc16(&[]), 0);
+
+        let mut packet = get_device.to_vec();
+        packet.extend_from_slice(&crc16(&get_device).to_le_bytes());
+        assert!(crc16_ok(&packet));
+        packet[3] ^= 0x10;
+        assert!(!crc16_ok(&packet));
+        assert!(!crc16_ok(&[0x00]));
+        assert_eq!(sof(0x801), sof(0x001));
+    }
+
+    #[test]
+    fn test_line_coding() {
+        let mut symbols = Symbols::<8>::new();
+        assert!(symbols.packet(&[Pid::Ack.byte()]));
+        // SYNC and PID, then the EOP
+        assert_eq!(symbols.len(), 19);
+        let sync = [K, J, K, J, K, J, K, K];
+        for (i, &state) in sync.iter().enumerate() {
+            assert_eq!(symbols.words()[0] >> (2 * i) & 0b11, state as u32);
+        }
+        assert_eq!(symbols.words()[0], leading_states(Pid::Ack));
+        assert_eq!(symbols.words()[1], (SE0 | SE0 << 2 | J << 4) as u32);
+
+        let mut out = [0u8; 4];
+        assert_eq!(decode(&samples(&symbols), &mut out), Some(1));
+        assert_eq!(out[0], 0xD2);
+    }
+
+    #[test]
+    fn test_bit_stuffing() {
+        // Eight 1s in a row get a 0 stuffed in after the sixth
+        let payload = [0xFF, 0xFF, 0x00, 0x7E];
+        let mut symbols = Symbols::<8>::new();
+        assert!(symbols.idle(2));
+        assert!(symbols.data(Pid::Data1, &payload));
+        let plain = 8 * (1 + 1 + payload.len() + 2) + 3;
+        assert!(symbols.len() > 2 + plain);
+
+        let samples = samples(&symbols);
+        // Sampling starts at the SYNC, not at the gap before it
+        let mut from_sync = Symbols::<8>::new();
+        from_sync.data(Pid::Data1, &payload);
+        let mut out = [0u8; 8];
+        let len = decode(&self::samples(&from_sync), &mut out).unwrap();
+        assert_eq!(len, 1 + payload.len() + 2);
+        assert_eq!(Pid::from_byte(out[0]), Some(Pid::Data1));
+        assert_eq!(&out[1..5], &payload);
+        assert!(crc16_ok(&out[1..len]));
+        assert_eq!(samples[0] & 0b1111, J | J << 2);
+
+        // Seven 1s in a row can't be sent, so are a damaged packet
+        let mut broken = self::samples(&from_sync);
+        let mut states: Vec<u8> = broken
+            .iter()
+            .flat_map(|&b| (0..4).map(move |i| b >> (2 * i) & 0b11))
+            .collect();
+        let stuffed = (9..states.len())
+            .find(|&i| (i - 6..i).all(|j| states[j] == states[i - 6]) && states[i] != states[i - 1])
+            .unwrap();
+        states[stuffed] = states[stuffed - 1];
+        broken = states
+            .chunks(4)
+            .map(|c| c.iter().enumerate().fold(0, |b, (i, s)| b | s << (2 * i)))
+            .collect();
+        assert_eq!(decode(&broken, &mut out), None);
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let mut symbols = Symbols::<8>::new();
+        symbols.data(Pid::Data0, &[1, 2, 3, 4]);
+        // Too little room for the packet
+        let mut out = [0u8; 4];
+        assert_eq!(decode(&samples(&symbols), &mut out), None);
+        // Noise that isn't a SYNC
+        assert_eq!(decode(&[0b1001_1001, 0b1001_1001, 0], &mut out), None);
+        // Only a few words to hold it
+        let mut small = Symbols::<1>::new();
+        assert!(!small.data(Pid::Data0, &[0; 8]));
+        assert!(!Symbols::<8>::new().data(Pid::Data0, &[0; MAX_DATA + 1]));
+    }
+
+    #[test]
+    fn test_se0() {
+        assert!(has_se0(J | SE0 << 2 | K << 4 | J << 6));
+        assert!(!has_se0(J | K << 2 | K << 4 | J << 6));
+    }
+
+    #[test]
+    fn test_find_midi_in() {
+        #[rustfmt::skip]
+        let descriptors = [
+            // Configuration 1, 101 bytes in all
+            9, 2, 101, 0, 2, 1, 0, 0x80, 50,
+            // Audio control interface
+            9, 4, 0, 0, 0, 1, 1, 0, 0,
+            9, 0x24, 1, 0, 1, 9, 0, 1, 1,
+            // MIDI streaming interface, its jacks, then a bulk OUT and a bulk IN
+            9, 4, 1, 0, 2, 1, 3, 0, 0,
+            7, 0x24, 1, 0, 1, 65, 0,
+            6, 0x24, 2, 1, 1, 0,
+            6, 0x24, 2, 2, 2, 0,
+            9, 0x24, 3, 1, 3, 1, 2, 1, 0,
+            9, 0x24, 3, 2, 4, 1, 1, 1, 0,
+            9, 5, 0x01, 2, 64, 0, 0, 0, 0,
+            5, 0x25, 1, 1, 1,
+            9, 5, 0x82, 2, 64, 0, 0, 0, 0,
+            5, 0x25, 1, 1, 3,
+        ];
+        assert_eq!(configuration(&descriptors), Some((101, 1)));
+        assert_eq!(
+            find_midi_in(&descriptors),
+            Some(MidiIn {
+                endpoint: 2,
+                max_packet: 64
+            })
+        );
+        // The IN endpoint of another interface doesn't count
+        let mut hid = descriptors;
+        hid[27 + 5] = 3;
+        assert_eq!(find_midi_in(&hid), None);
+        // Cut short, or with a zero length descriptor
+        assert_eq!(find_midi_in(&descriptors[..95]), None);
+        assert_eq!(find_midi_in(&[0, 4, 0]), None);
+    }
+
+    #[test]
+    fn test_requests() {
+        assert_eq!(set_address(1), [0, 5, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(set_configuration(1), [0, 9, 1, 0, 0, 0, 0, 0]);
+        let device = [18, 1, 0x00, 0x02, 0, 0, 0, 64];
+        assert_eq!(control_packet_size(&device), Some(64));
+        assert_eq!(control_packet_size(&device[..7]), None);
+        assert_eq!(control_packet_size(&[18, 1, 0, 2, 0, 0, 0, 12]), None);
+    }
+}
@@ -0,0 +1,116 @@
+/// Code Index Number of a USB-MIDI event packet carrying the message starting with
+/// `status`: channel messages and system common by their length, real-time and
+/// anything else as single bytes (0xF).
+pub fn code_index(status: u8) -> u8 {
+    match status {
+        0x80..=0xEF => status >> 4,
+        0xF1 | 0xF3 => 0x2,
+        0xF2 => 0x3,
+        0xF6 => 0x5,
+        _ => 0xF,
+    }
+}
+
+/// Wraps a message of up to 3 bytes (SysEx aside) in a USB-MIDI event packet (cable 0).
+pub fn encode(message: [u8; 3]) -> [u8; 4] {
+    [code_index(message[0]), message[0], message[1], message[2]]
+}
+
+/// The MIDI bytes carried by a channel voice event packet, or `None` for other packets.
+pub fn channel_message(packet: &[u8]) -> Option<&[u8]> {
+    let len = match packet.first()? & 0x0F {
+        0x8..=0xB | 0xE => 3,
+        0xC | 0xD => 2,
+        _ => return None,
+    };
+    packet.get(1..1 + len)
+}
+
+/// The MIDI bytes of a non-SysEx event packet, by its Code Index Number: channel
+/// messages, system common and single bytes.
+pub fn payload(packet: &[u8]) -> Option<&[u8]> {
+    let len = match packet.first()? & 0x0F {
+        0x5 | 0xF => 1,
+        0x2 | 0xC | 0xD => 2,
+        0x3 | 0x8..=0xB | 0xE => 3,
+        _ => return None,
+    };
+    packet.get(1..1 + len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{Coordinate, Layout};
+    use crate::tuning::{bent_pitch_cents, key_pitch_cents, mpe_note};
+
+    #[test]
+    fn test_encode_decode() {
+        let note_on = encode([0x93, 60, 100]);
+        assert_eq!(note_on, [0x09, 0x93, 60, 100]);
+        assert_eq!(channel_message(&note_on), Some(&[0x93, 60, 100][..]));
+
+        assert_eq!(channel_message(&[0x0C, 0xC0, 5, 0]), Some(&[0xC0, 5][..]));
+        // SysEx and short packets aren't channel messages
+        assert_eq!(channel_message(&[0x04, 0xF0, 0x7D, 0x20]), None);
+
+        assert_eq!(payload(&[0x0F, 0xF8, 0, 0]), Some(&[0xF8][..]));
+        assert_eq!(encode([0xF8, 0, 0]), [0x0F, 0xF8, 0, 0]);
+        assert_eq!(encode([0xF2, 0x10, 0x01]), [0x03, 0xF2, 0x10, 0x01]);
+        assert_eq!(encode([0xC0, 5, 0]), [0x0C, 0xC0, 5, 0]);
+        assert_eq!(
+            payload(&[0x03, 0xF2, 0x10, 0x01]),
+            Some(&[0xF2, 0x10, 0x01][..])
+        );
+        assert_eq!(payload(&note_on), Some(&[0x93, 60, 100][..]));
+        assert_eq!(channel_message(&[0x09, 0x90]), None);
+    }
+
+    struct Point;
+
+    impl Layout for Point {
+        fn key_to_coord(_row: usize, _col: usize) -> Option<Coordinate> {
+            None
+        }
+
+        fn led_to_coord(_idx: usize) -> Option<Coordinate> {
+            None
+        }
+
+        fn coord_to_led(_coord: Coordinate) -> Option<usize> {
+            None
+        }
+
+        fn center_coord() -> Coordinate {
+            Coordinate { x: 0, y: 0 }
+        }
+    }
+
+    /// The MPE note on path of the MIDI loopback self-test: key pitch to note and
+    /// bend, out as packets and back, and the synth's resulting pitch.
+    #[test]
+    fn test_mpe_loopback() {
+        let bend_range = 2.0;
+        for coord in Point::center_coord().ring(2) {
+            let pitch = key_pitch_cents::<Point>(coord, 696.0);
+            let (note, bend) = mpe_note(pitch, bend_range);
+
+            let bend_packet = encode([0xE1, (bend & 0x7F) as u8, (bend >> 7) as u8]);
+            let note_packet = encode([0x91, note, 100]);
+
+            let [_, lsb, msb] = channel_message(&bend_packet).unwrap() else {
+                panic!("pitch bend is 3 bytes");
+            };
+            let received_bend = *lsb as u16 | (*msb as u16) << 7;
+            let received_note = channel_message(&note_packet).unwrap()[1];
+            let heard = bent_pitch_cents(received_note, received_bend, bend_range);
+            assert!(
+                (heard - pitch).abs() < 0.1,
+                "{:?}: {} vs {}",
+                coord,
+                heard,
+                pitch
+            );
+        }
+    }
+}
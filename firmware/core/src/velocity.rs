@@ -0,0 +1,139 @@
+//! Key velocity from dual-contact switches: a key closes one contact, then a second one
+//! further down, and the faster it's struck the less time passes between the two.
+
+/// Gap between the contacts that plays velocity 127, in us; faster is no louder.
+pub const FASTEST_US: u32 = 2_000;
+/// Gap that plays velocity 1; slower is no softer.
+pub const SLOWEST_US: u32 = 50_000;
+
+/// How strike speed maps onto velocity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VelocityCurve {
+    Linear,
+    /// Louder for the same speed, for a light touch.
+    Soft,
+    /// Softer for the same speed, for a heavy touch.
+    Hard,
+}
+
+impl VelocityCurve {
+    pub const ALL: [VelocityCurve; 3] = [
+        VelocityCurve::Linear,
+        VelocityCurve::Soft,
+        VelocityCurve::Hard,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VelocityCurve::Linear => "Linear",
+            VelocityCurve::Soft => "Soft",
+            VelocityCurve::Hard => "Hard",
+        }
+    }
+
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            VelocityCurve::Linear => x,
+            VelocityCurve::Soft => x * (2.0 - x),
+            VelocityCurve::Hard => x * x,
+        }
+    }
+
+    /// Velocity 1-127 for a key whose second contact closed `gap_us` after its first.
+    pub fn velocity(self, gap_us: u32) -> u8 {
+        let gap = gap_us.clamp(FASTEST_US, SLOWEST_US) - FASTEST_US;
+        let speed = 1.0 - gap as f32 / (SLOWEST_US - FASTEST_US) as f32;
+        1 + (self.apply(speed) * 126.0 + 0.5) as u8
+    }
+}
+
+/// What a contact change did to its key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContactEvent {
+    /// The second contact closed this long after the first.
+    Press {
+        gap_us: u32,
+    },
+    Release,
+}
+
+/// Both contacts of one key. The note starts when the second contact closes and
+/// ends when the first opens, so a key let only part way up can't strike again.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DualContactKey {
+    /// When the first contact closed, in us (wrapping); `None` while it's open.
+    first_at: Option<u32>,
+    sounding: bool,
+}
+
+impl DualContactKey {
+    pub const fn new() -> Self {
+        Self {
+            first_at: None,
+            sounding: false,
+        }
+    }
+
+    /// The first contact closed or opened at `now_us`.
+    pub fn first(&mut self, closed: bool, now_us: u32) -> Option<ContactEvent> {
+        if closed {
+            self.first_at = Some(now_us);
+            return None;
+        }
+        self.first_at = None;
+        core::mem::take(&mut self.sounding).then_some(ContactEvent::Release)
+    }
+
+    /// The second contact closed or opened at `now_us`. Closing it without the first,
+    /// which a bounce can do, counts as the fastest strike.
+    pub fn second(&mut self, closed: bool, now_us: u32) -> Option<ContactEvent> {
+        if !closed || self.sounding {
+            return None;
+        }
+        self.sounding = true;
+        let gap_us = self.first_at.map_or(0, |at| now_us.wrapping_sub(at));
+        Some(ContactEvent::Press { gap_us })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_velocity_curves() {
+        for curve in VelocityCurve::ALL {
+            assert_eq!(curve.velocity(0), 127);
+            assert_eq!(curve.velocity(FASTEST_US), 127);
+            assert_eq!(curve.velocity(SLOWEST_US), 1);
+            assert_eq!(curve.velocity(1_000_000), 1);
+        }
+        let middle = (FASTEST_US + SLOWEST_US) / 2;
+        assert_eq!(VelocityCurve::Linear.velocity(middle), 64);
+        assert!(VelocityCurve::Soft.velocity(middle) > 64);
+        assert!(VelocityCurve::Hard.velocity(middle) < 64);
+    }
+
+    #[test]
+    fn test_dual_contact_key() {
+        let mut key = DualContactKey::new();
+        assert_eq!(key.first(true, 1_000), None);
+        assert_eq!(
+            key.second(true, 6_000),
+            Some(ContactEvent::Press { gap_us: 5_000 })
+        );
+        // The second contact bouncing or opening again doesn't restrike
+        assert_eq!(key.second(false, 7_000), None);
+        assert_eq!(key.second(true, 8_000), None);
+        assert_eq!(key.first(false, 9_000), Some(ContactEvent::Release));
+        // Partway down and back up plays nothing
+        assert_eq!(key.first(true, 10_000), None);
+        assert_eq!(key.first(false, 11_000), None);
+        // The clock wrapping between the contacts
+        key.first(true, u32::MAX - 99);
+        assert_eq!(
+            key.second(true, 400),
+            Some(ContactEvent::Press { gap_us: 500 })
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Per-key press and bounce counts, kept across power cycles to spot switches that
+//! are wearing out.
+//!
+//! Stored as a settings-style record (see [`crate::banks`]): for each matrix
+//! position, row by row, the presses then the bounces as u32 LE.
+
+/// Matrix positions counted; enough for every board.
+pub const WEAR_KEYS: usize = 256;
+/// Bytes in a stored record's payload.
+pub const WEAR_LEN: usize = WEAR_KEYS * 8;
+
+/// Presses a key needs this session before its bounce rate is judged.
+const MIN_RECENT_PRESSES: u32 = 20;
+/// Bounces per 1000 presses under which a key is never flagged.
+const QUIET_PERMILLE: u32 = 50;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyWear {
+    pub presses: u32,
+    /// Presses that bounced or chattered.
+    pub bounces: u32,
+}
+
+impl KeyWear {
+    pub const ZERO: Self = Self {
+        presses: 0,
+        bounces: 0,
+    };
+
+    pub fn plus(self, other: Self) -> Self {
+        Self {
+            presses: self.presses.saturating_add(other.presses),
+            bounces: self.bounces.saturating_add(other.bounces),
+        }
+    }
+
+    /// Bounces per 1000 presses, or `None` before the first press.
+    pub fn bounce_permille(self) -> Option<u32> {
+        (self.presses > 0).then(|| (self.bounces as u64 * 1000 / self.presses as u64) as u32)
+    }
+}
+
+/// Whether a key bounces clearly more often in `recent` presses than it did over its
+/// `lifetime` before them.
+pub fn bounce_climbing(lifetime: KeyWear, recent: KeyWear) -> bool {
+    if recent.presses < MIN_RECENT_PRESSES {
+        return false;
+    }
+    let recent_rate = recent.bounce_permille().unwrap_or(0);
+    let usual = lifetime.bounce_permille().unwrap_or(0);
+    recent_rate > QUIET_PERMILLE.max(2 * usual)
+}
+
+pub fn encode(counts: &[KeyWear; WEAR_KEYS], out: &mut [u8; WEAR_LEN]) {
+    for (key, bytes) in counts.iter().zip(out.chunks_exact_mut(8)) {
+        bytes[..4].copy_from_slice(&key.presses.to_le_bytes());
+        bytes[4..].copy_from_slice(&key.bounces.to_le_bytes());
+    }
+}
+
+/// Counts from a stored payload; keys past its end read as never pressed.
+pub fn decode(payload: &[u8]) -> [KeyWear; WEAR_KEYS] {
+    let mut counts = [KeyWear::ZERO; WEAR_KEYS];
+    for (key, bytes) in counts.iter_mut().zip(payload.chunks_exact(8)) {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        *key = KeyWear {
+            presses: word(0),
+            bounces: word(4),
+        };
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut counts = [KeyWear::ZERO; WEAR_KEYS];
+        counts[3] = KeyWear {
+            presses: 70_000,
+            bounces: 12,
+        };
+        counts[WEAR_KEYS - 1].presses = 1;
+        let mut bytes = [0; WEAR_LEN];
+        encode(&counts, &mut bytes);
+        assert_eq!(decode(&bytes), counts);
+        assert_eq!(decode(&bytes[..32])[3], counts[3]);
+        assert_eq!(decode(&bytes[..32])[WEAR_KEYS - 1], KeyWear::ZERO);
+    }
+
+    #[test]
+    fn test_bounce_climbing() {
+        let wear = |presses, bounces| KeyWear { presses, bounces };
+        assert!(!bounce_climbing(wear(0, 0), wear(10, 10)));
+        assert!(!bounce_climbing(wear(1000, 0), wear(100, 5)));
+        assert!(bounce_climbing(wear(1000, 0), wear(100, 6)));
+        // Always a bit bouncy, and no worse now
+        assert!(!bounce_climbing(wear(1000, 100), wear(100, 15)));
+        assert!(bounce_climbing(wear(1000, 100), wear(100, 21)));
+        assert_eq!(wear(0, 0).bounce_permille(), None);
+    }
+}
@@ -0,0 +1,290 @@
+//! Zones: rectangles of the lattice that play something other than their notes.
+//!
+//! Zones are stored in presets, encoded as the kind, the rectangle (lowest x and y,
+//! width, height) and then each pad's chord: its length and up to [`MAX_CHORD`] key
+//! coordinates.
+
+use crate::layout::Coordinate;
+
+/// Keys a pad's chord holds at most.
+pub const MAX_CHORD: usize = 6;
+/// Pads in a zone at most.
+pub const PADS: usize = 16;
+/// Bytes of an encoded zone.
+pub const ZONE_LEN: usize = 5 + PADS * (1 + 2 * MAX_CHORD);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZoneKind {
+    Off,
+    /// Each key plays a chord captured into it.
+    ChordPads,
+    /// Each key plays a General MIDI drum (see [`drum_note`]).
+    Drums,
+    /// The first two keys bend the newest MPE voice down and up while held; the
+    /// rest play as usual.
+    BendKeys,
+    /// The first key repeats the other held notes while held; the rest play as usual.
+    TremoloKey,
+}
+
+impl ZoneKind {
+    pub const ALL: [ZoneKind; 5] = [
+        ZoneKind::Off,
+        ZoneKind::ChordPads,
+        ZoneKind::Drums,
+        ZoneKind::BendKeys,
+        ZoneKind::TremoloKey,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ZoneKind::Off => "Off",
+            ZoneKind::ChordPads => "Chord pads",
+            ZoneKind::Drums => "Drums",
+            ZoneKind::BendKeys => "Bend keys",
+            ZoneKind::TremoloKey => "Tremolo key",
+        }
+    }
+}
+
+/// General MIDI percussion notes of the drum pads, bottom row first: kick, snare and
+/// hi-hats, then toms, then cymbals and claps, then hand percussion.
+const DRUM_NOTES: [u8; PADS] = [
+    36, 38, 42, 46, // Kick, snare, closed and open hi-hat
+    41, 45, 48, 50, // Toms, low to high
+    49, 51, 39, 37, // Crash, ride, clap, side stick
+    56, 54, 70, 75, // Cowbell, tambourine, maracas, claves
+];
+
+/// The General MIDI percussion note of drum pad `pad`.
+pub fn drum_note(pad: usize) -> u8 {
+    DRUM_NOTES[pad % PADS]
+}
+
+/// Keys from `min` up to `width` steps right and `height` steps up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub min: Coordinate,
+    pub width: u8,
+    pub height: u8,
+}
+
+impl Rect {
+    /// The rectangle with `a` and `b` at opposite corners.
+    pub fn spanning(a: Coordinate, b: Coordinate) -> Self {
+        let side = |from: i8, to: i8| (from.abs_diff(to) as u16 + 1).min(u8::MAX as u16) as u8;
+        Rect {
+            min: Coordinate {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+            },
+            width: side(a.x, b.x),
+            height: side(a.y, b.y),
+        }
+    }
+
+    /// Keys in the rectangle.
+    pub fn pads(self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// The keys at its four corners, lowest row first.
+    pub fn corners(self) -> [Coordinate; 4] {
+        let last = |min: i8, len: u8| (min as i16 + len as i16 - 1).clamp(-128, 127) as i8;
+        let (max_x, max_y) = (last(self.min.x, self.width), last(self.min.y, self.height));
+        [
+            self.min,
+            Coordinate {
+                x: max_x,
+                ..self.min
+            },
+            Coordinate {
+                y: max_y,
+                ..self.min
+            },
+            Coordinate { x: max_x, y: max_y },
+        ]
+    }
+
+    /// Index of the pad at `coord`, row by row from `min`, if it's in the rectangle.
+    pub fn pad(self, coord: Coordinate) -> Option<usize> {
+        let dx = coord.x as i16 - self.min.x as i16;
+        let dy = coord.y as i16 - self.min.y as i16;
+        let inside = (0..self.width as i16).contains(&dx) && (0..self.height as i16).contains(&dy);
+        inside.then_some((dy * self.width as i16 + dx) as usize)
+    }
+}
+
+/// A chord captured from held keys, played back by pressing its pad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StoredChord {
+    keys: [Coordinate; MAX_CHORD],
+    len: u8,
+}
+
+impl StoredChord {
+    pub const EMPTY: StoredChord = StoredChord {
+        keys: [Coordinate { x: 0, y: 0 }; MAX_CHORD],
+        len: 0,
+    };
+
+    /// The first [`MAX_CHORD`] of `keys`.
+    pub fn from_keys(keys: &[Coordinate]) -> Self {
+        let mut chord = Self::EMPTY;
+        for (slot, &key) in chord.keys.iter_mut().zip(keys) {
+            *slot = key;
+            chord.len += 1;
+        }
+        chord
+    }
+
+    pub fn keys(&self) -> &[Coordinate] {
+        &self.keys[..self.len as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Zone {
+    pub kind: ZoneKind,
+    pub rect: Rect,
+    /// Chord of each pad, by [`Rect::pad`] index.
+    pub chords: [StoredChord; PADS],
+}
+
+impl Zone {
+    /// An unused zone of 4 by 4 pads at `min`.
+    pub const fn new(min: Coordinate) -> Self {
+        Self {
+            kind: ZoneKind::Off,
+            rect: Rect {
+                min,
+                width: 4,
+                height: 4,
+            },
+            chords: [StoredChord::EMPTY; PADS],
+        }
+    }
+
+    /// The pad at `coord`, if the zone is in use and covers it.
+    pub fn pad(&self, coord: Coordinate) -> Option<usize> {
+        match self.kind {
+            ZoneKind::Off => None,
+            ZoneKind::BendKeys => self.rect.pad(coord).filter(|&pad| pad < 2),
+            ZoneKind::TremoloKey => self.rect.pad(coord).filter(|&pad| pad == 0),
+            _ => self.rect.pad(coord),
+        }
+    }
+
+    pub fn encode(&self, out: &mut [u8; ZONE_LEN]) {
+        let rect = self.rect;
+        out[..5].copy_from_slice(&[
+            self.kind as u8,
+            rect.min.x as u8,
+            rect.min.y as u8,
+            rect.width,
+            rect.height,
+        ]);
+        for (record, chord) in out[5..].chunks_mut(1 + 2 * MAX_CHORD).zip(&self.chords) {
+            record[0] = chord.len;
+            for (pair, key) in record[1..].chunks_mut(2).zip(&chord.keys) {
+                pair.copy_from_slice(&[key.x as u8, key.y as u8]);
+            }
+        }
+    }
+
+    /// Reads a zone written by [`Zone::encode`], or `None` if it doesn't hold one.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..ZONE_LEN)?;
+        let kind = *ZoneKind::ALL.get(bytes[0] as usize)?;
+        let rect = Rect {
+            min: Coordinate {
+                x: bytes[1] as i8,
+                y: bytes[2] as i8,
+            },
+            width: bytes[3],
+            height: bytes[4],
+        };
+        if rect.pads() > PADS {
+            return None;
+        }
+        let mut zone = Zone {
+            kind,
+            rect,
+            ..Zone::new(rect.min)
+        };
+        for (record, chord) in bytes[5..].chunks(1 + 2 * MAX_CHORD).zip(&mut zone.chords) {
+            let len = record[0] as usize;
+            if len > MAX_CHORD {
+                return None;
+            }
+            for (pair, key) in record[1..].chunks(2).zip(&mut chord.keys[..len]) {
+                *key = Coordinate {
+                    x: pair[0] as i8,
+                    y: pair[1] as i8,
+                };
+            }
+            chord.len = len as u8;
+        }
+        Some(zone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: i8, y: i8) -> Coordinate {
+        Coordinate { x, y }
+    }
+
+    #[test]
+    fn test_pads() {
+        let mut zone = Zone::new(at(-6, -2));
+        assert_eq!(zone.pad(at(-6, -2)), None);
+        zone.kind = ZoneKind::ChordPads;
+        assert_eq!(zone.pad(at(-6, -2)), Some(0));
+        assert_eq!(zone.pad(at(-5, -2)), Some(1));
+        assert_eq!(zone.pad(at(-6, -1)), Some(4));
+        assert_eq!(zone.pad(at(-3, 1)), Some(15));
+        assert_eq!(zone.pad(at(-2, 1)), None);
+        assert_eq!(zone.pad(at(-7, 0)), None);
+        zone.kind = ZoneKind::BendKeys;
+        assert_eq!(zone.pad(at(-5, -2)), Some(1));
+        assert_eq!(zone.pad(at(-4, -2)), None);
+        // Bass drum in the corner, claves in the opposite one
+        assert_eq!(drum_note(0), 36);
+        assert_eq!(drum_note(PADS - 1), 75);
+    }
+
+    #[test]
+    fn test_rect_spanning() {
+        // Either pair of opposite corners, in either order
+        let rect = Rect::spanning(at(2, -1), at(-1, 1));
+        assert_eq!(rect, Rect::spanning(at(-1, -1), at(2, 1)));
+        assert_eq!(rect.min, at(-1, -1));
+        assert_eq!((rect.width, rect.height, rect.pads()), (4, 3, 12));
+        assert_eq!(rect.corners(), [at(-1, -1), at(2, -1), at(-1, 1), at(2, 1)]);
+        // A single key is its own corners
+        let key = Rect::spanning(at(5, 5), at(5, 5));
+        assert_eq!(key.pads(), 1);
+        assert_eq!(key.corners(), [at(5, 5); 4]);
+        assert_eq!(Rect::spanning(at(-128, 0), at(127, 0)).width, u8::MAX);
+    }
+
+    #[test]
+    fn test_zone_round_trip() {
+        let mut zone = Zone::new(at(3, -4));
+        zone.kind = ZoneKind::ChordPads;
+        zone.chords[2] = StoredChord::from_keys(&[at(0, 0), at(1, -1), at(-1, 2)]);
+        let many: Vec<Coordinate> = (0..8).map(|i| at(i, -i)).collect();
+        zone.chords[15] = StoredChord::from_keys(&many);
+        assert_eq!(zone.chords[15].keys(), &many[..MAX_CHORD]);
+
+        let mut bytes = [0u8; ZONE_LEN];
+        zone.encode(&mut bytes);
+        assert_eq!(Zone::decode(&bytes), Some(zone));
+        // Erased flash doesn't read as a zone
+        assert_eq!(Zone::decode(&[0xFF; ZONE_LEN]), None);
+        assert_eq!(Zone::decode(&bytes[..ZONE_LEN - 1]), None);
+    }
+}
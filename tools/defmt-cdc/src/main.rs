@@ -0,0 +1,89 @@
+//! Prints the defmt logs of a board with no debug probe attached, read from its second
+//! serial port and decoded with the ELF it's running.
+//!
+//! Usage: `defmt-cdc [--serial PORT] [ELF]`. Without an ELF the firmware's debug
+//! build is used; it must be the exact build on the board.
+
+use defmt_decoder::{DecodeError, Frame, Locations, Table};
+use std::io::{ErrorKind, Read};
+use std::process::ExitCode;
+use std::time::Duration;
+
+const DEFAULT_ELF: &str = "../../firmware/target/thumbv6m-none-eabi/debug/lattice-board-controller";
+// The console is the board's first serial port and the defmt frames its second
+#[cfg(target_os = "macos")]
+const DEFAULT_SERIAL: &str = "/dev/tty.usbmodem3";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_SERIAL: &str = "/dev/ttyACM1";
+
+fn main() -> ExitCode {
+    let mut serial = DEFAULT_SERIAL.to_string();
+    let mut elf = DEFAULT_ELF.to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--serial" => serial = args.next().unwrap_or_default(),
+            _ => elf = arg,
+        }
+    }
+
+    match run(&serial, &elf) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(serial_path: &str, elf_path: &str) -> Result<(), String> {
+    let elf = std::fs::read(elf_path).map_err(|e| format!("{}: {}", elf_path, e))?;
+    let table = Table::parse(&elf)
+        .map_err(|e| format!("{}: {}", elf_path, e))?
+        .ok_or_else(|| format!("{}: no defmt data; is it the firmware ELF?", elf_path))?;
+    // Source locations are a nicety; logs still print without them
+    let locations = table.get_locations(&elf).ok().filter(|l| !l.is_empty());
+
+    let mut port = serialport::new(serial_path, 115_200)
+        .timeout(Duration::from_millis(500))
+        .open()
+        .map_err(|e| format!("{}: {}", serial_path, e))?;
+    let mut decoder = table.new_stream_decoder();
+    let mut buf = [0u8; 256];
+    loop {
+        let n = match port.read(&mut buf) {
+            Ok(0) => return Err(format!("{}: closed", serial_path)),
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::TimedOut => continue,
+            Err(e) => return Err(format!("{}: {}", serial_path, e)),
+        };
+        decoder.received(&buf[..n]);
+        loop {
+            match decoder.decode() {
+                Ok(frame) => print_frame(&frame, locations.as_ref()),
+                Err(DecodeError::UnexpectedEof) => break,
+                // The board drops whole frames when the host falls behind, but bytes can
+                // still be lost when the port is opened mid-frame
+                Err(DecodeError::Malformed) if table.encoding().can_recover() => {
+                    eprintln!("(skipped a malformed frame)");
+                }
+                Err(DecodeError::Malformed) => {
+                    return Err("malformed frame; is the ELF the build on the board?".into())
+                }
+            }
+        }
+    }
+}
+
+fn print_frame(frame: &Frame, locations: Option<&Locations>) {
+    println!("{}", frame.display(true));
+    let location = locations.and_then(|l| l.get(&frame.index()));
+    if let Some(location) = location {
+        println!(
+            "└─ {} @ {}:{}",
+            location.module,
+            location.file.display(),
+            location.line
+        );
+    }
+}
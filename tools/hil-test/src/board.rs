@@ -0,0 +1,128 @@
+//! Connection to a board: its MIDI port for commands and output, and its CDC serial
+//! port for log lines.
+
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+use std::io::Read;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Something the board sent, stamped with when it arrived.
+pub type Received<T> = (Instant, T);
+
+pub struct Board {
+    out: MidiOutputConnection,
+    _input: MidiInputConnection<()>,
+    midi: Receiver<Received<Vec<u8>>>,
+    log: Receiver<Received<String>>,
+}
+
+impl Board {
+    /// Opens the first MIDI port whose name contains `midi_name`, and the serial port
+    /// at `serial_path`.
+    pub fn connect(midi_name: &str, serial_path: &str) -> Result<Self, String> {
+        let output = MidiOutput::new("hil-test").map_err(|e| e.to_string())?;
+        let port = output
+            .ports()
+            .into_iter()
+            .find(|p| output.port_name(p).is_ok_and(|n| n.contains(midi_name)))
+            .ok_or_else(|| format!("no MIDI output matching {:?}", midi_name))?;
+        let out = output
+            .connect(&port, "hil-test-out")
+            .map_err(|e| e.to_string())?;
+
+        let mut input = MidiInput::new("hil-test").map_err(|e| e.to_string())?;
+        input.ignore(Ignore::None);
+        let port = input
+            .ports()
+            .into_iter()
+            .find(|p| input.port_name(p).is_ok_and(|n| n.contains(midi_name)))
+            .ok_or_else(|| format!("no MIDI input matching {:?}", midi_name))?;
+        let (midi_tx, midi) = channel();
+        let _input = input
+            .connect(
+                &port,
+                "hil-test-in",
+                move |_, message, _| {
+                    let _ = midi_tx.send((Instant::now(), message.to_vec()));
+                },
+                (),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut serial = serialport::new(serial_path, 115_200)
+            .timeout(Duration::from_millis(50))
+            .open()
+            .map_err(|e| format!("{}: {}", serial_path, e))?;
+        let (log_tx, log) = channel();
+        std::thread::spawn(move || {
+            let mut line = Vec::new();
+            let mut buf = [0u8; 64];
+            loop {
+                match serial.read(&mut buf) {
+                    Ok(0) => {}
+                    Ok(n) => {
+                        for &b in &buf[..n] {
+                            if b == b'\n' {
+                                let text = String::from_utf8_lossy(&line).trim_end().to_string();
+                                if log_tx.send((Instant::now(), text)).is_err() {
+                                    return;
+                                }
+                                line.clear();
+                            } else {
+                                line.push(b);
+                            }
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                    Err(_) => return,
+                }
+            }
+        });
+
+        Ok(Self {
+            out,
+            _input,
+            midi,
+            log,
+        })
+    }
+
+    pub fn send(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.out.send(bytes).map_err(|e| e.to_string())
+    }
+
+    /// Drops anything received so far, so expectations only see what follows.
+    pub fn drain(&mut self) {
+        while self.midi.try_recv().is_ok() {}
+        while self.log.try_recv().is_ok() {}
+    }
+
+    /// Waits until `deadline` for a MIDI message accepted by `accept`.
+    pub fn expect_midi(
+        &mut self,
+        deadline: Instant,
+        accept: impl Fn(&[u8]) -> bool,
+    ) -> Option<Received<Vec<u8>>> {
+        wait_for(&self.midi, deadline, |m| accept(m))
+    }
+
+    /// Waits until `deadline` for a log line containing `text`.
+    pub fn expect_log(&mut self, deadline: Instant, text: &str) -> Option<Received<String>> {
+        wait_for(&self.log, deadline, |line| line.contains(text))
+    }
+}
+
+fn wait_for<T>(
+    rx: &Receiver<Received<T>>,
+    deadline: Instant,
+    accept: impl Fn(&T) -> bool,
+) -> Option<Received<T>> {
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(left) {
+            Ok((at, item)) if accept(&item) => return Some((at, item)),
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
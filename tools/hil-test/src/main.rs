@@ -0,0 +1,126 @@
+//! Hardware-in-the-loop tests: runs scenario scripts against a connected board and
+//! fails if an expected MIDI message or log line doesn't arrive within its bound.
+//!
+//! Usage: `hil-test [--serial PORT] [--midi NAME] [scenario.hil...]`. Without
+//! scenario files the built-in ones in `scenarios/` are run.
+
+mod board;
+mod scenario;
+
+use board::Board;
+use scenario::{Scenario, Step};
+use std::process::ExitCode;
+use std::time::Instant;
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("selftest", include_str!("../scenarios/selftest.hil")),
+    ("press", include_str!("../scenarios/press.hil")),
+];
+
+const DEFAULT_MIDI: &str = "LatticeBoard";
+#[cfg(target_os = "macos")]
+const DEFAULT_SERIAL: &str = "/dev/tty.usbmodem1";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_SERIAL: &str = "/dev/ttyACM0";
+
+fn main() -> ExitCode {
+    let mut midi = DEFAULT_MIDI.to_string();
+    let mut serial = DEFAULT_SERIAL.to_string();
+    let mut files = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--midi" => midi = args.next().unwrap_or_default(),
+            "--serial" => serial = args.next().unwrap_or_default(),
+            _ => files.push(arg),
+        }
+    }
+
+    let scenarios: Result<Vec<_>, String> = if files.is_empty() {
+        BUILTIN
+            .iter()
+            .map(|(name, source)| Scenario::parse(name, source))
+            .collect()
+    } else {
+        files
+            .iter()
+            .map(|path| {
+                let source =
+                    std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+                Scenario::parse(path, &source).map_err(|e| format!("{}: {}", path, e))
+            })
+            .collect()
+    };
+    let scenarios = match scenarios {
+        Ok(scenarios) => scenarios,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut board = match Board::connect(&midi, &serial) {
+        Ok(board) => board,
+        Err(e) => {
+            eprintln!("Couldn't connect to the board: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut failed = 0;
+    for scenario in &scenarios {
+        match run(&mut board, scenario) {
+            Ok(()) => println!("PASS {}", scenario.name),
+            Err(e) => {
+                println!("FAIL {}: {}", scenario.name, e);
+                failed += 1;
+            }
+        }
+    }
+    println!(
+        "{}/{} scenarios passed",
+        scenarios.len() - failed,
+        scenarios.len()
+    );
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Runs the steps in order. Expectation bounds count from the latest `send`.
+fn run(board: &mut Board, scenario: &Scenario) -> Result<(), String> {
+    board.drain();
+    let mut sent_at = Instant::now();
+    for (i, step) in scenario.steps.iter().enumerate() {
+        let step_no = i + 1;
+        match step {
+            Step::Send(bytes) => {
+                board.send(bytes)?;
+                sent_at = Instant::now();
+            }
+            Step::Wait(duration) => std::thread::sleep(*duration),
+            Step::ExpectMidi { within, pattern } => {
+                let (at, message) = board
+                    .expect_midi(sent_at + *within, |m| scenario::matches(pattern, m))
+                    .ok_or_else(|| {
+                        format!("step {}: no matching MIDI within {:?}", step_no, within)
+                    })?;
+                println!(
+                    "  step {}: {:02X?} after {:?}",
+                    step_no,
+                    message,
+                    at - sent_at
+                );
+            }
+            Step::ExpectLog { within, text } => {
+                let (at, _) = board.expect_log(sent_at + *within, text).ok_or_else(|| {
+                    format!("step {}: no log {:?} within {:?}", step_no, text, within)
+                })?;
+                println!("  step {}: {:?} after {:?}", step_no, text, at - sent_at);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+//! Scenario scripts: one step per line, `#` starts a comment.
+//!
+//! ```text
+//! send 90 3C 64                   raw MIDI bytes to the board
+//! sysex SELF_TEST [payload...]    a board command, by its name in `sysex::cmd`
+//! press 2 5 [velocity]            press the key at matrix row 2, col 5 (decimal)
+//! release 2 5
+//! wait 100ms
+//! expect-midi 20ms 9? 3C *        MIDI from the board within the bound; `*` matches any
+//!                                 byte, `9?` any byte whose high nibble is 9
+//! expect-log 500ms some text      a log line containing the text within the bound
+//! ```
+
+use lattice_board_core::sysex::{cmd, MANUFACTURER_ID, SYSEX_END, SYSEX_START};
+use std::time::Duration;
+
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Send(Vec<u8>),
+    Wait(Duration),
+    ExpectMidi {
+        within: Duration,
+        pattern: Vec<BytePattern>,
+    },
+    ExpectLog {
+        within: Duration,
+        text: String,
+    },
+}
+
+/// Matches bytes equal to `value` in the bits set in `mask`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BytePattern {
+    pub mask: u8,
+    pub value: u8,
+}
+
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+/// Board commands usable in `sysex` steps.
+const COMMANDS: &[(&str, u8)] = &[
+    ("PLAYER_CLEAR", cmd::PLAYER_CLEAR),
+    ("PLAYER_APPEND", cmd::PLAYER_APPEND),
+    ("PLAYER_PLAY", cmd::PLAYER_PLAY),
+    ("PLAYER_STOP", cmd::PLAYER_STOP),
+    ("SET_BOARD", cmd::SET_BOARD),
+    ("SELF_TEST", cmd::SELF_TEST),
+    ("PRESS", cmd::PRESS),
+    ("RELEASE", cmd::RELEASE),
+    ("TRANSFER_BEGIN", cmd::TRANSFER_BEGIN),
+    ("TRANSFER_CHUNK", cmd::TRANSFER_CHUNK),
+    ("TRANSFER_END", cmd::TRANSFER_END),
+    ("TRANSFER_STATUS", cmd::TRANSFER_STATUS),
+];
+
+impl Scenario {
+    pub fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let steps = source
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let line = line.split('#').next().unwrap_or("").trim();
+                (!line.is_empty())
+                    .then(|| parse_step(line).map_err(|e| format!("line {}: {}", i + 1, e)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            name: name.to_string(),
+            steps,
+        })
+    }
+}
+
+fn parse_step(line: &str) -> Result<Step, String> {
+    let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let rest = rest.trim();
+    match keyword {
+        "send" => Ok(Step::Send(parse_bytes(rest)?)),
+        "sysex" => {
+            let (name, payload) = rest.split_once(' ').unwrap_or((rest, ""));
+            let &(_, id) = COMMANDS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .ok_or_else(|| format!("unknown command {:?}", name))?;
+            Ok(Step::Send(sysex(id, &parse_bytes(payload)?)))
+        }
+        "press" | "release" => {
+            let args = rest
+                .split_whitespace()
+                .map(|a| a.parse::<u8>().map_err(|_| format!("bad number {:?}", a)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let (id, arity) = if keyword == "press" {
+                (cmd::PRESS, 2..=3)
+            } else {
+                (cmd::RELEASE, 2..=2)
+            };
+            if !arity.contains(&args.len()) {
+                return Err(format!(
+                    "{} takes row, col{}",
+                    keyword,
+                    if id == cmd::PRESS { " [velocity]" } else { "" }
+                ));
+            }
+            Ok(Step::Send(sysex(id, &args)))
+        }
+        "wait" => Ok(Step::Wait(parse_duration(rest)?)),
+        "expect-midi" => {
+            let (within, pattern) = rest.split_once(' ').ok_or("expected a bound and bytes")?;
+            let pattern = pattern
+                .split_whitespace()
+                .map(parse_pattern)
+                .collect::<Result<_, _>>()?;
+            Ok(Step::ExpectMidi {
+                within: parse_duration(within)?,
+                pattern,
+            })
+        }
+        "expect-log" => {
+            let (within, text) = rest.split_once(' ').ok_or("expected a bound and text")?;
+            Ok(Step::ExpectLog {
+                within: parse_duration(within)?,
+                text: text.trim().to_string(),
+            })
+        }
+        _ => Err(format!("unknown step {:?}", keyword)),
+    }
+}
+
+fn sysex(id: u8, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![SYSEX_START, MANUFACTURER_ID, id];
+    bytes.extend(payload);
+    bytes.push(SYSEX_END);
+    bytes
+}
+
+/// `*`, `9?` or `3C`.
+fn parse_pattern(s: &str) -> Result<BytePattern, String> {
+    if s == "*" {
+        return Ok(BytePattern { mask: 0, value: 0 });
+    }
+    if let Some(high) = s.strip_suffix('?') {
+        let value = u8::from_str_radix(high, 16)
+            .ok()
+            .filter(|&v| v < 0x10)
+            .ok_or_else(|| format!("bad pattern {:?}", s))?;
+        return Ok(BytePattern {
+            mask: 0xF0,
+            value: value << 4,
+        });
+    }
+    parse_byte(s).map(|value| BytePattern { mask: 0xFF, value })
+}
+
+fn parse_byte(s: &str) -> Result<u8, String> {
+    u8::from_str_radix(s, 16).map_err(|_| format!("bad hex byte {:?}", s))
+}
+
+fn parse_bytes(s: &str) -> Result<Vec<u8>, String> {
+    s.split_whitespace().map(parse_byte).collect()
+}
+
+/// `250ms` or `2s`.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let bad = || format!("bad duration {:?}", s);
+    if let Some(ms) = s.strip_suffix("ms") {
+        ms.parse().map(Duration::from_millis).map_err(|_| bad())
+    } else if let Some(secs) = s.strip_suffix('s') {
+        secs.parse().map(Duration::from_secs).map_err(|_| bad())
+    } else {
+        Err(bad())
+    }
+}
+
+/// Whether a received message matches an `expect-midi` pattern.
+pub fn matches(pattern: &[BytePattern], message: &[u8]) -> bool {
+    pattern.len() == message.len()
+        && pattern
+            .iter()
+            .zip(message)
+            .all(|(p, b)| b & p.mask == p.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let scenario = Scenario::parse(
+            "t",
+            "# comment\nsysex SELF_TEST\n\nwait 2s\nexpect-midi 20ms 9? 3C * # note on\nexpect-log 500ms 14/14 passed\npress 2 5\nrelease 2 5\n",
+        )
+        .unwrap();
+        assert_eq!(
+            scenario.steps,
+            [
+                Step::Send(vec![0xF0, 0x7D, cmd::SELF_TEST, 0xF7]),
+                Step::Wait(Duration::from_secs(2)),
+                Step::ExpectMidi {
+                    within: Duration::from_millis(20),
+                    pattern: vec![
+                        BytePattern {
+                            mask: 0xF0,
+                            value: 0x90
+                        },
+                        BytePattern {
+                            mask: 0xFF,
+                            value: 0x3C
+                        },
+                        BytePattern { mask: 0, value: 0 },
+                    ],
+                },
+                Step::ExpectLog {
+                    within: Duration::from_millis(500),
+                    text: "14/14 passed".to_string(),
+                },
+                Step::Send(vec![0xF0, 0x7D, cmd::PRESS, 2, 5, 0xF7]),
+                Step::Send(vec![0xF0, 0x7D, cmd::RELEASE, 2, 5, 0xF7]),
+            ]
+        );
+        assert!(Scenario::parse("t", "sysex NOPE").is_err());
+        assert!(Scenario::parse("t", "wait 5").is_err());
+        assert!(Scenario::parse("t", "release 2 5 100").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let pattern: Vec<_> = ["9?", "*", "64"]
+            .into_iter()
+            .map(|p| parse_pattern(p).unwrap())
+            .collect();
+        assert!(matches(&pattern, &[0x93, 0x3C, 0x64]));
+        assert!(!matches(&pattern, &[0x80, 0x3C, 0x64]));
+        assert!(!matches(&pattern, &[0x90, 0x3C]));
+        assert!(parse_pattern("1F?").is_err());
+    }
+}